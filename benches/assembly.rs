@@ -0,0 +1,61 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use hack_assembler::{
+    assembler::Assembler,
+    emulator::ROM_SIZE,
+    generate::{self, GeneratorConfig},
+    golden,
+    program::Program,
+};
+
+/// The inputs: Pong, the largest sample program, and a synthetic program of
+/// a million instructions, which is too large for the ROM and so is only
+/// parsed and resolved.
+fn inputs() -> Vec<(&'static str, String)> {
+    let pong = golden::CORPUS
+        .iter()
+        .find(|case| case.name == "Pong")
+        .expect("Pong is in the corpus");
+    let synthetic = generate::generate(&GeneratorConfig {
+        instructions: 1_000_000,
+        ..Default::default()
+    });
+    vec![
+        ("pong", pong.source.to_string()),
+        ("synthetic-1m", synthetic),
+    ]
+}
+
+fn bench_assembly(c: &mut Criterion) {
+    for (name, source) in inputs() {
+        let mut group = c.benchmark_group(name);
+        group.sample_size(10);
+        group.throughput(Throughput::Elements(source.lines().count() as u64));
+
+        group.bench_function("parse", |b| b.iter(|| Program::from_source(&source)));
+        let program = Program::from_source(&source);
+        group.bench_function("resolve", |b| b.iter(|| program.resolve()));
+        if program.rom_address(program.len()) as usize > ROM_SIZE {
+            group.finish();
+            continue;
+        }
+        group.bench_function("assemble", |b| {
+            b.iter(|| {
+                Assembler::from_source(&source)
+                    .fill_symbol_table()
+                    .assemble()
+            })
+        });
+        group.bench_function("assemble-single-pass", |b| {
+            b.iter(|| {
+                Assembler::from_source(&source)
+                    .single_pass()
+                    .fill_symbol_table()
+                    .assemble()
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_assembly);
+criterion_main!(benches);
@@ -1,8 +1,25 @@
-use std::{marker::PhantomData, path::PathBuf};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    marker::PhantomData,
+    ops::RangeInclusive,
+    path::PathBuf,
+};
 
 use crate::{
-    code::{a_value_to_binary, comp_to_binary, dest_to_binary, jump_to_binary},
-    parser::{InstructionType, Parser},
+    backpatch::SinglePass,
+    cancel::CancellationToken,
+    dialect::{Dialect, Translate},
+    error::AssemblerError,
+    halt::HaltLoop,
+    limits::Limits,
+    lint::{LintReport, MemoryLint, SymbolLint},
+    optimize::{
+        peephole_rules, DeadCodeElimination, DeadCodeReport, JumpThreading, OptLevel, Optimize,
+    },
+    pass::{Context, Emit, EmitFormat, Parse, PassManager, Stage},
+    spec::SpecStrict,
+    stats::{Statistics, StatsReport},
     symbol_table::SymbolTable,
 };
 
@@ -10,95 +27,314 @@ pub struct Uninitialized;
 pub struct Initialized;
 
 pub struct Assembler<T> {
-    parser: Parser,
-    symbol_table: SymbolTable,
-    output_path: PathBuf,
+    context: Context,
+    passes: PassManager,
+    output_path: Option<PathBuf>,
+    /// Whether the output path was set explicitly, rather than derived from
+    /// the input path.
+    explicit_output: bool,
+    /// The error of the stages run while filling the symbol table, reported
+    /// when running the remaining stages.
+    error: Option<AssemblerError>,
     _phantom: std::marker::PhantomData<T>,
 }
 
-const C_PREFIX: &str = "111";
-
 impl Assembler<Uninitialized> {
     /// Returns a new Assembler instance with the given path.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the file can't be read.
     pub fn new(path: PathBuf) -> Self {
+        Self::open(path).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Returns a new Assembler instance with the given path, or an error if
+    /// the file can't be read.
+    pub fn open(path: PathBuf) -> Result<Self, AssemblerError> {
         let mut output_path = path.clone();
         output_path.set_extension("hack");
 
-        let parser = Parser::new(path);
+        let source = std::fs::read_to_string(&path).map_err(|error| {
+            AssemblerError::io(format!("failed to read {}", path.display()), error)
+        })?;
+        Ok(Self {
+            context: Context::new(source),
+            passes: PassManager::default(),
+            output_path: Some(output_path),
+            explicit_output: false,
+            error: None,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns a new Assembler instance for the given program source.
+    /// The instance has no output path: it's compiled to a writer with
+    /// [`Assembler::compile_to`], or to a file given by
+    /// [`Assembler::with_output`].
+    pub fn from_source(source: &str) -> Self {
         Self {
-            parser,
-            symbol_table: SymbolTable::new(),
-            output_path,
+            context: Context::new(source.to_string()),
+            passes: PassManager::default(),
+            output_path: None,
+            explicit_output: false,
+            error: None,
             _phantom: PhantomData,
         }
     }
 
-    /// Fills the symbol table with the labels from the program.
+    /// Returns the pass manager, allowing to add, enable or disable passes.
+    pub fn passes_mut(&mut self) -> &mut PassManager {
+        &mut self.passes
+    }
+
+    /// Sets the output format, changing the extension of the output path
+    /// accordingly, unless it was set explicitly.
     #[must_use]
-    pub fn fill_symbol_table(mut self) -> Assembler<Initialized> {
-        // Clone the parser otherwise the rest of the code will consume
-        // the program.
-        let mut parser = self.parser.clone();
-
-        while parser.has_more_lines() {
-            // Consumes the parser
-            parser.advance();
-
-            if matches!(parser.instruction_type(), InstructionType::L) {
-                self.symbol_table
-                    .add_label(parser.symbol(), parser.instruction_index());
-            }
+    pub fn emit(mut self, format: EmitFormat) -> Self {
+        if let Some(output_path) = self.output_path.as_mut().filter(|_| !self.explicit_output) {
+            output_path.set_extension(format.extension());
+        }
+        self.passes.replace(Emit(format));
+        self
+    }
+
+    /// Sets the path the output is written to, instead of the input path with
+    /// the extension of the output format. The program can then be compiled
+    /// even if it was built from its source.
+    #[must_use]
+    pub fn with_output(mut self, path: PathBuf) -> Self {
+        self.output_path = Some(path);
+        self.explicit_output = true;
+        self
+    }
+
+    /// Accepts the source in the given dialect, rewriting it in the canonical
+    /// syntax before parsing it.
+    #[must_use]
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        if !self.passes.replace(Translate(dialect)) {
+            self.passes.add(Translate(dialect));
+        }
+        self
+    }
+
+    /// Accepts the comps with their operands swapped, such as `A+D` or `M&D`,
+    /// assembling them as their canonical spelling.
+    #[must_use]
+    pub fn accept_commuted(mut self) -> Self {
+        self.passes.replace(Parse {
+            accept_commuted: true,
+        });
+        self
+    }
+
+    /// Rejects the source if it relies on any extension of the Hack grammar.
+    #[must_use]
+    pub fn spec_strict(mut self) -> Self {
+        if !self.passes.set_enabled("spec-strict", true) {
+            self.passes.add(SpecStrict);
+        }
+        self
+    }
+
+    /// Appends a halting loop to the program if its execution can run past
+    /// its last instruction.
+    #[must_use]
+    pub fn halt_loop(mut self) -> Self {
+        if !self.passes.set_enabled("halt-loop", true) {
+            self.passes.add(HaltLoop);
+        }
+        self
+    }
+
+    /// Optimizes the IR at the given level before encoding it.
+    #[must_use]
+    pub fn optimize(mut self, level: OptLevel) -> Self {
+        if !self.passes.replace(Optimize::new(peephole_rules(level))) {
+            self.passes.add(Optimize::new(peephole_rules(level)));
+        }
+        let global = level >= OptLevel::O2;
+        if !self.passes.set_enabled("jump-threading", global) && global {
+            self.passes.add(JumpThreading);
+        }
+        if !self.passes.set_enabled("dead-code", global) && global {
+            self.passes.add(DeadCodeElimination);
+        }
+        self
+    }
+
+    /// Assembles the program in a single traversal of the source, patching
+    /// the forward references once the labels are known. The program isn't
+    /// parsed nor lowered, so the optimizations and the emit formats other
+    /// than `.hack` are unavailable.
+    #[must_use]
+    pub fn single_pass(mut self) -> Self {
+        for name in ["parse", "resolve", "lower", "encode"] {
+            self.passes.set_enabled(name, false);
         }
+        if !self.passes.set_enabled("single-pass", true) {
+            self.passes.add(SinglePass);
+        }
+        self
+    }
+
+    /// Sets the token allowing to cancel the assembly from another thread.
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.context.cancellation = token;
+        self
+    }
+
+    /// Sets the report receiving the blocks removed by the dead-code elimination.
+    #[must_use]
+    pub fn with_dead_code_report(mut self, report: DeadCodeReport) -> Self {
+        self.context.dead_code = report;
+        self
+    }
+
+    /// Counts the composition of the encoded program, recording it in the
+    /// report.
+    #[must_use]
+    pub fn with_stats_report(mut self, report: StatsReport) -> Self {
+        self.context.stats = report;
+        if !self.passes.set_enabled("stats", true) {
+            self.passes.add(Statistics);
+        }
+        self
+    }
+
+    /// Checks the accesses to the screen and keyboard memory maps and the
+    /// symbols of the program, recording the lints in the report.
+    #[must_use]
+    pub fn with_lint_report(mut self, report: LintReport) -> Self {
+        self.context.lints = report;
+        if !self.passes.set_enabled("memory-lint", true) {
+            self.passes.add(MemoryLint);
+        }
+        if !self.passes.set_enabled("symbol-lint", true) {
+            self.passes.add(SymbolLint);
+        }
+        self
+    }
+
+    /// Sets the resource limits enforced while assembling.
+    #[must_use]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.context.limits = limits;
+        self
+    }
+
+    /// Fills the symbol table with the labels and variables from the program.
+    /// The invalid instructions are left out and their errors reported when
+    /// compiling, along with those of the later stages. If a stage fails,
+    /// the symbol table is left incomplete.
+    #[must_use]
+    pub fn fill_symbol_table(mut self) -> Assembler<Initialized> {
+        let error = self
+            .passes
+            .run_stages(Stage::Preprocess..=Stage::Resolve, &mut self.context)
+            .err();
 
         Assembler {
-            parser: self.parser,
-            symbol_table: self.symbol_table,
+            context: self.context,
+            passes: self.passes,
             output_path: self.output_path,
+            explicit_output: self.explicit_output,
+            error,
             _phantom: PhantomData,
         }
     }
 }
 
 impl Assembler<Initialized> {
-    /// Compiles the program and writes the output to the output path.
-    pub fn compile(mut self) {
-        let mut compiled_output = String::new();
-        while self.parser.has_more_lines() {
-            self.parser.advance();
-            let bits = match self.parser.instruction_type() {
-                InstructionType::A => {
-                    let symbol = self.parser.symbol();
-                    let symbol = self.add_variable(symbol);
-                    a_value_to_binary(symbol)
-                }
-                InstructionType::C => {
-                    let dest = self.parser.dest();
-                    let comp = self.parser.comp();
-                    let jump = self.parser.jump();
-                    C_PREFIX.to_string()
-                        + &comp_to_binary(comp)
-                        + &dest_to_binary(dest)
-                        + &jump_to_binary(jump)
-                }
-                InstructionType::L => continue,
-            };
-            compiled_output += &(bits + "\n");
-        }
+    /// Returns the symbol table of the program.
+    pub fn symbol_table(&self) -> &SymbolTable {
+        &self.context.symbol_table
+    }
+
+    /// Compiles the program and writes the output to the output path, through
+    /// a buffer rather than all at once. The output file is only created once
+    /// the program is encoded. Returns an error if the program can't be
+    /// assembled, the output can't be written, or the assembly was cancelled.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the assembler was created from a source without an output
+    /// path.
+    pub fn compile(mut self) -> Result<(), AssemblerError> {
+        let output_path = self
+            .output_path
+            .take()
+            .expect("missing output path for compiled output");
+        self.run_stages(Stage::Analyze..=Stage::Encode)?;
 
-        std::fs::write(self.output_path, compiled_output).expect("failed to write compiled output");
+        let file = File::create(&output_path).map_err(|error| {
+            AssemblerError::io(format!("failed to create {}", output_path.display()), error)
+        })?;
+        self.context.writer = Some(Box::new(BufWriter::new(file)));
+        self.run_stages(Stage::Emit..=Stage::Emit)?;
+        tracing::info!(output = %output_path.display(), "wrote output");
+        Ok(())
     }
 
-    /// Adds the variable symbol to the symbol table and returns the decimal value for it.
-    #[must_use]
-    fn add_variable(&mut self, symbol: String) -> String {
-        if let Some(x) = self.symbol_table.address(&symbol) {
-            x.to_string()
-        }
-        // If the symbol isn't numeric, we can assume it's a variable
-        else if str::parse::<u32>(&symbol).is_err() {
-            self.symbol_table.add_variable(symbol).to_string()
-        } else {
-            symbol
+    /// Compiles the program and writes the output to the writer, such as the
+    /// standard output, through a buffer. Returns an error if the program
+    /// can't be assembled, the output can't be written, or the assembly was
+    /// cancelled.
+    pub fn compile_to(
+        mut self,
+        writer: impl Write + Send + Sync + 'static,
+    ) -> Result<(), AssemblerError> {
+        self.run_stages(Stage::Analyze..=Stage::Encode)?;
+        self.context.writer = Some(Box::new(BufWriter::new(writer)));
+        self.run_stages(Stage::Emit..=Stage::Emit)
+    }
+
+    /// Compiles the program and returns the output, as it would be written by
+    /// [`Assembler::compile`].
+    ///
+    /// # Panic
+    ///
+    /// Panics if the program can't be assembled or the assembly was cancelled.
+    pub fn render(self) -> String {
+        self.try_render()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Compiles the program and returns the output, as it would be written by
+    /// [`Assembler::compile`], or an error if the program can't be assembled
+    /// or the assembly was cancelled.
+    pub fn try_render(mut self) -> Result<String, AssemblerError> {
+        self.run_stages(Stage::Analyze..=Stage::Emit)?;
+        Ok(self.context.output)
+    }
+
+    /// Assembles the program and returns the binary words, one per instruction.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the program can't be assembled or the assembly was cancelled.
+    pub fn assemble(self) -> Vec<u16> {
+        self.try_assemble()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Assembles the program and returns the binary words, one per instruction,
+    /// or an error if the program can't be assembled or the assembly was
+    /// cancelled.
+    pub fn try_assemble(mut self) -> Result<Vec<u16>, AssemblerError> {
+        self.run_stages(Stage::Analyze..=Stage::Encode)?;
+        Ok(self.context.words)
+    }
+
+    /// Runs the given stages, unless an earlier stage failed, then reports
+    /// the errors recovered from since the first stage.
+    fn run_stages(&mut self, stages: RangeInclusive<Stage>) -> Result<(), AssemblerError> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
         }
+        self.passes.run_stages(stages, &mut self.context)?;
+        self.context.take_errors()
     }
 }
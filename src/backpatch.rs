@@ -0,0 +1,324 @@
+use rustc_hash::FxHashMap;
+
+use crate::{
+    code::{a_instruction, c_instruction},
+    emulator::ROM_SIZE,
+    error::AssemblerError,
+    limits::Limits,
+    parser::{BlockComments, InstructionType, Parser},
+    pass::{Context, Pass, Stage, CANCELLATION_CHUNK},
+    symbol_table::SymbolTable,
+};
+
+/// The word emitted for a reference to a symbol not known yet, replaced once
+/// the whole source is read.
+const PLACEHOLDER: u16 = 0;
+
+/// The state of a single-pass assembly: the symbols known so far, and the
+/// forward references waiting for the end of the source.
+#[derive(Default)]
+pub(crate) struct Backpatcher {
+    symbol_table: SymbolTable,
+    /// The number of words encoded so far.
+    words: usize,
+    /// The symbols not known when first used, in order, with the source line
+    /// of their first use and the indices of the words referencing them.
+    forward: Vec<(String, usize, Vec<usize>)>,
+    indices: FxHashMap<String, usize>,
+    /// The source line of each label defined so far.
+    label_lines: FxHashMap<String, usize>,
+    /// The source line of the first instruction past the ROM, if any.
+    overflow_line: Option<usize>,
+}
+
+impl Backpatcher {
+    pub(crate) fn new() -> Self {
+        Self {
+            symbol_table: SymbolTable::new(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the number of words encoded so far.
+    pub(crate) fn len(&self) -> usize {
+        self.words
+    }
+
+    /// Encodes the current instruction of the parser, returning its word, or
+    /// `None` for a label. A reference to a symbol not known yet is encoded
+    /// as a placeholder. Returns an error if the instruction is invalid or a
+    /// label is defined twice.
+    pub(crate) fn encode(&mut self, parser: &Parser) -> Result<Option<u16>, AssemblerError> {
+        let at_line = |error: AssemblerError| error.at(parser.line(), parser.column());
+        let word = match parser.instruction_type()? {
+            InstructionType::L => {
+                let label = parser.symbol()?.into_owned();
+                if let Some(first) = self.label_lines.get(&label) {
+                    return Err(at_line(AssemblerError::semantic(format!(
+                        "label {} is defined twice",
+                        label
+                    )))
+                    .with_note(format!("first defined on line {}", first)));
+                }
+                self.label_lines.insert(label.clone(), parser.line());
+                self.symbol_table.add_label(label, self.words as u32);
+                return Ok(None);
+            }
+            InstructionType::A => {
+                let symbol = parser.symbol()?;
+                let address = symbol
+                    .parse::<u32>()
+                    .ok()
+                    .or_else(|| self.symbol_table.address(&symbol).copied());
+                match address {
+                    Some(address) => a_instruction(address).map_err(at_line)?,
+                    None => {
+                        let forward = &mut self.forward;
+                        let index = *self.indices.entry(symbol.to_string()).or_insert_with(|| {
+                            forward.push((symbol.to_string(), parser.line(), Vec::new()));
+                            forward.len() - 1
+                        });
+                        forward[index].2.push(self.words);
+                        PLACEHOLDER
+                    }
+                }
+            }
+            InstructionType::C => {
+                c_instruction(&parser.dest()?, &parser.comp()?, &parser.jump()?).map_err(at_line)?
+            }
+        };
+        if self.words == ROM_SIZE {
+            self.overflow_line = Some(parser.line());
+        }
+        self.words += 1;
+        Ok(Some(word))
+    }
+
+    /// Resolves the forward references at the end of the source, allocating
+    /// the variables in order of first use. Returns the symbol table and the
+    /// words replacing the placeholders, with their indices, or an error if
+    /// the program doesn't fit in the ROM, a label in an A-instruction or a
+    /// variable in the RAM the limits leave to the variables.
+    pub(crate) fn finish(
+        mut self,
+        limits: &Limits,
+    ) -> Result<(SymbolTable, Vec<(usize, u16)>), AssemblerError> {
+        if self.words > ROM_SIZE {
+            return Err(rom_overflow(self.words).at_line(self.overflow_line));
+        }
+        let mut patches = Vec::new();
+        for (symbol, line, uses) in self.forward {
+            let address = match self.symbol_table.address(&symbol) {
+                Some(address) => *address,
+                None => {
+                    let address = self.symbol_table.add_variable(symbol.clone());
+                    if address > limits.max_variable_address {
+                        return Err(out_of_ram(&symbol, address, limits).at_line(Some(line)));
+                    }
+                    address
+                }
+            };
+            let word = a_instruction(address)?;
+            patches.extend(uses.into_iter().map(|index| (index, word)));
+        }
+        Ok((self.symbol_table, patches))
+    }
+}
+
+/// Returns an error if the program has more instructions than the limits allow.
+pub(crate) fn check_instructions(count: usize, limits: &Limits) -> Result<(), AssemblerError> {
+    if count > limits.max_instructions {
+        return Err(AssemblerError::semantic(format!(
+            "program exceeds the limit of {} instructions",
+            limits.max_instructions
+        )));
+    }
+    Ok(())
+}
+
+/// Returns an error if the program has more symbols than the limits allow.
+pub(crate) fn check_symbols(
+    symbol_table: &SymbolTable,
+    limits: &Limits,
+) -> Result<(), AssemblerError> {
+    if symbol_table.len() > limits.max_symbols {
+        return Err(AssemblerError::semantic(format!(
+            "program exceeds the limit of {} symbols",
+            limits.max_symbols
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the error of a program of `count` instructions, more than the ROM
+/// holds, to be located at the first instruction past the ROM.
+pub(crate) fn rom_overflow(count: usize) -> AssemblerError {
+    AssemblerError::semantic(format!(
+        "program has {} instructions, more than the {} of the ROM",
+        count, ROM_SIZE
+    ))
+    .with_note(format!(
+        "the ROM ends at ROM[{}], and this instruction would take ROM[{}]",
+        ROM_SIZE - 1,
+        ROM_SIZE
+    ))
+}
+
+/// Returns the error of a variable allocated past the RAM the limits leave to
+/// the variables, at the address.
+pub(crate) fn out_of_ram(variable: &str, address: u32, limits: &Limits) -> AssemblerError {
+    let region = match address {
+        16384..=24575 => ", in the screen",
+        24576 => ", the keyboard",
+        24577.. => ", past the end of the RAM",
+        _ => "",
+    };
+    AssemblerError::semantic(format!("out of RAM for variable {}", variable)).with_note(format!(
+        "the variables fit in RAM[16] to RAM[{}], and it would take RAM[{}]{}",
+        limits.max_variable_address, address, region
+    ))
+}
+
+/// Assembles the source in a single traversal: the words are encoded as the
+/// instructions are parsed, the references to symbols not known yet getting
+/// a placeholder. At the end of the source, these symbols are either labels
+/// defined later or variables, allocated in order of first use, and their
+/// placeholders are patched.
+///
+/// Neither the program nor its IR is built, so the passes working on them
+/// have nothing to do.
+///
+/// The errors of the invalid instructions and of the labels defined twice are
+/// recorded, those instructions being left out. Fails if the program exceeds
+/// the instruction or symbol limit.
+pub struct SinglePass;
+
+impl Pass for SinglePass {
+    fn name(&self) -> &'static str {
+        "single-pass"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Parse
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        let mut comments = BlockComments::default();
+        let source = comments.blank(&context.source);
+        let mut parser = Parser::from_source(&source);
+        let mut backpatcher = Backpatcher::new();
+        let mut words = Vec::new();
+
+        while parser.has_more_lines() {
+            parser.advance();
+            match backpatcher.encode(&parser) {
+                Ok(word) => words.extend(word),
+                Err(error) => context.errors.push(error),
+            }
+            if words.len() % CANCELLATION_CHUNK == 0 && context.cancellation.is_cancelled() {
+                return Ok(());
+            }
+            check_instructions(words.len(), &context.limits)?;
+        }
+
+        context.errors.extend(comments.finish().err());
+        let (symbol_table, patches) = backpatcher.finish(&context.limits)?;
+        for (index, word) in patches {
+            words[index] = word;
+        }
+        check_symbols(&symbol_table, &context.limits)?;
+
+        context.symbol_table = symbol_table;
+        context.words = words;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{assembler::Assembler, golden::CORPUS, limits::Limits};
+
+    #[test]
+    fn test_single_pass_matches_two_passes() {
+        for case in CORPUS {
+            // Given
+            let assembler = Assembler::from_source(case.source);
+
+            // When
+            let words = assembler.single_pass().fill_symbol_table().assemble();
+
+            // Then
+            let expected = Assembler::from_source(case.source)
+                .fill_symbol_table()
+                .assemble();
+            assert_eq!(expected, words, "{}", case.name);
+        }
+    }
+
+    #[test]
+    fn test_forward_references_are_patched() {
+        // Given
+        let source = "@i\nM=0\n@END\n0;JMP\n@j\n(END)\n@i\n(i)\n@END\n";
+
+        // When
+        let assembler = Assembler::from_source(source)
+            .single_pass()
+            .fill_symbol_table();
+
+        // Then
+        assert_eq!(Some(&6), assembler.symbol_table().address("i"));
+        assert_eq!(Some(&16), assembler.symbol_table().address("j"));
+        assert_eq!(
+            vec![6, 0b1110_1010_1000_1000, 5, 0b1110_1010_1000_0111, 16, 6, 5],
+            assembler.assemble()
+        );
+    }
+
+    #[test]
+    fn test_variables_past_the_limit_are_errors() {
+        // Given
+        let source = "@a\nM=0\n@SCREEN\nD=A\n@b\nM=D\n@c\nM=D\n@a\n";
+        let limits = Limits {
+            max_variable_address: 17,
+            ..Limits::default()
+        };
+
+        for single_pass in [false, true] {
+            // When
+            let mut assembler = Assembler::from_source(source).with_limits(limits);
+            if single_pass {
+                assembler = assembler.single_pass();
+            }
+            let error = assembler.fill_symbol_table().try_assemble().unwrap_err();
+
+            // Then
+            assert_eq!(Some(7), error.line());
+            assert_eq!(
+                "[\"the variables fit in RAM[16] to RAM[17], and it would take RAM[18]\"]",
+                format!("{:?}", error.to_diagnostics(None, source)[0].notes)
+            );
+            assert!(error.to_string().ends_with("out of RAM for variable c"));
+        }
+    }
+
+    #[test]
+    fn test_programs_past_the_rom_are_errors() {
+        // Given
+        let source = format!("(START)\n{}", "D=0\n".repeat(32770));
+
+        for single_pass in [false, true] {
+            // When
+            let mut assembler = Assembler::from_source(&source);
+            if single_pass {
+                assembler = assembler.single_pass();
+            }
+            let error = assembler.fill_symbol_table().try_assemble().unwrap_err();
+
+            // Then
+            assert_eq!(
+                "line 32770: program has 32770 instructions, more than the 32768 of the ROM",
+                error.to_string()
+            );
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::path::{Path, PathBuf};
+
+use glob::MatchOptions;
+use rayon::prelude::*;
+
+use crate::{error::AssemblerError, vm::files_with_extension};
+
+/// The outcome of processing one of many inputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchOutcome<T> {
+    pub input: PathBuf,
+    /// The value returned for the input.
+    pub result: T,
+}
+
+/// The characters making a path a glob pattern.
+const WILDCARDS: [char; 3] = ['*', '?', '['];
+
+/// Returns whether the path is a glob pattern, such as `projects/**/*.asm`,
+/// rather than the path of an existing file.
+pub fn is_pattern(path: &Path) -> bool {
+    !path.exists() && path.to_string_lossy().contains(WILDCARDS)
+}
+
+/// Returns the directory searched by the pattern, made of its components
+/// before the first wildcard.
+pub fn pattern_base(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|component| !component.as_os_str().to_string_lossy().contains(WILDCARDS))
+        .collect()
+}
+
+/// Returns the paths matching the pattern in path order, or the path itself
+/// if it's not a pattern. Like the recursive search, the wildcards don't
+/// match hidden files and directories.
+///
+/// Returns a syntax error if the pattern is invalid, and an IO error if it
+/// matches nothing or a directory can't be read.
+fn expand(path: &Path) -> Result<Vec<PathBuf>, AssemblerError> {
+    if !is_pattern(path) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let pattern = path.to_string_lossy();
+    let options = MatchOptions {
+        require_literal_leading_dot: true,
+        ..MatchOptions::new()
+    };
+    let paths = glob::glob_with(&pattern, options)
+        .map_err(|error| AssemblerError::syntax(format!("invalid pattern {}: {}", pattern, error)))?
+        .map(|path| {
+            path.map_err(|error| {
+                let context = format!("failed to read {}", error.path().display());
+                AssemblerError::io(context, error.into())
+            })
+        })
+        .collect::<Result<Vec<PathBuf>, AssemblerError>>()?;
+    if paths.is_empty() {
+        return Err(AssemblerError::io(
+            format!("failed to read {}", pattern),
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no input matches the pattern"),
+        ));
+    }
+    Ok(paths)
+}
+
+/// Returns the inputs, the glob patterns being replaced by their matches and
+/// the directories by their `.asm` files, in name order.
+///
+/// Returns a syntax error if a pattern is invalid, and an IO error if a
+/// pattern matches nothing or a directory can't be read.
+pub fn inputs(paths: &[PathBuf]) -> Result<Vec<PathBuf>, AssemblerError> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        for path in expand(path)? {
+            inputs.extend(files_with_extension(&path, "asm")?);
+        }
+    }
+    Ok(inputs)
+}
+
+/// Returns the inputs, the glob patterns being replaced by their matches and
+/// the directories by the `.asm` files found in them and their
+/// subdirectories, in path order. The hidden subdirectories, such as `.git`,
+/// are skipped.
+///
+/// Returns a syntax error if a pattern is invalid, and an IO error if a
+/// pattern matches nothing or a directory can't be read.
+pub fn inputs_recursive(paths: &[PathBuf]) -> Result<Vec<PathBuf>, AssemblerError> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        for path in expand(path)? {
+            match path.is_dir() {
+                true => asm_files(&path, &mut inputs)?,
+                false => inputs.push(path),
+            }
+        }
+    }
+    Ok(inputs)
+}
+
+/// Appends the `.asm` files of the directory and its subdirectories.
+fn asm_files(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), AssemblerError> {
+    let context = || format!("failed to read {}", directory.display());
+    let mut paths = std::fs::read_dir(directory)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<PathBuf>>>()
+        })
+        .map_err(|error| AssemblerError::io(context(), error))?;
+    paths.sort();
+    for path in paths {
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if path.is_dir() && !hidden {
+            asm_files(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "asm") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Runs the function on each input concurrently. The outcomes are in the
+/// order of the inputs.
+pub fn map<T: Send>(inputs: &[PathBuf], f: impl Fn(&Path) -> T + Sync) -> Vec<BatchOutcome<T>> {
+    inputs
+        .par_iter()
+        .map(|input| BatchOutcome {
+            input: input.clone(),
+            result: f(input),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+
+    #[test]
+    fn test_map_returns_the_result_of_each_input() {
+        // Given
+        let inputs = inputs(&[
+            PathBuf::from("test_data/rect"),
+            PathBuf::from("test_data/missing.asm"),
+        ])
+        .unwrap();
+
+        // When
+        let outcomes = map(&inputs, |input| {
+            Assembler::open(input.to_path_buf())
+                .and_then(|assembler| assembler.fill_symbol_table().try_assemble())
+                .map(|words| words.len())
+        });
+
+        // Then
+        let results: Vec<(&Path, Option<usize>)> = outcomes
+            .iter()
+            .map(|outcome| {
+                (
+                    outcome.input.as_path(),
+                    outcome.result.as_ref().ok().copied(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (Path::new("test_data/rect/Rect.asm"), Some(26)),
+                (Path::new("test_data/rect/RectL.asm"), Some(26)),
+                (Path::new("test_data/missing.asm"), None),
+            ],
+            results
+        );
+    }
+
+    #[test]
+    fn test_inputs_recursive_finds_nested_files() {
+        // Given
+        let paths = [PathBuf::from("test_data"), PathBuf::from("missing.asm")];
+
+        // When
+        let inputs = inputs_recursive(&paths).unwrap();
+
+        // Then
+        assert_eq!(
+            vec![
+                "test_data/add/Add.asm",
+                "test_data/max/Max.asm",
+                "test_data/max/MaxL.asm",
+                "test_data/pong/Pong.asm",
+                "test_data/pong/PongL.asm",
+                "test_data/rect/Rect.asm",
+                "test_data/rect/RectL.asm",
+                "missing.asm",
+            ],
+            inputs
+                .iter()
+                .map(|input| input.to_string_lossy())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_inputs_expand_patterns() {
+        // Given
+        let pattern = PathBuf::from("test_data/**/*L.asm");
+
+        // When
+        let inputs = inputs(std::slice::from_ref(&pattern)).unwrap();
+
+        // Then
+        assert_eq!(
+            vec![
+                PathBuf::from("test_data/max/MaxL.asm"),
+                PathBuf::from("test_data/pong/PongL.asm"),
+                PathBuf::from("test_data/rect/RectL.asm"),
+            ],
+            inputs
+        );
+        assert_eq!(PathBuf::from("test_data"), pattern_base(&pattern));
+    }
+
+    #[test]
+    fn test_inputs_pattern_matching_nothing() {
+        // Given
+        let pattern = PathBuf::from("test_data/**/*.missing");
+
+        // When
+        let error = inputs(&[pattern]).unwrap_err();
+
+        // Then
+        assert_eq!(
+            "failed to read test_data/**/*.missing: no input matches the pattern",
+            error.to_string()
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    cfg::{Cfg, EdgeKind},
+    ir::Ir,
+};
+
+/// The name of the code before the first function, such as the bootstrap.
+pub const ENTRY: &str = "<entry>";
+
+/// A function of a program following the VM conventions, from its label to
+/// the label of the next function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    /// The ROM address of the first instruction.
+    pub address: u32,
+    /// The number of instructions of the function.
+    pub instructions: usize,
+    /// The number of call sites of each callee.
+    pub calls: BTreeMap<String, usize>,
+}
+
+/// The call graph of a program translated from VM code, in ROM order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallGraph {
+    pub functions: Vec<Function>,
+}
+
+/// Returns whether a label names a function: `Foo.bar`, as opposed to the
+/// `Foo.bar$label` labels within functions and their return addresses.
+pub fn is_function_label(label: &str) -> bool {
+    label.contains('.') && !label.contains('$')
+}
+
+impl CallGraph {
+    /// Extracts the call graph of the IR. A call is a jump straight to a
+    /// function label, and a function ends where the next one starts.
+    pub fn new(ir: &Ir) -> Self {
+        let mut graph = CallGraph::default();
+        // The index of the function of each node.
+        let mut owners = Vec::with_capacity(ir.nodes.len());
+        for node in &ir.nodes {
+            let label = node.labels.iter().find(|label| is_function_label(label));
+            if label.is_some() || graph.functions.is_empty() {
+                graph.functions.push(Function {
+                    name: label.map_or(ENTRY, String::as_str).to_string(),
+                    address: node.address,
+                    instructions: 0,
+                    calls: BTreeMap::new(),
+                });
+            }
+            let owner = graph.functions.len() - 1;
+            graph.functions[owner].instructions += 1;
+            owners.push(owner);
+        }
+
+        let cfg = Cfg::new(ir);
+        for edge in cfg.edges.iter().filter(|edge| edge.kind == EdgeKind::Jump) {
+            let callee = cfg.blocks[edge.to]
+                .labels
+                .iter()
+                .find(|label| is_function_label(label));
+            if let Some(callee) = callee {
+                let caller = owners[cfg.blocks[edge.from].nodes.start];
+                *graph.functions[caller]
+                    .calls
+                    .entry(callee.clone())
+                    .or_default() += 1;
+            }
+        }
+        graph
+    }
+
+    /// Returns the function with the name.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+}
+
+/// Lists the functions with their instruction counts and callees.
+impl fmt::Display for CallGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for function in &self.functions {
+            writeln!(
+                f,
+                "{} @{} ({} instructions)",
+                function.name, function.address, function.instructions
+            )?;
+            for (callee, count) in &function.calls {
+                writeln!(f, "    -> {} x{}", callee, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{program::Program, vm};
+
+    #[test]
+    fn test_call_graph_of_translated_program() {
+        // Given
+        let source = "function Sys.init 0\ncall Main.main 0\ncall Main.main 0\nlabel HALT\ngoto HALT\nfunction Main.main 0\npush constant 1\nreturn\n";
+        let assembly =
+            vm::translate(&[(String::from("Sys"), vm::parse("Sys.vm", source).unwrap())]);
+        let program = Program::from_source(&assembly);
+        let ir = Ir::lower(&program, &program.resolve());
+
+        // When
+        let graph = CallGraph::new(&ir);
+
+        // Then
+        let names: Vec<&str> = graph
+            .functions
+            .iter()
+            .map(|function| function.name.as_str())
+            .collect();
+        assert_eq!(vec![ENTRY, "Sys.init", "Main.main"], names);
+        assert_eq!(
+            BTreeMap::from([(String::from("Sys.init"), 1)]),
+            graph.functions[0].calls
+        );
+        assert_eq!(
+            BTreeMap::from([(String::from("Main.main"), 2)]),
+            graph.function("Sys.init").unwrap().calls
+        );
+        assert_eq!(
+            ir.nodes.len(),
+            graph
+                .functions
+                .iter()
+                .map(|function| function.instructions)
+                .sum::<usize>()
+        );
+    }
+}
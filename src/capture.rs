@@ -0,0 +1,92 @@
+use std::io::Write;
+
+use crate::screen::{pixel, HEIGHT, WIDTH};
+
+/// The GIF palette, white for the index 0 and black for the index 1.
+const PALETTE: [u8; 6] = [0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00];
+
+/// Writes the screen memory as a black and white PNG image.
+pub fn write_png(ram: &[u16], writer: impl Write) {
+    let mut encoder = png::Encoder::new(writer, WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::One);
+
+    // Rows are packed 8 pixels per byte, the leftmost in the most significant
+    // bit, and a set bit is white.
+    let mut data = vec![0u8; WIDTH / 8 * HEIGHT];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            if !pixel(ram, x, y) {
+                data[y * WIDTH / 8 + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+
+    let mut writer = encoder.write_header().expect("failed to write PNG header");
+    writer
+        .write_image_data(&data)
+        .expect("failed to write PNG data");
+}
+
+/// Records successive screen frames as an animated GIF.
+pub struct GifRecorder<W: Write> {
+    encoder: gif::Encoder<W>,
+    /// The delay between frames, in hundredths of a second.
+    delay: u16,
+}
+
+impl<W: Write> GifRecorder<W> {
+    /// Returns a recorder writing a looping GIF played at the given frame rate.
+    pub fn new(writer: W, fps: u32) -> Self {
+        let mut encoder = gif::Encoder::new(writer, WIDTH as u16, HEIGHT as u16, &PALETTE)
+            .expect("failed to write GIF header");
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .expect("failed to write GIF header");
+        Self {
+            encoder,
+            delay: (100 / fps.max(1)).max(1) as u16,
+        }
+    }
+
+    /// Adds the current screen memory as the next frame.
+    pub fn add_frame(&mut self, ram: &[u16]) {
+        let mut pixels = vec![0u8; WIDTH * HEIGHT];
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                pixels[y * WIDTH + x] = pixel(ram, x, y) as u8;
+            }
+        }
+
+        let mut frame = gif::Frame::from_indexed_pixels(WIDTH as u16, HEIGHT as u16, pixels, None);
+        frame.delay = self.delay;
+        self.encoder
+            .write_frame(&frame)
+            .expect("failed to write GIF frame");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{emulator::RAM_SIZE, screen::SCREEN};
+
+    #[test]
+    fn test_write_png() {
+        // Given
+        let mut ram = vec![0; RAM_SIZE];
+        ram[SCREEN] = 1;
+        let mut output = Vec::new();
+
+        // When
+        write_png(&ram, &mut output);
+
+        // Then
+        let decoder = png::Decoder::new(std::io::Cursor::new(output));
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut data).unwrap();
+        assert_eq!(0x7F, data[0]);
+        assert_eq!(0xFF, data[1]);
+    }
+}
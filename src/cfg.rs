@@ -0,0 +1,305 @@
+use std::{collections::HashSet, fmt::Write, ops::Range};
+
+use crate::ir::{Ir, IrInstruction, IrNode};
+
+/// How control flows from a block to another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// To the next block, when the block doesn't end with an unconditional jump.
+    Fallthrough,
+    /// To the block whose address is loaded into A right before the jump.
+    Jump,
+    /// To a block whose label is loaded somewhere, when the jump address is
+    /// computed at runtime.
+    Indirect,
+}
+
+/// An edge of the control-flow graph, between block indices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions always executed in sequence: it's only entered at its
+/// first instruction, and only its last instruction may jump.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// The indices of the nodes of the block in the IR.
+    pub nodes: Range<usize>,
+    /// The ROM address of the first instruction.
+    pub address: u32,
+    /// The labels of the first instruction.
+    pub labels: Vec<String>,
+}
+
+/// The control-flow graph of a program, the first block being the entry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+impl Cfg {
+    /// Builds the control-flow graph of the IR.
+    ///
+    /// A block starts at the first instruction, at a label, after a jump, or
+    /// at a numeric jump target. A jump whose address can't be traced back to
+    /// an A-instruction of its block may go to any block with a loaded label.
+    pub fn new(ir: &Ir) -> Self {
+        let nodes = &ir.nodes;
+        let targets: Vec<Option<JumpTarget>> = (0..nodes.len())
+            .map(|index| jump_target(nodes, index))
+            .collect();
+
+        let mut leaders: HashSet<usize> = HashSet::from([0]);
+        for (index, node) in nodes.iter().enumerate() {
+            if !node.labels.is_empty() {
+                leaders.insert(index);
+            }
+            if is_jump(node) {
+                leaders.insert(index + 1);
+            }
+            if let Some(JumpTarget::Address(address)) = targets[index] {
+                leaders.extend(nodes.iter().position(|node| node.address == address));
+            }
+        }
+        let mut starts: Vec<usize> = leaders
+            .into_iter()
+            .filter(|index| *index < nodes.len())
+            .collect();
+        starts.sort_unstable();
+
+        let mut cfg = Cfg::default();
+        for (position, start) in starts.iter().enumerate() {
+            let end = starts.get(position + 1).copied().unwrap_or(nodes.len());
+            cfg.blocks.push(BasicBlock {
+                nodes: *start..end,
+                address: nodes[*start].address,
+                labels: nodes[*start].labels.clone(),
+            });
+        }
+
+        let symbols: HashSet<&str> = nodes
+            .iter()
+            .filter_map(|node| match &node.instruction {
+                IrInstruction::A {
+                    symbol: Some(symbol),
+                    ..
+                } => Some(symbol.as_str()),
+                _ => None,
+            })
+            .collect();
+        let loaded: Vec<usize> = (0..cfg.blocks.len())
+            .filter(|index| {
+                cfg.blocks[*index]
+                    .labels
+                    .iter()
+                    .any(|label| symbols.contains(label.as_str()))
+            })
+            .collect();
+        for (from, block) in cfg.blocks.iter().enumerate() {
+            let last = block.nodes.end - 1;
+            let unconditional = matches!(
+                &nodes[last].instruction,
+                IrInstruction::C { jump, .. } if jump == "JMP"
+            );
+            if is_jump(&nodes[last]) {
+                match targets[last] {
+                    Some(JumpTarget::Address(address)) => {
+                        let to = cfg.blocks.iter().position(|block| block.address == address);
+                        cfg.edges.extend(to.map(|to| Edge {
+                            from,
+                            to,
+                            kind: EdgeKind::Jump,
+                        }));
+                    }
+                    _ => cfg.edges.extend(loaded.iter().map(|to| Edge {
+                        from,
+                        to: *to,
+                        kind: EdgeKind::Indirect,
+                    })),
+                }
+            }
+            if !unconditional && from + 1 < cfg.blocks.len() {
+                cfg.edges.push(Edge {
+                    from,
+                    to: from + 1,
+                    kind: EdgeKind::Fallthrough,
+                });
+            }
+        }
+        cfg
+    }
+
+    /// Returns the index of the block containing the node at the index.
+    pub fn block_of(&self, node: usize) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.nodes.contains(&node))
+    }
+
+    /// Returns the edges leaving the block.
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> + '_ {
+        self.edges.iter().filter(move |edge| edge.from == block)
+    }
+
+    /// Returns the edges entering the block.
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge> + '_ {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+
+    /// Returns whether each block is reachable from the entry block.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut pending: Vec<usize> = (!self.blocks.is_empty()).then_some(0).into_iter().collect();
+        while let Some(block) = pending.pop() {
+            if std::mem::replace(&mut reachable[block], true) {
+                continue;
+            }
+            pending.extend(self.successors(block).map(|edge| edge.to));
+        }
+        reachable
+    }
+
+    /// Renders the graph in the Graphviz DOT language, each block listing its
+    /// instructions.
+    pub fn to_dot(&self, ir: &Ir) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        for (index, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for label_name in &block.labels {
+                let _ = write!(label, "({})\\l", label_name);
+            }
+            for node in &ir.nodes[block.nodes.clone()] {
+                let _ = write!(label, "{:>5} {}\\l", node.address, node.instruction);
+            }
+            let _ = writeln!(dot, "    b{} [label=\"{}\"];", index, label);
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Fallthrough => "solid",
+                EdgeKind::Jump => "bold",
+                EdgeKind::Indirect => "dashed",
+            };
+            let _ = writeln!(dot, "    b{} -> b{} [style={}];", edge.from, edge.to, style);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Where a jump goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum JumpTarget {
+    Address(u32),
+    Computed,
+}
+
+fn is_jump(node: &IrNode) -> bool {
+    matches!(&node.instruction, IrInstruction::C { jump, .. } if !jump.is_empty())
+}
+
+/// Returns the target of the jump at the index.
+fn jump_target(nodes: &[IrNode], index: usize) -> Option<JumpTarget> {
+    if !is_jump(&nodes[index]) {
+        return None;
+    }
+    match jump_load(nodes, index) {
+        Some(IrInstruction::A { value, .. }) => Some(JumpTarget::Address(*value)),
+        _ => Some(JumpTarget::Computed),
+    }
+}
+
+/// Returns the A-instruction loading the address of the jump at the index, by
+/// finding the last instruction writing A before it in the same straight-line
+/// code, or `None` if the address is computed.
+fn jump_load(nodes: &[IrNode], index: usize) -> Option<&IrInstruction> {
+    for current in (0..index).rev() {
+        let node = &nodes[current];
+        if is_jump(node) {
+            break;
+        }
+        match &node.instruction {
+            instruction @ IrInstruction::A { .. } => return Some(instruction),
+            IrInstruction::C { dest, .. } if dest.contains('A') => return None,
+            _ => {}
+        }
+        if !node.labels.is_empty() {
+            break;
+        }
+    }
+    None
+}
+
+/// Returns whether a jump of the IR goes to a numeric address, such as `@5`
+/// then `0;JMP`, rather than to a label. Such an address can't be told apart
+/// from a constant, so the instructions can't move without breaking the jump.
+pub fn has_numeric_jumps(ir: &Ir) -> bool {
+    (0..ir.nodes.len()).any(|index| {
+        is_jump(&ir.nodes[index])
+            && matches!(
+                jump_load(&ir.nodes, index),
+                Some(IrInstruction::A { symbol: None, .. })
+            )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+
+    #[test]
+    fn test_cfg() {
+        // Given
+        let source = "@R0\nD=M\n@END\nD;JLE\n@ret\nD=A\n@R1\nM=D\n@R1\nA=M\n0;JMP\n(ret)\nD=0\n(END)\n@END\n0;JMP\n";
+        let program = Program::from_source(source);
+        let symbol_table = program.resolve();
+        let ir = Ir::lower(&program, &symbol_table);
+
+        // When
+        let cfg = Cfg::new(&ir);
+
+        // Then
+        let starts: Vec<u32> = cfg.blocks.iter().map(|block| block.address).collect();
+        assert_eq!(vec![0, 4, 11, 12], starts);
+        assert_eq!(
+            vec![
+                Edge {
+                    from: 0,
+                    to: 3,
+                    kind: EdgeKind::Jump
+                },
+                Edge {
+                    from: 0,
+                    to: 1,
+                    kind: EdgeKind::Fallthrough
+                },
+                Edge {
+                    from: 1,
+                    to: 2,
+                    kind: EdgeKind::Indirect
+                },
+                Edge {
+                    from: 1,
+                    to: 3,
+                    kind: EdgeKind::Indirect
+                },
+                Edge {
+                    from: 2,
+                    to: 3,
+                    kind: EdgeKind::Fallthrough
+                },
+                Edge {
+                    from: 3,
+                    to: 3,
+                    kind: EdgeKind::Jump
+                },
+            ],
+            cfg.edges
+        );
+        assert_eq!(vec![true; 4], cfg.reachable());
+    }
+}
@@ -1,61 +1,166 @@
-/// Convert Hack assembly language A-instruction to binary
-pub fn a_value_to_binary(instruction: String) -> String {
-    let val = str::parse::<u32>(&instruction).expect("failed to parse A instruction");
-    format!("{:016b}", val)
+use crate::{error::AssemblerError, suggest::closest};
+
+/// The bits set in every C-instruction.
+const C_PREFIX: u16 = 0b111 << 13;
+
+/// Encodes an A-instruction loading the value, or returns an error if the
+/// value doesn't fit in 15 bits.
+pub fn a_instruction(value: u32) -> Result<u16, AssemblerError> {
+    if value >= 0x8000 {
+        return Err(
+            AssemblerError::semantic(format!("constant {} is out of range", value))
+                .with_note("A-instructions load constants from 0 to 32767"),
+        );
+    }
+    Ok(value as u16)
+}
+
+/// Encodes a C-instruction from its mnemonics, or returns an error if the
+/// comp or the jump is invalid.
+pub fn c_instruction(dest: &str, comp: &str, jump: &str) -> Result<u16, AssemblerError> {
+    Ok(C_PREFIX | comp_bits(comp)? << 6 | dest_bits(dest)? << 3 | jump_bits(jump)?)
+}
+
+/// Convert Hack assembly language C-instruction dest part to its 3 bits, or
+/// returns an error if the dest isn't made of A, D and M, each at most once,
+/// in any order.
+pub fn dest_bits(dest: &str) -> Result<u16, AssemblerError> {
+    let mut bits = 0;
+    let mut valid = true;
+    for register in dest.chars() {
+        let bit = match register {
+            'M' => 1,
+            'D' => 2,
+            'A' => 4,
+            _ => 0,
+        };
+        valid &= bit != 0 && bits & bit == 0;
+        bits |= bit;
+    }
+    if valid {
+        return Ok(bits);
+    }
+    let error = AssemblerError::syntax(format!("unexpected dest {}", dest))
+        .with_note("dest is made of A, D and M, each at most once");
+    Err(match bits {
+        0 => error,
+        _ => error.with_suggestion(DEST[bits as usize]),
+    })
+}
+
+/// The comp mnemonics and their binary encoding, `a` bit included.
+pub(crate) const COMP: [(&str, u16); 28] = [
+    ("0", 0b0101010),
+    ("1", 0b0111111),
+    ("-1", 0b0111010),
+    ("D", 0b0001100),
+    ("A", 0b0110000),
+    ("!D", 0b0001101),
+    ("!A", 0b0110001),
+    ("-D", 0b0001111),
+    ("-A", 0b0110011),
+    ("D+1", 0b0011111),
+    ("A+1", 0b0110111),
+    ("D-1", 0b0001110),
+    ("A-1", 0b0110010),
+    ("D+A", 0b0000010),
+    ("D-A", 0b0010011),
+    ("A-D", 0b0000111),
+    ("D&A", 0b0000000),
+    ("D|A", 0b0010101),
+    ("M", 0b1110000),
+    ("!M", 0b1110001),
+    ("-M", 0b1110011),
+    ("M+1", 0b1110111),
+    ("M-1", 0b1110010),
+    ("D+M", 0b1000010),
+    ("D-M", 0b1010011),
+    ("M-D", 0b1000111),
+    ("D&M", 0b1000000),
+    ("D|M", 0b1010101),
+];
+
+/// The dest mnemonics, indexed by their binary encoding.
+pub(crate) const DEST: [&str; 8] = ["", "M", "D", "MD", "A", "AM", "AD", "AMD"];
+
+/// The jump mnemonics, indexed by their binary encoding.
+pub(crate) const JUMP: [&str; 8] = ["", "JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"];
+
+/// Convert Hack assembly language C-instruction comp part to its 7 bits, or
+/// returns an error if the comp is invalid.
+pub fn comp_bits(comp: &str) -> Result<u16, AssemblerError> {
+    COMP.iter()
+        .find(|(mnemonic, _)| *mnemonic == comp)
+        .map(|(_, bits)| *bits)
+        .ok_or_else(|| {
+            let error = AssemblerError::syntax(format!("unexpected comp {}", comp));
+            match suggested_comp(comp) {
+                Some(suggestion) => error.with_suggestion(suggestion),
+                None => error,
+            }
+        })
+}
+
+/// Convert Hack assembly language C-instruction jump part to its 3 bits, or
+/// returns an error if the jump is invalid.
+pub fn jump_bits(jump: &str) -> Result<u16, AssemblerError> {
+    JUMP.iter()
+        .position(|mnemonic| *mnemonic == jump)
+        .map(|bits| bits as u16)
+        .ok_or_else(|| {
+            let error = AssemblerError::syntax(format!("unexpected jump {}", jump));
+            match suggested_jump(jump) {
+                Some(suggestion) => error.with_suggestion(suggestion),
+                None => error,
+            }
+        })
 }
 
-/// Convert Hack assembly language C-instruction dest part to binary
-pub fn dest_to_binary(instruction: String) -> String {
-    let mut dest: u8 = if instruction.contains('M') { 1 } else { 0 };
-    dest += if instruction.contains('D') { 2 } else { 0 };
-    dest += if instruction.contains('A') { 4 } else { 0 };
+/// Returns the valid comp with the operands of the invalid comp swapped, such
+/// as `D+M` for `M+D`, if any.
+pub fn commuted_comp(comp: &str) -> Option<&'static str> {
+    let (x, operator, y) = comp
+        .find(['+', '&', '|'])
+        .map(|index| (&comp[..index], &comp[index..index + 1], &comp[index + 1..]))?;
+    let commuted = format!("{}{}{}", y, operator, x);
+    COMP.iter()
+        .map(|(mnemonic, _)| *mnemonic)
+        .find(|mnemonic| *mnemonic == commuted)
+}
+
+/// Returns the valid comp the invalid comp most likely stands for: the comp
+/// with its operands swapped, or else the comp one character away, such as
+/// `D+M` for `D+M1`.
+pub fn suggested_comp(comp: &str) -> Option<&'static str> {
+    commuted_comp(comp).or_else(|| closest(comp, COMP.iter().map(|(mnemonic, _)| *mnemonic), 1))
+}
+
+/// Returns the jump one character away from the invalid jump, such as `JGT`
+/// for `JGTT`, if any.
+pub fn suggested_jump(jump: &str) -> Option<&'static str> {
+    closest(
+        jump,
+        JUMP.into_iter().filter(|mnemonic| !mnemonic.is_empty()),
+        1,
+    )
+}
 
-    format!("{:03b}", dest)
+/// Convert the 7 comp bits of a C-instruction, `a` bit included, to its mnemonic.
+/// Returns `None` if the bits don't encode a valid comp.
+pub fn binary_to_comp(bits: u16) -> Option<&'static str> {
+    COMP.iter()
+        .find(|(_, encoding)| *encoding == bits)
+        .map(|(mnemonic, _)| *mnemonic)
 }
 
-/// Convert Hack assembly language C-instruction comp part to binary
-pub fn comp_to_binary(instruction: String) -> String {
-    let prefix = if instruction.contains('M') { "1" } else { "0" }.to_string();
-    let comp = match instruction.as_str() {
-        "0" => "101010",
-        "1" => "111111",
-        "-1" => "111010",
-        "D" => "001100",
-        "A" | "M" => "110000",
-        "!D" => "001101",
-        "!A" | "!M" => "110001",
-        "-D" => "001111",
-        "-A" | "-M" => "110011",
-        "D+1" => "011111",
-        "A+1" | "M+1" => "110111",
-        "D-1" => "001110",
-        "A-1" | "M-1" => "110010",
-        "D+A" | "D+M" => "000010",
-        "D-A" | "D-M" => "010011",
-        "A-D" | "M-D" => "000111",
-        "D&A" | "D&M" => "000000",
-        "D|A" | "D|M" => "010101",
-        _ => panic!("unexpected comp"),
-    };
-
-    prefix + comp
+/// Convert the 3 dest bits of a C-instruction to its mnemonic.
+pub fn binary_to_dest(bits: u16) -> &'static str {
+    DEST[(bits & 0b111) as usize]
 }
 
-/// Convert Hack assembly language C-instruction jump part to binary
-pub fn jump_to_binary(instruction: String) -> String {
-    let dest = match instruction.as_str() {
-        "" => "000",
-        "JGT" => "001",
-        "JEQ" => "010",
-        "JGE" => "011",
-        "JLT" => "100",
-        "JNE" => "101",
-        "JLE" => "110",
-        "JMP" => "111",
-        _ => panic!("unexpected dest"),
-    };
-
-    dest.to_string()
+/// Convert the 3 jump bits of a C-instruction to its mnemonic.
+pub fn binary_to_jump(bits: u16) -> &'static str {
+    JUMP[(bits & 0b111) as usize]
 }
 
 #[cfg(test)]
@@ -63,14 +168,61 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_a_value_to_binary() {
-        //Given
-        let value = String::from("12345");
+    fn test_encode_instructions() {
+        // When
+        let load = a_instruction(12345).unwrap();
+        let compute = c_instruction("AM", "M-1", "JGT").unwrap();
+
+        // Then
+        assert_eq!(0b0011_0000_0011_1001, load);
+        assert_eq!(0b1111_1100_1010_1001, compute);
+        assert_eq!(Some("M-1"), binary_to_comp(0b1110010));
+    }
+
+    #[test]
+    fn test_invalid_instructions_are_errors() {
+        // When
+        let load = a_instruction(0x8000);
+        let compute = c_instruction("D", "D+X", "");
+        let jump = c_instruction("", "0", "JMPX");
 
+        // Then
+        assert!(matches!(load, Err(AssemblerError::Semantic { .. })));
+        assert_eq!("unexpected comp D+X", compute.unwrap_err().to_string());
+        assert_eq!("unexpected jump JMPX", jump.unwrap_err().to_string());
+        assert_eq!(Some("D+M"), commuted_comp("M+D"));
+        assert_eq!(None, commuted_comp("D+X"));
+    }
+
+    #[test]
+    fn test_suggestions() {
         // When
-        let binary = a_value_to_binary(value);
+        let comp = c_instruction("D", "D+M1", "").unwrap_err();
+        let jump = c_instruction("", "0", "JGTT").unwrap_err();
 
         // Then
-        assert_eq!("0011000000111001", binary);
+        assert!(matches!(
+            comp,
+            AssemblerError::Syntax { suggestion: Some(suggestion), .. } if suggestion == "D+M"
+        ));
+        assert!(matches!(
+            jump,
+            AssemblerError::Syntax { suggestion: Some(suggestion), .. } if suggestion == "JGT"
+        ));
+        assert_eq!(Some("D|M"), suggested_comp("M|D"));
+        assert_eq!(
+            Ok(0b011),
+            dest_bits("DM").map_err(|error| error.to_string())
+        );
+        assert_eq!(
+            Err(String::from("unexpected dest X")),
+            dest_bits("X").map_err(|error| error.to_string())
+        );
+        assert!(matches!(
+            dest_bits("MM"),
+            Err(AssemblerError::Syntax { suggestion: Some(suggestion), .. }) if suggestion == "M"
+        ));
+        assert_eq!(None, suggested_comp("D*M*A"));
+        assert_eq!(None, suggested_jump("J"));
     }
 }
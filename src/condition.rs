@@ -0,0 +1,293 @@
+use std::fmt;
+
+use crate::{emulator::Emulator, error::AssemblerError, symbol_table::SymbolTable};
+
+/// A token of a condition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(i32),
+    Name(String),
+    Operator(&'static str),
+}
+
+/// The operators, the two-character ones first so that they match before their prefix.
+const OPERATORS: [&str; 18] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "&", "|", "!", "(", ")", "[", "]",
+];
+
+/// A node of the expression tree of a condition.
+#[derive(Clone, Debug)]
+enum Expression {
+    Constant(i32),
+    A,
+    D,
+    Pc,
+    /// `M`, the RAM cell addressed by A.
+    M,
+    /// `RAM[address]`.
+    Ram(Box<Expression>),
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+}
+
+/// A condition over the registers and the RAM, such as `D < 0` or
+/// `RAM[SP] > 2048 && A == LCL`.
+///
+/// Values are signed 16-bit words, symbols evaluate to their address,
+/// comparisons and logical operators evaluate to 1 or 0.
+#[derive(Clone, Debug)]
+pub struct Condition {
+    source: String,
+    expression: Expression,
+}
+
+impl Condition {
+    /// Parses a condition, resolving its symbols with the symbol table.
+    ///
+    /// # Errors
+    ///
+    /// Returns a syntax error if the condition is invalid, and a semantic
+    /// error if it uses an unknown symbol.
+    pub fn parse(source: &str, symbol_table: &SymbolTable) -> Result<Self, AssemblerError> {
+        let tokens = tokenize(source)?;
+        let mut parser = ConditionParser {
+            tokens: &tokens,
+            position: 0,
+            symbol_table,
+        };
+        let expression = parser.binary(0)?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(AssemblerError::syntax(format!(
+                "unexpected {:?} in condition {}",
+                token, source
+            )));
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            expression,
+        })
+    }
+
+    /// Returns whether the condition holds for the current state of the emulator.
+    pub fn is_true(&self, emulator: &Emulator) -> bool {
+        evaluate(&self.expression, emulator) != 0
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, AssemblerError> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        let length = if let Some(operator) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Operator(operator));
+            operator.len()
+        } else {
+            let length = rest
+                .find(|c: char| !(c.is_alphanumeric() || "_.$:".contains(c)))
+                .unwrap_or(rest.len());
+            if length == 0 {
+                return Err(AssemblerError::syntax(format!(
+                    "unexpected character in condition {}",
+                    source
+                )));
+            }
+            let word = &rest[..length];
+            tokens.push(match word.parse::<i32>() {
+                Ok(number) => Token::Number(number),
+                Err(_) => Token::Name(word.to_string()),
+            });
+            length
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// The binary operators by increasing precedence.
+const PRECEDENCE: [&[&str]; 6] = [
+    &["||"],
+    &["&&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["+", "-", "|"],
+    &["*", "&"],
+];
+
+struct ConditionParser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    symbol_table: &'a SymbolTable,
+}
+
+impl ConditionParser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, operator: &str) -> Result<(), AssemblerError> {
+        match self.next() {
+            Some(Token::Operator(op)) if op == operator => Ok(()),
+            token => Err(AssemblerError::syntax(format!(
+                "expected {} in condition, found {:?}",
+                operator, token
+            ))),
+        }
+    }
+
+    /// Parses the binary operators of the given precedence level and above.
+    fn binary(&mut self, level: usize) -> Result<Expression, AssemblerError> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Operator(op)) = self.tokens.get(self.position) {
+            if !PRECEDENCE[level].contains(op) {
+                break;
+            }
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            left = Expression::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, AssemblerError> {
+        let expression = match self.next() {
+            Some(Token::Operator("-")) => Expression::Negate(Box::new(self.unary()?)),
+            Some(Token::Operator("!")) => Expression::Not(Box::new(self.unary()?)),
+            Some(Token::Operator("(")) => {
+                let expression = self.binary(0)?;
+                self.expect(")")?;
+                expression
+            }
+            Some(Token::Number(number)) => Expression::Constant(number),
+            Some(Token::Name(name)) => match name.as_str() {
+                "A" => Expression::A,
+                "D" => Expression::D,
+                "PC" => Expression::Pc,
+                "M" => Expression::M,
+                "RAM" => {
+                    self.expect("[")?;
+                    let address = self.binary(0)?;
+                    self.expect("]")?;
+                    Expression::Ram(Box::new(address))
+                }
+                symbol => {
+                    let address = self.symbol_table.address(symbol).ok_or_else(|| {
+                        AssemblerError::semantic(format!("unknown symbol {} in condition", symbol))
+                    })?;
+                    Expression::Constant(*address as i32)
+                }
+            },
+            token => {
+                return Err(AssemblerError::syntax(format!(
+                    "expected a value in condition, found {:?}",
+                    token
+                )))
+            }
+        };
+        Ok(expression)
+    }
+}
+
+fn evaluate(expression: &Expression, emulator: &Emulator) -> i32 {
+    let ram = |address: i32| {
+        let address = address as u16 as usize;
+        emulator
+            .ram()
+            .get(address)
+            .map_or(0, |value| *value as i16 as i32)
+    };
+
+    match expression {
+        Expression::Constant(value) => *value,
+        Expression::A => emulator.a() as i16 as i32,
+        Expression::D => emulator.d() as i16 as i32,
+        Expression::Pc => emulator.pc() as i32,
+        Expression::M => ram(emulator.a() as i32),
+        Expression::Ram(address) => ram(evaluate(address, emulator)),
+        Expression::Negate(value) => evaluate(value, emulator).wrapping_neg(),
+        Expression::Not(value) => (evaluate(value, emulator) == 0) as i32,
+        Expression::Binary(op, left, right) => {
+            let left = evaluate(left, emulator);
+            // The right operand of a logical operator is only evaluated if needed.
+            match *op {
+                "&&" => return (left != 0 && evaluate(right, emulator) != 0) as i32,
+                "||" => return (left != 0 || evaluate(right, emulator) != 0) as i32,
+                _ => {}
+            }
+            let right = evaluate(right, emulator);
+            match *op {
+                "==" => (left == right) as i32,
+                "!=" => (left != right) as i32,
+                "<" => (left < right) as i32,
+                "<=" => (left <= right) as i32,
+                ">" => (left > right) as i32,
+                ">=" => (left >= right) as i32,
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                "*" => left.wrapping_mul(right),
+                "&" => left & right,
+                "|" => left | right,
+                _ => unreachable!("unknown operator {}", op),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_condition() {
+        // Given
+        let mut emulator = Emulator::new(vec![0x0100]);
+        emulator.ram_mut()[0] = 256;
+        emulator.ram_mut()[256] = 2049;
+        emulator.step();
+        let condition = Condition::parse(
+            "RAM[RAM[SP]] > 2048 && A == 2 * (100 + 28)",
+            &SymbolTable::new(),
+        )
+        .unwrap();
+
+        // When
+        let is_true = condition.is_true(&emulator);
+
+        // Then
+        assert!(is_true);
+        assert!(!Condition::parse("D < 0 || !1", &SymbolTable::new())
+            .unwrap()
+            .is_true(&emulator));
+    }
+
+    #[test]
+    fn test_condition_errors() {
+        // Given
+        let symbol_table = SymbolTable::new();
+
+        for (source, expected) in [
+            ("D < 0 )", "unexpected Operator(\")\") in condition D < 0 )"),
+            ("D # 1", "unexpected character in condition D # 1"),
+            ("RAM[0", "expected ] in condition, found None"),
+            ("D == FOO", "unknown symbol FOO in condition"),
+            ("D <", "expected a value in condition, found None"),
+        ] {
+            // When
+            let error = Condition::parse(source, &symbol_table).unwrap_err();
+
+            // Then
+            assert_eq!(expected, error.to_string());
+        }
+    }
+}
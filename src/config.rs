@@ -0,0 +1,49 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    error::{self, AssemblerError},
+    format::FormatConfig,
+};
+
+/// The name of the project configuration file.
+pub const CONFIG_FILE: &str = "hack.json";
+
+/// The project configuration, read from a `hack.json` file. Missing sections
+/// and fields take their default values.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub format: FormatConfig,
+}
+
+impl ProjectConfig {
+    /// Loads the configuration from a file, or returns an error if the file
+    /// can't be read or is not a valid configuration.
+    pub fn load(path: &Path) -> Result<Self, AssemblerError> {
+        let json = error::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|err| {
+            AssemblerError::syntax(format!(
+                "{}: invalid project config: {}",
+                path.display(),
+                err
+            ))
+        })
+    }
+
+    /// Loads the configuration of the project containing the path, from the
+    /// closest `hack.json` in its ancestors. Returns the default configuration
+    /// if there is none, or an error if the closest configuration is not valid.
+    pub fn discover(path: &Path) -> Result<Self, AssemblerError> {
+        find(path).map_or_else(|| Ok(Self::default()), |config| Self::load(&config))
+    }
+}
+
+/// Returns the closest `hack.json` in the ancestors of the path.
+fn find(path: &Path) -> Option<PathBuf> {
+    let path = std::path::absolute(path).ok()?;
+    path.ancestors()
+        .map(|directory| directory.join(CONFIG_FILE))
+        .find(|config| config.is_file())
+}
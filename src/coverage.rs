@@ -0,0 +1,88 @@
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use crate::{
+    profile::listing,
+    program::{Instruction, Program},
+};
+
+/// Returns the coverage report of a run: the source of the program with the
+/// execution count of each line, uncovered instructions marked with `#####`.
+/// Programs loaded from a `.hack` file are reported on their disassembly.
+pub fn report(path: &Path, rom: &[u16], counts: &[u64]) -> String {
+    let program = listing(path, rom);
+    let source = if path
+        .extension()
+        .is_some_and(|extension| extension == "hack")
+    {
+        program.to_string()
+    } else {
+        std::fs::read_to_string(path).expect("failed to read file")
+    };
+    annotate(&program, &source, counts)
+}
+
+fn annotate(program: &Program, source: &str, counts: &[u64]) -> String {
+    // The execution count of each source line holding an instruction.
+    let mut line_counts: HashMap<usize, u64> = HashMap::new();
+    let mut covered = 0;
+    let mut address = 0;
+    for (index, instruction) in program.instructions().iter().enumerate() {
+        if let Instruction::L(_) = instruction {
+            continue;
+        }
+        let count = counts.get(address).copied().unwrap_or(0);
+        if count > 0 {
+            covered += 1;
+        }
+        // Disassembled programs have no source lines, one instruction per line.
+        let line = program.source_line(index).unwrap_or(index + 1);
+        *line_counts.entry(line).or_default() += count;
+        address += 1;
+    }
+
+    let mut report = format!(
+        "covered {} of {} instructions ({:.2}%)\n",
+        covered,
+        address,
+        if address == 0 {
+            100.0
+        } else {
+            covered as f64 * 100.0 / address as f64
+        }
+    );
+    for (index, text) in source.lines().enumerate() {
+        let count = match line_counts.get(&(index + 1)) {
+            None => String::from("-"),
+            Some(0) => String::from("#####"),
+            Some(count) => count.to_string(),
+        };
+        writeln!(report, "{:>9}:{:>5}:{}", count, index + 1, text).expect("write to string");
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annotate() {
+        // Given
+        let source = "// Skip\n@END\n0;JMP\nD=0\n(END)\n";
+        let program = Program::from_source(source);
+
+        // When
+        let report = annotate(&program, source, &[1, 1, 0]);
+
+        // Then
+        assert_eq!(
+            "covered 2 of 3 instructions (66.67%)\n\
+             \x20       -:    1:// Skip\n\
+             \x20       1:    2:@END\n\
+             \x20       1:    3:0;JMP\n\
+             \x20   #####:    4:D=0\n\
+             \x20       -:    5:(END)\n",
+            report
+        );
+    }
+}
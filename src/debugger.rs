@@ -0,0 +1,707 @@
+use clap::ValueEnum;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    condition::Condition,
+    diagnostic::{Diagnostic, DiagnosticsSink, TerminalSink},
+    disassembler::decode,
+    dump::{dump, DumpFormat},
+    emulator::{load_state, Access, Emulator, Stop, Undo, RAM_SIZE},
+    error::AssemblerError,
+    symbol_table::SymbolTable,
+};
+
+/// Why the debugger gave the control back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The requested instructions were executed.
+    Stepped,
+    /// The program counter reached a breakpoint.
+    Breakpoint(u16),
+    /// The instruction at `pc` accessed a watched RAM address.
+    Watchpoint {
+        address: u16,
+        pc: u16,
+        access: Watch,
+    },
+    /// The emulator stopped running.
+    Stopped(Stop),
+    /// Stepping backwards reached the oldest recorded instruction.
+    StartOfTrace,
+}
+
+/// The default number of instructions recorded for stepping backwards.
+pub const TRACE_CAPACITY: usize = 100_000;
+
+/// The RAM accesses which trigger a watchpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watch {
+    Read,
+    Write,
+    /// Both reads and writes.
+    Access,
+}
+
+/// A debugger driving an emulator, with breakpoints on ROM addresses and
+/// watchpoints on RAM addresses.
+pub struct Debugger {
+    emulator: Emulator,
+    symbol_table: SymbolTable,
+    /// The breakpoints, with the condition under which they break, if any.
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    watchpoints: BTreeMap<u16, Watch>,
+    /// The most recently executed instructions, the oldest first.
+    trace: VecDeque<Undo>,
+    trace_capacity: usize,
+}
+
+impl Debugger {
+    /// Returns a debugger for the emulator, resolving locations with the symbol table.
+    pub fn new(emulator: Emulator, symbol_table: SymbolTable) -> Self {
+        Self {
+            emulator,
+            symbol_table,
+            breakpoints: BTreeMap::new(),
+            watchpoints: BTreeMap::new(),
+            trace: VecDeque::new(),
+            trace_capacity: TRACE_CAPACITY,
+        }
+    }
+
+    /// Returns the emulator.
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    /// Returns the emulator, allowing to modify its RAM.
+    pub fn emulator_mut(&mut self) -> &mut Emulator {
+        &mut self.emulator
+    }
+
+    /// Replaces the emulator, such as with a restored machine state, clearing the trace.
+    pub fn replace_emulator(&mut self, emulator: Emulator) {
+        self.emulator = emulator;
+        self.trace.clear();
+    }
+
+    /// Returns the address of a location, either a number or a symbol.
+    pub fn resolve(&self, location: &str) -> Option<u16> {
+        match location.parse::<u16>() {
+            Ok(address) => Some(address),
+            Err(_) => self
+                .symbol_table
+                .address(location)
+                .and_then(|address| u16::try_from(*address).ok()),
+        }
+    }
+
+    /// Adds a breakpoint at a ROM address or label, returning its address.
+    pub fn add_breakpoint(&mut self, location: &str) -> Option<u16> {
+        let address = self.resolve(location)?;
+        self.add_breakpoint_at(address);
+        Some(address)
+    }
+
+    /// Adds a breakpoint at a ROM address, replacing any previous one.
+    pub fn add_breakpoint_at(&mut self, address: u16) {
+        self.breakpoints.insert(address, None);
+    }
+
+    /// Adds a breakpoint at a ROM address or label which only breaks when the
+    /// condition holds, returning its address, or `None` if the location is
+    /// unknown.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the condition is invalid.
+    pub fn add_conditional_breakpoint(
+        &mut self,
+        location: &str,
+        condition: &str,
+    ) -> Result<Option<u16>, AssemblerError> {
+        let Some(address) = self.resolve(location) else {
+            return Ok(None);
+        };
+        let condition = Condition::parse(condition, &self.symbol_table)?;
+        self.breakpoints.insert(address, Some(condition));
+        Ok(Some(address))
+    }
+
+    /// Removes the breakpoint at a ROM address or label.
+    /// Returns whether there was a breakpoint.
+    pub fn remove_breakpoint(&mut self, location: &str) -> bool {
+        self.resolve(location)
+            .is_some_and(|address| self.remove_breakpoint_at(address))
+    }
+
+    /// Removes the breakpoint at a ROM address.
+    /// Returns whether there was a breakpoint.
+    pub fn remove_breakpoint_at(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    /// Returns the addresses of the breakpoints and their conditions, in
+    /// increasing address order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (u16, Option<&Condition>)> + '_ {
+        self.breakpoints
+            .iter()
+            .map(|(address, condition)| (*address, condition.as_ref()))
+    }
+
+    /// Adds a watchpoint at a RAM address or symbol, returning its address.
+    pub fn add_watchpoint(&mut self, location: &str, watch: Watch) -> Option<u16> {
+        let address = self.resolve(location)?;
+        self.add_watchpoint_at(address, watch);
+        Some(address)
+    }
+
+    /// Adds a watchpoint at a RAM address, replacing any previous one.
+    pub fn add_watchpoint_at(&mut self, address: u16, watch: Watch) {
+        self.watchpoints.insert(address, watch);
+    }
+
+    /// Removes the watchpoint at a RAM address or symbol.
+    /// Returns whether there was a watchpoint.
+    pub fn remove_watchpoint(&mut self, location: &str) -> bool {
+        self.resolve(location)
+            .is_some_and(|address| self.remove_watchpoint_at(address))
+    }
+
+    /// Removes the watchpoint at a RAM address.
+    /// Returns whether there was a watchpoint.
+    pub fn remove_watchpoint_at(&mut self, address: u16) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    /// Returns the watchpoints, in increasing address order.
+    pub fn watchpoints(&self) -> impl Iterator<Item = (u16, Watch)> + '_ {
+        self.watchpoints
+            .iter()
+            .map(|(address, watch)| (*address, *watch))
+    }
+
+    /// Sets the number of executed instructions recorded for stepping
+    /// backwards, dropping the oldest ones if needed.
+    pub fn set_trace_capacity(&mut self, capacity: usize) {
+        self.trace_capacity = capacity;
+        while self.trace.len() > capacity {
+            self.trace.pop_front();
+        }
+    }
+
+    /// Returns the ROM addresses of the recorded instructions, the oldest first.
+    pub fn trace(&self) -> impl ExactSizeIterator<Item = u16> + '_ {
+        self.trace.iter().map(|undo| undo.pc)
+    }
+
+    /// Executes a single instruction, ignoring the breakpoints and watchpoints.
+    pub fn step(&mut self) -> Event {
+        if let Some(stop) = self.stop() {
+            return Event::Stopped(stop);
+        }
+        self.record();
+        Event::Stepped
+    }
+
+    /// Undoes the last executed instruction.
+    pub fn step_back(&mut self) -> Event {
+        match self.trace.pop_back() {
+            Some(undo) => {
+                self.emulator.undo(&undo);
+                Event::Stepped
+            }
+            None => Event::StartOfTrace,
+        }
+    }
+
+    /// Undoes executed instructions until a breakpoint is reached or the
+    /// trace is exhausted.
+    pub fn reverse_resume(&mut self) -> Event {
+        loop {
+            if self.step_back() == Event::StartOfTrace {
+                return Event::StartOfTrace;
+            }
+            let pc = self.emulator.pc();
+            if self.is_breakpoint(pc) {
+                return Event::Breakpoint(pc);
+            }
+        }
+    }
+
+    /// Executes instructions until the program counter reaches the instruction
+    /// following the current one, stepping over jumps which come back to it.
+    pub fn step_over(&mut self, max_cycles: u64) -> Event {
+        let next = self.emulator.pc() + 1;
+        self.run_until(max_cycles, |pc| pc == next)
+    }
+
+    /// Executes instructions until a breakpoint is reached or the emulator stops.
+    pub fn resume(&mut self, max_cycles: u64) -> Event {
+        self.run_until(max_cycles, |_| false)
+    }
+
+    fn run_until(&mut self, max_cycles: u64, done: impl Fn(u16) -> bool) -> Event {
+        let start = self.emulator.cycles();
+        loop {
+            if let Some(stop) = self.stop() {
+                return Event::Stopped(stop);
+            }
+            if self.emulator.cycles() - start >= max_cycles {
+                return Event::Stopped(Stop::CycleLimit);
+            }
+            let pc = self.emulator.pc();
+            let access = self.record();
+            if let Some(event) = self.watchpoint(pc, access) {
+                return event;
+            }
+
+            let pc = self.emulator.pc();
+            if done(pc) {
+                return Event::Stepped;
+            }
+            if self.is_breakpoint(pc) {
+                return Event::Breakpoint(pc);
+            }
+        }
+    }
+
+    /// Executes a single instruction, recording it in the trace.
+    fn record(&mut self) -> Access {
+        let (access, undo) = self.emulator.step_undoable();
+        if self.trace_capacity > 0 {
+            if self.trace.len() == self.trace_capacity {
+                self.trace.pop_front();
+            }
+            self.trace.push_back(undo);
+        }
+        access
+    }
+
+    /// Returns whether there's a breakpoint at `pc` whose condition holds.
+    fn is_breakpoint(&self, pc: u16) -> bool {
+        match self.breakpoints.get(&pc) {
+            Some(None) => true,
+            Some(Some(condition)) => condition.is_true(&self.emulator),
+            None => false,
+        }
+    }
+
+    /// Returns the watchpoint event triggered by the accesses of the instruction at `pc`.
+    fn watchpoint(&self, pc: u16, access: Access) -> Option<Event> {
+        let hit = |address: Option<u16>, access: Watch| {
+            let address = address?;
+            let watch = *self.watchpoints.get(&address)?;
+            (watch == access || watch == Watch::Access).then_some(Event::Watchpoint {
+                address,
+                pc,
+                access,
+            })
+        };
+        hit(access.write, Watch::Write).or_else(|| hit(access.read, Watch::Read))
+    }
+
+    fn stop(&self) -> Option<Stop> {
+        if self.emulator.is_at_end() {
+            Some(Stop::EndOfRom)
+        } else if self.emulator.is_halted() {
+            Some(Stop::Halted)
+        } else if self.emulator.is_out_of_ram() {
+            Some(Stop::OutOfRam)
+        } else {
+            None
+        }
+    }
+}
+
+/// The maximum number of cycles executed by a single `next` or `continue` command.
+const MAX_CYCLES: u64 = 100_000_000;
+
+const HELP: &str = "\
+break|b <location> [if <condition>]
+                          add a breakpoint at a ROM address or label
+delete|d <location>       remove a breakpoint
+watch|w <location> [read|write|access]
+                          add a watchpoint on a RAM address or symbol
+unwatch <location>        remove a watchpoint
+info|i                    list the breakpoints and watchpoints
+step|s [count]            execute instructions
+next|n                    step over the current instruction
+continue|c                run until a breakpoint
+back|bs [count]           undo executed instructions
+reverse-continue|rc       undo executed instructions until a breakpoint
+trace|t [count]           list the last executed instructions
+regs|r                    print the registers
+print|p <location>[..end] print RAM cells
+set <location>=<value>    set a RAM cell
+dump <path> <location>[..end] [hex|binary|text]
+                          write RAM cells to a file
+save <path>               save the machine state
+load <path>               restore a saved machine state
+quit|q                    exit the debugger
+";
+
+/// Runs an interactive debugging session, reading commands from the input
+/// until it's exhausted or the user quits. Invalid commands are reported to
+/// the output as errors.
+///
+/// # Errors
+///
+/// Returns an error if the input can't be read or the output written.
+pub fn repl(
+    debugger: &mut Debugger,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<()> {
+    let mut line = String::new();
+    loop {
+        write!(output, "(hack) ")?;
+        output.flush()?;
+
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        if matches!(command, "quit" | "q") {
+            return Ok(());
+        }
+        let arguments: Vec<&str> = words.collect();
+
+        match execute(debugger, command, &arguments) {
+            Ok(response) => write!(output, "{}", response)?,
+            Err(message) => TerminalSink::new(&mut output).emit(Diagnostic::error(message)),
+        }
+    }
+}
+
+/// Executes a command and returns its response, or an error message if the
+/// command or its argument is invalid.
+fn execute(debugger: &mut Debugger, command: &str, arguments: &[&str]) -> Result<String, String> {
+    let argument = arguments.first().copied();
+    let location = || argument.ok_or_else(|| "missing location".to_string());
+    let resolve = |location: &str| {
+        debugger
+            .resolve(location)
+            .ok_or_else(|| format!("unknown location {}", location))
+    };
+    let ram_range = |range: &str| {
+        let (start, end) = match range.split_once("..") {
+            Some((start, end)) => (resolve(start)?, resolve(end)?),
+            None => {
+                let start = resolve(range)?;
+                let end = start
+                    .checked_add(1)
+                    .ok_or_else(|| "invalid RAM range".to_string())?;
+                (start, end)
+            }
+        };
+        if start >= end || end as usize > RAM_SIZE {
+            return Err("invalid RAM range".to_string());
+        }
+        Ok(start as usize..end as usize)
+    };
+
+    let event = match command {
+        "break" | "b" => {
+            let location = location()?;
+            let address = match arguments.get(1) {
+                Some(&"if") => debugger
+                    .add_conditional_breakpoint(location, &arguments[2..].join(" "))
+                    .map_err(|error| error.to_string())?,
+                Some(argument) => {
+                    return Err(format!("unexpected {}, expected if <condition>", argument))
+                }
+                None => debugger.add_breakpoint(location),
+            }
+            .ok_or_else(|| format!("unknown location {}", location))?;
+            return Ok(format!("breakpoint at {}\n", address));
+        }
+        "delete" | "d" => {
+            let location = location()?;
+            if !debugger.remove_breakpoint(location) {
+                return Err(format!("no breakpoint at {}", location));
+            }
+            return Ok(String::new());
+        }
+        "watch" | "w" => {
+            let location = location()?;
+            let watch = match arguments.get(1).copied() {
+                None | Some("write") => Watch::Write,
+                Some("read") => Watch::Read,
+                Some("access") => Watch::Access,
+                Some(watch) => return Err(format!("unknown watchpoint kind {}", watch)),
+            };
+            let address = debugger
+                .add_watchpoint(location, watch)
+                .ok_or_else(|| format!("unknown location {}", location))?;
+            return Ok(format!("watchpoint on RAM[{}]\n", address));
+        }
+        "unwatch" => {
+            let location = location()?;
+            if !debugger.remove_watchpoint(location) {
+                return Err(format!("no watchpoint at {}", location));
+            }
+            return Ok(String::new());
+        }
+        "info" | "i" => {
+            let breakpoints = debugger
+                .breakpoints()
+                .map(|(address, condition)| match condition {
+                    Some(condition) => format!("breakpoint at {} if {}\n", address, condition),
+                    None => format!("breakpoint at {}\n", address),
+                });
+            let watchpoints = debugger.watchpoints().map(|(address, watch)| {
+                let watch = match watch {
+                    Watch::Read => "read",
+                    Watch::Write => "write",
+                    Watch::Access => "access",
+                };
+                format!("{} watchpoint on RAM[{}]\n", watch, address)
+            });
+            return Ok(breakpoints.chain(watchpoints).collect());
+        }
+        "regs" | "r" => return Ok(registers(debugger.emulator())),
+        "print" | "p" => {
+            let range = ram_range(location()?)?;
+            let dump = dump(
+                debugger.emulator().ram(),
+                range,
+                DumpFormat::Text,
+                &debugger.symbol_table,
+            );
+            return Ok(String::from_utf8(dump).expect("text dump"));
+        }
+        "dump" => {
+            let path = argument.ok_or_else(|| "missing path".to_string())?;
+            let range = arguments
+                .get(1)
+                .ok_or_else(|| "missing range".to_string())?;
+            let range = ram_range(range)?;
+            let format = match arguments.get(2).copied() {
+                None => DumpFormat::Text,
+                Some(format) => DumpFormat::from_str(format, true)
+                    .map_err(|_| format!("unknown dump format {}", format))?,
+            };
+            let dump = dump(
+                debugger.emulator().ram(),
+                range,
+                format,
+                &debugger.symbol_table,
+            );
+            std::fs::write(path, dump)
+                .map_err(|error| format!("failed to write RAM dump: {}", error))?;
+            return Ok(format!("dumped RAM to {}\n", path));
+        }
+        "set" => {
+            let (location, value) = location()?
+                .split_once('=')
+                .ok_or_else(|| "expected <location>=<value>".to_string())?;
+            let address = resolve(location)? as usize;
+            let value = value
+                .parse::<i16>()
+                .map_err(|_| format!("invalid value {}", value))?;
+            if address >= RAM_SIZE {
+                return Err(format!("RAM address {} is out of bounds", address));
+            }
+            debugger.emulator_mut().ram_mut()[address] = value as u16;
+            return Ok(String::new());
+        }
+        "step" | "s" | "back" | "bs" => {
+            let count = argument.map_or(Ok(1), parse_count)?;
+            let mut event = Event::Stepped;
+            for _ in 0..count {
+                event = if matches!(command, "back" | "bs") {
+                    debugger.step_back()
+                } else {
+                    debugger.step()
+                };
+                if event != Event::Stepped {
+                    break;
+                }
+            }
+            event
+        }
+        "next" | "n" => debugger.step_over(MAX_CYCLES),
+        "continue" | "c" => debugger.resume(MAX_CYCLES),
+        "reverse-continue" | "rc" => debugger.reverse_resume(),
+        "trace" | "t" => {
+            let count = argument.map_or(Ok(10), parse_count)?;
+            let rom = debugger.emulator().rom();
+            let skip = debugger.trace().len().saturating_sub(count);
+            return Ok(debugger
+                .trace()
+                .skip(skip)
+                .map(|pc| format!("{}: {}\n", pc, decode(rom[pc as usize])))
+                .collect());
+        }
+        "save" => {
+            let path = argument.ok_or_else(|| "missing path".to_string())?;
+            std::fs::write(path, debugger.emulator().save_state())
+                .map_err(|error| format!("failed to save machine state: {}", error))?;
+            return Ok(format!("saved machine state to {}\n", path));
+        }
+        "load" => {
+            let path = argument.ok_or_else(|| "missing path".to_string())?;
+            let emulator = load_state(Path::new(path)).map_err(|error| error.to_string())?;
+            debugger.replace_emulator(emulator);
+            Event::Stepped
+        }
+        "help" | "h" => return Ok(HELP.to_string()),
+        _ => return Err(format!("unknown command {}, try help", command)),
+    };
+
+    let mut response = match event {
+        Event::Stepped => String::new(),
+        Event::Breakpoint(address) => format!("hit breakpoint at {}\n", address),
+        Event::StartOfTrace => "reached the start of the trace\n".to_string(),
+        Event::Watchpoint {
+            address,
+            pc,
+            access,
+        } => format!(
+            "RAM[{}] {} by {}: {}, now {}\n",
+            address,
+            if access == Watch::Write {
+                "written"
+            } else {
+                "read"
+            },
+            pc,
+            decode(debugger.emulator().rom()[pc as usize]),
+            debugger.emulator().ram()[address as usize] as i16
+        ),
+        Event::Stopped(Stop::Halted) => "halted\n".to_string(),
+        Event::Stopped(Stop::EndOfRom) => "reached the end of the ROM\n".to_string(),
+        Event::Stopped(Stop::CycleLimit) => "reached the cycle limit\n".to_string(),
+        Event::Stopped(Stop::Interrupted) => "interrupted\n".to_string(),
+        Event::Stopped(Stop::OutOfRam) => format!(
+            "accessing M at {}, past the end of the RAM\n",
+            debugger.emulator().a()
+        ),
+    };
+    response.push_str(&current_instruction(debugger.emulator()));
+    Ok(response)
+}
+
+fn parse_count(count: &str) -> Result<usize, String> {
+    count
+        .parse()
+        .map_err(|_| format!("invalid count {}", count))
+}
+
+fn registers(emulator: &Emulator) -> String {
+    format!(
+        "A: {}  D: {}  PC: {}  cycles: {}\n",
+        emulator.a() as i16,
+        emulator.d() as i16,
+        emulator.pc(),
+        emulator.cycles()
+    )
+}
+
+fn current_instruction(emulator: &Emulator) -> String {
+    match emulator.rom().get(emulator.pc() as usize) {
+        Some(word) => format!("{}: {}\n", emulator.pc(), decode(*word)),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::load_program;
+
+    #[test]
+    fn test_breakpoint_on_label() {
+        // Given
+        let (rom, symbol_table) = load_program(Path::new("test_data/max/Max.asm")).unwrap();
+        let mut emulator = Emulator::new(rom);
+        emulator.ram_mut()[0] = 3;
+        emulator.ram_mut()[1] = 5;
+        let mut debugger = Debugger::new(emulator, symbol_table);
+        let address = debugger.add_breakpoint("END").unwrap();
+
+        // When
+        let event = debugger.resume(1000);
+
+        // Then
+        assert_eq!(Event::Breakpoint(address), event);
+        assert_eq!(5, debugger.emulator().d());
+        assert_eq!(Event::Stopped(Stop::Halted), debugger.resume(1000));
+        assert_eq!(5, debugger.emulator().ram()[2]);
+    }
+
+    #[test]
+    fn test_watchpoint_on_write() {
+        // Given
+        let (rom, symbol_table) = load_program(Path::new("test_data/max/Max.asm")).unwrap();
+        let mut debugger = Debugger::new(Emulator::new(rom), symbol_table);
+        debugger.add_watchpoint("R2", Watch::Write).unwrap();
+
+        // When
+        let event = debugger.resume(1000);
+
+        // Then
+        assert_eq!(
+            Event::Watchpoint {
+                address: 2,
+                pc: 9,
+                access: Watch::Write
+            },
+            event
+        );
+    }
+
+    #[test]
+    fn test_repl_reports_invalid_commands() {
+        // Given
+        let (rom, symbol_table) = load_program(Path::new("test_data/max/Max.asm")).unwrap();
+        let mut debugger = Debugger::new(Emulator::new(rom), symbol_table);
+        let input = "print 65535\nbreak NOWHERE\nbreak 0 if D <\nstep x\njump\nregs\n";
+        let mut output = Vec::new();
+
+        // When
+        repl(&mut debugger, input.as_bytes(), &mut output).unwrap();
+
+        // Then
+        assert_eq!(
+            "(hack) error: invalid RAM range\n\
+             (hack) error: unknown location NOWHERE\n\
+             (hack) error: expected a value in condition, found None\n\
+             (hack) error: invalid count x\n\
+             (hack) error: unknown command jump, try help\n\
+             (hack) A: 0  D: 0  PC: 0  cycles: 0\n\
+             (hack) ",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_reverse_resume() {
+        // Given
+        let (rom, symbol_table) = load_program(Path::new("test_data/max/Max.asm")).unwrap();
+        let mut emulator = Emulator::new(rom);
+        emulator.ram_mut()[1] = 5;
+        let mut debugger = Debugger::new(emulator, symbol_table);
+        debugger.add_breakpoint("4").unwrap();
+        debugger.resume(1000);
+        debugger.resume(1000);
+
+        // When
+        let event = debugger.reverse_resume();
+
+        // Then
+        assert_eq!(Event::Breakpoint(4), event);
+        assert_eq!(0, debugger.emulator().ram()[2]);
+        assert_eq!(4, debugger.emulator().cycles());
+        assert_eq!(Event::StartOfTrace, debugger.reverse_resume());
+        assert_eq!(0, debugger.emulator().pc());
+    }
+}
@@ -0,0 +1,443 @@
+use std::{
+    fmt::{self, Write as _},
+    io::{self, IsTerminal, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use clap::ValueEnum;
+use crossterm::style::{ContentStyle, Stylize};
+use serde::Serialize;
+
+/// When to color the diagnostics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Only when writing to a terminal and `NO_COLOR` isn't set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Returns whether to color the output written to a terminal or not.
+    pub fn enabled(&self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => is_terminal && std::env::var_os("NO_COLOR").is_none(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// How the diagnostics are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// Rendered for a reader, with the line they point to.
+    #[default]
+    Human,
+    /// One JSON object per line, for editors and CI bots.
+    Json,
+}
+
+/// Whether the diagnostics rendered to the standard error are colored.
+static STDERR_COLOR: AtomicBool = AtomicBool::new(false);
+/// Whether the diagnostics written to the standard error are JSON.
+static STDERR_JSON: AtomicBool = AtomicBool::new(false);
+
+/// Sets when to color the diagnostics rendered to the standard error, which
+/// are plain by default.
+pub fn set_stderr_color(choice: ColorChoice) {
+    STDERR_COLOR.store(
+        choice.enabled(io::stderr().is_terminal()),
+        Ordering::Relaxed,
+    );
+}
+
+/// Returns whether the diagnostics rendered to the standard error are colored.
+pub fn stderr_color() -> bool {
+    STDERR_COLOR.load(Ordering::Relaxed)
+}
+
+/// Sets how the diagnostics are written to the standard error, rendered for
+/// a reader by default.
+pub fn set_stderr_format(format: ErrorFormat) {
+    STDERR_JSON.store(format == ErrorFormat::Json, Ordering::Relaxed);
+}
+
+/// Returns how the diagnostics are written to the standard error.
+pub fn stderr_format() -> ErrorFormat {
+    match STDERR_JSON.load(Ordering::Relaxed) {
+        true => ErrorFormat::Json,
+        false => ErrorFormat::Human,
+    }
+}
+
+/// The severity of a diagnostic.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// The position in the source a diagnostic points to, with the text of its
+/// line.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// The path of the source, if it was read from a file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The line, starting at 1.
+    pub line: usize,
+    /// The column in characters, starting at 1.
+    pub column: usize,
+    /// The text of the line.
+    pub snippet: String,
+}
+
+impl Location {
+    /// Returns the location of the line of the source, at the column if
+    /// known, else at the first character of the line which isn't a space.
+    pub fn new(file: Option<&Path>, source: &str, line: usize, column: Option<usize>) -> Self {
+        // The byte order mark is left out, as it is by the parser.
+        let snippet = source
+            .lines()
+            .nth(line.saturating_sub(1))
+            .unwrap_or("")
+            .trim_start_matches('\u{feff}')
+            .trim_end();
+        let column =
+            column.unwrap_or_else(|| snippet.chars().take_while(|c| c.is_whitespace()).count() + 1);
+        Self {
+            file: file.map(|file| file.display().to_string()),
+            line,
+            column,
+            snippet: snippet.to_string(),
+        }
+    }
+}
+
+/// A diagnostic emitted while assembling a program.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The kind of the diagnostic, such as `syntax` or `unused-label`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub severity: Severity,
+    pub message: String,
+    /// Where the diagnostic points in the source, if known.
+    #[serde(rename = "span", skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    /// The explanations shown after the diagnostic.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+    /// The text that most likely fixes the diagnostic, replacing the one at
+    /// its location.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    /// Returns a new error diagnostic with the given message.
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            code: None,
+            severity: Severity::Error,
+            message: message.into(),
+            location: None,
+            notes: Vec::new(),
+            suggestion: None,
+        }
+    }
+
+    /// Returns a new warning diagnostic with the given message.
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            code: None,
+            severity: Severity::Warning,
+            message: message.into(),
+            location: None,
+            notes: Vec::new(),
+            suggestion: None,
+        }
+    }
+
+    /// Returns the diagnostic pointing to the location.
+    #[must_use]
+    pub fn at(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// Returns the diagnostic of a kind.
+    #[must_use]
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Returns the diagnostic with a note appended.
+    #[must_use]
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Returns the diagnostic with the text fixing it.
+    #[must_use]
+    pub fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+
+    /// Serializes the diagnostic to JSON, on a single line.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("failed to serialize diagnostic")
+    }
+
+    /// Renders the diagnostic for a terminal: its severity, code and
+    /// message, the line it points to with a caret under the column, then
+    /// its notes and suggestion. Colors are only used if asked.
+    pub fn render(&self, color: bool) -> String {
+        let (severity, style) = match self.severity {
+            Severity::Error => ("error", ContentStyle::new().red().bold()),
+            Severity::Warning => ("warning", ContentStyle::new().yellow().bold()),
+        };
+        let paint = |text: &str, style: ContentStyle| match color {
+            true => style.apply(text).to_string(),
+            false => text.to_string(),
+        };
+        let margin = ContentStyle::new().blue().bold();
+        let bold = ContentStyle::new().bold();
+
+        let severity = match &self.code {
+            Some(code) => format!("{}[{}]", severity, code),
+            None => severity.to_string(),
+        };
+        let mut rendered = format!(
+            "{}{}",
+            paint(&severity, style),
+            paint(&format!(": {}", self.message), bold)
+        );
+        let width = self
+            .location
+            .as_ref()
+            .map_or(0, |location| location.line.to_string().len());
+        let gutter = " ".repeat(width);
+        if let Some(location) = &self.location {
+            let position = match &location.file {
+                Some(file) => format!("{}:{}:{}", file, location.line, location.column),
+                None => format!("line {}:{}", location.line, location.column),
+            };
+            // Tabs are kept so that the caret lines up whatever their width.
+            let indent: String = location
+                .snippet
+                .chars()
+                .take(location.column - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let bar = paint(&format!("{} |", gutter), margin);
+            write!(
+                rendered,
+                "\n{}{} {}\n{}\n{} {}\n{} {}{}",
+                gutter,
+                paint("-->", margin),
+                position,
+                bar,
+                paint(&format!("{} |", location.line), margin),
+                location.snippet,
+                bar,
+                indent,
+                paint("^", style)
+            )
+            .expect("write to string");
+        }
+        for note in &self.notes {
+            write!(rendered, "\n{} = {}: {}", gutter, paint("note", bold), note)
+                .expect("write to string");
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(
+                rendered,
+                "\n{} = {}: did you mean `{}`?",
+                gutter,
+                paint("help", bold),
+                suggestion
+            )
+            .expect("write to string");
+        }
+        rendered
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+}
+
+/// A destination for the diagnostics emitted while assembling a program.
+pub trait DiagnosticsSink {
+    /// Reports a diagnostic.
+    fn emit(&mut self, diagnostic: Diagnostic);
+}
+
+impl DiagnosticsSink for Vec<Diagnostic> {
+    fn emit(&mut self, diagnostic: Diagnostic) {
+        self.push(diagnostic);
+    }
+}
+
+/// Renders diagnostics as text, one after the other, or as JSON lines.
+pub struct TerminalSink<W> {
+    writer: W,
+    color: bool,
+    format: ErrorFormat,
+}
+
+impl TerminalSink<io::Stderr> {
+    /// Returns a sink rendering to the standard error, colored and formatted
+    /// as set by [`set_stderr_color`] and [`set_stderr_format`].
+    pub fn stderr() -> Self {
+        Self::new(io::stderr())
+            .with_color(stderr_color())
+            .with_format(stderr_format())
+    }
+}
+
+impl<W: Write> TerminalSink<W> {
+    /// Returns a sink rendering to the writer for a reader, without colors.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            color: false,
+            format: ErrorFormat::Human,
+        }
+    }
+
+    /// Returns the sink rendering with colors or not.
+    #[must_use]
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Returns the sink writing the diagnostics in the format.
+    #[must_use]
+    pub fn with_format(mut self, format: ErrorFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+impl<W: Write> DiagnosticsSink for TerminalSink<W> {
+    fn emit(&mut self, diagnostic: Diagnostic) {
+        let text = match self.format {
+            ErrorFormat::Human => diagnostic.render(self.color),
+            ErrorFormat::Json => diagnostic.to_json(),
+        };
+        // Nowhere left to report a failure to write a diagnostic.
+        let _ = writeln!(self.writer, "{}", text);
+    }
+}
+
+/// Collects diagnostics to be serialized as a JSON array.
+#[derive(Default)]
+pub struct JsonCollector {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl JsonCollector {
+    /// Returns the collected diagnostics as a JSON array.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.diagnostics).expect("failed to serialize diagnostics")
+    }
+}
+
+impl DiagnosticsSink for JsonCollector {
+    fn emit(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_sink() {
+        // Given
+        let mut sink = TerminalSink::new(Vec::new());
+
+        // When
+        sink.emit(Diagnostic::error("unexpected comp"));
+
+        // Then
+        assert_eq!(b"error: unexpected comp\n".to_vec(), sink.writer);
+    }
+
+    #[test]
+    fn test_location_points_to_the_column() {
+        // Given
+        let source = "@1\n\tD = Q // Load\n";
+        let location = Location::new(Some(Path::new("load.asm")), source, 2, Some(6));
+
+        // When
+        let rendered = Diagnostic::error("unexpected comp Q")
+            .at(location)
+            .to_string();
+
+        // Then
+        assert_eq!(
+            "error: unexpected comp Q\n --> load.asm:2:6\n  |\n2 | \tD = Q // Load\n  | \t    ^",
+            rendered
+        );
+    }
+
+    #[test]
+    fn test_render_with_colors_and_notes() {
+        // Given
+        let diagnostic = Diagnostic::error("unexpected comp M+D")
+            .at(Location::new(None, "M=M+D\n", 1, Some(3)))
+            .with_note("did you mean D+M?");
+
+        // When
+        let plain = diagnostic.render(false);
+        let colored = diagnostic.render(true);
+
+        // Then
+        assert!(plain.ends_with("1 | M=M+D\n  |   ^\n  = note: did you mean D+M?"));
+        assert!(!plain.contains('\x1b'));
+        assert!(colored.starts_with("\x1b["));
+        assert!(colored.contains("did you mean D+M?"));
+        assert!(!ColorChoice::Never.enabled(true));
+        assert!(ColorChoice::Always.enabled(false));
+    }
+
+    #[test]
+    fn test_json_sink() {
+        // Given
+        let mut sink = TerminalSink::new(Vec::new()).with_format(ErrorFormat::Json);
+        let diagnostic = Diagnostic::error("unexpected comp M+D")
+            .with_code("syntax")
+            .at(Location::new(None, "M=M+D\n", 1, Some(3)))
+            .with_suggestion("D+M");
+
+        // When
+        sink.emit(diagnostic.clone());
+
+        // Then
+        assert_eq!(
+            "{\"code\":\"syntax\",\"severity\":\"error\",\"message\":\"unexpected comp M+D\",\
+             \"span\":{\"line\":1,\"column\":3,\"snippet\":\"M=M+D\"},\"suggestion\":\"D+M\"}\n",
+            String::from_utf8(sink.writer).unwrap()
+        );
+        assert!(diagnostic
+            .render(false)
+            .starts_with("error[syntax]: unexpected comp M+D"));
+        assert!(diagnostic
+            .render(false)
+            .ends_with("  = help: did you mean `D+M`?"));
+    }
+}
@@ -0,0 +1,221 @@
+use std::borrow::Cow;
+
+use clap::ValueEnum;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    error::AssemblerError,
+    pass::{Context, Pass, Stage},
+};
+
+/// The dialects of Hack assembly, each accepting the quirks of other
+/// assemblers on top of the syntax of the previous one, so that existing
+/// material assembles unchanged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Dialect {
+    /// The syntax of the course, `//` comments and `(LABEL)` declarations,
+    /// along with the `/* */` comments the parser accepts.
+    #[default]
+    Canonical,
+    /// Also accepts `#` comments, `LABEL:` declarations and lowercase
+    /// mnemonics.
+    Relaxed,
+    /// Also accepts the `.label NAME` directive, and the `.equ`, `.set` and
+    /// `.define` directives naming a constant, as in `.equ WIDTH 32`.
+    Extended,
+}
+
+impl Dialect {
+    /// Returns the source rewritten in the canonical syntax. The lines are
+    /// kept in place, so diagnostics and source maps refer to the original.
+    ///
+    /// # Panic
+    ///
+    /// - Panics if a directive is unknown or malformed.
+    /// - Panics if a constant is defined twice.
+    pub fn translate(self, source: &str) -> Cow<'_, str> {
+        if self == Dialect::Canonical {
+            return Cow::Borrowed(source);
+        }
+
+        let mut in_comment = false;
+        let mut lines: Vec<String> = source
+            .split('\n')
+            .map(|line| relax(&strip_comments(line, &mut in_comment)))
+            .collect();
+        if self >= Dialect::Extended {
+            apply_directives(&mut lines);
+        }
+        Cow::Owned(lines.join("\n"))
+    }
+}
+
+/// Returns the line without its comments. `in_comment` tells whether the
+/// line starts, and is updated to whether it ends, within a block comment.
+fn strip_comments(line: &str, in_comment: &mut bool) -> String {
+    let mut code = String::new();
+    let mut rest = line;
+    loop {
+        if *in_comment {
+            match rest.find("*/") {
+                Some(end) => {
+                    rest = &rest[end + 2..];
+                    *in_comment = false;
+                }
+                None => return code,
+            }
+        }
+        let comment = ["//", "#", "/*"]
+            .into_iter()
+            .filter_map(|marker| rest.find(marker).map(|index| (index, marker)))
+            .min();
+        match comment {
+            Some((index, "/*")) => {
+                code.push_str(&rest[..index]);
+                code.push(' ');
+                rest = &rest[index + 2..];
+                *in_comment = true;
+            }
+            Some((index, _)) => {
+                code.push_str(&rest[..index]);
+                return code;
+            }
+            None => {
+                code.push_str(rest);
+                return code;
+            }
+        }
+    }
+}
+
+/// Returns the code with its tabs turned into spaces, its `LABEL:`
+/// declaration, if any, turned into `(LABEL)`, and its mnemonics, if it's a
+/// C-instruction, in uppercase.
+fn relax(code: &str) -> String {
+    let code = code.replace('\t', " ");
+    let code = code.trim();
+    match code.strip_suffix(':') {
+        Some(label) if !code.starts_with('@') && !code.contains(['=', ';']) => {
+            format!("({})", label.trim())
+        }
+        _ if code.contains(['=', ';']) && !code.starts_with('@') => code.to_uppercase(),
+        _ => code.to_string(),
+    }
+}
+
+/// Replaces the directives of the lines by the canonical declarations, and
+/// the constants they define by their values in the A-instructions.
+///
+/// # Panic
+///
+/// - Panics if a directive is unknown or malformed.
+/// - Panics if a constant is defined twice.
+fn apply_directives(lines: &mut [String]) {
+    let mut constants = FxHashMap::default();
+    for (index, line) in lines.iter_mut().enumerate() {
+        if !line.starts_with('.') {
+            continue;
+        }
+        let mut words = line.split(|c: char| c == ',' || c.is_whitespace());
+        let directive = words.next().unwrap_or_default().to_string();
+        let operands: Vec<&str> = words.filter(|word| !word.is_empty()).collect();
+        match (directive.as_str(), operands.as_slice()) {
+            (".label", [label]) => *line = format!("({})", label),
+            (".equ" | ".set" | ".define", [name, value]) => {
+                let previous = constants.insert(name.to_string(), value.to_string());
+                assert!(
+                    previous.is_none(),
+                    "line {}: constant {} is defined twice",
+                    index + 1,
+                    name
+                );
+                line.clear();
+            }
+            (".label" | ".equ" | ".set" | ".define", _) => {
+                panic!("line {}: invalid {} directive", index + 1, directive)
+            }
+            _ => panic!("line {}: unknown directive {}", index + 1, directive),
+        }
+    }
+    if constants.is_empty() {
+        return;
+    }
+
+    for line in lines {
+        let Some(symbol) = line.strip_prefix('@') else {
+            continue;
+        };
+        if let Some(value) = constants.get(symbol.trim()) {
+            *line = format!("@{}", value);
+        }
+    }
+}
+
+/// Rewrites the source of a dialect in the canonical syntax, before it's
+/// parsed.
+///
+/// # Panic
+///
+/// Panics if the source doesn't follow the dialect.
+pub struct Translate(pub Dialect);
+
+impl Pass for Translate {
+    fn name(&self) -> &'static str {
+        "dialect"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Preprocess
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        if let Cow::Owned(source) = self.0.translate(&context.source) {
+            context.source = source;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+
+    #[test]
+    fn test_dialects_assemble_like_the_canonical_source() {
+        // Given
+        let canonical = "@32\nD=A\n(LOOP)\n@i\nM=D // Store\n@LOOP\nD;JGT\n";
+        let relaxed = "# Width\n@32\nd\t=\tA /* inline */\nLOOP:\n@i\nM=D /* Store\nacross lines */\n@LOOP\nD;jgt\n";
+        let extended = ".equ WIDTH, 32\n@WIDTH\nD=A\n.label LOOP\n@i\nM=D\n@LOOP\nD;JGT\n";
+
+        // When
+        let assemble = |source: &str, dialect: Dialect| {
+            Assembler::from_source(source)
+                .dialect(dialect)
+                .fill_symbol_table()
+                .assemble()
+        };
+
+        // Then
+        let expected = assemble(canonical, Dialect::Canonical);
+        assert_eq!(expected, assemble(relaxed, Dialect::Relaxed));
+        assert_eq!(expected, assemble(extended, Dialect::Extended));
+        assert_eq!(expected, assemble(canonical, Dialect::Extended));
+    }
+
+    #[test]
+    fn test_translation_keeps_the_lines() {
+        // Given
+        let source = "/* Header\n   still header */ @1\n.set ONE 1\n@ONE\n";
+
+        // When
+        let translated = Dialect::Extended.translate(source);
+
+        // Then
+        assert_eq!("\n@1\n\n@1\n", translated);
+        assert!(matches!(
+            Dialect::Canonical.translate(source),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+use crate::{
+    assembler::Assembler,
+    error::AssemblerError,
+    ir::{Ir, IrInstruction},
+    program::Program,
+};
+
+/// An assembled instruction, with where it comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffEntry {
+    pub address: u32,
+    /// The source line of the instruction, if known.
+    pub line: Option<usize>,
+    /// The normalized instruction, with its symbol if any.
+    pub instruction: String,
+    pub word: u16,
+}
+
+/// A run of instructions of the old program replaced by a run of the new one,
+/// either possibly empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Vec<DiffEntry>,
+    pub new: Vec<DiffEntry>,
+}
+
+/// The differences between the machine code of two programs. Formatting,
+/// comments, case, symbol names and equivalent spellings such as `MD` and
+/// `DM` don't make a difference as long as the words are the same.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SemanticDiff {
+    pub hunks: Vec<Hunk>,
+}
+
+/// Assembles the source into its entries, or returns the errors of the
+/// assembly.
+pub fn entries(source: &str) -> Result<Vec<DiffEntry>, AssemblerError> {
+    let words = Assembler::from_source(source)
+        .fill_symbol_table()
+        .try_assemble()?;
+    let program = Program::parse(source)?;
+    let ir = Ir::lower(&program, &program.resolve());
+    Ok(ir
+        .nodes
+        .iter()
+        .zip(words)
+        .map(|(node, word)| DiffEntry {
+            address: node.address,
+            line: node.line,
+            instruction: match &node.instruction {
+                IrInstruction::A {
+                    symbol: Some(symbol),
+                    ..
+                } => format!("@{}", symbol),
+                instruction => instruction.to_string(),
+            },
+            word,
+        })
+        .collect())
+}
+
+impl SemanticDiff {
+    /// Assembles both sources and aligns their words, or returns the errors
+    /// of the first source failing to assemble.
+    pub fn new(old: &str, new: &str) -> Result<Self, AssemblerError> {
+        Ok(Self::from_entries(entries(old)?, entries(new)?))
+    }
+
+    /// Aligns the words of the entries of both programs.
+    pub fn from_entries(old: Vec<DiffEntry>, new: Vec<DiffEntry>) -> Self {
+        let old_words: Vec<u16> = old.iter().map(|entry| entry.word).collect();
+        let new_words: Vec<u16> = new.iter().map(|entry| entry.word).collect();
+
+        let mut diff = SemanticDiff::default();
+        for op in capture_diff_slices(Algorithm::Myers, &old_words, &new_words) {
+            if let DiffOp::Equal { .. } = op {
+                continue;
+            }
+            let (_, old_range, new_range) = op.as_tag_tuple();
+            diff.hunks.push(Hunk {
+                old: old[old_range].to_vec(),
+                new: new[new_range].to_vec(),
+            });
+        }
+        diff
+    }
+
+    /// Returns whether both programs assemble to the same words.
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+}
+
+impl fmt::Display for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line = self
+            .line
+            .map_or_else(|| String::from("?"), |line| line.to_string());
+        write!(
+            f,
+            "{:>5} {:016b}  {:<20} line {}",
+            self.address, self.word, self.instruction, line
+        )
+    }
+}
+
+/// Renders each hunk after a header with its old and new ROM addresses, the
+/// removed instructions prefixed with `-` and the added ones with `+`.
+impl fmt::Display for SemanticDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for hunk in &self.hunks {
+            let start = |entries: &[DiffEntry]| {
+                entries
+                    .first()
+                    .map_or_else(|| String::from("-"), |entry| entry.address.to_string())
+            };
+            writeln!(
+                f,
+                "@@ ROM[{}] -> ROM[{}] @@",
+                start(&hunk.old),
+                start(&hunk.new)
+            )?;
+            for entry in &hunk.old {
+                writeln!(f, "- {}", entry)?;
+            }
+            for entry in &hunk.new {
+                writeln!(f, "+ {}", entry)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semantic_diff() {
+        // Given
+        let old = "@R0\nD=M\n(LOOP)\n@LOOP\nD;JGT\n";
+        let new = "// Same program, reformatted.\n   @0\n   D = M  // Read R0\n(AGAIN)\n@AGAIN\nD=D-1\nD;JGT\n";
+
+        // When
+        let diff = SemanticDiff::new(old, new).unwrap();
+
+        // Then
+        assert_eq!(1, diff.hunks.len());
+        assert!(diff.hunks[0].old.is_empty());
+        assert_eq!(
+            vec![DiffEntry {
+                address: 3,
+                line: Some(6),
+                instruction: String::from("D=D-1"),
+                word: 0b1110_0011_1001_0000,
+            }],
+            diff.hunks[0].new
+        );
+        assert!(SemanticDiff::new(old, "@0\nD=M\n@2\nD;JGT\n")
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+use std::fmt;
+
+use crate::{
+    disassembler::decode,
+    emulator::Emulator,
+    error::AssemblerError,
+    tst::{parse_value, parse_variable, Variable},
+};
+
+/// The first point where the emulator disagrees with a reference trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The line of the trace, starting at 1.
+    pub line: usize,
+    /// The number of cycles executed when the states diverged.
+    pub cycle: u64,
+    /// The address of the last executed instruction, if any.
+    pub pc: Option<u16>,
+    /// The last executed instruction, disassembled.
+    pub instruction: Option<String>,
+    /// The column of the trace which differs.
+    pub column: String,
+    pub expected: u16,
+    pub actual: u16,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "divergence at cycle {} (trace line {}): {} is {}, expected {}",
+            self.cycle, self.line, self.column, self.actual as i16, self.expected as i16
+        )?;
+        if let (Some(pc), Some(instruction)) = (self.pc, &self.instruction) {
+            write!(f, " after executing {} at ROM[{}]", instruction, pc)?;
+        }
+        Ok(())
+    }
+}
+
+/// A reference trace, as output by the nand2tetris CPU emulator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trace {
+    /// The names of the columns, with the variables they hold.
+    columns: Vec<(String, Variable)>,
+    rows: Vec<Row>,
+}
+
+/// A full-cycle row of a trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Row {
+    /// The line of the trace, starting at 1.
+    line: usize,
+    /// The value of the `time` column, if any.
+    time: Option<u64>,
+    /// The values of the columns, in order.
+    values: Vec<u16>,
+}
+
+impl Trace {
+    /// Parses a table such as `|time|PC|ARegister|DRegister|RAM[0]|`, with
+    /// one row per state. Half-cycle rows (`time` ending with `+`) are
+    /// skipped. Values are signed decimal, 16-digit binary, or prefixed with
+    /// `%X`, `%B` or `%D`.
+    ///
+    /// Returns a syntax error if the trace is empty, a column or a value is
+    /// invalid, or a row goes back in time.
+    pub fn parse(trace: &str) -> Result<Self, AssemblerError> {
+        let mut lines = trace
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line))
+            .filter(|(_, line)| !line.trim().is_empty());
+        let (number, header) = lines
+            .next()
+            .ok_or_else(|| AssemblerError::syntax("empty trace"))?;
+        let columns = cells(header)
+            .map(|name| Ok((name.to_string(), parse_variable(name)?)))
+            .collect::<Result<Vec<(String, Variable)>, AssemblerError>>()
+            .map_err(|error| error.at_line(Some(number)))?;
+
+        let mut rows: Vec<Row> = Vec::new();
+        for (number, line) in lines {
+            let cells: Vec<&str> = cells(line).collect();
+            if cells.len() != columns.len() {
+                return Err(AssemblerError::syntax(format!(
+                    "expected {} columns, found {}",
+                    columns.len(),
+                    cells.len()
+                ))
+                .at_line(Some(number)));
+            }
+
+            let time = columns
+                .iter()
+                .zip(&cells)
+                .find(|((_, variable), _)| *variable == Variable::Time)
+                .map(|(_, time)| *time);
+            if time.is_some_and(|time| time.ends_with('+')) {
+                continue;
+            }
+            let time = time
+                .map(|time| {
+                    time.parse::<u64>()
+                        .map_err(|_| AssemblerError::syntax(format!("invalid time {}", time)))
+                })
+                .transpose()
+                .map_err(|error| error.at_line(Some(number)))?;
+            if let (Some(time), Some(previous)) = (time, rows.last().and_then(|row| row.time)) {
+                if time < previous {
+                    return Err(
+                        AssemblerError::syntax("the trace goes back in time").at_line(Some(number))
+                    );
+                }
+            }
+
+            let values = cells
+                .iter()
+                .map(|value| parse_cell(value))
+                .collect::<Result<Vec<u16>, AssemblerError>>()
+                .map_err(|error| error.at_line(Some(number)))?;
+            rows.push(Row {
+                line: number,
+                time,
+                values,
+            });
+        }
+        Ok(Self { columns, rows })
+    }
+}
+
+/// Replays a reference trace against the emulator and returns the number of
+/// cycles compared, or the first divergence.
+///
+/// The first row seeds the registers and the RAM, and each following row is
+/// compared after executing up to its `time`, or one more cycle without a
+/// `time` column.
+pub fn diff_trace(rom: Vec<u16>, trace: &Trace) -> Result<u64, Divergence> {
+    let mut emulator = Emulator::new(rom);
+    let mut rows = trace.rows.iter();
+    let Some(first) = rows.next() else {
+        return Ok(0);
+    };
+    for ((_, variable), value) in trace.columns.iter().zip(&first.values) {
+        match variable {
+            Variable::A => emulator.set_a(*value),
+            Variable::D => emulator.set_d(*value),
+            Variable::Pc => emulator.set_pc(*value),
+            Variable::Ram(address) => emulator.ram_mut()[*address] = *value,
+            Variable::Time => {}
+        }
+    }
+    let start = first.time.unwrap_or(0);
+
+    let mut last = None;
+    for row in rows {
+        // The times never go back, and the first one is the start.
+        let target = match row.time {
+            Some(time) => time - start,
+            None => emulator.cycles() + 1,
+        };
+        while emulator.cycles() < target && !emulator.is_at_end() {
+            last = Some(emulator.pc());
+            emulator.step();
+        }
+
+        for ((name, variable), expected) in trace.columns.iter().zip(&row.values) {
+            let actual = match variable {
+                Variable::A => emulator.a(),
+                Variable::D => emulator.d(),
+                Variable::Pc => emulator.pc(),
+                Variable::Ram(address) => emulator.ram()[*address],
+                Variable::Time => continue,
+            };
+            if actual != *expected {
+                return Err(Divergence {
+                    line: row.line,
+                    cycle: emulator.cycles(),
+                    pc: last,
+                    instruction: last.map(|pc| decode(emulator.rom()[pc as usize]).to_string()),
+                    column: name.clone(),
+                    expected: *expected,
+                    actual,
+                });
+            }
+        }
+    }
+    Ok(emulator.cycles())
+}
+
+/// Returns the trimmed cells of a table row such as `| 1 | 2 |`.
+fn cells(line: &str) -> impl Iterator<Item = &str> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    line.split('|').map(str::trim)
+}
+
+/// Parses a cell, reading 16 binary digits as a binary word.
+fn parse_cell(value: &str) -> Result<u16, AssemblerError> {
+    if value.len() == 16 && value.bytes().all(|b| b == b'0' || b == b'1') {
+        return u16::from_str_radix(value, 2)
+            .map_err(|_| AssemblerError::syntax(format!("invalid binary word {}", value)));
+    }
+    parse_value(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // @5, D=A, @0, M=D
+    const ROM: [u16; 4] = [
+        0b0000000000000101,
+        0b1110110000010000,
+        0,
+        0b1110001100001000,
+    ];
+
+    #[test]
+    fn test_diff_trace() {
+        // Given
+        let trace = "\
+            |time| PC |ARegister|DRegister|RAM[0]|\n\
+            |0   | 0  |    0    |    0    |  7   |\n\
+            |1   | 1  |    5    |    0    |  7   |\n\
+            |1+  | 1  |    5    |    0    |  7   |\n\
+            |2   | 2  |    5    |    5    |  7   |\n\
+            |4   | 4  |    0    |    5    |  5   |\n";
+
+        // When
+        let cycles = diff_trace(ROM.to_vec(), &Trace::parse(trace).unwrap());
+
+        // Then
+        assert_eq!(Ok(4), cycles);
+    }
+
+    #[test]
+    fn test_diff_trace_divergence() {
+        // Given
+        let trace = "\
+            |PC|DRegister|\n\
+            |0 |    0    |\n\
+            |1 |    0    |\n\
+            |2 |    6    |\n";
+
+        // When
+        let divergence = diff_trace(ROM.to_vec(), &Trace::parse(trace).unwrap()).unwrap_err();
+
+        // Then
+        assert_eq!(
+            "divergence at cycle 2 (trace line 4): DRegister is 5, expected 6 \
+             after executing D=A at ROM[1]",
+            divergence.to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_trace_going_back_in_time() {
+        // Given
+        let trace = "|time|PC|\n|2|0|\n|1|1|\n";
+
+        // When
+        let error = Trace::parse(trace).unwrap_err();
+
+        // Then
+        assert_eq!(Some(3), error.line());
+        assert_eq!("line 3: the trace goes back in time", error.to_string());
+    }
+}
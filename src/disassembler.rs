@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::{
+    code::{binary_to_comp, binary_to_dest, binary_to_jump},
+    error::AssemblerError,
+    program::{Instruction, Program},
+};
+
+/// Reads a ROM image, either as the text of a `.hack` file with one binary
+/// word per line, or as raw big-endian 16-bit words.
+///
+/// Returns a syntax error if a line of a text image isn't a 16-bit binary
+/// word, located at the line, or if a raw image has an odd number of bytes.
+pub fn read_rom(bytes: &[u8]) -> Result<Vec<u16>, AssemblerError> {
+    let is_text = bytes
+        .iter()
+        .all(|b| matches!(b, b'0' | b'1' | b'\n' | b'\r' | b' ' | b'\t'));
+
+    if is_text {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(number, line)| {
+                u16::from_str_radix(line, 2)
+                    .ok()
+                    .filter(|_| line.len() == 16)
+                    .ok_or_else(|| {
+                        AssemblerError::syntax(format!("invalid binary word {}", line))
+                            .at_line(Some(number))
+                    })
+            })
+            .collect()
+    } else if !bytes.len().is_multiple_of(2) {
+        Err(AssemblerError::syntax("raw ROM image has an odd length"))
+    } else {
+        Ok(bytes
+            .chunks_exact(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .collect())
+    }
+}
+
+/// Decodes a single binary word into an instruction.
+///
+/// # Panic
+///
+/// Panics if the word is a C-instruction with an invalid comp.
+pub fn decode(word: u16) -> Instruction {
+    if word & 0x8000 == 0 {
+        return Instruction::A(word.to_string());
+    }
+
+    let comp = binary_to_comp((word >> 6) & 0b111_1111)
+        .unwrap_or_else(|| panic!("invalid comp in instruction {:016b}", word));
+    Instruction::C {
+        dest: binary_to_dest(word >> 3).to_string(),
+        comp: comp.to_string(),
+        jump: binary_to_jump(word).to_string(),
+    }
+}
+
+/// Disassembles the ROM words into a program.
+pub fn disassemble(words: &[u16]) -> Program {
+    let mut program = Program::new();
+    for word in words {
+        program.push(decode(*word));
+    }
+    program
+}
+
+/// The first RAM address available for variables.
+const FIRST_VARIABLE: u32 = 16;
+/// The first RAM address of the memory-mapped screen.
+const SCREEN: u32 = 16384;
+
+/// Replaces the numeric addresses of a disassembled program with symbols:
+/// - jump targets get labels `LBL0`, `LBL1`... in ROM order,
+/// - RAM addresses from 16 used more than once get variables `VAR0`, `VAR1`...
+///
+/// Variables are only named while their first uses are in increasing address
+/// order starting at 16, so that re-assembling allocates the same addresses.
+pub fn reconstruct_symbols(program: &mut Program) {
+    let instructions = program.instructions().to_vec();
+    let value = |index: usize| match &instructions[index] {
+        Instruction::A(value) => str::parse::<u32>(value).ok(),
+        _ => None,
+    };
+
+    // An A-instruction followed by a jump which doesn't overwrite A loads a jump target.
+    let target_sites: HashSet<usize> = (0..instructions.len().saturating_sub(1))
+        .filter(|index| {
+            matches!(
+                &instructions[index + 1],
+                Instruction::C { dest, jump, .. } if !jump.is_empty() && !dest.contains('A')
+            )
+        })
+        .filter(|index| value(*index).is_some_and(|v| v as usize <= instructions.len()))
+        .collect();
+
+    let labels: BTreeMap<u32, String> = target_sites
+        .iter()
+        .filter_map(|index| value(*index))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .enumerate()
+        .map(|(n, address)| (address, format!("LBL{}", n)))
+        .collect();
+
+    let mut uses: HashMap<u32, usize> = HashMap::new();
+    let data_sites: Vec<(usize, u32)> = (0..instructions.len())
+        .filter(|index| !target_sites.contains(index))
+        .filter_map(|index| value(index).map(|v| (index, v)))
+        .filter(|(_, v)| (FIRST_VARIABLE..SCREEN).contains(v))
+        .collect();
+    for (_, address) in &data_sites {
+        *uses.entry(*address).or_default() += 1;
+    }
+
+    let mut variables: HashMap<u32, String> = HashMap::new();
+    for (_, address) in &data_sites {
+        if uses[address] < 2 || variables.contains_key(address) {
+            continue;
+        }
+        if *address != FIRST_VARIABLE + variables.len() as u32 {
+            break;
+        }
+        variables.insert(*address, format!("VAR{}", variables.len()));
+    }
+
+    for (index, address) in &data_sites {
+        if let Some(variable) = variables.get(address) {
+            program.replace(*index, Instruction::A(variable.clone()));
+        }
+    }
+    for index in &target_sites {
+        let label = labels[&value(*index).expect("target site")].clone();
+        program.replace(*index, Instruction::A(label));
+    }
+    // Insert from the end so that the indices still match the ROM addresses.
+    for (address, label) in labels.into_iter().rev() {
+        program.insert(address as usize, Instruction::L(label));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble_round_trip() {
+        // Given
+        let source = "@2\nD=A\n@3\nD=D+A\n@0\nM=D\n";
+        let words = Program::from_source(source).assemble();
+
+        // When
+        let program = disassemble(&words);
+
+        // Then
+        assert_eq!(source, program.to_string());
+    }
+
+    #[test]
+    fn test_read_rom_errors() {
+        for (bytes, expected) in [
+            (
+                b"0000000000000010\n\n101\n".as_slice(),
+                "line 3: invalid binary word 101",
+            ),
+            (b"\x00\x02\x00", "raw ROM image has an odd length"),
+        ] {
+            // When
+            let error = read_rom(bytes).unwrap_err();
+
+            // Then
+            assert_eq!(expected, error.to_string());
+        }
+    }
+
+    #[test]
+    fn test_reconstruct_symbols() {
+        // Given
+        let source = "@i\nM=1\n(LOOP)\n@i\nM=M+1\n@j\nM=0\n@LOOP\n0;JMP\n@j\nM=1\n";
+        let words = Program::from_source(source).assemble();
+        let mut program = disassemble(&words);
+
+        // When
+        reconstruct_symbols(&mut program);
+
+        // Then
+        assert_eq!(
+            source
+                .replace('i', "VAR0")
+                .replace('j', "VAR1")
+                .replace("LOOP", "LBL0"),
+            program.to_string()
+        );
+    }
+}
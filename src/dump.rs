@@ -0,0 +1,83 @@
+use std::{collections::BTreeMap, fmt::Write, ops::Range};
+
+use clap::ValueEnum;
+
+use crate::symbol_table::SymbolTable;
+
+/// The format of a memory dump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DumpFormat {
+    /// One `address: value` line per cell, in hexadecimal, annotated with symbols.
+    Hex,
+    /// Raw big-endian 16-bit words, readable as a ROM image.
+    Binary,
+    /// One `RAM[address]: value` line per cell, in signed decimal, annotated with symbols.
+    Text,
+}
+
+/// Dumps a range of the RAM in the given format. Text formats annotate each
+/// cell with the variables and predefined symbols at its address.
+///
+/// # Panic
+///
+/// Panics if the range is outside of the RAM.
+pub fn dump(
+    ram: &[u16],
+    range: Range<usize>,
+    format: DumpFormat,
+    symbol_table: &SymbolTable,
+) -> Vec<u8> {
+    let cells = ram
+        .get(range.clone())
+        .expect("dump range is outside of the RAM");
+
+    if format == DumpFormat::Binary {
+        return cells.iter().flat_map(|word| word.to_be_bytes()).collect();
+    }
+
+    let mut symbols: BTreeMap<usize, Vec<&str>> = BTreeMap::new();
+    for (symbol, address) in symbol_table.iter() {
+        if !symbol_table.is_label(symbol) && range.contains(&(address as usize)) {
+            symbols.entry(address as usize).or_default().push(symbol);
+        }
+    }
+
+    let mut output = String::new();
+    for (address, value) in range.zip(cells) {
+        match format {
+            DumpFormat::Hex => write!(output, "{:04x}: {:04x}", address, value),
+            _ => write!(output, "RAM[{}]: {}", address, *value as i16),
+        }
+        .expect("write to string");
+        if let Some(names) = symbols.get_mut(&address) {
+            names.sort_unstable();
+            write!(output, "  // {}", names.join(", ")).expect("write to string");
+        }
+        output.push('\n');
+    }
+    output.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_text() {
+        // Given
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add_variable(String::from("i"));
+        symbol_table.add_label(String::from("LOOP"), 16);
+        let mut ram = vec![0; 32];
+        ram[16] = 0xFFFF;
+
+        // When
+        let output = dump(&ram, 15..17, DumpFormat::Text, &symbol_table);
+
+        // Then
+        assert_eq!(
+            "RAM[15]: 0  // R15\nRAM[16]: -1  // i\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
@@ -0,0 +1,513 @@
+use std::path::Path;
+
+use crate::{
+    assembler::Assembler,
+    disassembler::read_rom,
+    error::{self, AssemblerError},
+    snapshot::{Reader, SnapshotError},
+    symbol_table::SymbolTable,
+};
+
+/// The number of words of the RAM, screen and keyboard included.
+pub const RAM_SIZE: usize = 32768;
+/// The maximum number of words of the ROM.
+pub const ROM_SIZE: usize = 32768;
+
+/// The magic bytes at the start of a saved machine state.
+const STATE_MAGIC: &[u8; 8] = b"HACKSTAT";
+/// The current version of the machine state format.
+const STATE_VERSION: u32 = 1;
+
+/// Why the emulator stopped running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// The program reached a `(END) @END 0;JMP` style infinite loop.
+    Halted,
+    /// The program counter went past the end of the ROM.
+    EndOfRom,
+    /// The cycle budget was exhausted.
+    CycleLimit,
+    /// The user interrupted the run.
+    Interrupted,
+    /// The next instruction accesses M while A is past the end of the RAM.
+    OutOfRam,
+}
+
+/// The RAM accesses of an instruction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Access {
+    /// The address of M if the instruction read it.
+    pub read: Option<u16>,
+    /// The address of M if the instruction wrote it.
+    pub write: Option<u16>,
+    /// The value of M before the instruction wrote it.
+    pub previous: u16,
+}
+
+/// The state overwritten by an instruction, allowing to undo it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Undo {
+    pub pc: u16,
+    pub a: u16,
+    pub d: u16,
+    /// The address read by the instruction.
+    pub read: Option<u16>,
+    /// The address written by the instruction and its previous value.
+    pub write: Option<(u16, u16)>,
+}
+
+/// An emulator of the Hack CPU, with its ROM and RAM.
+#[derive(Clone)]
+pub struct Emulator {
+    rom: Vec<u16>,
+    ram: Vec<u16>,
+    a: u16,
+    d: u16,
+    pc: u16,
+    cycles: u64,
+    /// The number of times each ROM address was executed.
+    counts: Vec<u64>,
+    /// The number of times each RAM address was read or written.
+    accesses: Vec<u64>,
+}
+
+impl Emulator {
+    /// Returns a new emulator with the program loaded in ROM and a zeroed RAM.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the program doesn't fit in the ROM.
+    pub fn new(rom: Vec<u16>) -> Self {
+        assert!(rom.len() <= ROM_SIZE, "program doesn't fit in the ROM");
+        Self {
+            counts: vec![0; rom.len()],
+            accesses: vec![0; RAM_SIZE],
+            rom,
+            ram: vec![0; RAM_SIZE],
+            a: 0,
+            d: 0,
+            pc: 0,
+            cycles: 0,
+        }
+    }
+
+    /// Returns the A register.
+    pub fn a(&self) -> u16 {
+        self.a
+    }
+
+    /// Returns the D register.
+    pub fn d(&self) -> u16 {
+        self.d
+    }
+
+    /// Returns the program counter.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Sets the A register.
+    pub fn set_a(&mut self, a: u16) {
+        self.a = a;
+    }
+
+    /// Sets the D register.
+    pub fn set_d(&mut self, d: u16) {
+        self.d = d;
+    }
+
+    /// Sets the program counter.
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    /// Returns the number of instructions executed.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the number of times each ROM address was executed.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns the number of times each RAM address was read or written.
+    pub fn accesses(&self) -> &[u64] {
+        &self.accesses
+    }
+
+    /// Returns the ROM.
+    pub fn rom(&self) -> &[u16] {
+        &self.rom
+    }
+
+    /// Returns the RAM.
+    pub fn ram(&self) -> &[u16] {
+        &self.ram
+    }
+
+    /// Returns the RAM, allowing to set inputs before running the program.
+    pub fn ram_mut(&mut self) -> &mut [u16] {
+        &mut self.ram
+    }
+
+    /// Replaces the program and restarts it from its first instruction, its
+    /// registers and counts reset. The RAM is kept if asked, zeroed otherwise.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the program doesn't fit in the ROM.
+    pub fn reload(&mut self, rom: Vec<u16>, keep_ram: bool) {
+        let mut reloaded = Self::new(rom);
+        if keep_ram {
+            reloaded.ram = std::mem::take(&mut self.ram);
+        }
+        *self = reloaded;
+    }
+
+    /// Returns whether the program counter is past the end of the ROM.
+    pub fn is_at_end(&self) -> bool {
+        self.pc as usize >= self.rom.len()
+    }
+
+    /// Returns whether the program is stuck in the conventional halting loop,
+    /// an A-instruction loading its own address followed by an unconditional jump.
+    pub fn is_halted(&self) -> bool {
+        let pc = self.pc as usize;
+        match (self.rom.get(pc), self.rom.get(pc + 1)) {
+            (Some(&load), Some(&jump)) => load as usize == pc && jump & 0xE007 == 0xE007,
+            _ => false,
+        }
+    }
+
+    /// Returns whether the next instruction accesses M while A is past the
+    /// end of the RAM, which would fault.
+    pub fn is_out_of_ram(&self) -> bool {
+        match self.rom.get(self.pc as usize) {
+            Some(&instruction) if instruction & 0x8000 != 0 => {
+                instruction & 0x1008 != 0 && self.a as usize >= RAM_SIZE
+            }
+            _ => false,
+        }
+    }
+
+    /// Executes a single instruction and returns its RAM accesses.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the program counter is past the end of the ROM, or if the
+    /// instruction accesses M while A is outside of the RAM.
+    pub fn step(&mut self) -> Access {
+        let instruction = *self
+            .rom
+            .get(self.pc as usize)
+            .expect("program counter is past the end of the ROM");
+        self.cycles += 1;
+        self.counts[self.pc as usize] += 1;
+
+        let mut access = Access::default();
+        if instruction & 0x8000 == 0 {
+            self.a = instruction;
+            self.pc += 1;
+            return access;
+        }
+
+        let y = if instruction & 0x1000 != 0 {
+            let address = self.m_address();
+            access.read = Some(address as u16);
+            self.accesses[address] += 1;
+            self.ram[address]
+        } else {
+            self.a
+        };
+        let out = alu(self.d, y, (instruction >> 6) & 0b11_1111);
+
+        // M is written at the address held by A before the instruction.
+        if instruction & 0b001_000 != 0 {
+            let address = self.m_address();
+            access.write = Some(address as u16);
+            access.previous = self.ram[address];
+            self.accesses[address] += 1;
+            self.ram[address] = out;
+        }
+        let target = self.a;
+        if instruction & 0b100_000 != 0 {
+            self.a = out;
+        }
+        if instruction & 0b010_000 != 0 {
+            self.d = out;
+        }
+
+        let out = out as i16;
+        let jump = (instruction & 0b100 != 0 && out < 0)
+            || (instruction & 0b010 != 0 && out == 0)
+            || (instruction & 0b001 != 0 && out > 0);
+        self.pc = if jump { target } else { self.pc + 1 };
+        access
+    }
+
+    /// Executes a single instruction and returns its RAM accesses along with
+    /// what's needed to undo it.
+    ///
+    /// # Panic
+    ///
+    /// Panics in the same cases as [`Emulator::step`].
+    pub fn step_undoable(&mut self) -> (Access, Undo) {
+        let (pc, a, d) = (self.pc, self.a, self.d);
+        let access = self.step();
+        let undo = Undo {
+            pc,
+            a,
+            d,
+            read: access.read,
+            write: access.write.map(|address| (address, access.previous)),
+        };
+        (access, undo)
+    }
+
+    /// Restores the state preceding an instruction executed by [`Emulator::step_undoable`].
+    /// Instructions must be undone in the reverse order of their execution.
+    pub fn undo(&mut self, undo: &Undo) {
+        self.pc = undo.pc;
+        self.a = undo.a;
+        self.d = undo.d;
+        if let Some(address) = undo.read {
+            self.accesses[address as usize] -= 1;
+        }
+        if let Some((address, value)) = undo.write {
+            self.ram[address as usize] = value;
+            self.accesses[address as usize] -= 1;
+        }
+        self.cycles -= 1;
+        self.counts[undo.pc as usize] -= 1;
+    }
+
+    /// Runs the program until it halts, reaches the end of the ROM, is about
+    /// to access M past the end of the RAM, or executes the maximum number of
+    /// instructions.
+    pub fn run(&mut self, max_cycles: u64) -> Stop {
+        let start = self.cycles;
+        loop {
+            if self.is_at_end() {
+                return Stop::EndOfRom;
+            }
+            if self.is_halted() {
+                return Stop::Halted;
+            }
+            if self.is_out_of_ram() {
+                return Stop::OutOfRam;
+            }
+            if self.cycles - start >= max_cycles {
+                return Stop::CycleLimit;
+            }
+            self.step();
+        }
+    }
+
+    /// Saves the full machine state. All integers are little-endian:
+    /// - the magic bytes `HACKSTAT` and the `u32` version,
+    /// - the `u16` A, D and PC registers and the `u64` cycle count,
+    /// - the `u32` ROM length followed by the `u16` ROM words,
+    /// - the `u16` words of the whole RAM.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 + 2 * (self.rom.len() + RAM_SIZE));
+        bytes.extend_from_slice(STATE_MAGIC);
+        bytes.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        for register in [self.a, self.d, self.pc] {
+            bytes.extend_from_slice(&register.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.cycles.to_le_bytes());
+        bytes.extend_from_slice(&(self.rom.len() as u32).to_le_bytes());
+        for word in self.rom.iter().chain(&self.ram) {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Restores an emulator from a machine state saved by [`Emulator::save_state`].
+    /// The execution and access counts start over from zero.
+    pub fn restore_state(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader { bytes };
+        if reader.take(STATE_MAGIC.len())? != STATE_MAGIC {
+            return Err(SnapshotError::Malformed(String::from(
+                "invalid magic bytes",
+            )));
+        }
+        let version = reader.u32()?;
+        if version != STATE_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let (a, d, pc) = (reader.u16()?, reader.u16()?, reader.u16()?);
+        let cycles = reader.u64()?;
+        let rom_len = reader.u32()? as usize;
+        if rom_len > ROM_SIZE {
+            return Err(SnapshotError::Malformed(String::from(
+                "program doesn't fit in the ROM",
+            )));
+        }
+        let rom = (0..rom_len)
+            .map(|_| reader.u16())
+            .collect::<Result<_, _>>()?;
+        let ram = (0..RAM_SIZE)
+            .map(|_| reader.u16())
+            .collect::<Result<_, _>>()?;
+        if !reader.bytes.is_empty() {
+            return Err(SnapshotError::Malformed(String::from("trailing bytes")));
+        }
+
+        Ok(Self {
+            counts: vec![0; rom_len],
+            accesses: vec![0; RAM_SIZE],
+            rom,
+            ram,
+            a,
+            d,
+            pc,
+            cycles,
+        })
+    }
+
+    fn m_address(&self) -> usize {
+        let address = self.a as usize;
+        assert!(
+            address < RAM_SIZE,
+            "RAM address {} is out of bounds",
+            address
+        );
+        address
+    }
+}
+
+/// Loads a ROM image from a `.hack` file, or assembles it from any other file.
+pub fn load_rom(path: &Path) -> Result<Vec<u16>, AssemblerError> {
+    Ok(load_program(path)?.0)
+}
+
+/// Loads a ROM image along with its symbols from a `.hack` file, or assembles
+/// them from any other file. A `.hack` file only has the predefined symbols.
+///
+/// Returns an error if the file can't be read, doesn't assemble, or if the
+/// program doesn't fit in the ROM.
+pub fn load_program(path: &Path) -> Result<(Vec<u16>, SymbolTable), AssemblerError> {
+    let (rom, symbol_table) = if path
+        .extension()
+        .is_some_and(|extension| extension == "hack")
+    {
+        (read_rom(&error::read(path)?)?, SymbolTable::new())
+    } else {
+        let source = error::read_to_string(path)?;
+        let assembler = Assembler::from_source(&source).fill_symbol_table();
+        let symbol_table = assembler.symbol_table().clone();
+        (assembler.try_assemble()?, symbol_table)
+    };
+    if rom.len() > ROM_SIZE {
+        return Err(AssemblerError::semantic(format!(
+            "program of {} words doesn't fit in the ROM of {} words",
+            rom.len(),
+            ROM_SIZE
+        )));
+    }
+    Ok((rom, symbol_table))
+}
+
+/// Restores an emulator from a machine state file. Returns an error if the
+/// file can't be read or isn't a valid machine state.
+pub fn load_state(path: &Path) -> Result<Emulator, AssemblerError> {
+    Emulator::restore_state(&error::read(path)?)
+        .map_err(|error| AssemblerError::syntax(format!("{}: {}", path.display(), error)))
+}
+
+/// Computes the Hack ALU output from its inputs and the 6 control bits
+/// `zx nx zy ny f no`.
+fn alu(x: u16, y: u16, control: u16) -> u16 {
+    let bit = |n: u16| control & (1 << (5 - n)) != 0;
+
+    let x = if bit(0) { 0 } else { x };
+    let x = if bit(1) { !x } else { x };
+    let y = if bit(2) { 0 } else { y };
+    let y = if bit(3) { !y } else { y };
+    let out = if bit(4) { x.wrapping_add(y) } else { x & y };
+    if bit(5) {
+        !out
+    } else {
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_max() {
+        // Given
+        let rom = load_rom(Path::new("test_data/max/Max.asm")).unwrap();
+        let mut emulator = Emulator::new(rom);
+        emulator.ram_mut()[0] = 3;
+        emulator.ram_mut()[1] = 5;
+
+        // When
+        let stop = emulator.run(1000);
+
+        // Then
+        assert_eq!(Stop::Halted, stop);
+        assert_eq!(5, emulator.ram()[2]);
+    }
+
+    #[test]
+    fn test_run_stops_before_accessing_m_past_the_ram() {
+        // Given
+        let rom = Assembler::from_source("@32767\nA=A+1\nM=0\n")
+            .fill_symbol_table()
+            .assemble();
+        let mut emulator = Emulator::new(rom);
+
+        // When
+        let stop = emulator.run(1000);
+
+        // Then
+        assert_eq!(Stop::OutOfRam, stop);
+        assert_eq!(
+            (2, 2, 32768),
+            (emulator.cycles(), emulator.pc(), emulator.a())
+        );
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        // Given
+        let rom = load_rom(Path::new("test_data/max/Max.asm")).unwrap();
+        let mut emulator = Emulator::new(rom);
+        emulator.ram_mut()[1] = 5;
+        emulator.run(5);
+
+        // When
+        let mut restored = Emulator::restore_state(&emulator.save_state()).unwrap();
+
+        // Then
+        assert_eq!(emulator.save_state(), restored.save_state());
+        assert_eq!(Stop::Halted, restored.run(1000));
+        assert_eq!(5, restored.ram()[2]);
+    }
+
+    #[test]
+    fn test_reload_keeps_the_ram_if_asked() {
+        // Given
+        let mut emulator = Emulator::new(load_rom(Path::new("test_data/max/Max.asm")).unwrap());
+        emulator.ram_mut()[0] = 3;
+        emulator.ram_mut()[1] = 5;
+        emulator.run(1000);
+        let mut zeroed = emulator.clone();
+
+        // When
+        emulator.reload(vec![0, 0xEA87], true);
+        zeroed.reload(vec![0, 0xEA87], false);
+
+        // Then
+        assert_eq!((0, 0, 0), (emulator.pc(), emulator.cycles(), emulator.a()));
+        assert_eq!(&[3, 5, 5], &emulator.ram()[..3]);
+        assert_eq!(&[0, 0, 0], &zeroed.ram()[..3]);
+        assert_eq!(2, emulator.rom().len());
+    }
+}
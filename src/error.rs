@@ -0,0 +1,263 @@
+use std::{fmt, io, path::Path};
+
+use crate::{
+    cancel::Cancelled,
+    diagnostic::{Diagnostic, Location},
+};
+
+/// An error raised while assembling a program.
+#[derive(Debug)]
+pub enum AssemblerError {
+    /// A file couldn't be read or written.
+    Io { context: String, source: io::Error },
+    /// A line isn't a valid instruction.
+    Syntax {
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+        /// A hint on how to fix the error.
+        note: Option<String>,
+        /// The text most likely fixing the error.
+        suggestion: Option<String>,
+    },
+    /// The instructions are valid, but the program can't be assembled.
+    Semantic {
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+        /// A hint on how to fix the error.
+        note: Option<String>,
+        /// The text most likely fixing the error.
+        suggestion: Option<String>,
+    },
+    /// The assembly was cancelled.
+    Cancelled,
+    /// Several errors, in source order, found before giving up.
+    Multiple(Vec<AssemblerError>),
+}
+
+impl AssemblerError {
+    /// Returns an IO error, `context` telling what failed.
+    pub fn io(context: impl Into<String>, source: io::Error) -> Self {
+        Self::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// Returns a syntax error without a line.
+    pub fn syntax(message: impl Into<String>) -> Self {
+        Self::Syntax {
+            line: None,
+            column: None,
+            message: message.into(),
+            note: None,
+            suggestion: None,
+        }
+    }
+
+    /// Returns a semantic error without a line.
+    pub fn semantic(message: impl Into<String>) -> Self {
+        Self::Semantic {
+            line: None,
+            column: None,
+            message: message.into(),
+            note: None,
+            suggestion: None,
+        }
+    }
+
+    /// Returns the error combining the errors, if any: the error itself if
+    /// there is only one.
+    pub fn combine(mut errors: Vec<AssemblerError>) -> Result<(), Self> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(Self::Multiple(errors)),
+        }
+    }
+
+    /// Returns the errors, [`AssemblerError::Multiple`] being flattened.
+    pub fn errors(&self) -> Vec<&AssemblerError> {
+        match self {
+            Self::Multiple(errors) => errors.iter().flat_map(Self::errors).collect(),
+            error => vec![error],
+        }
+    }
+
+    /// Returns the error with a hint on how to fix it.
+    #[must_use]
+    pub fn with_note(mut self, hint: impl Into<String>) -> Self {
+        if let Self::Syntax { note, .. } | Self::Semantic { note, .. } = &mut self {
+            *note = Some(hint.into());
+        }
+        self
+    }
+
+    /// Returns the error with the text replacing the one it points to, that
+    /// most likely fixes it.
+    #[must_use]
+    pub fn with_suggestion(mut self, replacement: impl Into<String>) -> Self {
+        if let Self::Syntax { suggestion, .. } | Self::Semantic { suggestion, .. } = &mut self {
+            *suggestion = Some(replacement.into());
+        }
+        self
+    }
+
+    /// Returns the kind of the error, as the code of its diagnostics.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Io { .. } => "io",
+            Self::Syntax { .. } => "syntax",
+            Self::Semantic { .. } => "semantic",
+            Self::Cancelled => "cancelled",
+            Self::Multiple(_) => "multiple",
+        }
+    }
+
+    /// Returns the error located at the source line, unless it has a line
+    /// already.
+    #[must_use]
+    pub fn at_line(mut self, source_line: Option<usize>) -> Self {
+        if let Self::Syntax { line, .. } | Self::Semantic { line, .. } = &mut self {
+            *line = line.or(source_line);
+        }
+        self
+    }
+
+    /// Returns the error located at the 1-based column of the source line,
+    /// unless it has a line already.
+    #[must_use]
+    pub fn at(mut self, source_line: usize, source_column: usize) -> Self {
+        if let Self::Syntax { line, column, .. } | Self::Semantic { line, column, .. } = &mut self {
+            if line.is_none() {
+                *line = Some(source_line);
+                *column = Some(source_column);
+            }
+        }
+        self
+    }
+
+    /// Returns the source line of the error, if known.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Self::Syntax { line, .. } | Self::Semantic { line, .. } => *line,
+            Self::Io { .. } | Self::Cancelled | Self::Multiple(_) => None,
+        }
+    }
+
+    /// Returns the source column of the error, if known.
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            Self::Syntax { column, .. } | Self::Semantic { column, .. } => *column,
+            Self::Io { .. } | Self::Cancelled | Self::Multiple(_) => None,
+        }
+    }
+
+    /// Returns the errors as diagnostics pointing into the source they were
+    /// raised on, the file being the path of the source, if any. The errors
+    /// without a line are reported as is.
+    pub fn to_diagnostics(&self, file: Option<&Path>, source: &str) -> Vec<Diagnostic> {
+        self.errors()
+            .into_iter()
+            .map(|error| error.to_diagnostic(file, source))
+            .collect()
+    }
+
+    fn to_diagnostic(&self, file: Option<&Path>, source: &str) -> Diagnostic {
+        match self {
+            Self::Syntax {
+                line,
+                column,
+                message,
+                note,
+                suggestion,
+            }
+            | Self::Semantic {
+                line,
+                column,
+                message,
+                note,
+                suggestion,
+            } => {
+                let mut diagnostic = match line {
+                    Some(line) => Diagnostic::error(message.clone())
+                        .at(Location::new(file, source, *line, *column)),
+                    None => Diagnostic::error(self.to_string()),
+                };
+                if let Some(note) = note {
+                    diagnostic = diagnostic.with_note(note.clone());
+                }
+                if let Some(suggestion) = suggestion {
+                    diagnostic = diagnostic.with_suggestion(suggestion.clone());
+                }
+                diagnostic.with_code(self.code())
+            }
+            _ => Diagnostic::error(self.to_string()).with_code(self.code()),
+        }
+    }
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { context, source } => write!(f, "{}: {}", context, source),
+            Self::Syntax {
+                line,
+                column,
+                message,
+                ..
+            }
+            | Self::Semantic {
+                line,
+                column,
+                message,
+                ..
+            } => {
+                match (line, column) {
+                    (Some(line), Some(column)) => write!(f, "line {}:{}: ", line, column)?,
+                    (Some(line), None) => write!(f, "line {}: ", line)?,
+                    _ => {}
+                }
+                write!(f, "{}", message)
+            }
+            Self::Cancelled => write!(f, "{}", Cancelled),
+            Self::Multiple(errors) => {
+                for (index, error) in errors.iter().enumerate() {
+                    if index > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssemblerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<Cancelled> for AssemblerError {
+    fn from(_: Cancelled) -> Self {
+        Self::Cancelled
+    }
+}
+
+/// Reads a file, the error naming the file.
+pub fn read(path: &Path) -> Result<Vec<u8>, AssemblerError> {
+    std::fs::read(path)
+        .map_err(|error| AssemblerError::io(format!("failed to read {}", path.display()), error))
+}
+
+/// Reads a file to a string, the error naming the file.
+pub fn read_to_string(path: &Path) -> Result<String, AssemblerError> {
+    std::fs::read_to_string(path)
+        .map_err(|error| AssemblerError::io(format!("failed to read {}", path.display()), error))
+}
@@ -0,0 +1,190 @@
+use serde::Deserialize;
+
+/// The case of the C-instruction mnemonics once formatted.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MnemonicCase {
+    /// Keeps the mnemonics as written.
+    #[default]
+    Preserve,
+    Upper,
+    /// Lowercase, which only assembles in the relaxed dialect.
+    Lower,
+}
+
+/// The formatting options, the `format` section of the project config.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatConfig {
+    /// The number of spaces instructions are indented by. Labels are never
+    /// indented.
+    pub indent: usize,
+    /// The column trailing comments are aligned to, or `None` to separate
+    /// them from the code by a single space.
+    pub comment_column: Option<usize>,
+    /// The number of blank lines before a label and the comments above it.
+    pub blank_lines_before_labels: usize,
+    /// The number of blank lines after a label.
+    pub blank_lines_after_labels: usize,
+    pub mnemonics: MnemonicCase,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            indent: 4,
+            comment_column: None,
+            blank_lines_before_labels: 1,
+            blank_lines_after_labels: 0,
+            mnemonics: MnemonicCase::Preserve,
+        }
+    }
+}
+
+/// A source line, split into its code and its trailing comment.
+enum Line<'a> {
+    Blank,
+    Comment(&'a str),
+    Label(String, Option<&'a str>),
+    Instruction(String, Option<&'a str>),
+}
+
+impl<'a> Line<'a> {
+    fn parse(source: &'a str, config: &FormatConfig) -> Self {
+        let (code, comment) = match source.find("//") {
+            Some(index) => (&source[..index], Some(source[index..].trim_end())),
+            None => (source, None),
+        };
+        let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+        match comment {
+            _ if code.starts_with('(') => Line::Label(code, comment),
+            _ if code.starts_with('@') => Line::Instruction(code, comment),
+            _ if !code.is_empty() => {
+                let code = match config.mnemonics {
+                    MnemonicCase::Preserve => code,
+                    MnemonicCase::Upper => code.to_uppercase(),
+                    MnemonicCase::Lower => code.to_lowercase(),
+                };
+                Line::Instruction(code, comment)
+            }
+            Some(comment) => Line::Comment(comment),
+            None => Line::Blank,
+        }
+    }
+}
+
+/// Formats an assembly source. Lines that aren't valid instructions are kept,
+/// reindented, so that a document can be formatted while it's being edited.
+///
+/// Runs of blank lines are collapsed to one, and comment lines take the
+/// indentation of the code following them.
+pub fn format(source: &str, config: &FormatConfig) -> String {
+    let lines: Vec<Line> = source
+        .lines()
+        .map(|line| Line::parse(line, config))
+        .collect();
+    let mut output: Vec<String> = Vec::new();
+    let mut blank_lines = 0;
+    // The blank lines wanted before the next code line, after a label.
+    let mut after_label = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let (code, comment, is_label) = match line {
+            Line::Blank => {
+                blank_lines += 1;
+                continue;
+            }
+            Line::Comment(comment) => {
+                let next = lines[index..]
+                    .iter()
+                    .find(|line| !matches!(line, Line::Blank | Line::Comment(_)));
+                (None, Some(*comment), matches!(next, Some(Line::Label(..))))
+            }
+            Line::Label(code, comment) => (Some(code), *comment, true),
+            Line::Instruction(code, comment) => (Some(code), *comment, false),
+        };
+
+        let starts_block = is_label
+            && !matches!(
+                index.checked_sub(1).map(|previous| &lines[previous]),
+                Some(Line::Comment(_))
+            );
+        let blank_lines_wanted = if output.is_empty() {
+            0
+        } else if starts_block {
+            config.blank_lines_before_labels
+        } else if code.is_some() && !is_label && after_label > 0 {
+            after_label
+        } else {
+            blank_lines.min(1)
+        };
+        output.extend(std::iter::repeat_n(String::new(), blank_lines_wanted));
+        blank_lines = 0;
+        if code.is_some() {
+            after_label = if is_label {
+                config.blank_lines_after_labels
+            } else {
+                0
+            };
+        }
+
+        let indent = if is_label { 0 } else { config.indent };
+        let mut formatted = " ".repeat(indent);
+        if let Some(code) = code {
+            formatted.push_str(code);
+        }
+        if let Some(comment) = comment {
+            if code.is_some() {
+                let column = config
+                    .comment_column
+                    .unwrap_or_default()
+                    .max(formatted.len() + 1);
+                formatted = format!("{:<width$}", formatted, width = column);
+            }
+            formatted.push_str(comment);
+        }
+        output.push(formatted);
+    }
+
+    let mut formatted = output.join("\n");
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dialect::Dialect, program::Program};
+
+    #[test]
+    fn test_format() {
+        // Given
+        let source = "// Sum\n@i\nm = 1 // i = 1\n\n\n// Loop\n(LOOP)\n  @LOOP\n0 ; jmp\n";
+        let config = FormatConfig {
+            indent: 2,
+            comment_column: Some(12),
+            blank_lines_after_labels: 1,
+            mnemonics: MnemonicCase::Upper,
+            ..Default::default()
+        };
+
+        // When
+        let formatted = format(source, &config);
+
+        // Then
+        assert_eq!(
+            "  // Sum\n  @i\n  M=1       // i = 1\n\n// Loop\n(LOOP)\n\n  @LOOP\n  0;JMP\n",
+            formatted
+        );
+        let lowercase = FormatConfig {
+            mnemonics: MnemonicCase::Lower,
+            ..Default::default()
+        };
+        let formatted = format("@LOOP\n(LOOP)\nM=M+1\n0;JMP\n", &lowercase);
+        assert_eq!(
+            Program::from_source("@LOOP\n(LOOP)\nM=M+1\n0;JMP\n").assemble(),
+            Program::from_source(&Dialect::Relaxed.translate(&formatted)).assemble()
+        );
+    }
+}
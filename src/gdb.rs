@@ -0,0 +1,365 @@
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    debugger::{Debugger, Event, Watch},
+    emulator::{Stop, RAM_SIZE},
+    error::AssemblerError,
+};
+
+/// The number of cycles executed between two checks for an interrupt request.
+const CHUNK_CYCLES: u64 = 100_000;
+
+/// The interrupt request sent by GDB while the target is running.
+const INTERRUPT: u8 = 0x03;
+
+/// What to do after handling a packet.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    /// Send the reply.
+    Reply(String),
+    /// Execute a single instruction, then report the stop.
+    Step,
+    /// Run until a breakpoint, then report the stop.
+    Continue,
+    /// Undo the last instruction, then report the stop.
+    ReverseStep,
+    /// Undo instructions until a breakpoint, then report the stop.
+    ReverseContinue,
+    /// Acknowledge and close the connection.
+    Detach,
+    /// Close the connection and stop serving.
+    Kill,
+}
+
+/// Serves the debugger over the GDB remote serial protocol on the given address,
+/// one connection at a time, until GDB kills the target.
+///
+/// The target has three 16-bit registers, A, D and PC. Memory addresses are
+/// byte addresses into the RAM, each word being stored little-endian, while
+/// breakpoints and the PC are ROM addresses.
+///
+/// Returns an error if the server can't listen on the address.
+pub fn serve(addr: &str, mut debugger: Debugger) -> Result<(), AssemblerError> {
+    let listener = TcpListener::bind(addr)
+        .map_err(|error| AssemblerError::io(format!("failed to listen on {}", addr), error))?;
+    println!("listening for GDB on {}", addr);
+
+    for stream in listener.incoming().flatten() {
+        match handle_connection(&mut debugger, stream) {
+            Ok(true) => break,
+            Ok(false) | Err(_) => continue,
+        }
+    }
+    Ok(())
+}
+
+/// Handles the packets of a connection, returning whether the target was killed.
+fn handle_connection(debugger: &mut Debugger, stream: TcpStream) -> std::io::Result<bool> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    while let Some(packet) = read_packet(&mut reader, &mut writer)? {
+        let reply = match handle_packet(debugger, &packet) {
+            Action::Reply(reply) => reply,
+            Action::Step => {
+                let event = debugger.step();
+                stop_reply(debugger, event)
+            }
+            Action::Continue => {
+                let event = resume(debugger, reader.get_ref());
+                stop_reply(debugger, event)
+            }
+            Action::ReverseStep => {
+                let event = debugger.step_back();
+                stop_reply(debugger, event)
+            }
+            Action::ReverseContinue => {
+                let event = debugger.reverse_resume();
+                stop_reply(debugger, event)
+            }
+            Action::Detach => {
+                write_packet(&mut writer, "OK")?;
+                return Ok(false);
+            }
+            Action::Kill => return Ok(true),
+        };
+        write_packet(&mut writer, &reply)?;
+    }
+    Ok(false)
+}
+
+/// Reads the next packet, acknowledging it, or returns `None` at the end of the stream.
+fn read_packet(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+) -> std::io::Result<Option<String>> {
+    loop {
+        let mut byte = [0];
+        // Skip the acknowledgments and stray interrupts until the start of a packet.
+        loop {
+            if reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+
+        let mut data = Vec::new();
+        reader.read_until(b'#', &mut data)?;
+        if data.pop() != Some(b'#') {
+            return Ok(None);
+        }
+        let mut received = [0; 2];
+        reader.read_exact(&mut received)?;
+
+        let received = std::str::from_utf8(&received)
+            .ok()
+            .and_then(|received| u8::from_str_radix(received, 16).ok());
+        if received == Some(checksum(&data)) {
+            writer.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+        writer.write_all(b"-")?;
+    }
+}
+
+fn write_packet(writer: &mut impl Write, data: &str) -> std::io::Result<()> {
+    write!(writer, "${}#{:02x}", data, checksum(data.as_bytes()))?;
+    writer.flush()
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Handles a packet, without resuming the target.
+fn handle_packet(debugger: &mut Debugger, packet: &str) -> Action {
+    let (command, arguments) = packet.split_at(packet.len().min(1));
+    let reply = match command {
+        "?" => "S05".to_string(),
+        "g" => {
+            let emulator = debugger.emulator();
+            [emulator.a(), emulator.d(), emulator.pc()]
+                .iter()
+                .map(|value| hex_word(*value))
+                .collect()
+        }
+        "p" => {
+            let emulator = debugger.emulator();
+            match u8::from_str_radix(arguments, 16) {
+                Ok(0) => hex_word(emulator.a()),
+                Ok(1) => hex_word(emulator.d()),
+                Ok(2) => hex_word(emulator.pc()),
+                _ => "E01".to_string(),
+            }
+        }
+        "m" => read_memory(debugger, arguments).unwrap_or_else(|| "E01".to_string()),
+        "M" => match write_memory(debugger, arguments) {
+            Some(()) => "OK".to_string(),
+            None => "E01".to_string(),
+        },
+        "Z" | "z" => match parse_breakpoint(arguments) {
+            Some((None, address)) if command == "Z" => {
+                debugger.add_breakpoint_at(address);
+                "OK".to_string()
+            }
+            Some((None, address)) => {
+                debugger.remove_breakpoint_at(address);
+                "OK".to_string()
+            }
+            Some((Some(watch), address)) if command == "Z" => {
+                debugger.add_watchpoint_at(address, watch);
+                "OK".to_string()
+            }
+            Some((Some(_), address)) => {
+                debugger.remove_watchpoint_at(address);
+                "OK".to_string()
+            }
+            None => String::new(),
+        },
+        "s" if arguments.is_empty() => return Action::Step,
+        "c" if arguments.is_empty() => return Action::Continue,
+        "b" if arguments == "s" => return Action::ReverseStep,
+        "b" if arguments == "c" => return Action::ReverseContinue,
+        "D" => return Action::Detach,
+        "k" => return Action::Kill,
+        "q" if arguments.starts_with("Supported") => {
+            "PacketSize=1000;ReverseStep+;ReverseContinue+".to_string()
+        }
+        "q" if arguments == "Attached" => "1".to_string(),
+        "q" if arguments == "C" => "QC1".to_string(),
+        "H" => "OK".to_string(),
+        // An empty reply tells GDB that the packet isn't supported.
+        _ => String::new(),
+    };
+    Action::Reply(reply)
+}
+
+/// Formats a word as the hex of its little-endian bytes.
+fn hex_word(value: u16) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Parses the `address,length` arguments of a memory packet, as a byte range
+/// within the RAM.
+fn parse_range(arguments: &str) -> Option<std::ops::Range<usize>> {
+    let (address, length) = arguments.split_once(',')?;
+    let address = usize::from_str_radix(address, 16).ok()?;
+    let length = usize::from_str_radix(length, 16).ok()?;
+    let end = address.checked_add(length)?;
+    (end <= RAM_SIZE * 2).then_some(address..end)
+}
+
+fn read_memory(debugger: &Debugger, arguments: &str) -> Option<String> {
+    let ram = debugger.emulator().ram();
+    let bytes = parse_range(arguments)?
+        .map(|address| ram[address / 2].to_le_bytes()[address % 2])
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Some(bytes)
+}
+
+fn write_memory(debugger: &mut Debugger, arguments: &str) -> Option<()> {
+    let (range, data) = arguments.split_once(':')?;
+    let range = parse_range(range)?;
+    if data.len() != range.len() * 2 {
+        return None;
+    }
+
+    let ram = debugger.emulator_mut().ram_mut();
+    for (address, byte) in range.zip(data.as_bytes().chunks(2)) {
+        let byte = u8::from_str_radix(std::str::from_utf8(byte).ok()?, 16).ok()?;
+        let mut word = ram[address / 2].to_le_bytes();
+        word[address % 2] = byte;
+        ram[address / 2] = u16::from_le_bytes(word);
+    }
+    Some(())
+}
+
+/// Parses the `type,address,kind` arguments of a breakpoint packet, returning
+/// the kind of watchpoint, if any, and the ROM or RAM word address.
+fn parse_breakpoint(arguments: &str) -> Option<(Option<Watch>, u16)> {
+    let mut arguments = arguments.split(',');
+    let watch = match arguments.next()? {
+        "0" | "1" => None,
+        "2" => Some(Watch::Write),
+        "3" => Some(Watch::Read),
+        "4" => Some(Watch::Access),
+        _ => return None,
+    };
+    let address = u16::from_str_radix(arguments.next()?, 16).ok()?;
+    match watch {
+        // Watchpoints are set on byte addresses.
+        Some(_) => ((address as usize) < RAM_SIZE * 2).then_some((watch, address / 2)),
+        None => Some((None, address)),
+    }
+}
+
+/// Runs the target until a breakpoint, checking the connection for an
+/// interrupt request between chunks of cycles.
+fn resume(debugger: &mut Debugger, stream: &TcpStream) -> Event {
+    loop {
+        match debugger.resume(CHUNK_CYCLES) {
+            Event::Stopped(Stop::CycleLimit) if interrupted(stream) => {
+                return Event::Stopped(Stop::Interrupted)
+            }
+            Event::Stopped(Stop::CycleLimit) => continue,
+            event => return event,
+        }
+    }
+}
+
+/// Returns whether GDB sent an interrupt request, without blocking.
+fn interrupted(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let mut byte = [0];
+    let interrupted = match stream.peek(&mut byte) {
+        Ok(1) if byte[0] == INTERRUPT => {
+            let _ = (&mut &*stream).read(&mut byte);
+            true
+        }
+        Ok(_) => false,
+        Err(err) => err.kind() != ErrorKind::WouldBlock,
+    };
+    let _ = stream.set_nonblocking(false);
+    interrupted
+}
+
+/// Returns the stop reply to the event the target stopped on, reporting a
+/// fault if the program would access memory out of bounds.
+fn stop_reply(debugger: &Debugger, event: Event) -> String {
+    match event {
+        Event::Stepped | Event::Breakpoint(_) => "S05".to_string(),
+        Event::StartOfTrace => "T05replaylog:begin;".to_string(),
+        Event::Watchpoint { address, .. } => {
+            let kind = match debugger
+                .watchpoints()
+                .find(|(watched, _)| *watched == address)
+            {
+                Some((_, Watch::Read)) => "rwatch",
+                Some((_, Watch::Access)) => "awatch",
+                _ => "watch",
+            };
+            format!("T05{}:{:x};", kind, address as usize * 2)
+        }
+        Event::Stopped(Stop::Halted | Stop::EndOfRom) => "W00".to_string(),
+        Event::Stopped(Stop::Interrupted | Stop::CycleLimit) => "S02".to_string(),
+        Event::Stopped(Stop::OutOfRam) => "S0b".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{emulator::Emulator, symbol_table::SymbolTable};
+
+    #[test]
+    fn test_serve_on_a_taken_address() {
+        // Given
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let debugger = Debugger::new(Emulator::new(Vec::new()), SymbolTable::new());
+
+        // When
+        let error = serve(&addr, debugger).unwrap_err();
+
+        // Then
+        assert!(matches!(error, AssemblerError::Io { .. }), "{}", error);
+    }
+
+    #[test]
+    fn test_handle_packet() {
+        // Given
+        let mut emulator = Emulator::new(vec![0x0005, 0xEC10]);
+        emulator.ram_mut()[1] = 0x1234;
+        let mut debugger = Debugger::new(emulator, SymbolTable::new());
+        debugger.step();
+
+        // When
+        let registers = handle_packet(&mut debugger, "g");
+        let memory = handle_packet(&mut debugger, "m2,2");
+        let breakpoint = handle_packet(&mut debugger, "Z0,1,2");
+
+        // Then
+        assert_eq!(Action::Reply("050000000100".to_string()), registers);
+        assert_eq!(Action::Reply("3412".to_string()), memory);
+        assert_eq!(Action::Reply("OK".to_string()), breakpoint);
+        assert_eq!(
+            vec![1],
+            debugger
+                .breakpoints()
+                .map(|(address, _)| address)
+                .collect::<Vec<_>>()
+        );
+    }
+}
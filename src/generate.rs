@@ -0,0 +1,174 @@
+use std::fmt::Write;
+
+use crate::code::{COMP, DEST, JUMP};
+
+/// The relative weights of the kinds of generated instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstructionMix {
+    /// A-instructions loading a constant, a variable or a predefined symbol.
+    pub a: u32,
+    /// C-instructions without jump.
+    pub c: u32,
+    /// Jumps to a label: an A-instruction and a jumping C-instruction.
+    pub jump: u32,
+}
+
+impl Default for InstructionMix {
+    fn default() -> Self {
+        Self {
+            a: 4,
+            c: 4,
+            jump: 1,
+        }
+    }
+}
+
+/// The shape of the generated programs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeneratorConfig {
+    /// The number of instructions, before the final halting loop.
+    pub instructions: usize,
+    /// The probability for an instruction to be labelled.
+    pub label_density: f64,
+    /// The number of distinct variables.
+    pub variables: usize,
+    pub mix: InstructionMix,
+    /// The seed of the generator: the same seed gives the same program.
+    pub seed: u64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            instructions: 100,
+            label_density: 0.1,
+            variables: 8,
+            mix: InstructionMix::default(),
+            seed: 0,
+        }
+    }
+}
+
+/// The predefined symbols the A-instructions may load.
+const PREDEFINED: [&str; 8] = ["R0", "R1", "R13", "R15", "SP", "THAT", "SCREEN", "KBD"];
+
+/// Generates a random valid program: every instruction assembles, every
+/// loaded label is defined, M is only accessed at RAM addresses, and the
+/// program ends with an `(END)` loop.
+///
+/// # Panic
+///
+/// Panics if all the weights of the mix are 0.
+pub fn generate(config: &GeneratorConfig) -> String {
+    let mix = config.mix;
+    let total = mix.a + mix.c + mix.jump;
+    assert!(total > 0, "the instruction mix is empty");
+
+    let mut rng = fastrand::Rng::with_seed(config.seed);
+    let labelled: Vec<bool> = (0..config.instructions)
+        .map(|_| rng.f64() < config.label_density)
+        .collect();
+    let labels = labelled.iter().filter(|labelled| **labelled).count();
+    let label = |rng: &mut fastrand::Rng| match rng.usize(..=labels) {
+        index if index == labels => String::from("END"),
+        index => format!("L{}", index),
+    };
+
+    let mut program = String::new();
+    let mut defined = 0;
+    let mut count = 0;
+    // Whether A holds a computed value, possibly outside of the RAM: the next
+    // instruction then loads A, so that M is only accessed at valid addresses.
+    let mut computed = false;
+    while count < config.instructions {
+        if labelled[count] {
+            writeln!(program, "(L{})", defined).expect("write to string");
+            defined += 1;
+        }
+        let kind = match computed {
+            true if mix.a + mix.jump > 0 => match rng.u32(..mix.a + mix.jump) {
+                kind if kind < mix.a => kind,
+                kind => kind + mix.c,
+            },
+            _ => rng.u32(..total),
+        };
+        computed = false;
+        if kind < mix.a {
+            let value = match rng.u8(..3) {
+                0 => rng.u16(..0x8000).to_string(),
+                1 if config.variables > 0 => format!("v{}", rng.usize(..config.variables)),
+                _ => PREDEFINED[rng.usize(..PREDEFINED.len())].to_string(),
+            };
+            writeln!(program, "@{}", value).expect("write to string");
+        } else if kind < mix.a + mix.c {
+            // Without loads, A can't be written.
+            let dests = if mix.a + mix.jump > 0 { DEST.len() } else { 4 };
+            let dest = DEST[rng.usize(1..dests)];
+            let (comp, _) = COMP[rng.usize(..COMP.len())];
+            writeln!(program, "{}={}", dest, comp).expect("write to string");
+            computed = dest.contains('A');
+        } else if count + 1 == config.instructions {
+            // No room for the jump itself.
+            writeln!(program, "@{}", label(&mut rng)).expect("write to string");
+        } else {
+            let (comp, _) = COMP[rng.usize(..COMP.len())];
+            let jump = JUMP[rng.usize(1..JUMP.len())];
+            writeln!(program, "@{}\n{};{}", label(&mut rng), comp, jump).expect("write to string");
+            // The label of the jumping instruction moves to the next one.
+            if labelled[count + 1] {
+                writeln!(program, "(L{})", defined).expect("write to string");
+                defined += 1;
+            }
+            count += 1;
+        }
+        count += 1;
+    }
+    // The labels past the end, skipped by a jump ending the program.
+    for index in defined..labels {
+        writeln!(program, "(L{})", index).expect("write to string");
+    }
+    program.push_str("(END)\n@END\n0;JMP\n");
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assembler::Assembler, disassembler, emulator::Emulator, linker, object::Object,
+        program::Program,
+    };
+
+    #[test]
+    fn test_generated_programs_assemble_consistently() {
+        for seed in 0..32 {
+            // Given
+            let config = GeneratorConfig {
+                instructions: 200,
+                label_density: 0.2,
+                seed,
+                ..Default::default()
+            };
+            let source = generate(&config);
+
+            // When
+            let words = Assembler::from_source(&source)
+                .fill_symbol_table()
+                .assemble();
+            let linked =
+                linker::link(vec![Object::assemble("Main", &source).unwrap()], &[]).unwrap();
+            let disassembled = disassembler::disassemble(&words).to_string();
+            let reassembled = Program::from_source(&disassembled).assemble();
+
+            // Then
+            assert_eq!(config.instructions + 2, words.len(), "seed {}", seed);
+            assert_eq!(words, linked.words, "seed {}", seed);
+            assert_eq!(words, reassembled, "seed {}", seed);
+            let mut emulator = Emulator::new(words.clone());
+            let mut relinked = Emulator::new(linked.words);
+            emulator.run(10_000);
+            relinked.run(10_000);
+            assert_eq!(emulator.ram(), relinked.ram(), "seed {}", seed);
+        }
+    }
+}
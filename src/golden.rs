@@ -0,0 +1,122 @@
+use std::fmt;
+
+use crate::{assembler::Assembler, error::AssemblerError};
+
+/// A sample program with its expected machine code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GoldenCase {
+    pub name: &'static str,
+    pub source: &'static str,
+    /// The `.hack` output, byte for byte.
+    pub expected: &'static str,
+}
+
+macro_rules! golden_case {
+    ($dir:literal, $name:literal) => {
+        GoldenCase {
+            name: $name,
+            source: include_str!(concat!("../test_data/", $dir, "/", $name, ".asm")),
+            expected: include_str!(concat!("../test_data/", $dir, "/", $name, ".hack")),
+        }
+    };
+}
+
+/// The nand2tetris sample programs, with and without symbols.
+pub const CORPUS: [GoldenCase; 7] = [
+    golden_case!("add", "Add"),
+    golden_case!("max", "Max"),
+    golden_case!("max", "MaxL"),
+    golden_case!("rect", "Rect"),
+    golden_case!("rect", "RectL"),
+    golden_case!("pong", "Pong"),
+    golden_case!("pong", "PongL"),
+];
+
+/// The first difference between the assembled and the expected output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub name: &'static str,
+    /// The 1-based line of the output.
+    pub line: usize,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |line: &Option<String>| line.clone().unwrap_or_else(|| String::from("<end>"));
+        write!(
+            f,
+            "{}: line {}: expected {}, got {}",
+            self.name,
+            self.line,
+            show(&self.expected),
+            show(&self.actual)
+        )
+    }
+}
+
+impl GoldenCase {
+    /// Assembles the source and compares the output with the expected one,
+    /// returning the first difference, or an error if the source fails to
+    /// assemble.
+    pub fn verify(&self) -> Result<Option<Mismatch>, AssemblerError> {
+        let actual = Assembler::from_source(self.source)
+            .fill_symbol_table()
+            .try_render()?;
+        if actual == self.expected {
+            return Ok(None);
+        }
+        let mut expected_lines = self.expected.split_inclusive('\n');
+        let mut actual_lines = actual.split_inclusive('\n');
+        let mut line = 1;
+        loop {
+            match (expected_lines.next(), actual_lines.next()) {
+                (expected, actual) if expected != actual => {
+                    return Ok(Some(Mismatch {
+                        name: self.name,
+                        line,
+                        expected: expected.map(|line| format!("{:?}", line)),
+                        actual: actual.map(|line| format!("{:?}", line)),
+                    }));
+                }
+                _ => line += 1,
+            }
+        }
+    }
+}
+
+/// Verifies the whole corpus, returning the mismatches, or an error if a
+/// source fails to assemble.
+pub fn self_test() -> Result<Vec<Mismatch>, AssemblerError> {
+    let mut mismatches = Vec::new();
+    for case in &CORPUS {
+        mismatches.extend(case.verify()?);
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mismatch_reports_first_difference() {
+        // Given
+        let case = GoldenCase {
+            name: "Add",
+            expected: "0000000000000010\n1110110000010000\n0000000000000000\n",
+            ..CORPUS[0]
+        };
+
+        // When
+        let mismatch = case.verify().unwrap().unwrap();
+
+        // Then
+        assert_eq!(3, mismatch.line);
+        assert_eq!(
+            "Add: line 3: expected \"0000000000000000\\n\", got \"0000000000000011\\n\"",
+            mismatch.to_string()
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assembler::Assembler,
+    disassembler::read_rom,
+    emulator::{Emulator, Stop, RAM_SIZE},
+    error::{self, AssemblerError},
+    keyboard::KBD,
+    limits::Limits,
+    symbol_table::SymbolTable,
+};
+
+/// The number of cycles executed between two checks of the wall-clock timeout.
+const CHUNK_CYCLES: u64 = 10_000;
+
+/// The test cases a submission is graded against, usually loaded from JSON.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GradeSpec {
+    /// The maximum number of instructions executed by each case.
+    #[serde(default = "default_max_cycles")]
+    pub max_cycles: u64,
+    /// The maximum wall-clock time of each case, in milliseconds.
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    pub cases: Vec<GradeCase>,
+}
+
+fn default_max_cycles() -> u64 {
+    1_000_000
+}
+
+fn default_timeout_ms() -> u64 {
+    1_000
+}
+
+/// A single test case: inputs written to the RAM before running, and the
+/// values expected in the RAM once the program halts.
+///
+/// Addresses are numbers, `Rn` registers or symbols of the program, values
+/// are signed or unsigned 16-bit words.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GradeCase {
+    pub name: String,
+    #[serde(default)]
+    pub set: BTreeMap<String, i32>,
+    /// The code of the key held during the run, if any.
+    #[serde(default)]
+    pub keyboard: Option<u16>,
+    pub expect: BTreeMap<String, i32>,
+}
+
+/// The outcome of a test case.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CaseReport {
+    pub name: String,
+    pub passed: bool,
+    /// The number of instructions executed.
+    pub cycles: u64,
+    /// Why the run stopped: `halted`, `end`, `cycle_limit`, `timeout` or `error`.
+    pub stop: String,
+    /// What went wrong, if the case failed.
+    pub failures: Vec<String>,
+}
+
+/// The outcome of grading a submission.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct GradeReport {
+    pub submission: String,
+    pub passed: bool,
+    /// The error raised while loading the submission, if any.
+    pub error: Option<String>,
+    pub cases: Vec<CaseReport>,
+}
+
+/// Grades a submission, as a `.hack` ROM image or assembly source, against the
+/// test cases. Load errors and faults are reported as failures.
+pub fn grade(path: &Path, spec: &GradeSpec, limits: Limits) -> GradeReport {
+    let mut report = GradeReport {
+        submission: path.display().to_string(),
+        passed: false,
+        error: None,
+        cases: Vec::new(),
+    };
+    let (rom, symbol_table) = match load(path, limits) {
+        Ok(program) => program,
+        Err(error) => {
+            report.error = Some(error.to_string());
+            return report;
+        }
+    };
+
+    report.cases = spec
+        .cases
+        .iter()
+        .map(|case| run_case(&rom, &symbol_table, spec, case))
+        .collect();
+    report.passed = report.cases.iter().all(|case| case.passed);
+    report
+}
+
+/// Loads a submission within the limits, or returns an error if it can't be
+/// read, exceeds the limits or doesn't assemble.
+fn load(path: &Path, limits: Limits) -> Result<(Vec<u16>, SymbolTable), AssemblerError> {
+    let bytes = error::read(path)?;
+    if bytes.len() > limits.max_source_bytes {
+        return Err(AssemblerError::semantic(format!(
+            "submission exceeds {} bytes",
+            limits.max_source_bytes
+        )));
+    }
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "hack")
+    {
+        return Ok((read_rom(&bytes)?, SymbolTable::new()));
+    }
+
+    let source = String::from_utf8(bytes)
+        .map_err(|_| AssemblerError::syntax("submission isn't valid UTF-8"))?;
+    let assembler = Assembler::from_source(&source)
+        .with_limits(limits)
+        .fill_symbol_table();
+    let symbol_table = assembler.symbol_table().clone();
+    let rom = assembler.try_assemble()?;
+    Ok((rom, symbol_table))
+}
+
+fn run_case(
+    rom: &[u16],
+    symbol_table: &SymbolTable,
+    spec: &GradeSpec,
+    case: &GradeCase,
+) -> CaseReport {
+    let mut report = CaseReport {
+        name: case.name.clone(),
+        passed: false,
+        cycles: 0,
+        stop: String::from("error"),
+        failures: Vec::new(),
+    };
+
+    let mut emulator = Emulator::new(rom.to_vec());
+    for (location, value) in &case.set {
+        match address(location, symbol_table) {
+            Some(address) => emulator.ram_mut()[address] = *value as u16,
+            None => report
+                .failures
+                .push(format!("unknown address {}", location)),
+        }
+    }
+    if !report.failures.is_empty() {
+        return report;
+    }
+
+    let stop = run(
+        &mut emulator,
+        case.keyboard,
+        spec.max_cycles,
+        spec.timeout_ms,
+    );
+    report.cycles = emulator.cycles();
+    report.stop = match stop {
+        Stop::Halted => "halted",
+        Stop::EndOfRom => "end",
+        Stop::CycleLimit => "cycle_limit",
+        Stop::Interrupted => "timeout",
+        Stop::OutOfRam => "out_of_ram",
+    }
+    .to_string();
+    match stop {
+        Stop::Halted | Stop::EndOfRom => {}
+        Stop::OutOfRam => report.failures.push(format!(
+            "program accessed M at {}, past the end of the RAM",
+            emulator.a()
+        )),
+        _ => report.failures.push(format!(
+            "program didn't halt within {} cycles and {} ms",
+            spec.max_cycles, spec.timeout_ms
+        )),
+    }
+
+    for (location, expected) in &case.expect {
+        let Some(address) = address(location, symbol_table) else {
+            report
+                .failures
+                .push(format!("unknown address {}", location));
+            continue;
+        };
+        let actual = emulator.ram()[address];
+        if actual != *expected as u16 {
+            report.failures.push(format!(
+                "expected {} at {}, got {}",
+                expected, location, actual as i16
+            ));
+        }
+    }
+
+    report.passed = report.failures.is_empty();
+    report
+}
+
+/// Runs the emulator within the cycle budget and the timeout, holding a key if
+/// any. A timeout stops the run as [`Stop::Interrupted`].
+fn run(emulator: &mut Emulator, keyboard: Option<u16>, max_cycles: u64, timeout_ms: u64) -> Stop {
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    while Instant::now() < deadline {
+        if let Some(code) = keyboard {
+            emulator.ram_mut()[KBD] = code;
+        }
+        let remaining = max_cycles - emulator.cycles();
+        match emulator.run(remaining.min(CHUNK_CYCLES)) {
+            Stop::CycleLimit if remaining > CHUNK_CYCLES => continue,
+            stop => return stop,
+        }
+    }
+    Stop::Interrupted
+}
+
+fn address(location: &str, symbol_table: &SymbolTable) -> Option<usize> {
+    let address = match location.parse::<u32>() {
+        Ok(address) => address,
+        Err(_) => *symbol_table.address(location)?,
+    };
+    ((address as usize) < RAM_SIZE).then_some(address as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grade_max() {
+        // Given
+        let spec: GradeSpec = serde_json::from_str(
+            r#"{
+                "cases": [
+                    { "name": "first", "set": { "R0": 7, "R1": 3 }, "expect": { "R2": 7 } },
+                    { "name": "wrong", "set": { "R0": 1, "R1": -2 }, "expect": { "R2": -2 } }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        // When
+        let report = grade(
+            Path::new("test_data/max/Max.asm"),
+            &spec,
+            Limits::untrusted(),
+        );
+
+        // Then
+        assert!(!report.passed);
+        assert!(report.cases[0].passed);
+        assert_eq!("halted", report.cases[0].stop);
+        assert_eq!(
+            vec!["expected -2 at R2, got 1".to_string()],
+            report.cases[1].failures
+        );
+    }
+}
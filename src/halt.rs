@@ -0,0 +1,134 @@
+use crate::{
+    cfg::Cfg,
+    error::AssemblerError,
+    ir::{Ir, IrInstruction, IrNode},
+    pass::{Context, Pass, Stage},
+    symbol_table::SymbolTable,
+};
+
+/// Returns whether the execution can run past the last instruction: the
+/// program is empty, or its last block is reachable and doesn't end with an
+/// unconditional jump. A jump to a label past the last instruction is also
+/// a way out.
+pub fn falls_off_the_end(ir: &Ir) -> bool {
+    let Some(last) = ir.nodes.last() else {
+        return true;
+    };
+    let cfg = Cfg::new(ir);
+    let reachable = cfg.reachable();
+    let ends_with_jump =
+        matches!(&last.instruction, IrInstruction::C { jump, .. } if jump == "JMP");
+    let loads_trailing_label = ir.nodes.iter().any(|node| {
+        matches!(
+            &node.instruction,
+            IrInstruction::A { symbol: Some(symbol), .. } if ir.trailing_labels.contains(symbol)
+        )
+    });
+    (reachable.last() == Some(&true) && !ends_with_jump) || loads_trailing_label
+}
+
+/// Appends the conventional `(END) @END 0;JMP` loop to the programs whose
+/// execution can run past the last instruction, so that they halt instead of
+/// running whatever is left in the ROM. The loop takes the labels declared
+/// after the last instruction, or a new `END` label.
+pub struct HaltLoop;
+
+impl Pass for HaltLoop {
+    fn name(&self) -> &'static str {
+        "halt-loop"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Optimize
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        let ir = &mut context.ir;
+        if !falls_off_the_end(ir) {
+            return Ok(());
+        }
+
+        let address = ir.nodes.last().map_or(0, |node| node.address + 1);
+        let mut labels = std::mem::take(&mut ir.trailing_labels);
+        if labels.is_empty() {
+            let label = unique_label(&context.symbol_table);
+            context.symbol_table.add_label(label.clone(), address);
+            labels.push(label);
+        }
+        let label = labels[0].clone();
+        ir.nodes.push(IrNode {
+            address,
+            labels,
+            instruction: IrInstruction::A {
+                value: address,
+                symbol: Some(label),
+            },
+            line: None,
+        });
+        ir.nodes.push(IrNode {
+            address: address + 1,
+            labels: Vec::new(),
+            instruction: IrInstruction::C {
+                dest: String::new(),
+                comp: String::from("0"),
+                jump: String::from("JMP"),
+            },
+            line: None,
+        });
+        Ok(())
+    }
+}
+
+/// Returns `END`, or `END.<n>` with the first `n` that isn't a symbol yet.
+fn unique_label(symbol_table: &SymbolTable) -> String {
+    std::iter::once(String::from("END"))
+        .chain((1..).map(|n| format!("END.{}", n)))
+        .find(|label| symbol_table.address(label).is_none())
+        .expect("a free label")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assembler::Assembler, program::Program};
+
+    fn assemble(source: &str) -> Vec<u16> {
+        let mut assembler = Assembler::from_source(source);
+        assembler.passes_mut().add(HaltLoop);
+        assembler.fill_symbol_table().assemble()
+    }
+
+    #[test]
+    fn test_halt_loop_is_appended_when_falling_off_the_end() {
+        // Given
+        let cases = [
+            ("@3\nD;JGT\nD=0\n", vec![3, 0xE301, 0xEA90, 3, 0xEA87]),
+            ("@END\n0;JMP\n(END)\n", vec![2, 0xEA87, 2, 0xEA87]),
+            ("", vec![0, 0xEA87]),
+        ];
+
+        for (source, expected) in cases {
+            // When
+            let words = assemble(source);
+
+            // Then
+            assert_eq!(expected, words, "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn test_halt_loop_is_not_appended_when_unneeded() {
+        for source in [
+            "D=0\n(END)\n@END\n0;JMP\n",
+            "@LOOP\n0;JMP\n(DEAD)\nD=0\n(LOOP)\n@LOOP\n0;JMP\n",
+            "@LOOP\n0;JMP\n(LOOP)\n@LOOP\n0;JMP\nD=1\n",
+            "(LOOP)\n@LOOP\n0;JMP\n(END)\n",
+        ] {
+            // When
+            let words = assemble(source);
+
+            // Then
+            assert_eq!(Program::from_source(source).assemble(), words);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::{fmt::Write as _, io::Write};
+
+/// The number of addresses per row of a heatmap.
+const COLUMNS: usize = 256;
+/// The size of the square drawn for each address of a PNG heatmap, in pixels.
+const CELL: usize = 2;
+/// The color of the addresses which were never executed or accessed.
+const UNTOUCHED: [u8; 3] = [0x20, 0x20, 0x20];
+
+/// Returns the color of a count, from blue for the coldest addresses through
+/// yellow to red for the hottest, on a logarithmic scale.
+fn color(count: u64, max: u64) -> [u8; 3] {
+    if count == 0 {
+        return UNTOUCHED;
+    }
+    let heat = if max <= 1 {
+        1.0
+    } else {
+        (count as f64).ln() / (max as f64).ln()
+    };
+    let (r, g, b) = if heat < 0.5 {
+        let t = heat * 2.0;
+        (t, t, 1.0 - t)
+    } else {
+        let t = (heat - 0.5) * 2.0;
+        (1.0, 1.0 - t, 0.0)
+    };
+    [r, g, b].map(|channel| (channel * 255.0).round() as u8)
+}
+
+/// Writes the execution counts of the ROM and the access counts of the RAM as
+/// a PNG image, the ROM on top of the RAM, 256 addresses per row.
+pub fn write_png(rom_counts: &[u64], ram_counts: &[u64], writer: impl Write) {
+    let rom_rows = rom_counts.len().div_ceil(COLUMNS);
+    let ram_rows = ram_counts.len().div_ceil(COLUMNS);
+    // A blank row separates the ROM from the RAM.
+    let rows = rom_rows + 1 + ram_rows;
+    let width = COLUMNS * CELL;
+    let height = rows * CELL;
+
+    let mut data = vec![0xFF; width * height * 3];
+    let mut draw = |counts: &[u64], first_row: usize| {
+        let max = counts.iter().copied().max().unwrap_or(0);
+        for (address, count) in counts.iter().enumerate() {
+            let pixel = color(*count, max);
+            let (row, column) = (first_row + address / COLUMNS, address % COLUMNS);
+            for y in row * CELL..(row + 1) * CELL {
+                for x in column * CELL..(column + 1) * CELL {
+                    let offset = (y * width + x) * 3;
+                    data[offset..offset + 3].copy_from_slice(&pixel);
+                }
+            }
+        }
+    };
+    draw(rom_counts, 0);
+    draw(ram_counts, rom_rows + 1);
+
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().expect("failed to write PNG header");
+    writer
+        .write_image_data(&data)
+        .expect("failed to write PNG data");
+}
+
+/// Returns the execution counts of the ROM and the access counts of the RAM as
+/// an HTML page, each address showing its count when hovered.
+pub fn html(rom_counts: &[u64], ram_counts: &[u64]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Hack heatmap</title>\n\
+         <style>\n\
+         body { font-family: sans-serif; background: #fff; }\n\
+         .map { display: grid; grid-template-columns: repeat(256, 4px); gap: 0; }\n\
+         .map i { width: 4px; height: 4px; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    for (title, counts) in [("ROM executions", rom_counts), ("RAM accesses", ram_counts)] {
+        let max = counts.iter().copied().max().unwrap_or(0);
+        let touched = counts.iter().filter(|count| **count > 0).count();
+        writeln!(
+            html,
+            "<h2>{}</h2>\n<p>{} of {} addresses touched, at most {} times</p>\n<div class=\"map\">",
+            title,
+            touched,
+            counts.len(),
+            max
+        )
+        .expect("write to string");
+        for (address, count) in counts.iter().enumerate() {
+            let [r, g, b] = color(*count, max);
+            writeln!(
+                html,
+                "<i style=\"background:#{:02x}{:02x}{:02x}\" title=\"{}: {}\"></i>",
+                r, g, b, address, count
+            )
+            .expect("write to string");
+        }
+        html.push_str("</div>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color() {
+        assert_eq!(UNTOUCHED, color(0, 100));
+        assert_eq!([0, 0, 255], color(1, 100));
+        assert_eq!([255, 0, 0], color(100, 100));
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use crate::{
+    program::{Instruction, Program},
+    symbol_table::SymbolTable,
+};
+
+/// An instruction with its symbols resolved to concrete values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IrInstruction {
+    /// An A-instruction, with the symbol it was resolved from if any.
+    A { value: u32, symbol: Option<String> },
+    /// A C-instruction `dest=comp;jump`, where `dest` and `jump` can be empty.
+    C {
+        dest: String,
+        comp: String,
+        jump: String,
+    },
+}
+
+/// A resolved instruction located at its ROM address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IrNode {
+    /// The ROM address of the instruction.
+    pub address: u32,
+    /// The labels declared right before the instruction.
+    pub labels: Vec<String>,
+    pub instruction: IrInstruction,
+    /// The source line of the instruction, if known.
+    pub line: Option<usize>,
+}
+
+/// The intermediate representation of a program, between parsing and encoding.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ir {
+    pub nodes: Vec<IrNode>,
+    /// The labels declared after the last instruction.
+    pub trailing_labels: Vec<String>,
+}
+
+impl Ir {
+    /// Lowers the program to its IR, using the symbol table to resolve symbols.
+    ///
+    /// # Panic
+    ///
+    /// Panics if an A-instruction symbol is neither a number nor in the symbol table.
+    pub fn lower(program: &Program, symbol_table: &SymbolTable) -> Self {
+        let mut ir = Ir::default();
+        ir.nodes.reserve(program.len());
+        let mut labels = Vec::new();
+
+        for (index, instruction) in program.instructions().iter().enumerate() {
+            let instruction = match instruction {
+                Instruction::L(symbol) => {
+                    labels.push(symbol.clone());
+                    continue;
+                }
+                Instruction::A(symbol) => match symbol_table.address(symbol) {
+                    Some(address) => IrInstruction::A {
+                        value: *address,
+                        symbol: Some(symbol.clone()),
+                    },
+                    None => IrInstruction::A {
+                        value: str::parse::<u32>(symbol).expect("failed to parse A instruction"),
+                        symbol: None,
+                    },
+                },
+                Instruction::C { dest, comp, jump } => IrInstruction::C {
+                    dest: dest.clone(),
+                    comp: comp.clone(),
+                    jump: jump.clone(),
+                },
+            };
+            ir.nodes.push(IrNode {
+                address: ir.nodes.len() as u32,
+                labels: std::mem::take(&mut labels),
+                instruction,
+                line: program.source_line(index),
+            });
+        }
+        ir.trailing_labels = labels;
+
+        ir
+    }
+}
+
+impl fmt::Display for IrInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IrInstruction::A { value, .. } => write!(f, "@{}", value),
+            IrInstruction::C { dest, comp, jump } => {
+                let instruction = Instruction::C {
+                    dest: dest.clone(),
+                    comp: comp.clone(),
+                    jump: jump.clone(),
+                };
+                write!(f, "{}", instruction)
+            }
+        }
+    }
+}
+
+/// Pretty-prints the IR, one instruction per line prefixed by its ROM address.
+impl fmt::Display for Ir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in &self.nodes {
+            for label in &node.labels {
+                writeln!(f, "      ({})", label)?;
+            }
+            let instruction = node.instruction.to_string();
+            match &node.instruction {
+                IrInstruction::A {
+                    symbol: Some(symbol),
+                    ..
+                } => writeln!(f, "{:>5} {:<16} // {}", node.address, instruction, symbol)?,
+                _ => writeln!(f, "{:>5} {}", node.address, instruction)?,
+            }
+        }
+        for label in &self.trailing_labels {
+            writeln!(f, "      ({})", label)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ir_pretty_print() {
+        // Given
+        let program = Program::from_source("@i\nM=0\n(LOOP)\n@LOOP\n0;JMP\n");
+        let symbol_table = program.resolve();
+
+        // When
+        let ir = Ir::lower(&program, &symbol_table);
+
+        // Then
+        assert_eq!(
+            "    0 @16              // i\n    1 M=0\n      (LOOP)\n    2 @2               // LOOP\n    3 0;JMP\n",
+            ir.to_string()
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+
+use super::parser::{
+    Call, Class, ClassVariableKind, Expression, Statement, Subroutine, SubroutineKind, Term,
+};
+use crate::{
+    error::AssemblerError,
+    vm::{Operation, Segment, VmCommand},
+};
+
+/// A variable in scope: its type, and where it lives.
+#[derive(Clone, Debug)]
+struct Variable {
+    ty: String,
+    segment: Segment,
+    index: u16,
+}
+
+/// Compiles a class into VM commands.
+///
+/// Returns a semantic error if a subroutine uses an undefined variable, or if
+/// a function uses a field or calls a method without a receiver.
+pub fn compile(class: &Class) -> Result<Vec<VmCommand>, AssemblerError> {
+    let mut statics = 0;
+    let mut fields = 0;
+    let mut class_scope = HashMap::new();
+    for variables in &class.variables {
+        let (segment, count) = match variables.kind {
+            ClassVariableKind::Static => (Segment::Static, &mut statics),
+            ClassVariableKind::Field => (Segment::This, &mut fields),
+        };
+        for name in &variables.names {
+            let variable = Variable {
+                ty: variables.ty.clone(),
+                segment,
+                index: *count,
+            };
+            class_scope.insert(name.clone(), variable);
+            *count += 1;
+        }
+    }
+
+    let mut commands = Vec::new();
+    for subroutine in &class.subroutines {
+        let mut compiler = Compiler {
+            class,
+            subroutine,
+            class_scope: &class_scope,
+            scope: HashMap::new(),
+            labels: 0,
+            commands: Vec::new(),
+        };
+        compiler.subroutine(fields)?;
+        commands.extend(compiler.commands);
+    }
+    Ok(commands)
+}
+
+struct Compiler<'a> {
+    class: &'a Class,
+    subroutine: &'a Subroutine,
+    class_scope: &'a HashMap<String, Variable>,
+    /// The parameters and the local variables of the subroutine.
+    scope: HashMap<String, Variable>,
+    /// The number of labels generated, keeping them unique in the subroutine.
+    labels: usize,
+    commands: Vec<VmCommand>,
+}
+
+impl Compiler<'_> {
+    fn error(&self, message: &str) -> AssemblerError {
+        AssemblerError::semantic(format!(
+            "{}.{}: {}",
+            self.class.name, self.subroutine.name, message
+        ))
+    }
+
+    fn emit(&mut self, command: VmCommand) {
+        self.commands.push(command);
+    }
+
+    fn label(&mut self, name: &str) -> String {
+        self.labels += 1;
+        format!("{}{}", name, self.labels - 1)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Option<&Variable>, AssemblerError> {
+        let Some(variable) = self.scope.get(name).or_else(|| self.class_scope.get(name)) else {
+            return Ok(None);
+        };
+        if variable.segment == Segment::This && self.subroutine.kind == SubroutineKind::Function {
+            return Err(self.error(&format!("field {} used in a function", name)));
+        }
+        Ok(Some(variable))
+    }
+
+    fn variable(&self, name: &str) -> Result<Variable, AssemblerError> {
+        self.lookup(name)?
+            .cloned()
+            .ok_or_else(|| self.error(&format!("undefined variable {}", name)))
+    }
+
+    fn subroutine(&mut self, fields: u16) -> Result<(), AssemblerError> {
+        // A method receives its object as the first argument.
+        let first_argument = u16::from(self.subroutine.kind == SubroutineKind::Method);
+        for (index, (ty, name)) in self.subroutine.parameters.iter().enumerate() {
+            let variable = Variable {
+                ty: ty.clone(),
+                segment: Segment::Argument,
+                index: first_argument + index as u16,
+            };
+            self.scope.insert(name.clone(), variable);
+        }
+        for (index, (ty, name)) in self.subroutine.locals.iter().enumerate() {
+            let variable = Variable {
+                ty: ty.clone(),
+                segment: Segment::Local,
+                index: index as u16,
+            };
+            self.scope.insert(name.clone(), variable);
+        }
+
+        let name = format!("{}.{}", self.class.name, self.subroutine.name);
+        self.emit(VmCommand::Function(
+            name,
+            self.subroutine.locals.len() as u16,
+        ));
+        match self.subroutine.kind {
+            SubroutineKind::Constructor => {
+                self.emit(VmCommand::Push(Segment::Constant, fields));
+                self.emit(VmCommand::Call(String::from("Memory.alloc"), 1));
+                self.emit(VmCommand::Pop(Segment::Pointer, 0));
+            }
+            SubroutineKind::Method => {
+                self.emit(VmCommand::Push(Segment::Argument, 0));
+                self.emit(VmCommand::Pop(Segment::Pointer, 0));
+            }
+            SubroutineKind::Function => {}
+        }
+        self.statements(&self.subroutine.statements)
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Result<(), AssemblerError> {
+        for statement in statements {
+            self.statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), AssemblerError> {
+        match statement {
+            Statement::Let {
+                name,
+                index: None,
+                value,
+            } => {
+                let variable = self.variable(name)?;
+                self.expression(value)?;
+                self.emit(VmCommand::Pop(variable.segment, variable.index));
+            }
+            Statement::Let {
+                name,
+                index: Some(index),
+                value,
+            } => {
+                let variable = self.variable(name)?;
+                self.emit(VmCommand::Push(variable.segment, variable.index));
+                self.expression(index)?;
+                self.emit(VmCommand::Arithmetic(Operation::Add));
+                // The value may itself access an array, so the address waits on
+                // the stack until the value is computed.
+                self.expression(value)?;
+                self.emit(VmCommand::Pop(Segment::Temp, 0));
+                self.emit(VmCommand::Pop(Segment::Pointer, 1));
+                self.emit(VmCommand::Push(Segment::Temp, 0));
+                self.emit(VmCommand::Pop(Segment::That, 0));
+            }
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let otherwise_label = self.label("IF_FALSE");
+                let end = self.label("IF_END");
+                self.expression(condition)?;
+                self.emit(VmCommand::Arithmetic(Operation::Not));
+                self.emit(VmCommand::IfGoto(otherwise_label.clone()));
+                self.statements(then)?;
+                self.emit(VmCommand::Goto(end.clone()));
+                self.emit(VmCommand::Label(otherwise_label));
+                self.statements(otherwise)?;
+                self.emit(VmCommand::Label(end));
+            }
+            Statement::While { condition, body } => {
+                let start = self.label("WHILE_EXP");
+                let end = self.label("WHILE_END");
+                self.emit(VmCommand::Label(start.clone()));
+                self.expression(condition)?;
+                self.emit(VmCommand::Arithmetic(Operation::Not));
+                self.emit(VmCommand::IfGoto(end.clone()));
+                self.statements(body)?;
+                self.emit(VmCommand::Goto(start));
+                self.emit(VmCommand::Label(end));
+            }
+            Statement::Do(call) => {
+                self.call(call)?;
+                self.emit(VmCommand::Pop(Segment::Temp, 0));
+            }
+            Statement::Return(value) => {
+                match value {
+                    Some(value) => self.expression(value)?,
+                    None => self.emit(VmCommand::Push(Segment::Constant, 0)),
+                }
+                self.emit(VmCommand::Return);
+            }
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Result<(), AssemblerError> {
+        self.term(&expression.term)?;
+        for (operator, term) in &expression.operations {
+            self.term(term)?;
+            let command = match operator {
+                '+' => VmCommand::Arithmetic(Operation::Add),
+                '-' => VmCommand::Arithmetic(Operation::Sub),
+                '&' => VmCommand::Arithmetic(Operation::And),
+                '|' => VmCommand::Arithmetic(Operation::Or),
+                '<' => VmCommand::Arithmetic(Operation::Lt),
+                '>' => VmCommand::Arithmetic(Operation::Gt),
+                '=' => VmCommand::Arithmetic(Operation::Eq),
+                '*' => VmCommand::Call(String::from("Math.multiply"), 2),
+                '/' => VmCommand::Call(String::from("Math.divide"), 2),
+                operator => unreachable!("parsed operator {}", operator),
+            };
+            self.emit(command);
+        }
+        Ok(())
+    }
+
+    fn term(&mut self, term: &Term) -> Result<(), AssemblerError> {
+        match term {
+            Term::Integer(value) => self.emit(VmCommand::Push(Segment::Constant, *value)),
+            Term::String(value) => {
+                self.emit(VmCommand::Push(Segment::Constant, value.len() as u16));
+                self.emit(VmCommand::Call(String::from("String.new"), 1));
+                for c in value.chars() {
+                    self.emit(VmCommand::Push(Segment::Constant, c as u16));
+                    self.emit(VmCommand::Call(String::from("String.appendChar"), 2));
+                }
+            }
+            Term::Keyword("true") => {
+                self.emit(VmCommand::Push(Segment::Constant, 0));
+                self.emit(VmCommand::Arithmetic(Operation::Not));
+            }
+            Term::Keyword("this") => {
+                if self.subroutine.kind == SubroutineKind::Function {
+                    return Err(self.error("this used in a function"));
+                }
+                self.emit(VmCommand::Push(Segment::Pointer, 0));
+            }
+            Term::Keyword(_) => self.emit(VmCommand::Push(Segment::Constant, 0)),
+            Term::Variable(name) => {
+                let variable = self.variable(name)?;
+                self.emit(VmCommand::Push(variable.segment, variable.index));
+            }
+            Term::Index(name, index) => {
+                let variable = self.variable(name)?;
+                self.emit(VmCommand::Push(variable.segment, variable.index));
+                self.expression(index)?;
+                self.emit(VmCommand::Arithmetic(Operation::Add));
+                self.emit(VmCommand::Pop(Segment::Pointer, 1));
+                self.emit(VmCommand::Push(Segment::That, 0));
+            }
+            Term::Call(call) => self.call(call)?,
+            Term::Parenthesized(expression) => self.expression(expression)?,
+            Term::Unary(operator, term) => {
+                self.term(term)?;
+                let operation = match operator {
+                    '-' => Operation::Neg,
+                    _ => Operation::Not,
+                };
+                self.emit(VmCommand::Arithmetic(operation));
+            }
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, call: &Call) -> Result<(), AssemblerError> {
+        // Methods receive their object as a hidden first argument.
+        let (class, receiver) = match &call.receiver {
+            None => {
+                if self.subroutine.kind == SubroutineKind::Function {
+                    return Err(self.error(&format!("method {} called from a function", call.name)));
+                }
+                (self.class.name.clone(), Some((Segment::Pointer, 0)))
+            }
+            Some(name) => match self.lookup(name)? {
+                Some(variable) => (
+                    variable.ty.clone(),
+                    Some((variable.segment, variable.index)),
+                ),
+                None => (name.clone(), None),
+            },
+        };
+        if let Some((segment, index)) = receiver {
+            self.emit(VmCommand::Push(segment, index));
+        }
+        for argument in &call.arguments {
+            self.expression(argument)?;
+        }
+        let arguments = call.arguments.len() as u16 + u16::from(receiver.is_some());
+        self.emit(VmCommand::Call(
+            format!("{}.{}", class, call.name),
+            arguments,
+        ));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jack::{parser::parse, tokenizer::tokenize};
+
+    #[test]
+    fn test_compile_method() {
+        // Given
+        let source = "class Point {
+            field int x, y;
+            method int sum(int z) { var Array a; let a[x] = y + z; return a[x]; }
+        }";
+        let class = parse("Point.jack", &tokenize("Point.jack", source).unwrap()).unwrap();
+
+        // When
+        let commands = compile(&class).unwrap();
+
+        // Then
+        let vm: Vec<String> = commands.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            vec![
+                "function Point.sum 1",
+                "push argument 0",
+                "pop pointer 0",
+                "push local 0",
+                "push this 0",
+                "add",
+                "push this 1",
+                "push argument 1",
+                "add",
+                "pop temp 0",
+                "pop pointer 1",
+                "push temp 0",
+                "pop that 0",
+                "push local 0",
+                "push this 0",
+                "add",
+                "pop pointer 1",
+                "push that 0",
+                "return",
+            ],
+            vm
+        );
+    }
+}
@@ -0,0 +1,138 @@
+pub mod codegen;
+pub mod parser;
+pub mod tokenizer;
+
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+use crate::{
+    error::{self, AssemblerError},
+    vm::{self, VmCommand},
+};
+
+/// The stage of the pipeline the compiler stops at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum JackStage {
+    /// The tokens of each class, as a nand2tetris `T.xml` file.
+    Tokens,
+    /// The parse tree of each class, as a nand2tetris `.xml` file.
+    Tree,
+    /// The VM code of each class, as a `.vm` file.
+    Vm,
+    /// The Hack assembly of the program.
+    Asm,
+    /// The machine code of the program.
+    Hack,
+}
+
+/// Compiles the source of a Jack class into VM commands. The file names the
+/// errors.
+///
+/// Returns an error if the source is invalid.
+pub fn compile_source(file: &str, source: &str) -> Result<Vec<VmCommand>, AssemblerError> {
+    let tokens = tokenizer::tokenize(file, source)?;
+    codegen::compile(&parser::parse(file, &tokens)?)
+}
+
+/// Returns the `.jack` file, or the `.jack` files of the directory in name order.
+///
+/// Returns an error if the directory can't be read or has no `.jack` file.
+pub fn sources(path: &Path) -> Result<Vec<PathBuf>, AssemblerError> {
+    let paths = vm::files_with_extension(path, "jack")?;
+    if paths.is_empty() {
+        return Err(vm::no_file(path, "jack"));
+    }
+    Ok(paths)
+}
+
+/// Compiles a `.jack` file, or a directory of `.jack` files, into the VM files
+/// of a program. The `.vm` files of the directory which don't come from a
+/// `.jack` file, such as the operating system, are part of the program.
+///
+/// Returns an error if a file can't be read or is invalid.
+pub fn compile_program(path: &Path) -> Result<Vec<(String, Vec<VmCommand>)>, AssemblerError> {
+    let mut files = Vec::new();
+    for path in sources(path)? {
+        let source = error::read_to_string(&path)?;
+        let name = path
+            .file_stem()
+            .expect("missing file name")
+            .to_string_lossy()
+            .to_string();
+        files.push((name, compile_source(&path.display().to_string(), &source)?));
+    }
+
+    if path.is_dir() {
+        for path in vm::files_with_extension(path, "vm")? {
+            let (name, commands) = vm::read_file(&path)?;
+            if !files.iter().any(|(compiled, _)| *compiled == name) {
+                files.push((name, commands));
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assembler::Assembler, emulator::Emulator};
+
+    #[test]
+    fn test_compile_and_run() {
+        // Given
+        let sys = "class Sys {
+            static int result;
+            function void init() {
+                var Counter counter;
+                let counter = Counter.new(40);
+                do counter.add(2);
+                let result = counter.get();
+                while (true) {}
+                return;
+            }
+        }";
+        let counter = "class Counter {
+            field int value;
+            constructor Counter new(int start) { let value = start; return this; }
+            method void add(int n) { let value = value + n; return; }
+            method int get() { return value; }
+        }";
+        // Allocates objects from 2048 upwards, enough without the OS.
+        let memory = "function Memory.alloc 0\npush static 0\npush constant 2048\nadd\n\
+                      push static 0\npush argument 0\nadd\npop static 0\nreturn\n";
+
+        // When
+        let files = vec![
+            (
+                String::from("Counter"),
+                compile_source("Counter.jack", counter).unwrap(),
+            ),
+            (
+                String::from("Memory"),
+                vm::parse("Memory.vm", memory).unwrap(),
+            ),
+            (
+                String::from("Sys"),
+                compile_source("Sys.jack", sys).unwrap(),
+            ),
+        ];
+        let assembly = vm::translate(&files);
+
+        // Then
+        let rom = Assembler::from_source(&assembly)
+            .fill_symbol_table()
+            .assemble();
+        let mut emulator = Emulator::new(rom);
+        emulator.run(10_000);
+        let result = Assembler::from_source(&assembly)
+            .fill_symbol_table()
+            .symbol_table()
+            .address("Sys.0")
+            .copied()
+            .unwrap();
+        assert_eq!(42, emulator.ram()[result as usize]);
+        assert_eq!(42, emulator.ram()[2048]);
+    }
+}
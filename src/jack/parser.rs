@@ -0,0 +1,584 @@
+use std::fmt::Write;
+
+use super::tokenizer::{Token, TokenKind};
+use crate::error::AssemblerError;
+
+/// A Jack class.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Class {
+    pub name: String,
+    pub variables: Vec<ClassVariable>,
+    pub subroutines: Vec<Subroutine>,
+}
+
+/// The kind of a class variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClassVariableKind {
+    Static,
+    Field,
+}
+
+/// A `static` or `field` declaration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClassVariable {
+    pub kind: ClassVariableKind,
+    pub ty: String,
+    pub names: Vec<String>,
+}
+
+/// The kind of a subroutine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubroutineKind {
+    Constructor,
+    Function,
+    Method,
+}
+
+/// A constructor, function or method.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subroutine {
+    pub kind: SubroutineKind,
+    /// The return type, `void` included.
+    pub return_type: String,
+    pub name: String,
+    /// The parameters, as their type and name.
+    pub parameters: Vec<(String, String)>,
+    /// The local variables, as their type and name.
+    pub locals: Vec<(String, String)>,
+    pub statements: Vec<Statement>,
+}
+
+/// A Jack statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Statement {
+    Let {
+        name: String,
+        index: Option<Expression>,
+        value: Expression,
+    },
+    If {
+        condition: Expression,
+        then: Vec<Statement>,
+        otherwise: Vec<Statement>,
+    },
+    While {
+        condition: Expression,
+        body: Vec<Statement>,
+    },
+    Do(Call),
+    Return(Option<Expression>),
+}
+
+/// An expression: a term followed by binary operators and terms, evaluated
+/// from left to right as Jack has no operator precedence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expression {
+    pub term: Term,
+    pub operations: Vec<(char, Term)>,
+}
+
+/// A Jack term.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Term {
+    Integer(u16),
+    String(String),
+    /// `true`, `false`, `null` or `this`.
+    Keyword(&'static str),
+    Variable(String),
+    Index(String, Box<Expression>),
+    Call(Call),
+    Parenthesized(Box<Expression>),
+    Unary(char, Box<Term>),
+}
+
+/// A subroutine call, such as `draw()`, `ball.move(1)` or `Math.max(a, b)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Call {
+    /// The variable or class the subroutine is called on, if any.
+    pub receiver: Option<String>,
+    pub name: String,
+    pub arguments: Vec<Expression>,
+}
+
+/// Parses the tokens of a class. The file names the errors.
+///
+/// Returns a syntax error, with the line of the unexpected token.
+pub fn parse(file: &str, tokens: &[Token]) -> Result<Class, AssemblerError> {
+    Ok(parse_with_xml(file, tokens)?.0)
+}
+
+/// Parses the tokens of a class, also returning its parse tree as the
+/// nand2tetris `.xml` output.
+///
+/// Returns a syntax error, with the line of the unexpected token.
+pub fn parse_with_xml(file: &str, tokens: &[Token]) -> Result<(Class, String), AssemblerError> {
+    let mut parser = Parser {
+        file,
+        tokens,
+        position: 0,
+        xml: String::new(),
+        depth: 0,
+    };
+    let class = parser.class()?;
+    if parser.position < tokens.len() {
+        return Err(parser.error("expected end of file"));
+    }
+    Ok((class, parser.xml))
+}
+
+struct Parser<'a> {
+    file: &'a str,
+    tokens: &'a [Token],
+    position: usize,
+    /// The parse tree, as XML.
+    xml: String,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> AssemblerError {
+        AssemblerError::syntax(match self.tokens.get(self.position) {
+            Some(token) => format!(
+                "{}:{}: {}, found {}",
+                self.file,
+                token.line,
+                message,
+                token.to_xml()
+            ),
+            None => format!("{}: {}, found end of file", self.file, message),
+        })
+    }
+
+    fn open(&mut self, tag: &str) {
+        writeln!(self.xml, "{:indent$}<{}>", "", tag, indent = self.depth * 2)
+            .expect("write to string");
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        writeln!(
+            self.xml,
+            "{:indent$}</{}>",
+            "",
+            tag,
+            indent = self.depth * 2
+        )
+        .expect("write to string");
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.position).map(|token| &token.kind)
+    }
+
+    fn peek_symbol(&self, symbol: char) -> bool {
+        self.peek() == Some(&TokenKind::Symbol(symbol))
+    }
+
+    fn peek_keyword(&self, keywords: &[&str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(TokenKind::Keyword(keyword)) if keywords.contains(keyword) => Some(keyword),
+            _ => None,
+        }
+    }
+
+    /// Consumes the next token, writing it to the parse tree.
+    fn advance(&mut self) -> Result<TokenKind, AssemblerError> {
+        let Some(token) = self.tokens.get(self.position) else {
+            return Err(self.error("unexpected end of file"));
+        };
+        writeln!(
+            self.xml,
+            "{:indent$}{}",
+            "",
+            token.to_xml(),
+            indent = self.depth * 2
+        )
+        .expect("write to string");
+        self.position += 1;
+        Ok(token.kind.clone())
+    }
+
+    fn symbol(&mut self, symbol: char) -> Result<(), AssemblerError> {
+        if !self.peek_symbol(symbol) {
+            return Err(self.error(&format!("expected {}", symbol)));
+        }
+        self.advance()?;
+        Ok(())
+    }
+
+    fn keyword(&mut self, keywords: &[&str]) -> Result<&'static str, AssemblerError> {
+        if self.peek_keyword(keywords).is_none() {
+            return Err(self.error(&format!("expected {}", keywords.join(" or "))));
+        }
+        match self.advance()? {
+            TokenKind::Keyword(keyword) => Ok(keyword),
+            _ => unreachable!("peeked a keyword"),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, AssemblerError> {
+        match self.peek() {
+            Some(TokenKind::Identifier(_)) => match self.advance()? {
+                TokenKind::Identifier(name) => Ok(name),
+                _ => unreachable!("peeked an identifier"),
+            },
+            _ => Err(self.error("expected an identifier")),
+        }
+    }
+
+    /// Parses a type, `void` being allowed for return types.
+    fn ty(&mut self, allow_void: bool) -> Result<String, AssemblerError> {
+        let keywords: &[&str] = if allow_void {
+            &["int", "char", "boolean", "void"]
+        } else {
+            &["int", "char", "boolean"]
+        };
+        match self.peek_keyword(keywords) {
+            Some(_) => Ok(self.keyword(keywords)?.to_string()),
+            None => self.identifier(),
+        }
+    }
+
+    /// Parses `name (, name)* ;`.
+    fn names(&mut self) -> Result<Vec<String>, AssemblerError> {
+        let mut names = vec![self.identifier()?];
+        while self.peek_symbol(',') {
+            self.advance()?;
+            names.push(self.identifier()?);
+        }
+        self.symbol(';')?;
+        Ok(names)
+    }
+
+    fn class(&mut self) -> Result<Class, AssemblerError> {
+        self.open("class");
+        self.keyword(&["class"])?;
+        let name = self.identifier()?;
+        self.symbol('{')?;
+
+        let mut variables = Vec::new();
+        while let Some(kind) = self.peek_keyword(&["static", "field"]) {
+            self.open("classVarDec");
+            self.advance()?;
+            let kind = match kind {
+                "static" => ClassVariableKind::Static,
+                _ => ClassVariableKind::Field,
+            };
+            let ty = self.ty(false)?;
+            let names = self.names()?;
+            variables.push(ClassVariable { kind, ty, names });
+            self.close("classVarDec");
+        }
+
+        let mut subroutines = Vec::new();
+        while self
+            .peek_keyword(&["constructor", "function", "method"])
+            .is_some()
+        {
+            subroutines.push(self.subroutine()?);
+        }
+
+        self.symbol('}')?;
+        self.close("class");
+        Ok(Class {
+            name,
+            variables,
+            subroutines,
+        })
+    }
+
+    fn subroutine(&mut self) -> Result<Subroutine, AssemblerError> {
+        self.open("subroutineDec");
+        let kind = match self.keyword(&["constructor", "function", "method"])? {
+            "constructor" => SubroutineKind::Constructor,
+            "function" => SubroutineKind::Function,
+            _ => SubroutineKind::Method,
+        };
+        let return_type = self.ty(true)?;
+        let name = self.identifier()?;
+
+        self.symbol('(')?;
+        self.open("parameterList");
+        let mut parameters = Vec::new();
+        if !self.peek_symbol(')') {
+            loop {
+                let ty = self.ty(false)?;
+                parameters.push((ty, self.identifier()?));
+                if !self.peek_symbol(',') {
+                    break;
+                }
+                self.advance()?;
+            }
+        }
+        self.close("parameterList");
+        self.symbol(')')?;
+
+        self.open("subroutineBody");
+        self.symbol('{')?;
+        let mut locals = Vec::new();
+        while self.peek_keyword(&["var"]).is_some() {
+            self.open("varDec");
+            self.advance()?;
+            let ty = self.ty(false)?;
+            locals.extend(self.names()?.into_iter().map(|name| (ty.clone(), name)));
+            self.close("varDec");
+        }
+        let statements = self.statements()?;
+        self.symbol('}')?;
+        self.close("subroutineBody");
+        self.close("subroutineDec");
+
+        Ok(Subroutine {
+            kind,
+            return_type,
+            name,
+            parameters,
+            locals,
+            statements,
+        })
+    }
+
+    fn statements(&mut self) -> Result<Vec<Statement>, AssemblerError> {
+        self.open("statements");
+        let mut statements = Vec::new();
+        while let Some(keyword) = self.peek_keyword(&["let", "if", "while", "do", "return"]) {
+            let statement = match keyword {
+                "let" => self.let_statement()?,
+                "if" => self.if_statement()?,
+                "while" => self.while_statement()?,
+                "do" => self.do_statement()?,
+                _ => self.return_statement()?,
+            };
+            statements.push(statement);
+        }
+        self.close("statements");
+        Ok(statements)
+    }
+
+    fn let_statement(&mut self) -> Result<Statement, AssemblerError> {
+        self.open("letStatement");
+        self.advance()?;
+        let name = self.identifier()?;
+        let index = if self.peek_symbol('[') {
+            self.advance()?;
+            let index = self.expression()?;
+            self.symbol(']')?;
+            Some(index)
+        } else {
+            None
+        };
+        self.symbol('=')?;
+        let value = self.expression()?;
+        self.symbol(';')?;
+        self.close("letStatement");
+        Ok(Statement::Let { name, index, value })
+    }
+
+    fn if_statement(&mut self) -> Result<Statement, AssemblerError> {
+        self.open("ifStatement");
+        self.advance()?;
+        let condition = self.condition()?;
+        let then = self.block()?;
+        let otherwise = if self.peek_keyword(&["else"]).is_some() {
+            self.advance()?;
+            self.block()?
+        } else {
+            Vec::new()
+        };
+        self.close("ifStatement");
+        Ok(Statement::If {
+            condition,
+            then,
+            otherwise,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Statement, AssemblerError> {
+        self.open("whileStatement");
+        self.advance()?;
+        let condition = self.condition()?;
+        let body = self.block()?;
+        self.close("whileStatement");
+        Ok(Statement::While { condition, body })
+    }
+
+    fn do_statement(&mut self) -> Result<Statement, AssemblerError> {
+        self.open("doStatement");
+        self.advance()?;
+        let name = self.identifier()?;
+        let call = self.call(name)?;
+        self.symbol(';')?;
+        self.close("doStatement");
+        Ok(Statement::Do(call))
+    }
+
+    fn return_statement(&mut self) -> Result<Statement, AssemblerError> {
+        self.open("returnStatement");
+        self.advance()?;
+        let value = if self.peek_symbol(';') {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.symbol(';')?;
+        self.close("returnStatement");
+        Ok(Statement::Return(value))
+    }
+
+    /// Parses `( expression )`.
+    fn condition(&mut self) -> Result<Expression, AssemblerError> {
+        self.symbol('(')?;
+        let condition = self.expression()?;
+        self.symbol(')')?;
+        Ok(condition)
+    }
+
+    /// Parses `{ statements }`.
+    fn block(&mut self) -> Result<Vec<Statement>, AssemblerError> {
+        self.symbol('{')?;
+        let statements = self.statements()?;
+        self.symbol('}')?;
+        Ok(statements)
+    }
+
+    fn expression(&mut self) -> Result<Expression, AssemblerError> {
+        self.open("expression");
+        let term = self.term()?;
+        let mut operations = Vec::new();
+        while let Some(TokenKind::Symbol(operator)) = self.peek() {
+            let operator = *operator;
+            if !"+-*/&|<>=".contains(operator) {
+                break;
+            }
+            self.advance()?;
+            operations.push((operator, self.term()?));
+        }
+        self.close("expression");
+        Ok(Expression { term, operations })
+    }
+
+    fn term(&mut self) -> Result<Term, AssemblerError> {
+        self.open("term");
+        let term = match self.peek() {
+            Some(TokenKind::Integer(_)) => match self.advance()? {
+                TokenKind::Integer(value) => Term::Integer(value),
+                _ => unreachable!("peeked an integer"),
+            },
+            Some(TokenKind::String(_)) => match self.advance()? {
+                TokenKind::String(value) => Term::String(value),
+                _ => unreachable!("peeked a string"),
+            },
+            Some(TokenKind::Keyword(_)) => {
+                Term::Keyword(self.keyword(&["true", "false", "null", "this"])?)
+            }
+            Some(TokenKind::Symbol('(')) => {
+                self.advance()?;
+                let expression = self.expression()?;
+                self.symbol(')')?;
+                Term::Parenthesized(Box::new(expression))
+            }
+            Some(TokenKind::Symbol(operator @ ('-' | '~'))) => {
+                let operator = *operator;
+                self.advance()?;
+                Term::Unary(operator, Box::new(self.term()?))
+            }
+            Some(TokenKind::Identifier(_)) => {
+                let name = self.identifier()?;
+                if self.peek_symbol('[') {
+                    self.advance()?;
+                    let index = self.expression()?;
+                    self.symbol(']')?;
+                    Term::Index(name, Box::new(index))
+                } else if self.peek_symbol('(') || self.peek_symbol('.') {
+                    Term::Call(self.call(name)?)
+                } else {
+                    Term::Variable(name)
+                }
+            }
+            _ => return Err(self.error("expected a term")),
+        };
+        self.close("term");
+        Ok(term)
+    }
+
+    /// Parses the rest of a subroutine call, after its first identifier.
+    fn call(&mut self, name: String) -> Result<Call, AssemblerError> {
+        let (receiver, name) = if self.peek_symbol('.') {
+            self.advance()?;
+            (Some(name), self.identifier()?)
+        } else {
+            (None, name)
+        };
+        self.symbol('(')?;
+        self.open("expressionList");
+        let mut arguments = Vec::new();
+        if !self.peek_symbol(')') {
+            loop {
+                arguments.push(self.expression()?);
+                if !self.peek_symbol(',') {
+                    break;
+                }
+                self.advance()?;
+            }
+        }
+        self.close("expressionList");
+        self.symbol(')')?;
+        Ok(Call {
+            receiver,
+            name,
+            arguments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jack::tokenizer::tokenize;
+
+    #[test]
+    fn test_parse_with_xml() {
+        // Given
+        let source = "class Main { function void main() { return; } }";
+        let tokens = tokenize("Main.jack", source).unwrap();
+
+        // When
+        let (class, xml) = parse_with_xml("Main.jack", &tokens).unwrap();
+
+        // Then
+        assert_eq!("Main", class.name);
+        assert_eq!(
+            vec![Statement::Return(None)],
+            class.subroutines[0].statements
+        );
+        let expected = "\
+<class>
+  <keyword> class </keyword>
+  <identifier> Main </identifier>
+  <symbol> { </symbol>
+  <subroutineDec>
+    <keyword> function </keyword>
+    <keyword> void </keyword>
+    <identifier> main </identifier>
+    <symbol> ( </symbol>
+    <parameterList>
+    </parameterList>
+    <symbol> ) </symbol>
+    <subroutineBody>
+      <symbol> { </symbol>
+      <statements>
+        <returnStatement>
+          <keyword> return </keyword>
+          <symbol> ; </symbol>
+        </returnStatement>
+      </statements>
+      <symbol> } </symbol>
+    </subroutineBody>
+  </subroutineDec>
+  <symbol> } </symbol>
+</class>
+";
+        assert_eq!(expected, xml);
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt::Write;
+
+use crate::error::AssemblerError;
+
+/// The keywords of the Jack language.
+const KEYWORDS: [&str; 21] = [
+    "class",
+    "constructor",
+    "function",
+    "method",
+    "field",
+    "static",
+    "var",
+    "int",
+    "char",
+    "boolean",
+    "void",
+    "true",
+    "false",
+    "null",
+    "this",
+    "let",
+    "do",
+    "if",
+    "else",
+    "while",
+    "return",
+];
+
+/// The symbols of the Jack language.
+const SYMBOLS: &str = "{}()[].,;+-*/&|<>=~";
+
+/// The kind and value of a token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword(&'static str),
+    Symbol(char),
+    Integer(u16),
+    String(String),
+    Identifier(String),
+}
+
+/// A token of a Jack source, with the line it starts on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+}
+
+impl Token {
+    /// Returns the token as an element of the nand2tetris XML outputs, such as
+    /// `<symbol> &lt; </symbol>`.
+    pub fn to_xml(&self) -> String {
+        let (tag, value) = match &self.kind {
+            TokenKind::Keyword(keyword) => ("keyword", keyword.to_string()),
+            TokenKind::Symbol(symbol) => ("symbol", symbol.to_string()),
+            TokenKind::Integer(value) => ("integerConstant", value.to_string()),
+            TokenKind::String(value) => ("stringConstant", value.clone()),
+            TokenKind::Identifier(name) => ("identifier", name.clone()),
+        };
+        let value = value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;");
+        format!("<{}> {} </{}>", tag, value, tag)
+    }
+}
+
+/// Splits a Jack source into tokens, dropping comments. The file names the
+/// errors.
+///
+/// Returns a syntax error on an invalid character, an unterminated string or
+/// comment, or an integer constant above 32767.
+pub fn tokenize(file: &str, source: &str) -> Result<Vec<Token>, AssemblerError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    let error = |line: usize, message: String| {
+        AssemblerError::syntax(format!("{}:{}: {}", file, line, message))
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                let start = line;
+                chars.next();
+                let mut previous = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if previous == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            previous = c;
+                        }
+                        None => return Err(error(start, String::from("unterminated comment"))),
+                    }
+                }
+            }
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => {
+                            return Err(error(line, String::from("unterminated string")))
+                        }
+                        Some(c) => value.push(c),
+                    }
+                }
+                tokens.push(Token {
+                    kind: TokenKind::String(value),
+                    line,
+                });
+            }
+            c if SYMBOLS.contains(c) => tokens.push(Token {
+                kind: TokenKind::Symbol(c),
+                line,
+            }),
+            c if c.is_ascii_digit() => {
+                let mut digits = String::from(c);
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(c);
+                }
+                let value = digits
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|value| *value <= 0x7FFF)
+                    .ok_or_else(|| error(line, format!("integer {} out of range", digits)))?;
+                tokens.push(Token {
+                    kind: TokenKind::Integer(value),
+                    line,
+                });
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut word = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    word.push(c);
+                }
+                let kind = match KEYWORDS.iter().find(|keyword| **keyword == word) {
+                    Some(keyword) => TokenKind::Keyword(keyword),
+                    None => TokenKind::Identifier(word),
+                };
+                tokens.push(Token { kind, line });
+            }
+            c => return Err(error(line, format!("unexpected character {:?}", c))),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Returns the tokens as the nand2tetris `T.xml` output.
+pub fn tokens_xml(tokens: &[Token]) -> String {
+    let mut xml = String::from("<tokens>\n");
+    for token in tokens {
+        writeln!(xml, "{}", token.to_xml()).expect("write to string");
+    }
+    xml.push_str("</tokens>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        // Given
+        let source = "/** doc */\nlet x = x < 10; // comment\ndo Output.printString(\"a&b\");";
+
+        // When
+        let tokens = tokenize("Main.jack", source).unwrap();
+
+        // Then
+        assert_eq!(15, tokens.len());
+        assert_eq!(
+            Token {
+                kind: TokenKind::Keyword("let"),
+                line: 2
+            },
+            tokens[0]
+        );
+        assert_eq!("<symbol> &lt; </symbol>", tokens[4].to_xml());
+        assert_eq!(
+            "<stringConstant> a&amp;b </stringConstant>",
+            tokens[12].to_xml()
+        );
+        assert_eq!(3, tokens[12].line);
+    }
+}
@@ -0,0 +1,123 @@
+use std::time::{Duration, Instant};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal,
+};
+
+/// The RAM address of the memory-mapped keyboard.
+pub const KBD: usize = 24576;
+
+/// How long a key stays pressed after its last event. Terminals don't report
+/// key releases, so a held key is kept alive by the terminal's auto-repeat.
+const HOLD: Duration = Duration::from_millis(150);
+
+/// Returns the Hack character code of a key, if it has one.
+pub fn hack_code(code: KeyCode) -> Option<u16> {
+    let code = match code {
+        KeyCode::Char(c) if c.is_ascii() && !c.is_ascii_control() => c as u16,
+        KeyCode::Enter => 128,
+        KeyCode::Backspace => 129,
+        KeyCode::Left => 130,
+        KeyCode::Up => 131,
+        KeyCode::Right => 132,
+        KeyCode::Down => 133,
+        KeyCode::Home => 134,
+        KeyCode::End => 135,
+        KeyCode::PageUp => 136,
+        KeyCode::PageDown => 137,
+        KeyCode::Insert => 138,
+        KeyCode::Delete => 139,
+        KeyCode::Esc => 140,
+        KeyCode::F(n @ 1..=12) => 140 + n as u16,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Puts the terminal in raw mode until dropped.
+pub struct RawMode;
+
+impl RawMode {
+    /// Enables the raw mode of the terminal.
+    pub fn enable() -> Self {
+        terminal::enable_raw_mode().expect("failed to enable the terminal raw mode");
+        Self
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Tracks the key currently pressed on the host keyboard.
+pub struct Keyboard {
+    code: u16,
+    held_until: Instant,
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self {
+            code: 0,
+            held_until: Instant::now(),
+        }
+    }
+}
+
+impl Keyboard {
+    /// Returns the Hack code of the key currently pressed, or 0 if none is.
+    pub fn code(&self) -> u16 {
+        if Instant::now() < self.held_until {
+            self.code
+        } else {
+            0
+        }
+    }
+
+    /// Processes the pending terminal events without blocking.
+    /// Returns `false` if the user asked to quit with `Ctrl+C`.
+    pub fn poll(&mut self) -> bool {
+        while event::poll(Duration::ZERO).unwrap_or_default() {
+            let Ok(Event::Key(key)) = event::read() else {
+                continue;
+            };
+            if !self.handle(key) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> bool {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return false;
+        }
+
+        match (key.kind, hack_code(key.code)) {
+            (KeyEventKind::Release, _) => self.held_until = Instant::now(),
+            (_, Some(code)) => {
+                self.code = code;
+                self.held_until = Instant::now() + HOLD;
+            }
+            (_, None) => {}
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hack_code() {
+        assert_eq!(Some(65), hack_code(KeyCode::Char('A')));
+        assert_eq!(Some(128), hack_code(KeyCode::Enter));
+        assert_eq!(Some(130), hack_code(KeyCode::Left));
+        assert_eq!(Some(152), hack_code(KeyCode::F(12)));
+        assert_eq!(None, hack_code(KeyCode::Tab));
+    }
+}
@@ -0,0 +1,88 @@
+//! An assembler for the Hack computer of the nand2tetris course.
+//!
+//! Downstream tools should depend on the [`prelude`], which is the stable
+//! API of the crate. The other modules are public for the binary and for
+//! experimentation, and may change in any release.
+
+pub mod assembler;
+pub mod backpatch;
+pub mod batch;
+pub mod callgraph;
+pub mod cancel;
+pub mod capture;
+pub mod cfg;
+pub mod code;
+pub mod condition;
+pub mod config;
+pub mod coverage;
+pub mod debugger;
+pub mod diagnostic;
+pub mod dialect;
+pub mod diff;
+pub mod difftest;
+pub mod disassembler;
+pub mod dump;
+pub mod emulator;
+pub mod error;
+pub mod format;
+pub mod gdb;
+pub mod generate;
+pub mod golden;
+pub mod grade;
+pub mod halt;
+pub mod heatmap;
+pub mod ir;
+#[cfg(feature = "jack")]
+pub mod jack;
+pub mod keyboard;
+pub mod limits;
+pub mod linker;
+pub mod lint;
+pub mod live;
+pub mod lsp;
+pub mod memmap;
+pub mod obfuscate;
+pub mod object;
+pub mod optimize;
+pub mod parser;
+pub mod pass;
+pub mod patch;
+pub mod prelude;
+pub mod profile;
+pub mod program;
+pub mod project;
+pub mod screen;
+pub mod script;
+pub mod server;
+pub mod snapshot;
+pub mod spec;
+pub mod stack;
+pub mod stats;
+pub mod stream;
+pub mod suggest;
+pub mod symbol_table;
+pub mod testing;
+pub mod tst;
+pub mod tutor;
+pub mod usage;
+pub mod vm;
+pub mod watch;
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_core_types_are_send_sync() {
+        assert_send_sync::<crate::parser::Parser<'static>>();
+        assert_send_sync::<Assembler<Uninitialized>>();
+        assert_send_sync::<Assembler<Initialized>>();
+        assert_send_sync::<Program>();
+        assert_send_sync::<SymbolTable>();
+        assert_send_sync::<PassManager>();
+        assert_send_sync::<Context>();
+        assert_send_sync::<Snapshot>();
+    }
+}
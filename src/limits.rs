@@ -0,0 +1,43 @@
+/// The last RAM address before the screen, where the variables end by
+/// default.
+pub const LAST_VARIABLE_ADDRESS: u32 = 16383;
+
+/// Resource limits enforced while assembling a program, so that untrusted
+/// submissions can't exhaust the memory or time of the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum size of the source, in bytes.
+    pub max_source_bytes: usize,
+    /// The maximum number of instructions, labels excluded.
+    pub max_instructions: usize,
+    /// The maximum number of symbols, predefined symbols included.
+    pub max_symbols: usize,
+    /// The last RAM address a variable can take.
+    pub max_variable_address: u32,
+}
+
+impl Default for Limits {
+    /// Returns limits which never trigger, except for the variables, which
+    /// must fit below the screen.
+    fn default() -> Self {
+        Self {
+            max_source_bytes: usize::MAX,
+            max_instructions: usize::MAX,
+            max_symbols: usize::MAX,
+            max_variable_address: LAST_VARIABLE_ADDRESS,
+        }
+    }
+}
+
+impl Limits {
+    /// Returns limits suited to untrusted submissions: a source of 1 MiB
+    /// and a program filling at most the 32K of ROM.
+    pub fn untrusted() -> Self {
+        Self {
+            max_source_bytes: 1 << 20,
+            max_instructions: 1 << 15,
+            max_symbols: 1 << 15,
+            max_variable_address: LAST_VARIABLE_ADDRESS,
+        }
+    }
+}
@@ -0,0 +1,507 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write},
+    ops::Range,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    error::{self, AssemblerError},
+    object::{Library, Object, SymbolUse},
+};
+
+/// The number of words of the ROM.
+const ROM_SIZE: usize = 32768;
+/// The first RAM address available for variables.
+const FIRST_VARIABLE: u32 = 16;
+
+/// An error raised while linking.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkError {
+    /// A label is defined by two objects.
+    DuplicateSymbol {
+        symbol: String,
+        first: String,
+        second: String,
+    },
+    /// A jump target isn't defined by any object.
+    UndefinedSymbol { symbol: String, object: String },
+    /// The program doesn't fit in the ROM.
+    RomOverflow { size: usize },
+    /// The layout names a section which isn't linked.
+    UnknownSection(String),
+    /// The layout places a label which isn't defined.
+    UnknownLabel(String),
+    /// The layout places a label somewhere its section can't start from.
+    ConflictingPlacement { section: String, label: String },
+    /// A section overlaps another section or a reserved region.
+    Overlap { section: String, other: String },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::DuplicateSymbol {
+                symbol,
+                first,
+                second,
+            } => write!(
+                f,
+                "symbol {} is defined in {} and {}",
+                symbol, first, second
+            ),
+            LinkError::UndefinedSymbol { symbol, object } => {
+                write!(f, "undefined symbol {} referenced in {}", symbol, object)
+            }
+            LinkError::RomOverflow { size } => write!(
+                f,
+                "program of {} words doesn't fit in the ROM of {} words",
+                size, ROM_SIZE
+            ),
+            LinkError::UnknownSection(section) => write!(f, "unknown section {}", section),
+            LinkError::UnknownLabel(label) => write!(f, "unknown label {}", label),
+            LinkError::ConflictingPlacement { section, label } => write!(
+                f,
+                "label {} can't be placed without moving section {}",
+                label, section
+            ),
+            LinkError::Overlap { section, other } => {
+                write!(f, "section {} overlaps {}", section, other)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+impl From<LinkError> for AssemblerError {
+    fn from(error: LinkError) -> Self {
+        AssemblerError::semantic(error.to_string())
+    }
+}
+
+impl From<Vec<LinkError>> for AssemblerError {
+    fn from(errors: Vec<LinkError>) -> Self {
+        let mut errors: Vec<AssemblerError> = errors.into_iter().map(Self::from).collect();
+        match errors.len() {
+            1 => errors.remove(0),
+            _ => AssemblerError::Multiple(errors),
+        }
+    }
+}
+
+/// The code of an object, placed in the ROM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    /// The name of the object.
+    pub name: String,
+    pub start: u16,
+    pub size: u16,
+}
+
+/// Where a section is placed in the ROM.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SectionPlacement {
+    /// The name of the section, which is the name of its object.
+    pub name: String,
+    /// The address of the section, placed after the previous one if omitted.
+    #[serde(default)]
+    pub address: Option<u16>,
+}
+
+/// A ROM region no section may occupy, its end being excluded.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReservedRegion {
+    pub start: u16,
+    pub end: u16,
+}
+
+/// A linker script, controlling where code lands in the ROM, usually loaded
+/// from JSON.
+///
+/// Sections are placed in the order of `sections`, then in link order, each
+/// after the previous one, skipping reserved regions and fixed sections.
+/// Sections with an address, or with a label in `labels`, are fixed.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Layout {
+    #[serde(default)]
+    pub sections: Vec<SectionPlacement>,
+    /// Labels at fixed addresses, fixing their sections.
+    #[serde(default)]
+    pub labels: BTreeMap<String, u16>,
+    #[serde(default)]
+    pub reserved: Vec<ReservedRegion>,
+}
+
+impl Layout {
+    /// Loads a layout from a JSON file. Returns an error if the file can't be
+    /// read or is not a valid layout.
+    pub fn load(path: &Path) -> Result<Self, AssemblerError> {
+        serde_json::from_str(&error::read_to_string(path)?).map_err(|error| {
+            AssemblerError::syntax(format!("{}: invalid layout: {}", path.display(), error))
+        })
+    }
+}
+
+/// A linked program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Executable {
+    /// The binary words, one per ROM address.
+    pub words: Vec<u16>,
+    /// The labels and the variables, and their addresses.
+    pub symbols: BTreeMap<String, u32>,
+    /// The sections in ROM order.
+    pub sections: Vec<Section>,
+    /// The variables allocated by the linker, also in the symbols.
+    pub variables: BTreeMap<String, u32>,
+}
+
+impl Executable {
+    /// Returns the map of the program: its sections and symbols, by address.
+    pub fn map(&self) -> String {
+        let mut map = String::from("sections:\n");
+        for section in &self.sections {
+            let end = section.start + section.size;
+            writeln!(map, "  {:>5}..{:<5} {}", section.start, end, section.name)
+                .expect("write to string");
+        }
+        map.push_str("symbols:\n");
+        let mut symbols: Vec<(&String, &u32)> = self.symbols.iter().collect();
+        symbols.sort_by_key(|(name, address)| (**address, *name));
+        for (name, address) in symbols {
+            writeln!(map, "  {:>5} {}", address, name).expect("write to string");
+        }
+        map
+    }
+}
+
+/// Links objects into an executable, laying out their code in the given
+/// order. Library objects are linked after them, only if they define a symbol
+/// referenced by a linked object.
+///
+/// Symbols defined as labels resolve to their ROM address. Other symbols
+/// become variables, allocated from RAM address 16 in order of first use,
+/// unless they are jump targets.
+pub fn link(objects: Vec<Object>, libraries: &[Library]) -> Result<Executable, Vec<LinkError>> {
+    link_with_layout(objects, libraries, &Layout::default())
+}
+
+/// Links objects into an executable like [`link`], placing sections as the
+/// layout says.
+pub fn link_with_layout(
+    objects: Vec<Object>,
+    libraries: &[Library],
+    layout: &Layout,
+) -> Result<Executable, Vec<LinkError>> {
+    let mut errors = Vec::new();
+    let mut linked = Vec::new();
+    // The object defining each label.
+    let mut definitions: HashMap<String, String> = HashMap::new();
+    let mut add = |object: Object, linked: &mut Vec<Object>| {
+        for symbol in object.labels.keys() {
+            if let Some(first) = definitions.get(symbol) {
+                errors.push(LinkError::DuplicateSymbol {
+                    symbol: symbol.clone(),
+                    first: first.clone(),
+                    second: object.name.clone(),
+                });
+            } else {
+                definitions.insert(symbol.clone(), object.name.clone());
+            }
+        }
+        linked.push(object);
+    };
+
+    for object in objects {
+        add(object, &mut linked);
+    }
+    let mut available: Vec<&Object> = libraries
+        .iter()
+        .flat_map(|library| &library.objects)
+        .collect();
+    loop {
+        let is_undefined = |symbol: &str| {
+            linked
+                .iter()
+                .all(|object| !object.labels.contains_key(symbol))
+        };
+        let needed = available.iter().position(|candidate| {
+            linked
+                .iter()
+                .flat_map(|object| &object.relocations)
+                .any(|relocation| {
+                    candidate.labels.contains_key(&relocation.symbol)
+                        && is_undefined(&relocation.symbol)
+                })
+        });
+        match needed {
+            Some(index) => add(available.remove(index).clone(), &mut linked),
+            None => break,
+        }
+    }
+
+    let sections = match place(&linked, layout) {
+        Ok(sections) => sections,
+        Err(placement_errors) => {
+            errors.extend(placement_errors);
+            return Err(errors);
+        }
+    };
+    let mut executable = relocate(&linked, sections, &mut errors);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    executable.sections.sort_by_key(|section| section.start);
+    Ok(executable)
+}
+
+/// Places the code of each object in the ROM, returning the sections in the
+/// order of the objects.
+fn place(objects: &[Object], layout: &Layout) -> Result<Vec<Section>, Vec<LinkError>> {
+    let mut errors = Vec::new();
+    let index = |name: &str| objects.iter().position(|object| object.name == name);
+
+    let mut starts: Vec<Option<usize>> = vec![None; objects.len()];
+    let mut order = Vec::new();
+    for placement in &layout.sections {
+        match index(&placement.name) {
+            Some(index) => {
+                starts[index] = placement.address.map(usize::from);
+                order.push(index);
+            }
+            None => errors.push(LinkError::UnknownSection(placement.name.clone())),
+        }
+    }
+    for (label, address) in &layout.labels {
+        let Some(index) = objects
+            .iter()
+            .position(|object| object.labels.contains_key(label))
+        else {
+            errors.push(LinkError::UnknownLabel(label.clone()));
+            continue;
+        };
+        let start = (*address as usize).checked_sub(objects[index].labels[label] as usize);
+        match (start, starts[index]) {
+            (Some(start), None) => starts[index] = Some(start),
+            (Some(start), Some(fixed)) if start == fixed => {}
+            _ => errors.push(LinkError::ConflictingPlacement {
+                section: objects[index].name.clone(),
+                label: label.clone(),
+            }),
+        }
+    }
+    let unordered: Vec<usize> = (0..objects.len())
+        .filter(|index| !order.contains(index))
+        .collect();
+    order.extend(unordered);
+
+    // The ROM ranges taken so far, and what they're taken by.
+    let mut occupied: Vec<(Range<usize>, String)> = layout
+        .reserved
+        .iter()
+        .map(|region| {
+            let range = region.start as usize..region.end as usize;
+            (
+                range,
+                format!("reserved region {}..{}", region.start, region.end),
+            )
+        })
+        .collect();
+    for (index, start) in starts.iter().enumerate() {
+        if let Some(start) = start {
+            let range = *start..start + objects[index].words.len();
+            take(&mut occupied, &objects[index].name, range, &mut errors);
+        }
+    }
+    let mut cursor = 0;
+    for index in order {
+        if starts[index].is_some() {
+            continue;
+        }
+        let size = objects[index].words.len();
+        let mut start = cursor;
+        while let Some((taken, _)) = occupied
+            .iter()
+            .find(|(taken, _)| size > 0 && start < taken.end && taken.start < start + size)
+        {
+            start = taken.end;
+        }
+        take(
+            &mut occupied,
+            &objects[index].name,
+            start..start + size,
+            &mut errors,
+        );
+        starts[index] = Some(start);
+        cursor = start + size;
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(objects
+        .iter()
+        .zip(starts)
+        .map(|(object, start)| Section {
+            name: object.name.clone(),
+            start: start.expect("every section is placed") as u16,
+            size: object.words.len() as u16,
+        })
+        .collect())
+}
+
+/// Takes a ROM range for a section, reporting overlaps and overflows.
+fn take(
+    occupied: &mut Vec<(Range<usize>, String)>,
+    section: &str,
+    range: Range<usize>,
+    errors: &mut Vec<LinkError>,
+) {
+    if let Some((_, other)) = occupied
+        .iter()
+        .find(|(taken, _)| range.start < taken.end && taken.start < range.end)
+    {
+        errors.push(LinkError::Overlap {
+            section: section.to_string(),
+            other: other.clone(),
+        });
+    }
+    if range.end > ROM_SIZE {
+        errors.push(LinkError::RomOverflow { size: range.end });
+    }
+    occupied.push((range, format!("section {}", section)));
+}
+
+/// Copies the code of the objects to their sections and patches the relocated
+/// words, allocating variables.
+fn relocate(objects: &[Object], sections: Vec<Section>, errors: &mut Vec<LinkError>) -> Executable {
+    let size = sections
+        .iter()
+        .map(|section| section.start as usize + section.size as usize)
+        .max()
+        .unwrap_or(0);
+    let mut words = vec![0; size];
+    let mut symbols = BTreeMap::new();
+    for (object, section) in objects.iter().zip(&sections) {
+        for (label, offset) in &object.labels {
+            symbols.insert(label.clone(), (section.start + offset) as u32);
+        }
+    }
+
+    let mut variables = BTreeMap::new();
+    let mut next_variable = FIRST_VARIABLE;
+    for (object, section) in objects.iter().zip(&sections) {
+        let start = section.start as usize;
+        words[start..start + object.words.len()].copy_from_slice(&object.words);
+        for relocation in &object.relocations {
+            let address = match symbols.get(&relocation.symbol) {
+                Some(address) => *address,
+                None if relocation.usage == SymbolUse::Jump => {
+                    let error = LinkError::UndefinedSymbol {
+                        symbol: relocation.symbol.clone(),
+                        object: object.name.clone(),
+                    };
+                    if !errors.contains(&error) {
+                        errors.push(error);
+                    }
+                    continue;
+                }
+                None => {
+                    symbols.insert(relocation.symbol.clone(), next_variable);
+                    variables.insert(relocation.symbol.clone(), next_variable);
+                    next_variable += 1;
+                    next_variable - 1
+                }
+            };
+            words[start + relocation.offset as usize] = address as u16;
+        }
+    }
+
+    Executable {
+        words,
+        symbols,
+        sections,
+        variables,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_with_library() {
+        // Given
+        let main = Object::assemble("Main", "@x\nM=1\n@Lib.f\n0;JMP\n").unwrap();
+        let used = Object::assemble("Used", "(Lib.f)\n@y\nM=0\n@x\nM=0\n").unwrap();
+        let unused = Object::assemble("Unused", "(Lib.g)\n@Lib.g\n0;JMP\n").unwrap();
+        let library = Library::new(vec![unused, used]);
+
+        // When
+        let executable = link(vec![main], &[library]).unwrap();
+
+        // Then
+        assert_eq!(
+            vec![16, 0xEFC8, 4, 0xEA87, 17, 0xEA88, 16, 0xEA88],
+            executable.words
+        );
+        assert_eq!(
+            vec!["Main", "Used"],
+            executable
+                .sections
+                .iter()
+                .map(|section| section.name.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_link_with_layout() {
+        // Given
+        let main = Object::assemble("Main", "(Main)\n@Boot.start\n0;JMP\n").unwrap();
+        let boot = Object::assemble("Boot", "@1\n(Boot.start)\n@Main\n0;JMP\n").unwrap();
+        let layout: Layout = serde_json::from_str(
+            r#"{
+                "sections": [{ "name": "Boot" }],
+                "labels": { "Boot.start": 11 },
+                "reserved": [{ "start": 0, "end": 2 }]
+            }"#,
+        )
+        .unwrap();
+
+        // When
+        let executable = link_with_layout(vec![main, boot], &[], &layout).unwrap();
+
+        // Then
+        let sections: Vec<(&str, u16)> = executable
+            .sections
+            .iter()
+            .map(|section| (section.name.as_str(), section.start))
+            .collect();
+        assert_eq!(vec![("Main", 2), ("Boot", 10)], sections);
+        assert_eq!(Some(&11), executable.symbols.get("Boot.start"));
+        assert_eq!(13, executable.words.len());
+    }
+
+    #[test]
+    fn test_link_errors() {
+        // Given
+        let first = Object::assemble("First", "(START)\n@MISSING\n0;JMP\n").unwrap();
+        let second = Object::assemble("Second", "(START)\n@START\n0;JMP\n").unwrap();
+
+        // When
+        let errors = link(vec![first, second], &[]).unwrap_err();
+
+        // Then
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            vec![
+                "symbol START is defined in First and Second",
+                "undefined symbol MISSING referenced in First",
+            ],
+            errors
+        );
+    }
+}
@@ -0,0 +1,558 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use clap::ValueEnum;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    diagnostic::{Diagnostic, Location},
+    error::AssemblerError,
+    ir::{Ir, IrInstruction},
+    pass::{Context, Pass, Stage},
+    program::{Instruction, Program},
+    suggest::closest,
+    symbol_table::SymbolTable,
+    usage::UsageReport,
+};
+
+/// The address of the keyboard memory map, right after the screen.
+const KBD: u16 = 24576;
+
+/// The kinds of lints, named on the command line in kebab case.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum LintKind {
+    /// Accesses to the screen and keyboard that are certainly out of range.
+    MemoryMap,
+    /// Labels never loaded by an A-instruction.
+    UnusedLabel,
+    /// Labels named after a predefined symbol, such as `(R1)`.
+    ShadowedSymbol,
+    /// Variables written but never read.
+    UnreadVariable,
+    /// Variables named like a label but for one character, most likely
+    /// misspelling it.
+    MisspelledLabel,
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.to_possible_value().expect("lints are never skipped");
+        write!(f, "{}", name.get_name())
+    }
+}
+
+/// What to do with the lints of a kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintLevel {
+    /// Ignore them.
+    Allow,
+    /// Report them as warnings.
+    Warn,
+    /// Report them as errors, failing the assembly.
+    Deny,
+}
+
+/// The level of each kind of lint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LintLevels {
+    default: LintLevel,
+    levels: BTreeMap<LintKind, LintLevel>,
+}
+
+impl LintLevels {
+    /// Returns the levels with every kind at the given level.
+    pub fn new(default: LintLevel) -> Self {
+        Self {
+            default,
+            levels: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the level of a kind of lint.
+    pub fn set(&mut self, kind: LintKind, level: LintLevel) {
+        self.levels.insert(kind, level);
+    }
+
+    /// Returns the level of a kind of lint.
+    pub fn level(&self, kind: LintKind) -> LintLevel {
+        self.levels.get(&kind).copied().unwrap_or(self.default)
+    }
+
+    /// Returns whether any kind of lint is reported, so that the program
+    /// has to be linted.
+    pub fn any_reported(&self) -> bool {
+        LintKind::value_variants()
+            .iter()
+            .any(|kind| self.level(*kind) != LintLevel::Allow)
+    }
+
+    /// Returns the lint as a diagnostic of its level pointing into the
+    /// source it was found in, the file being the path of the source, if
+    /// any. Returns `None` if the lint is allowed.
+    pub fn diagnostic(&self, lint: &Lint, file: Option<&Path>, source: &str) -> Option<Diagnostic> {
+        let message = match lint.line {
+            Some(_) => format!("ROM[{}]: {}", lint.address, lint.message),
+            None => lint.to_string(),
+        };
+        let diagnostic = match self.level(lint.kind) {
+            LintLevel::Allow => return None,
+            LintLevel::Warn => Diagnostic::warning(message),
+            LintLevel::Deny => Diagnostic::error(message),
+        };
+        let mut diagnostic = diagnostic.with_code(lint.kind.to_string());
+        if let Some(suggestion) = &lint.suggestion {
+            diagnostic = diagnostic.with_suggestion(suggestion.clone());
+        }
+        Some(match lint.line {
+            Some(line) => diagnostic.at(Location::new(file, source, line, None)),
+            None => diagnostic,
+        })
+    }
+}
+
+impl Default for LintLevels {
+    /// Returns the levels warning about every kind of lint.
+    fn default() -> Self {
+        Self::new(LintLevel::Warn)
+    }
+}
+
+/// A warning about an instruction which is valid but most likely wrong.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    /// The ROM address of the instruction.
+    pub address: u32,
+    /// The source line of the instruction, if known.
+    pub line: Option<usize>,
+    pub message: String,
+    /// The text most likely fixing the instruction, if known.
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ROM[{}]", self.address)?;
+        if let Some(line) = self.line {
+            write!(f, " (line {})", line)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The lints found while assembling.
+///
+/// Clones share the same lints, so the report can be read after the
+/// assembler consumed the context.
+#[derive(Clone, Debug, Default)]
+pub struct LintReport {
+    lints: Arc<Mutex<Vec<Lint>>>,
+}
+
+impl LintReport {
+    /// Returns a new empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the lints, in ROM order.
+    pub fn lints(&self) -> Vec<Lint> {
+        self.lints.lock().expect("poisoned report").clone()
+    }
+}
+
+/// The values known to be held by the registers and the RAM cells at some
+/// point of a basic block.
+#[derive(Default)]
+struct Known {
+    a: Option<u16>,
+    /// Whether A was computed rather than loaded by an A-instruction.
+    computed: bool,
+    d: Option<u16>,
+    ram: FxHashMap<u16, u16>,
+}
+
+/// Returns the lints of the accesses to the screen and keyboard memory maps
+/// whose address is known: writes to the keyboard, which is read-only, such
+/// as the classic off-by-one past the last word of the screen, and accesses
+/// past the keyboard, the end of the memory map.
+///
+/// The addresses are found by propagating the constants loaded in A and D,
+/// and stored in RAM cells at known addresses, through each basic block.
+/// Nothing is known when a block is entered.
+pub fn memory_lints(ir: &Ir) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut known = Known::default();
+    for node in &ir.nodes {
+        if !node.labels.is_empty() {
+            known = Known::default();
+        }
+        let (dest, comp, jump) = match &node.instruction {
+            IrInstruction::A { value, .. } => {
+                known.a = Some(*value as u16);
+                known.computed = false;
+                continue;
+            }
+            IrInstruction::C { dest, comp, jump } => (dest, comp, jump),
+        };
+
+        let address = known.a;
+        let reads = comp.contains('M');
+        let writes = dest.contains('M');
+        if let Some(address) = address.filter(|_| reads || writes) {
+            let message = if writes && address == KBD && known.computed {
+                Some(format!(
+                    "writes KBD at {}, one past the last word of the screen",
+                    KBD
+                ))
+            } else if writes && address == KBD {
+                Some(format!("writes KBD at {}, which is read-only", KBD))
+            } else if address > KBD {
+                Some(format!(
+                    "{} RAM[{}], past the keyboard at the end of the memory map",
+                    if writes { "writes" } else { "reads" },
+                    address
+                ))
+            } else {
+                None
+            };
+            lints.extend(message.map(|message| Lint {
+                kind: LintKind::MemoryMap,
+                address: node.address,
+                line: node.line,
+                message,
+                suggestion: None,
+            }));
+        }
+
+        let m = address.and_then(|address| known.ram.get(&address).copied());
+        let out = eval(comp, known.a, known.d, m);
+        if writes {
+            match (address, out) {
+                (Some(address), Some(out)) => {
+                    known.ram.insert(address, out);
+                }
+                (Some(address), None) => {
+                    known.ram.remove(&address);
+                }
+                (None, _) => known.ram.clear(),
+            }
+        }
+        if dest.contains('A') {
+            known.a = out;
+            known.computed = true;
+        }
+        if dest.contains('D') {
+            known.d = out;
+        }
+        if !jump.is_empty() {
+            known = Known::default();
+        }
+    }
+    lints
+}
+
+/// Returns the value of the comp, if its operands are known.
+fn eval(comp: &str, a: Option<u16>, d: Option<u16>, m: Option<u16>) -> Option<u16> {
+    let operand = |name: char| match name {
+        'A' => a,
+        'D' => d,
+        'M' => m,
+        _ => None,
+    };
+    let chars: Vec<char> = comp.chars().collect();
+    match chars.as_slice() {
+        ['0'] => Some(0),
+        ['1'] => Some(1),
+        ['-', '1'] => Some(u16::MAX),
+        [x] => operand(*x),
+        ['!', x] => operand(*x).map(|x| !x),
+        ['-', x] => operand(*x).map(u16::wrapping_neg),
+        [x, '+', '1'] => operand(*x).map(|x| x.wrapping_add(1)),
+        [x, '-', '1'] => operand(*x).map(|x| x.wrapping_sub(1)),
+        [x, operator, y] => {
+            let (x, y) = (operand(*x)?, operand(*y)?);
+            match operator {
+                '+' => Some(x.wrapping_add(y)),
+                '-' => Some(x.wrapping_sub(y)),
+                '&' => Some(x & y),
+                '|' => Some(x | y),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns the lints of the symbols of the program: the labels never loaded
+/// by an A-instruction, the labels named after a predefined symbol, and the
+/// variables written but never read, according to the usage report.
+pub fn symbol_lints(program: &Program, ir: &Ir, symbol_table: &SymbolTable) -> Vec<Lint> {
+    let loaded: FxHashSet<&str> = program
+        .instructions()
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::A(symbol) => Some(symbol.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let mut labels: Vec<&str> = symbol_table
+        .iter()
+        .map(|(symbol, _)| symbol)
+        .filter(|symbol| symbol_table.is_label(symbol))
+        .collect();
+    labels.sort_unstable();
+
+    let mut lints = Vec::new();
+    let mut address = 0;
+    for (index, instruction) in program.instructions().iter().enumerate() {
+        let line = program.source_line(index);
+        let label = match instruction {
+            Instruction::L(label) => label,
+            Instruction::A(symbol) => {
+                let is_variable = symbol.parse::<u32>().is_err()
+                    && !symbol_table.is_label(symbol)
+                    && !SymbolTable::is_predefined(symbol);
+                let misspelled = match is_variable {
+                    true => closest(symbol, labels.iter().copied(), 1),
+                    false => None,
+                };
+                if let Some(label) = misspelled {
+                    lints.push(Lint {
+                        kind: LintKind::MisspelledLabel,
+                        address,
+                        line,
+                        message: format!(
+                            "variable {} is one character away from label {}",
+                            symbol, label
+                        ),
+                        suggestion: Some(label.to_string()),
+                    });
+                }
+                address += 1;
+                continue;
+            }
+            _ => {
+                address += 1;
+                continue;
+            }
+        };
+        if let Some(predefined) = SymbolTable::predefined(label) {
+            lints.push(Lint {
+                kind: LintKind::ShadowedSymbol,
+                address,
+                line,
+                message: format!(
+                    "label {} shadows the predefined symbol: @{} loads ROM[{}], not RAM[{}]",
+                    label, label, address, predefined
+                ),
+                suggestion: None,
+            });
+        }
+        if !loaded.contains(label.as_str()) {
+            lints.push(Lint {
+                kind: LintKind::UnusedLabel,
+                address,
+                line,
+                message: format!("label {} is never used", label),
+                suggestion: None,
+            });
+        }
+    }
+
+    let usage = UsageReport::new(ir, symbol_table);
+    for cell in usage.never_read() {
+        if cell.address < 16 || SymbolTable::is_predefined(&cell.name) {
+            continue;
+        }
+        lints.push(Lint {
+            kind: LintKind::UnreadVariable,
+            address: cell.first,
+            line: ir
+                .nodes
+                .iter()
+                .find(|node| node.address == cell.first)
+                .and_then(|node| node.line),
+            message: format!("variable {} is written but never read", cell.name),
+            suggestion: None,
+        });
+    }
+    lints.sort_by_key(|lint| lint.address);
+    lints
+}
+
+/// Reports the accesses to the screen and keyboard memory maps that are
+/// certainly wrong.
+pub struct MemoryLint;
+
+impl Pass for MemoryLint {
+    fn name(&self) -> &'static str {
+        "memory-lint"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Analyze
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        let lints = memory_lints(&context.ir);
+        context
+            .lints
+            .lints
+            .lock()
+            .expect("poisoned report")
+            .extend(lints);
+        Ok(())
+    }
+}
+
+/// Reports the symbols of the program that are unused or most likely
+/// misnamed.
+pub struct SymbolLint;
+
+impl Pass for SymbolLint {
+    fn name(&self) -> &'static str {
+        "symbol-lint"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Analyze
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        let lints = symbol_lints(&context.program, &context.ir, &context.symbol_table);
+        context
+            .lints
+            .lints
+            .lock()
+            .expect("poisoned report")
+            .extend(lints);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+
+    fn lints(source: &str) -> Vec<String> {
+        let program = Program::from_source(source);
+        let ir = Ir::lower(&program, &program.resolve());
+        memory_lints(&ir)
+            .iter()
+            .map(|lint| lint.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_out_of_range_accesses_are_reported() {
+        // Given
+        let source = "\
+            @SCREEN\nD=A\n@8191\nA=D+A\nM=-1\n\
+            @SCREEN\nD=A\n@8192\nA=D+A\nM=-1\n\
+            @KBD\nD=M\nM=0\n\
+            @KBD\nD=A\n@p\nM=D+1\n@p\nA=M\nD=M\n";
+
+        // When
+        let lints = lints(source);
+
+        // Then
+        assert_eq!(
+            vec![
+                "ROM[9] (line 10): writes KBD at 24576, one past the last word of the screen",
+                "ROM[12] (line 13): writes KBD at 24576, which is read-only",
+                "ROM[19] (line 20): reads RAM[24577], past the keyboard at the end of the memory map",
+            ],
+            lints
+        );
+    }
+
+    #[test]
+    fn test_symbol_lints() {
+        // Given
+        let source = "@x\nM=1\n@y\nD=M\n@y\nM=D\n(R1)\n(UNUSED)\n@R1\n0;JMP\n@UNUSEE\n0;JMP\n";
+        let program = Program::from_source(source);
+        let symbol_table = program.resolve();
+        let ir = Ir::lower(&program, &symbol_table);
+
+        // When
+        let lints = symbol_lints(&program, &ir, &symbol_table);
+
+        // Then
+        assert_eq!(
+            vec![
+                (
+                    LintKind::UnreadVariable,
+                    String::from("ROM[1] (line 2): variable x is written but never read")
+                ),
+                (
+                    LintKind::ShadowedSymbol,
+                    String::from(
+                        "ROM[6] (line 7): label R1 shadows the predefined symbol: @R1 loads ROM[6], not RAM[1]"
+                    )
+                ),
+                (
+                    LintKind::UnusedLabel,
+                    String::from("ROM[6] (line 8): label UNUSED is never used")
+                ),
+                (
+                    LintKind::MisspelledLabel,
+                    String::from(
+                        "ROM[8] (line 11): variable UNUSEE is one character away from label UNUSED"
+                    )
+                ),
+            ],
+            lints
+                .iter()
+                .map(|lint| (lint.kind, lint.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_levels() {
+        // Given
+        let mut levels = LintLevels::new(LintLevel::Allow);
+        levels.set(LintKind::UnusedLabel, LintLevel::Deny);
+        let lint = Lint {
+            kind: LintKind::UnusedLabel,
+            address: 0,
+            line: Some(1),
+            message: String::from("label END is never used"),
+            suggestion: None,
+        };
+
+        // When
+        let diagnostic = levels.diagnostic(&lint, None, "(END)\n");
+
+        // Then
+        assert!(levels.any_reported());
+        assert_eq!(
+            Some(
+                Diagnostic::error("ROM[0]: label END is never used")
+                    .with_code("unused-label")
+                    .at(Location::new(None, "(END)\n", 1, None))
+            ),
+            diagnostic
+        );
+        assert!(!LintLevels::new(LintLevel::Allow).any_reported());
+    }
+
+    #[test]
+    fn test_unknown_addresses_are_not_reported() {
+        // Given
+        let source = "@KBD\nD=A\n(LOOP)\nA=D\nM=0\n@KBD\nD=A\n@LOOP\nD;JGT\nA=D\nM=0\n";
+
+        // When
+        let lints = lints(source);
+
+        // Then
+        assert!(lints.is_empty(), "{:?}", lints);
+    }
+}
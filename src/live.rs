@@ -0,0 +1,199 @@
+use std::{
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+use tungstenite::{Message, WebSocket};
+
+use crate::{
+    diagnostic::Diagnostic, error::AssemblerError, limits::Limits, server::assemble_source,
+};
+
+/// A document update sent by the client.
+///
+/// Lines are zero-indexed, a change replaces the lines in `start..end` with `text`.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Update {
+    /// Replaces the whole document.
+    Open { text: String },
+    /// Replaces a range of lines in the document.
+    Change {
+        start: usize,
+        end: usize,
+        text: String,
+    },
+}
+
+/// The assembly result sent back after each update.
+///
+/// The output words in `start..start + delete` are replaced with `insert`.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Delta {
+    pub start: usize,
+    pub delete: usize,
+    pub insert: Vec<String>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A document being edited live, along with its last assembled output.
+#[derive(Default)]
+pub struct Document {
+    lines: Vec<String>,
+    words: Vec<String>,
+    limits: Limits,
+}
+
+impl Document {
+    /// Returns a new empty document, assembled within the limits.
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// Applies the update to the document, re-assembles it and returns
+    /// the changes to the output.
+    pub fn apply(&mut self, update: Update) -> Delta {
+        match update {
+            Update::Open { text } => self.lines = text.lines().map(String::from).collect(),
+            Update::Change { start, end, text } => {
+                let end = end.min(self.lines.len());
+                let start = start.min(end);
+                self.lines
+                    .splice(start..end, text.lines().map(String::from));
+            }
+        }
+
+        let response = assemble_source(&self.lines.join("\n"), self.limits);
+        // Keep the last successful output on errors so the client only
+        // receives the diagnostics.
+        if !response.diagnostics.is_empty() {
+            return Delta {
+                start: 0,
+                delete: 0,
+                insert: Vec::new(),
+                diagnostics: response.diagnostics,
+            };
+        }
+
+        let delta = diff(&self.words, &response.words);
+        self.words = response.words;
+        delta
+    }
+}
+
+/// Computes the delta between the old and new words by trimming their
+/// common prefix and suffix.
+fn diff(old: &[String], new: &[String]) -> Delta {
+    let prefix = old.iter().zip(new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+
+    Delta {
+        start: prefix,
+        delete: old.len() - prefix - suffix,
+        insert: new[prefix..new.len() - suffix].to_vec(),
+        diagnostics: Vec::new(),
+    }
+}
+
+/// Serves live assembly over WebSocket on the given address.
+///
+/// Clients send [`Update`]s as JSON text messages and receive a [`Delta`] for each.
+/// Returns an error if the server can't listen on the address.
+pub fn serve(addr: &str, limits: Limits) -> Result<(), AssemblerError> {
+    let listener = TcpListener::bind(addr)
+        .map_err(|error| AssemblerError::io(format!("failed to listen on {}", addr), error))?;
+    println!("listening on ws://{}", addr);
+
+    for stream in listener.incoming().flatten() {
+        thread::spawn(move || {
+            if let Ok(socket) = tungstenite::accept(stream) {
+                handle_connection(socket, limits);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle_connection(mut socket: WebSocket<TcpStream>, limits: Limits) {
+    let mut document = Document::new(limits);
+
+    while let Ok(message) = socket.read() {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let reply = match serde_json::from_str::<Update>(&text) {
+            Ok(update) => serde_json::to_string(&document.apply(update)),
+            Err(err) => serde_json::to_string(&Delta {
+                start: 0,
+                delete: 0,
+                insert: Vec::new(),
+                diagnostics: vec![Diagnostic::error(format!("invalid update: {}", err))],
+            }),
+        };
+
+        // A delta that can't be serialized ends the connection, like a failed send.
+        let Ok(reply) = reply else {
+            break;
+        };
+        if socket.send(Message::Text(reply.into())).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serve_on_a_taken_address() {
+        // Given
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        // When
+        let error = serve(&addr, Limits::default()).unwrap_err();
+
+        // Then
+        assert!(matches!(error, AssemblerError::Io { .. }), "{}", error);
+    }
+
+    #[test]
+    fn test_document_apply_change() {
+        // Given
+        let mut document = Document::default();
+        document.apply(Update::Open {
+            text: String::from("@1\nD=A\n@2\nD=D+A"),
+        });
+
+        // When
+        let delta = document.apply(Update::Change {
+            start: 2,
+            end: 3,
+            text: String::from("@3"),
+        });
+
+        // Then
+        assert_eq!(
+            Delta {
+                start: 2,
+                delete: 1,
+                insert: vec![String::from("0000000000000011")],
+                diagnostics: Vec::new(),
+            },
+            delta
+        );
+    }
+}
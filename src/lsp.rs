@@ -0,0 +1,502 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    code::{a_instruction, c_instruction},
+    config::ProjectConfig,
+    error::AssemblerError,
+    format,
+    parser::BlockComments,
+    program::{Instruction, Program},
+    symbol_table::SymbolTable,
+};
+
+/// The LSP `SymbolKind` of labels.
+const FUNCTION_KIND: u32 = 12;
+/// The LSP `SymbolKind` of variables.
+const VARIABLE_KIND: u32 = 13;
+
+/// A symbol written in the document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Occurrence {
+    pub name: String,
+    /// The zero-indexed line of the occurrence.
+    pub line: usize,
+    /// The columns of the symbol in the line.
+    pub start: usize,
+    pub end: usize,
+    /// Whether the occurrence is a label declaration.
+    pub is_declaration: bool,
+}
+
+/// The symbols, encodings and errors of a document, line by line.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub occurrences: Vec<Occurrence>,
+    /// The binary encoding of each instruction line.
+    pub encodings: HashMap<usize, String>,
+    /// The errors of the document and their zero-indexed lines.
+    pub errors: Vec<(usize, String)>,
+    /// The symbols of the valid lines.
+    pub symbol_table: SymbolTable,
+}
+
+impl Analysis {
+    /// Analyzes a document. Each line is parsed on its own so that an invalid
+    /// line is reported without hiding the symbols of the others.
+    pub fn new(text: &str) -> Self {
+        let mut analysis = Self::default();
+        let mut instructions = Vec::new();
+        // Block comments are blanked out first, as they can span lines.
+        let text = BlockComments::default().blank(text);
+        for (line, source) in text.lines().enumerate() {
+            match Program::parse(source) {
+                Ok(program) => {
+                    if let Some(instruction) = program.instructions().first().cloned() {
+                        analysis.add_occurrence(line, source, &instruction);
+                        instructions.push((line, instruction));
+                    }
+                }
+                Err(error) => analysis.errors.extend(
+                    error
+                        .to_diagnostics(None, source)
+                        .into_iter()
+                        .map(|diagnostic| (line, diagnostic.message)),
+                ),
+            }
+        }
+
+        let mut program = Program::new();
+        for (line, instruction) in &instructions {
+            if let Instruction::L(label) = instruction {
+                if program.label_index(label).is_some() {
+                    analysis
+                        .errors
+                        .push((*line, format!("label {} is defined twice", label)));
+                    continue;
+                }
+            }
+            program.push(instruction.clone());
+        }
+        analysis.symbol_table = program.resolve();
+
+        for (line, instruction) in instructions {
+            match analysis.encode(&instruction) {
+                Ok(Some(word)) => {
+                    analysis.encodings.insert(line, word);
+                }
+                Ok(None) => {}
+                Err(error) => analysis.errors.extend(
+                    error
+                        .to_diagnostics(None, "")
+                        .into_iter()
+                        .map(|diagnostic| (line, diagnostic.message)),
+                ),
+            }
+        }
+        analysis.errors.sort_by_key(|(line, _)| *line);
+        analysis
+    }
+
+    fn add_occurrence(&mut self, line: usize, source: &str, instruction: &Instruction) {
+        let (marker, name) = match instruction {
+            Instruction::A(value) if value.parse::<u32>().is_err() => ('@', value),
+            Instruction::L(label) => ('(', label),
+            _ => return,
+        };
+        let offset = source.find(marker).map_or(0, |index| index + 1);
+        let start = offset + source[offset..].len() - source[offset..].trim_start().len();
+        self.occurrences.push(Occurrence {
+            name: name.clone(),
+            line,
+            start,
+            end: start + name.len(),
+            is_declaration: marker == '(',
+        });
+    }
+
+    /// Encodes an instruction into its binary word, `None` for a label, or
+    /// returns an error if the instruction is invalid or its constant is out
+    /// of range.
+    fn encode(&self, instruction: &Instruction) -> Result<Option<String>, AssemblerError> {
+        let word = match instruction {
+            Instruction::A(value) => {
+                let address = match self.symbol_table.address(value) {
+                    Some(address) => *address,
+                    None => value.parse::<u32>().map_err(|_| {
+                        AssemblerError::syntax(format!("invalid A-instruction @{}", value))
+                    })?,
+                };
+                a_instruction(address)?
+            }
+            Instruction::C { dest, comp, jump } => c_instruction(dest, comp, jump)?,
+            Instruction::L(_) => return Ok(None),
+        };
+        Ok(Some(format!("{:016b}", word)))
+    }
+
+    /// Returns the symbol at the position, if any.
+    pub fn occurrence_at(&self, line: usize, column: usize) -> Option<&Occurrence> {
+        self.occurrences.iter().find(|occurrence| {
+            occurrence.line == line && (occurrence.start..=occurrence.end).contains(&column)
+        })
+    }
+
+    /// Returns the definition of a symbol: the declaration of a label, or
+    /// the first use of a variable. Predefined symbols have none.
+    pub fn definition(&self, name: &str) -> Option<&Occurrence> {
+        if self.symbol_table.is_label(name) {
+            return self
+                .occurrences
+                .iter()
+                .find(|occurrence| occurrence.name == name && occurrence.is_declaration);
+        }
+        if SymbolTable::new().address(name).is_some() {
+            return None;
+        }
+        self.occurrences
+            .iter()
+            .find(|occurrence| occurrence.name == name)
+    }
+
+    /// Returns the Markdown hover of the line at the position.
+    pub fn hover(&self, line: usize, column: usize) -> Option<String> {
+        let mut hover = Vec::new();
+        if let Some(occurrence) = self.occurrence_at(line, column) {
+            let name = &occurrence.name;
+            let description = match self.symbol_table.address(name) {
+                Some(address) if self.symbol_table.is_label(name) => {
+                    format!("label `{}`: ROM[{}]", name, address)
+                }
+                Some(address) if self.definition(name).is_none() => {
+                    format!("predefined symbol `{}`: RAM[{}]", name, address)
+                }
+                Some(address) => format!("variable `{}`: RAM[{}]", name, address),
+                None => format!("`{}` is unresolved", name),
+            };
+            hover.push(description);
+        }
+        if let Some(word) = self.encodings.get(&line) {
+            hover.push(format!("`{}`", word));
+        }
+        (!hover.is_empty()).then(|| hover.join("\n\n"))
+    }
+}
+
+/// A language server for Hack assembly, speaking JSON-RPC.
+#[derive(Default)]
+pub struct Server {
+    /// The text of the open documents, by URI.
+    documents: HashMap<String, String>,
+}
+
+impl Server {
+    /// Returns a server without open documents.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles a request or notification, returning the response and
+    /// notifications to send back, and whether the server should exit.
+    pub fn handle(&mut self, message: &Value) -> (Vec<Value>, bool) {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                    "renameProvider": true,
+                    "documentFormattingProvider": true,
+                },
+                "serverInfo": { "name": "hack-assembler" },
+            })),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+                return (vec![self.diagnostics(&uri)], false);
+            }
+            "textDocument/didChange" => {
+                // Full synchronization: the last change holds the whole text.
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+                return (vec![self.diagnostics(&uri)], false);
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                let clear = notification(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                );
+                return (vec![clear], false);
+            }
+            "textDocument/hover" => {
+                let (analysis, line, column) = self.position(&uri, params);
+                Ok(analysis.hover(line, column).map_or(
+                    Value::Null,
+                    |hover| json!({ "contents": { "kind": "markdown", "value": hover } }),
+                ))
+            }
+            "textDocument/definition" => {
+                let (analysis, line, column) = self.position(&uri, params);
+                Ok(analysis
+                    .occurrence_at(line, column)
+                    .and_then(|occurrence| analysis.definition(&occurrence.name))
+                    .map_or(
+                        Value::Null,
+                        |definition| json!({ "uri": uri, "range": range(definition) }),
+                    ))
+            }
+            "textDocument/documentSymbol" => {
+                let analysis = self.analysis(&uri);
+                let mut names: Vec<&str> = Vec::new();
+                let mut symbols = Vec::new();
+                for occurrence in &analysis.occurrences {
+                    let name = occurrence.name.as_str();
+                    if names.contains(&name) {
+                        continue;
+                    }
+                    names.push(name);
+                    let Some(definition) = analysis.definition(name) else {
+                        continue;
+                    };
+                    let kind = if definition.is_declaration {
+                        FUNCTION_KIND
+                    } else {
+                        VARIABLE_KIND
+                    };
+                    symbols.push(json!({
+                        "name": name,
+                        "kind": kind,
+                        "range": range(definition),
+                        "selectionRange": range(definition),
+                    }));
+                }
+                Ok(Value::Array(symbols))
+            }
+            "textDocument/rename" => {
+                let (analysis, line, column) = self.position(&uri, params);
+                let new_name = params["newName"].as_str().unwrap_or_default();
+                rename(&analysis, line, column, new_name)
+                    .map(|edits| json!({ "changes": { uri: edits } }))
+            }
+            "textDocument/formatting" => {
+                let text = self.documents.get(&uri).map_or("", String::as_str);
+                let config = match uri.strip_prefix("file://") {
+                    Some(path) => ProjectConfig::discover(Path::new(path)),
+                    None => Ok(ProjectConfig::default()),
+                };
+                let end = json!({ "line": text.lines().count() + 1, "character": 0 });
+                config
+                    .map(|config| {
+                        json!([{
+                            "range": { "start": { "line": 0, "character": 0 }, "end": end },
+                            "newText": format::format(text, &config.format),
+                        }])
+                    })
+                    .map_err(|error| (-32603, error.to_string()))
+            }
+            "shutdown" => Ok(Value::Null),
+            "exit" => return (Vec::new(), true),
+            _ => Err((-32601, format!("unsupported method {}", method))),
+        };
+
+        // Notifications don't get a response.
+        let Some(id) = id else {
+            return (Vec::new(), false);
+        };
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        (vec![response], false)
+    }
+
+    fn analysis(&self, uri: &str) -> Analysis {
+        Analysis::new(self.documents.get(uri).map_or("", String::as_str))
+    }
+
+    fn position(&self, uri: &str, params: &Value) -> (Analysis, usize, usize) {
+        let line = params["position"]["line"].as_u64().unwrap_or_default();
+        let column = params["position"]["character"].as_u64().unwrap_or_default();
+        (self.analysis(uri), line as usize, column as usize)
+    }
+
+    fn diagnostics(&self, uri: &str) -> Value {
+        let text = self.documents.get(uri).map_or("", String::as_str);
+        let lines: Vec<&str> = text.lines().collect();
+        let diagnostics: Vec<Value> = Analysis::new(text)
+            .errors
+            .iter()
+            .map(|(line, message)| {
+                json!({
+                    "range": {
+                        "start": { "line": line, "character": 0 },
+                        "end": { "line": line, "character": lines[*line].len() },
+                    },
+                    "severity": 1,
+                    "source": "hack",
+                    "message": message,
+                })
+            })
+            .collect();
+        notification(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        )
+    }
+}
+
+/// Returns the edits renaming the symbol at the position, or an error if
+/// there is no user symbol there or the new name isn't a valid symbol.
+fn rename(
+    analysis: &Analysis,
+    line: usize,
+    column: usize,
+    new_name: &str,
+) -> Result<Vec<Value>, (i32, String)> {
+    let occurrence = analysis
+        .occurrence_at(line, column)
+        .filter(|occurrence| analysis.definition(&occurrence.name).is_some())
+        .ok_or((-32602, String::from("no symbol to rename")))?;
+    let is_valid = new_name.chars().enumerate().all(|(index, c)| {
+        c.is_ascii_alphabetic() || "_.$:".contains(c) || (index > 0 && c.is_ascii_digit())
+    });
+    if new_name.is_empty() || !is_valid || SymbolTable::new().address(new_name).is_some() {
+        return Err((-32602, format!("invalid symbol name {}", new_name)));
+    }
+    Ok(analysis
+        .occurrences
+        .iter()
+        .filter(|other| other.name == occurrence.name)
+        .map(|other| json!({ "range": range(other), "newText": new_name }))
+        .collect())
+}
+
+fn range(occurrence: &Occurrence) -> Value {
+    json!({
+        "start": { "line": occurrence.line, "character": occurrence.start },
+        "end": { "line": occurrence.line, "character": occurrence.end },
+    })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// Reads a message framed by a `Content-Length` header. Returns `None` at
+/// the end of the input.
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0; length?];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Serves the language server over stdin and stdout until the client exits.
+pub fn serve() {
+    let mut reader = io::stdin().lock();
+    let mut writer = io::stdout().lock();
+    let mut server = Server::new();
+    while let Some(message) = read_message(&mut reader) {
+        let (replies, exit) = server.handle(&message);
+        for reply in &replies {
+            write_message(&mut writer, reply).expect("failed to write message");
+        }
+        if exit {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params })
+    }
+
+    #[test]
+    fn test_lsp_session() {
+        // Given
+        let mut server = Server::new();
+        let text = "@i\nM=1\n(LOOP)\n@i\nD=X\n@LOOP\n0;JMP\n";
+        let document = json!({ "uri": "file:///a.asm", "text": text });
+        let at = |line: u32, character: u32| {
+            json!({
+                "textDocument": { "uri": "file:///a.asm" },
+                "position": { "line": line, "character": character },
+                "newName": "n",
+            })
+        };
+
+        // When
+        let (opened, _) = server.handle(&notification(
+            "textDocument/didOpen",
+            json!({ "textDocument": document }),
+        ));
+        let (definition, _) = server.handle(&request("textDocument/definition", at(5, 1)));
+        let (hover, _) = server.handle(&request("textDocument/hover", at(3, 1)));
+        let (renamed, _) = server.handle(&request("textDocument/rename", at(0, 2)));
+
+        // Then
+        let diagnostics = &opened[0]["params"]["diagnostics"];
+        assert_eq!(1, diagnostics.as_array().unwrap().len());
+        assert_eq!(4, diagnostics[0]["range"]["start"]["line"]);
+        assert_eq!(
+            json!({ "line": 2, "character": 1 }),
+            definition[0]["result"]["range"]["start"]
+        );
+        assert_eq!(
+            "variable `i`: RAM[16]\n\n`0000000000010000`",
+            hover[0]["result"]["contents"]["value"]
+        );
+        let edits = renamed[0]["result"]["changes"]["file:///a.asm"]
+            .as_array()
+            .unwrap();
+        assert_eq!(2, edits.len());
+        assert_eq!(3, edits[1]["range"]["start"]["line"]);
+    }
+}
@@ -1,25 +1,1665 @@
-pub mod assembler;
-pub mod code;
-pub mod parser;
-pub mod symbol_table;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    num::NonZeroUsize,
+    ops::Range,
+    path::{Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant},
+};
 
-use std::path::PathBuf;
+use clap::{CommandFactory, Parser, Subcommand};
+#[cfg(feature = "jack")]
+use hack_assembler::jack::{self, JackStage};
+use hack_assembler::{
+    assembler::Assembler,
+    batch,
+    capture::{self, GifRecorder},
+    config::ProjectConfig,
+    coverage,
+    debugger::{self, Debugger},
+    diagnostic::{self, ColorChoice, Diagnostic, ErrorFormat, Severity},
+    dialect::Dialect,
+    diff::{self, SemanticDiff},
+    difftest::{self, Trace},
+    disassembler,
+    dump::{self, DumpFormat},
+    emulator::{self, Emulator, Stop},
+    error::{self, AssemblerError},
+    format, gdb,
+    generate::{self, GeneratorConfig, InstructionMix},
+    golden,
+    grade::{self, GradeReport, GradeSpec},
+    heatmap,
+    keyboard::{Keyboard, RawMode, KBD},
+    limits::{Limits, LAST_VARIABLE_ADDRESS},
+    linker::{self, Layout},
+    lint::{LintKind, LintLevel, LintLevels, LintReport},
+    live, lsp,
+    memmap::MemoryMap,
+    obfuscate,
+    object::{self, Library, Object},
+    optimize::{DeadCodeReport, OptLevel},
+    pass::EmitFormat,
+    patch::{self, PatchTarget},
+    profile,
+    program::Program,
+    project::{self, Manifest},
+    screen::{self, Charset},
+    script, server,
+    snapshot::Snapshot,
+    stack::StackReport,
+    stats::StatsReport,
+    stream,
+    symbol_table::SymbolTable,
+    tst,
+    tutor::{self, Tutor},
+    vm,
+    watch::{IncrementalCache, WATCH_INTERVAL},
+};
+use rayon::prelude::*;
+use tracing::Level;
 
-use assembler::Assembler;
-use clap::Parser;
+/// The exit codes of the assembly of the inputs, so that scripts and graders
+/// can tell failures apart. When the inputs fail in several ways, the highest
+/// code is returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Exit {
+    Success = 0,
+    /// An instruction isn't valid.
+    Syntax = 1,
+    /// The instructions are valid, but the program can't be assembled, or a
+    /// lint is denied.
+    Semantic = 2,
+    /// An input couldn't be read, or an output written.
+    Io = 3,
+    /// The assembler failed on its own.
+    Internal = 4,
+}
+
+impl Exit {
+    /// Returns the exit code of the error, the highest of its errors for
+    /// several of them.
+    fn of(error: &AssemblerError) -> Self {
+        error
+            .errors()
+            .into_iter()
+            .map(|error| match error {
+                AssemblerError::Syntax { .. } => Exit::Syntax,
+                AssemblerError::Semantic { .. } => Exit::Semantic,
+                AssemblerError::Io { .. } => Exit::Io,
+                AssemblerError::Cancelled | AssemblerError::Multiple(_) => Exit::Internal,
+            })
+            .max()
+            .unwrap_or(Exit::Internal)
+    }
+
+    /// Exits the process with the code, unless it's a success.
+    fn exit_on_failure(self) {
+        if self != Exit::Success {
+            process::exit(self as i32);
+        }
+    }
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Exit::Success => "success",
+            Exit::Syntax => "syntax errors",
+            Exit::Semantic => "semantic errors",
+            Exit::Io => "IO errors",
+            Exit::Internal => "internal errors",
+        };
+        write!(f, "{}", description)
+    }
+}
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[command(after_help = "\
+Exit codes when assembling, translating, linking or testing the inputs:
+  0  success
+  1  syntax errors, or malformed input files such as machine states
+  2  semantic errors, failed comparisons, or denied lints
+  3  IO errors
+  4  internal errors
+Invalid arguments exit with 2 as well.")]
 struct Args {
-    /// Path to the input file
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Paths to the input files, or directories of `.asm` files, assembled
+    /// concurrently. Glob patterns, such as `projects/**/*.asm`, are expanded
+    /// the same on every platform. `-` is the standard input, assembled to
+    /// the standard output unless `--output` names a file
+    #[arg(short, long, num_args = 1.., default_value = STDIN)]
+    input: Vec<PathBuf>,
+
+    /// Path to the output file, or to the directory of the outputs when
+    /// there are several inputs, or it's a directory or ends with a
+    /// separator. The outputs are next to their inputs by default
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    compile: CompileArgs,
+
+    /// Search the input directories recursively, mirroring their structure
+    /// in the `--output` directory
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Keep running, compiling the inputs again whenever they change
+    #[arg(long)]
+    watch: bool,
+
+    /// When to color the diagnostics
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
+
+    /// Log what is done: the outputs written with `-v`, the passes run with
+    /// `-vv`
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Only report the errors, silencing the warnings and summaries
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// How to write the diagnostics to the standard error
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human, global = true)]
+    error_format: ErrorFormat,
+}
+
+/// The input of the default command standing for the standard input.
+const STDIN: &str = "-";
+
+/// Returns whether the input is the standard input.
+fn is_stdin(input: &Path) -> bool {
+    input == Path::new(STDIN)
+}
+
+/// Where the outputs of the default command are written.
+enum Output {
+    /// Next to each input, with the extension of the output format.
+    NextToInputs,
+    /// The output of the single input.
+    File(PathBuf),
+    /// Inside the directory, at the path of each input relative to the input
+    /// directory or the base of the pattern it was found in, or named after
+    /// it.
+    Directory {
+        directory: PathBuf,
+        roots: Vec<PathBuf>,
+    },
+}
+
+impl Output {
+    /// Returns where the outputs of the inputs are written, given `--output`.
+    fn new(output: Option<&Path>, inputs: &[PathBuf]) -> Self {
+        let Some(output) = output else {
+            return Output::NextToInputs;
+        };
+        let ends_with_separator = output
+            .as_os_str()
+            .to_string_lossy()
+            .ends_with(std::path::is_separator);
+        let roots: Vec<PathBuf> = inputs
+            .iter()
+            .filter_map(|input| match batch::is_pattern(input) {
+                true => Some(batch::pattern_base(input)),
+                false => input.is_dir().then(|| input.clone()),
+            })
+            .collect();
+        match inputs.len() > 1 || !roots.is_empty() || ends_with_separator || output.is_dir() {
+            true => Output::Directory {
+                directory: output.to_path_buf(),
+                roots,
+            },
+            false => Output::File(output.to_path_buf()),
+        }
+    }
+
+    /// Returns the path of the output of the input, with the extension, or
+    /// `None` to leave it next to the input. The output of the standard input
+    /// is the standard output, unless it's a file.
+    fn path(&self, input: &Path, extension: &str) -> Option<PathBuf> {
+        match self {
+            Output::NextToInputs => None,
+            Output::File(path) => Some(path.clone()),
+            Output::Directory { .. } if is_stdin(input) => None,
+            Output::Directory { directory, roots } => {
+                let name = Path::new(input.file_name().unwrap_or(input.as_os_str()));
+                let relative = roots
+                    .iter()
+                    .find_map(|root| input.strip_prefix(root).ok())
+                    .unwrap_or(name);
+                Some(directory.join(relative).with_extension(extension))
+            }
+        }
+    }
+
+    /// Creates the directory of the outputs, if it's missing.
+    fn create_directory(&self) -> Result<(), AssemblerError> {
+        match self {
+            Output::Directory { directory, .. } => create_dir_all(directory),
+            Output::NextToInputs | Output::File(_) => Ok(()),
+        }
+    }
+}
+
+/// How the inputs of the default command are compiled.
+#[derive(clap::Args, Debug, Clone)]
+struct CompileArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = EmitFormat::Hack)]
+    emit: EmitFormat,
+
+    /// Optimization level
+    #[arg(short = 'O', value_enum, default_value = "0")]
+    optimize: OptLevel,
+
+    /// Print the blocks removed by the dead-code elimination of `-O2`
+    #[arg(long)]
+    dead_code_report: bool,
+
+    /// Stream the inputs to `.hack` files in a single pass, in bounded memory
+    #[arg(long, conflicts_with_all = ["emit", "optimize", "dead_code_report"])]
+    stream: bool,
+
+    /// Accept the quirks of other assemblers in the inputs
+    #[arg(long, value_enum, default_value_t = Dialect::Canonical, conflicts_with = "stream")]
+    dialect: Dialect,
+
+    /// Append an `(END) @END 0;JMP` loop to the programs that can run past
+    /// their last instruction
+    #[arg(long, conflicts_with = "stream")]
+    halt_loop: bool,
+
+    /// Warn about every kind of lint: out of range accesses to the screen
+    /// and keyboard, unused labels, labels shadowing predefined symbols,
+    /// variables never read and variables misspelling a label
+    #[arg(long, conflicts_with = "stream")]
+    lint: bool,
+
+    /// Warn about a kind of lint
+    #[arg(short = 'W', long = "warn", value_enum, conflicts_with = "stream")]
+    warn: Vec<LintKind>,
+
+    /// Ignore a kind of lint, even with `--lint`. The labels shadowing
+    /// predefined symbols are warned about unless allowed
+    #[arg(short = 'A', long = "allow", value_enum, conflicts_with = "stream")]
+    allow: Vec<LintKind>,
+
+    /// Fail on a kind of lint, reporting it as an error
+    #[arg(short = 'D', long = "deny", value_enum, conflicts_with = "stream")]
+    deny: Vec<LintKind>,
+
+    /// Accept the comps with their operands swapped, such as `A+D`, as their
+    /// canonical spelling
+    #[arg(long, conflicts_with_all = ["stream", "spec_strict"])]
+    accept_commuted: bool,
+
+    /// Print the number of A- and C-instructions, labels and variables of
+    /// each program, and its size in the ROM
+    #[arg(long, conflicts_with = "stream")]
+    stats: bool,
+
+    /// Report the diagnostics of the inputs without writing any output
+    #[arg(long, conflicts_with_all = ["stream", "output"])]
+    check: bool,
+
+    /// Reject the inputs relying on any extension of the nand2tetris grammar
+    #[arg(long, conflicts_with_all = ["stream", "dialect"])]
+    spec_strict: bool,
+
+    /// Last RAM address a variable can take, the variables past it being
+    /// reported as errors
+    #[arg(long, value_name = "ADDRESS", default_value_t = LAST_VARIABLE_ADDRESS)]
+    max_variable_address: u32,
+
+    /// Stop reporting diagnostics after this many errors, across all the
+    /// inputs, counting the rest in a summary
+    #[arg(long, value_name = "N")]
+    max_errors: Option<NonZeroUsize>,
+}
+
+impl CompileArgs {
+    /// Returns the limits of the compilation, which only bound the variables.
+    fn limits(&self) -> Limits {
+        Limits {
+            max_variable_address: self.max_variable_address,
+            ..Limits::default()
+        }
+    }
+
+    /// Returns the level of each kind of lint: all of them are warned about
+    /// with `--lint`, the labels shadowing predefined symbols always, then
+    /// `--allow`, `--warn` and `--deny` apply in this order, so that denying a
+    /// kind wins.
+    fn lint_levels(&self) -> LintLevels {
+        let mut levels = LintLevels::new(match self.lint {
+            true => LintLevel::Warn,
+            false => LintLevel::Allow,
+        });
+        levels.set(LintKind::ShadowedSymbol, LintLevel::Warn);
+        for (kinds, level) in [
+            (&self.allow, LintLevel::Allow),
+            (&self.warn, LintLevel::Warn),
+            (&self.deny, LintLevel::Deny),
+        ] {
+            for kind in kinds {
+                levels.set(*kind, level);
+            }
+        }
+        levels
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Serve the assembler over HTTP
+    Serve {
+        /// Address to listen on
+        #[arg(short, long, default_value = "127.0.0.1:8080")]
+        addr: String,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Serve live assembly of a document over WebSocket
+    Live {
+        /// Address to listen on
+        #[arg(short, long, default_value = "127.0.0.1:8081")]
+        addr: String,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Serve a language server for Hack assembly over stdin and stdout
+    Lsp,
+    /// Disassemble a ROM image back to Hack assembly, printed to stdout
+    Disassemble {
+        /// Path to the ROM image, as `.hack` text or raw big-endian words
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Keep numeric addresses instead of reconstructing labels and variables
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Compare the machine code of two assembly files, instruction by
+    /// instruction, failing if they differ
+    Diff {
+        /// Path to the old assembly file
+        old: PathBuf,
+
+        /// Path to the new assembly file
+        new: PathBuf,
+    },
+    /// Rename the symbols of a program and shuffle its code, printing the
+    /// equivalent assembly to stdout
+    Obfuscate {
+        /// Path to the assembly file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Seed of the renaming and shuffling, random by default
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Format assembly files in place, using the `format` section of the
+    /// closest `hack.json`
+    Fmt {
+        /// Paths to the assembly files
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Only list the files that aren't formatted, failing if there are any
+        #[arg(long)]
+        check: bool,
+
+        /// Path to the project config, instead of the closest `hack.json`
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Generate a random valid Hack program, printed to stdout
+    Generate {
+        /// Number of instructions, before the final halting loop
+        #[arg(short = 'n', long, default_value_t = 100)]
+        instructions: usize,
+
+        /// Probability for an instruction to be labelled
+        #[arg(long, default_value_t = 0.1)]
+        label_density: f64,
+
+        /// Number of distinct variables
+        #[arg(long, default_value_t = 8)]
+        variables: usize,
+
+        /// Relative weights of loads, computations and jumps, as `a:c:jump`
+        #[arg(long, default_value = "4:4:1", value_parser = parse_mix)]
+        mix: InstructionMix,
+
+        /// Seed of the generator, random by default
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Assemble the bundled nand2tetris sample programs and compare the output
+    /// with their expected machine code
+    SelfTest,
+    /// Translate VM code to Hack machine code, or to assembly with `--asm`
+    Vm {
+        /// Path to a `.vm` file, or a directory of `.vm` files
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the output file, next to the input by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Write the Hack assembly instead of the machine code
+        #[arg(long)]
+        asm: bool,
+
+        /// Print the estimated stack depth of each function
+        #[arg(long)]
+        stack_report: bool,
+
+        /// Optimization level of the machine code
+        #[arg(short = 'O', value_enum, default_value = "0")]
+        optimize: OptLevel,
+    },
+    /// Compile Jack classes, stopping at the given stage
+    #[cfg(feature = "jack")]
+    Jack {
+        /// Path to a `.jack` file, or a directory of `.jack` and `.vm` files
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the program output, next to the input by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// The stage to stop at: per-class files are written next to each
+        /// `.jack` file, the program to a single file
+        #[arg(long, value_enum, default_value_t = JackStage::Hack)]
+        emit: JackStage,
+
+        /// Optimization level of the machine code
+        #[arg(short = 'O', value_enum, default_value = "0")]
+        optimize: OptLevel,
+    },
+    /// Assemble a file into a relocatable `.hobj` object
+    Object {
+        /// Path to the assembly file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the object, next to the input by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Archive `.hobj` objects into a `.hlib` library
+    Archive {
+        /// Paths to the objects
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Path to the library
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Link `.hobj` objects and `.hlib` libraries into a `.hack` program
+    Link {
+        /// Paths to the objects, laid out in order, and to the libraries
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Path to the program
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Path to a JSON layout placing sections and labels in the ROM
+        #[arg(long)]
+        layout: Option<PathBuf>,
+
+        /// Write the map of the sections and symbols of the program, or its
+        /// memory map as Markdown or JSON for a `.md` or `.json` path
+        #[arg(long)]
+        map: Option<PathBuf>,
+    },
+    /// Build the multi-file project described by a `hackproj.toml` manifest
+    Build {
+        /// Path to the manifest
+        #[arg(short, long, default_value = project::MANIFEST_FILE)]
+        manifest: PathBuf,
+    },
+    /// Replace instructions of a ROM image, at a label or address, with
+    /// assembled code
+    Patch {
+        /// Path to the ROM image, as `.hack` text or raw big-endian words
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the debug info of the ROM, the snapshot written by `--emit snapshot`
+        #[arg(short, long)]
+        debug: PathBuf,
+
+        /// The label or ROM address of the first replaced instruction
+        #[arg(long)]
+        at: String,
+
+        /// Path to the assembly replacing the instructions, which may use the
+        /// symbols of the debug info
+        #[arg(long)]
+        code: PathBuf,
+
+        /// Number of replaced instructions, the length of the code by default,
+        /// the ones left over becoming no-ops
+        #[arg(long)]
+        count: Option<usize>,
+
+        /// Path to the patched `.hack` program, the input by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Run a program in the emulator and print the registers on exit
+    Run(RunArgs),
+    /// Run a nand2tetris CPU emulator test script and compare its output
+    Test {
+        /// Path to the `.tst` script
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+    /// Replay a reference trace and report the first divergence
+    DiffTrace {
+        /// Path to the program, as assembly or a `.hack` ROM image
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the trace, as a table output by the nand2tetris CPU emulator
+        #[arg(short, long)]
+        trace: PathBuf,
+    },
+    /// Grade submissions against test cases, printing a JSON report
+    Grade {
+        /// Path to the JSON test cases
+        #[arg(short, long)]
+        spec: PathBuf,
+
+        /// Paths to the submissions, as assembly or `.hack` ROM images
+        #[arg(required = true)]
+        submissions: Vec<PathBuf>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Run a Rhai script driving the emulator
+    Script {
+        /// Path to the script
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+    /// Walk through a program one instruction per keypress, explaining its
+    /// encoding and its effect on the registers and the RAM
+    Tutor {
+        /// Path to the program, as assembly or a `.hack` ROM image
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+    /// Debug a program in the emulator with an interactive prompt
+    Debug {
+        /// Path to the program, as assembly or a `.hack` ROM image
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Sets a RAM cell before running, as `address=value` or `Rn=value`
+        #[arg(long = "set", value_parser = parse_assignment)]
+        assignments: Vec<(usize, u16)>,
+
+        /// Serve the debugger over the GDB remote serial protocol on this address
+        #[arg(long, value_name = "ADDR")]
+        gdb: Option<String>,
+
+        /// Resume from a saved machine state instead of loading the program
+        #[arg(long)]
+        load_state: Option<PathBuf>,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    /// Path to the program, as `.hack` or assembly
     #[arg(short, long)]
     input: PathBuf,
+
+    /// Maximum number of instructions to execute, on top of those of the
+    /// loaded state if any
+    #[arg(long, default_value_t = 1_000_000)]
+    cycles: u64,
+
+    /// Sets a RAM cell before running, as `address=value` or `Rn=value`
+    #[arg(long = "set", value_parser = parse_assignment)]
+    assignments: Vec<(usize, u16)>,
+
+    /// Draw the screen in the terminal while running
+    #[arg(long)]
+    screen: bool,
+
+    /// Screen refresh rate, in frames per second
+    #[arg(long, default_value_t = 30)]
+    fps: u32,
+
+    /// Number of instructions executed between two frames
+    #[arg(long, default_value_t = 50_000)]
+    frame_cycles: u64,
+
+    /// Characters used to draw the screen
+    #[arg(long, value_enum, default_value_t = Charset::Braille)]
+    charset: Charset,
+
+    /// Forward the host keyboard to the emulated keyboard, `Ctrl+C` stops the run
+    #[arg(long)]
+    keyboard: bool,
+
+    /// Write the screen at exit to a PNG image
+    #[arg(long)]
+    png: Option<PathBuf>,
+
+    /// Record the screen after each frame to an animated GIF
+    #[arg(long)]
+    gif: Option<PathBuf>,
+
+    /// Maximum number of frames recorded to the GIF
+    #[arg(long, default_value_t = 100)]
+    gif_frames: u32,
+
+    /// Write an execution profile, flat by label and annotated per instruction
+    #[arg(long)]
+    profile: Option<PathBuf>,
+
+    /// Write a coverage report of the source lines executed during the run
+    #[arg(long)]
+    coverage: Option<PathBuf>,
+
+    /// Write a heatmap of the ROM executions and RAM accesses, as HTML if the
+    /// path ends with `.html` or PNG otherwise
+    #[arg(long)]
+    heatmap: Option<PathBuf>,
+
+    /// Resume from a saved machine state instead of loading the program
+    #[arg(long)]
+    load_state: Option<PathBuf>,
+
+    /// Keep running, reloading the program into the emulator whenever it
+    /// changes and assembles
+    #[arg(long)]
+    watch: bool,
+
+    /// Keep the RAM when reloading the program in watch mode
+    #[arg(long, requires = "watch")]
+    keep_ram: bool,
+
+    /// Save the machine state at exit
+    #[arg(long)]
+    save_state: Option<PathBuf>,
+
+    /// Dump a range of the RAM at exit
+    #[arg(long)]
+    dump: Option<PathBuf>,
+
+    /// The RAM range to dump, as `start..end` with `Rn` or numeric addresses
+    #[arg(long, default_value = "0..16", value_parser = parse_range)]
+    dump_range: Range<usize>,
+
+    /// The format of the RAM dump
+    #[arg(long, value_enum, default_value_t = DumpFormat::Text)]
+    dump_format: DumpFormat,
+}
+
+/// Parses a RAM address such as `R0` or `256`.
+fn parse_address(address: &str) -> Result<usize, String> {
+    let address = address
+        .strip_prefix('R')
+        .unwrap_or(address)
+        .parse::<usize>()
+        .map_err(|err| format!("invalid address: {}", err))?;
+    if address >= emulator::RAM_SIZE {
+        return Err(format!("address {} is out of the RAM", address));
+    }
+    Ok(address)
+}
+
+/// Parses a RAM assignment such as `R0=3`, `256=-1` or `16384=0`.
+fn parse_assignment(assignment: &str) -> Result<(usize, u16), String> {
+    let (address, value) = assignment
+        .split_once('=')
+        .ok_or_else(|| String::from("expected address=value"))?;
+    let address = parse_address(address)?;
+    let value = value
+        .parse::<u16>()
+        .or_else(|_| value.parse::<i16>().map(|v| v as u16))
+        .map_err(|err| format!("invalid value: {}", err))?;
+    Ok((address, value))
+}
+
+/// Parses an instruction mix such as `4:4:1`.
+fn parse_mix(mix: &str) -> Result<InstructionMix, String> {
+    let weights: Vec<u32> = mix
+        .split(':')
+        .map(|weight| weight.parse::<u32>())
+        .collect::<Result<_, _>>()
+        .map_err(|err| format!("invalid weight: {}", err))?;
+    match weights[..] {
+        [0, 0, 0] => Err(String::from("the weights can't all be 0")),
+        [a, c, jump] => Ok(InstructionMix { a, c, jump }),
+        _ => Err(String::from("expected a:c:jump")),
+    }
+}
+
+/// Parses a RAM range such as `0..16` or `R0..R3`, the end being excluded.
+fn parse_range(range: &str) -> Result<Range<usize>, String> {
+    let (start, end) = range
+        .split_once("..")
+        .ok_or_else(|| String::from("expected start..end"))?;
+    let start = parse_address(start)?;
+    // The end can be one past the last address of the RAM.
+    let end = match end.parse::<usize>() {
+        Ok(end) if end == emulator::RAM_SIZE => end,
+        _ => parse_address(end)?,
+    };
+    if start > end {
+        return Err(format!("range {} is reversed", range));
+    }
+    Ok(start..end)
+}
+
+/// Resource limits for the untrusted submissions of the server modes.
+#[derive(clap::Args, Debug)]
+struct LimitArgs {
+    /// Maximum size of a submitted source, in bytes
+    #[arg(long, default_value_t = Limits::untrusted().max_source_bytes)]
+    max_source_bytes: usize,
+
+    /// Maximum number of instructions of a submitted program
+    #[arg(long, default_value_t = Limits::untrusted().max_instructions)]
+    max_instructions: usize,
+
+    /// Maximum number of symbols of a submitted program
+    #[arg(long, default_value_t = Limits::untrusted().max_symbols)]
+    max_symbols: usize,
+}
+
+impl From<LimitArgs> for Limits {
+    fn from(args: LimitArgs) -> Self {
+        Self {
+            max_source_bytes: args.max_source_bytes,
+            max_instructions: args.max_instructions,
+            max_symbols: args.max_symbols,
+            ..Limits::untrusted()
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
+    diagnostic::set_stderr_color(args.color);
+    diagnostic::set_stderr_format(args.error_format);
+    init_logging(&args);
+
+    match args.command {
+        Some(Command::Serve { addr, limits }) => exit_on_error(server::serve(&addr, limits.into())),
+        Some(Command::Live { addr, limits }) => exit_on_error(live::serve(&addr, limits.into())),
+        Some(Command::Lsp) => lsp::serve(),
+        Some(Command::Vm {
+            input,
+            output,
+            asm,
+            stack_report,
+            optimize,
+        }) => {
+            let files = exit_on_error(vm::read_path(&input));
+            if stack_report {
+                print!("{}", StackReport::new(&files));
+            }
+            let assembly = vm::translate(&files);
+            exit_on_error(write_program(&input, output, &assembly, asm, optimize));
+        }
+        #[cfg(feature = "jack")]
+        Some(Command::Jack {
+            input,
+            output,
+            emit,
+            optimize,
+        }) => exit_on_error(compile_jack(&input, output, emit, optimize)),
+        Some(Command::Object { input, output }) => {
+            let source = exit_on_error(error::read_to_string(&input));
+            let name = input.file_stem().unwrap_or_default().to_string_lossy();
+            let object = exit_on_error_in(Object::assemble(&name, &source), Some(&input), &source);
+            let output = output.unwrap_or_else(|| input.with_extension("hobj"));
+            exit_on_error(write_file(&output, object.to_json()));
+        }
+        Some(Command::Archive { inputs, output }) => {
+            let objects = inputs
+                .iter()
+                .map(|path| object::read_object(path))
+                .collect::<Result<Vec<Object>, _>>();
+            let library = Library::new(exit_on_error(objects));
+            exit_on_error(write_file(&output, library.to_json()));
+        }
+        Some(Command::Link {
+            inputs,
+            output,
+            layout,
+            map,
+        }) => exit_on_error(link(&inputs, &output, layout.as_deref(), map.as_deref())),
+        Some(Command::Build { manifest }) => {
+            let project = exit_on_error(Manifest::load(&manifest));
+            let root = manifest.parent().unwrap_or(Path::new(""));
+            let build = exit_on_error(project.build(root));
+            exit_on_error(write_file(&build.output, build.render(project.format)));
+        }
+        Some(Command::Patch {
+            input,
+            debug,
+            at,
+            code,
+            count,
+            output,
+        }) => {
+            let bytes = exit_on_error(error::read(&input));
+            let mut rom = exit_on_input_error(disassembler::read_rom(&bytes), &input);
+            let json = exit_on_error(error::read_to_string(&debug));
+            let debug =
+                exit_on_error(Snapshot::from_json(&json).map_err(|err| {
+                    AssemblerError::syntax(format!("{}: {}", debug.display(), err))
+                }));
+            let source = exit_on_error(error::read_to_string(&code));
+            let target = PatchTarget::parse(&at);
+            let replaced = exit_on_error_in(
+                patch::patch(&mut rom, &debug, &target, &source, count),
+                Some(&code),
+                &source,
+            );
+            let hack: String = rom.iter().map(|word| format!("{:016b}\n", word)).collect();
+            exit_on_error(write_file(&output.unwrap_or(input), hack));
+            eprintln!("patched ROM[{}..{}]", replaced.start, replaced.end);
+        }
+        Some(Command::Run(args)) => run(args),
+        Some(Command::Test { input }) => {
+            let report = exit_on_error(tst::run_test(&input));
+            if let Some(path) = &report.output_file {
+                exit_on_error(write_file(path, &report.output));
+            }
+            if report.compared {
+                println!("End of script - Comparison ended successfully");
+            } else {
+                println!("End of script");
+            }
+        }
+        Some(Command::DiffTrace { input, trace }) => {
+            let rom = exit_on_input_error(emulator::load_rom(&input), &input);
+            let source = exit_on_error(error::read_to_string(&trace));
+            let trace = exit_on_error_in(Trace::parse(&source), Some(&trace), &source);
+            match difftest::diff_trace(rom, &trace) {
+                Ok(cycles) => println!("traces match over {} cycles", cycles),
+                Err(divergence) => {
+                    println!("{}", divergence);
+                    process::exit(1);
+                }
+            }
+        }
+        Some(Command::Grade {
+            spec,
+            submissions,
+            limits,
+        }) => {
+            let json = exit_on_error(error::read_to_string(&spec));
+            let spec = exit_on_error(serde_json::from_str::<GradeSpec>(&json).map_err(|err| {
+                AssemblerError::syntax(format!("{}: invalid test cases: {}", spec.display(), err))
+            }));
+            let limits = limits.into();
+            let reports: Vec<GradeReport> = submissions
+                .par_iter()
+                .map(|submission| grade::grade(submission, &spec, limits))
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&reports).expect("failed to serialize reports")
+            );
+            if reports.iter().any(|report| !report.passed) {
+                process::exit(1);
+            }
+        }
+        Some(Command::Script { input }) => {
+            let script = exit_on_error(error::read_to_string(&input));
+            exit_on_error(script::run(&script));
+        }
+        Some(Command::Debug {
+            input,
+            assignments,
+            gdb,
+            load_state,
+        }) => {
+            let (mut emulator, symbol_table) =
+                exit_on_input_error(load(&input, load_state.as_deref()), &input);
+            for (address, value) in &assignments {
+                emulator.ram_mut()[*address] = *value;
+            }
+            let mut debugger = Debugger::new(emulator, symbol_table);
+            match gdb {
+                Some(addr) => exit_on_error(gdb::serve(&addr, debugger)),
+                None => exit_on_error(
+                    debugger::repl(&mut debugger, io::stdin().lock(), io::stdout())
+                        .map_err(|error| AssemblerError::io("failed to run the debugger", error)),
+                ),
+            }
+        }
+        Some(Command::Tutor { input }) => {
+            let mut tutor = exit_on_input_error(Tutor::load(&input), &input);
+            exit_on_error(
+                tutor::run(&mut tutor, io::stdin().lock(), io::stdout())
+                    .map_err(|error| AssemblerError::io("failed to run the tutor", error)),
+            );
+        }
+        Some(Command::Disassemble { input, raw }) => {
+            let bytes = exit_on_error(error::read(&input));
+            let rom = exit_on_input_error(disassembler::read_rom(&bytes), &input);
+            let mut program = disassembler::disassemble(&rom);
+            if !raw {
+                disassembler::reconstruct_symbols(&mut program);
+            }
+            print!("{}", program);
+        }
+        Some(Command::Generate {
+            instructions,
+            label_density,
+            variables,
+            mix,
+            seed,
+        }) => {
+            let config = GeneratorConfig {
+                instructions,
+                label_density,
+                variables,
+                mix,
+                seed: seed.unwrap_or_else(|| fastrand::u64(..)),
+            };
+            print!("{}", generate::generate(&config));
+        }
+        Some(Command::SelfTest) => {
+            let mismatches = exit_on_error(golden::self_test());
+            for case in golden::CORPUS {
+                match mismatches
+                    .iter()
+                    .find(|mismatch| mismatch.name == case.name)
+                {
+                    Some(mismatch) => println!("FAILED {}", mismatch),
+                    None => println!("ok {}", case.name),
+                }
+            }
+            if !mismatches.is_empty() {
+                process::exit(1);
+            }
+        }
+        Some(Command::Diff { old, new }) => {
+            let entries = |path: &Path| {
+                let source = exit_on_error(error::read_to_string(path));
+                exit_on_error_in(diff::entries(&source), Some(path), &source)
+            };
+            let diff = SemanticDiff::from_entries(entries(&old), entries(&new));
+            if !diff.is_empty() {
+                print!("{}", diff);
+                process::exit(1);
+            }
+        }
+        Some(Command::Obfuscate { input, seed }) => {
+            let source = exit_on_error(error::read_to_string(&input));
+            let program = exit_on_error_in(Program::parse(&source), Some(&input), &source);
+            let seed = seed.unwrap_or_else(|| fastrand::u64(..));
+            print!("{}", obfuscate::obfuscate(&program, seed));
+        }
+        Some(Command::Fmt {
+            inputs,
+            check,
+            config,
+        }) => {
+            let mut unformatted = false;
+            for input in inputs {
+                let config = exit_on_error(match &config {
+                    Some(path) => ProjectConfig::load(path),
+                    None => ProjectConfig::discover(&input),
+                });
+                let source = exit_on_error(error::read_to_string(&input));
+                let formatted = format::format(&source, &config.format);
+                if formatted == source {
+                    continue;
+                }
+                if check {
+                    println!("{}", input.display());
+                } else {
+                    exit_on_error(write_file(&input, formatted));
+                }
+                unformatted = true;
+            }
+            if check && unformatted {
+                process::exit(1);
+            }
+        }
+        None if args.watch => {
+            let output = output(&args);
+            let mut cache = IncrementalCache::new();
+            loop {
+                // The inputs are listed again at each change, their errors
+                // reported without stopping the watch.
+                let changed = match inputs(&args) {
+                    Ok(inputs) => cache.changed(&inputs),
+                    Err(error) => {
+                        for diagnostic in error.to_diagnostics(None, "") {
+                            report("", &diagnostic);
+                        }
+                        Vec::new()
+                    }
+                };
+                if !changed.is_empty() {
+                    let start = Instant::now();
+                    let exit = compile_inputs(&changed, &args.compile, &output, true);
+                    let summary = format!(
+                        "compiled {} {} in {} ms",
+                        changed.len(),
+                        if changed.len() == 1 { "file" } else { "files" },
+                        start.elapsed().as_millis()
+                    );
+                    match exit {
+                        _ if args.quiet => {}
+                        Exit::Success => eprintln!("{}", summary),
+                        failure => eprintln!("{}, with {}", summary, failure),
+                    }
+                }
+                thread::sleep(WATCH_INTERVAL);
+            }
+        }
+        None => {
+            let output = output(&args);
+            let inputs = exit_on_error(inputs(&args));
+            let prefixed = inputs.len() > 1;
+            compile_inputs(&inputs, &args.compile, &output, prefixed).exit_on_failure();
+        }
+    }
+}
+
+/// Creates the directory and its missing parents.
+fn create_dir_all(directory: &Path) -> Result<(), AssemblerError> {
+    std::fs::create_dir_all(directory).map_err(|error| {
+        AssemblerError::io(format!("failed to create {}", directory.display()), error)
+    })
+}
+
+/// Logs to the standard error at the level set by `--verbose` and `--quiet`,
+/// warnings by default.
+fn init_logging(args: &Args) {
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::WARN,
+        (false, 1) => Level::INFO,
+        (false, 2) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_ansi(diagnostic::stderr_color())
+        .without_time()
+        .init();
+}
+
+/// Returns the inputs of the default command, searching the directories
+/// recursively if asked.
+fn inputs(args: &Args) -> Result<Vec<PathBuf>, AssemblerError> {
+    match args.recursive {
+        true => batch::inputs_recursive(&args.input),
+        false => batch::inputs(&args.input),
+    }
+}
+
+/// Returns where the outputs of the default command are written, creating
+/// their directory if needed. Exits the process if it can't be created, or
+/// the standard input is watched or streamed to the standard output.
+fn output(args: &Args) -> Output {
+    let output = Output::new(args.output.as_deref(), &args.input);
+    if args.input.iter().any(|input| is_stdin(input)) {
+        let conflict = |message| {
+            Args::command()
+                .error(clap::error::ErrorKind::ArgumentConflict, message)
+                .exit()
+        };
+        if args.watch {
+            conflict("the standard input can't be watched");
+        }
+        if args.compile.stream && !matches!(output, Output::File(_)) {
+            conflict("--stream can't write to the standard output, it seeks back into its output: name a file with --output");
+        }
+    }
+    exit_on_error(output.create_directory());
+    output
+}
+
+/// Compiles the inputs concurrently to their outputs, streaming them if
+/// asked, then reports their diagnostics, lints and dead-code blocks in
+/// order, prefixed with their paths if asked. Returns how the inputs failed,
+/// if any did.
+fn compile_inputs(inputs: &[PathBuf], args: &CompileArgs, output: &Output, prefixed: bool) -> Exit {
+    let levels = args.lint_levels();
+    // The standard input can only be read once, and is kept for the snippets
+    // of its diagnostics.
+    let stdin = match inputs.iter().any(|input| is_stdin(input)) {
+        true => match io::read_to_string(io::stdin()) {
+            Ok(stdin) => stdin,
+            Err(error) => {
+                let error = AssemblerError::io("failed to read the standard input", error);
+                for diagnostic in error.to_diagnostics(None, "") {
+                    report("", &diagnostic);
+                }
+                return Exit::Io;
+            }
+        },
+        false => String::new(),
+    };
+    let outcomes = batch::map(inputs, |input| {
+        let report = DeadCodeReport::new();
+        let lints = LintReport::new();
+        let stats = StatsReport::new();
+        let path = output.path(input, args.emit.extension());
+        let to_stdout = is_stdin(input) && path.is_none();
+        // The outputs of the nested inputs are in nested directories.
+        if let Some(parent) = path.as_deref().and_then(Path::parent) {
+            if !parent.as_os_str().is_empty() {
+                create_dir_all(parent)?;
+            }
+        }
+        if args.stream {
+            let output = output
+                .path(input, "hack")
+                .unwrap_or_else(|| input.with_extension("hack"));
+            match is_stdin(input) {
+                true => stream::compile_from(stdin.as_bytes(), &output, args.limits())?,
+                false => stream::compile(input, &output, args.limits())?,
+            };
+            return Ok((report.blocks(), lints.lints(), None));
+        }
+        let assembler = match is_stdin(input) {
+            true => Assembler::from_source(&stdin),
+            false => Assembler::open(input.to_path_buf())?,
+        };
+        let mut assembler = assembler.dialect(args.dialect).with_limits(args.limits());
+        if args.spec_strict {
+            assembler = assembler.spec_strict();
+        }
+        if args.halt_loop {
+            assembler = assembler.halt_loop();
+        }
+        if args.accept_commuted {
+            assembler = assembler.accept_commuted();
+        }
+        if levels.any_reported() {
+            assembler = assembler.with_lint_report(lints.clone());
+        }
+        if args.stats {
+            assembler = assembler.with_stats_report(stats.clone());
+        }
+        if let Some(path) = path {
+            assembler = assembler.with_output(path);
+        }
+        let assembler = assembler
+            .emit(args.emit)
+            .with_dead_code_report(report.clone())
+            .optimize(args.optimize);
+        let assembler = assembler.fill_symbol_table();
+        match to_stdout {
+            _ if args.check => {
+                assembler.try_assemble()?;
+            }
+            true => assembler.compile_to(io::stdout())?,
+            false => assembler.compile()?,
+        }
+        Ok::<_, AssemblerError>((report.blocks(), lints.lints(), stats.stats()))
+    });
+
+    let mut reporter = Reporter::new(args.max_errors);
+    let mut exit = Exit::Success;
+    for outcome in outcomes {
+        let prefix = match prefixed {
+            true => format!("{}: ", outcome.input.display()),
+            false => String::new(),
+        };
+        // The snippets are left empty if the source can't be read again.
+        let source = || match is_stdin(&outcome.input) {
+            true => stdin.clone(),
+            false => std::fs::read_to_string(&outcome.input).unwrap_or_default(),
+        };
+        let (blocks, lints, stats) = match outcome.result {
+            Ok(result) => result,
+            Err(error) => {
+                for diagnostic in error.to_diagnostics(Some(&outcome.input), &source()) {
+                    reporter.report(&prefix, &diagnostic);
+                }
+                exit = exit.max(Exit::of(&error));
+                continue;
+            }
+        };
+        let source = match lints.is_empty() {
+            true => String::new(),
+            false => source(),
+        };
+        for lint in &lints {
+            if let Some(diagnostic) = levels.diagnostic(lint, Some(&outcome.input), &source) {
+                if diagnostic.severity == Severity::Error {
+                    exit = exit.max(Exit::Semantic);
+                }
+                reporter.report(&prefix, &diagnostic);
+            }
+        }
+        if args.dead_code_report {
+            for block in blocks {
+                eprintln!("{}{}", prefix, block);
+            }
+        }
+        if let Some(stats) = stats {
+            eprintln!("{}{}", prefix, stats);
+        }
+    }
+    reporter.finish();
+    exit
+}
+
+/// Writes diagnostics to the standard error, in the format set by
+/// `--error-format`, until the maximum number of errors is reached. The
+/// diagnostics past it are only counted.
+struct Reporter {
+    max_errors: Option<NonZeroUsize>,
+    errors: usize,
+    suppressed: usize,
+}
+
+impl Reporter {
+    fn new(max_errors: Option<NonZeroUsize>) -> Self {
+        Self {
+            max_errors,
+            errors: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Writes the diagnostic, unless the maximum number of errors is reached.
+    /// The warnings are silenced with the logs by `--quiet`.
+    fn report(&mut self, prefix: &str, diagnostic: &Diagnostic) {
+        if diagnostic.severity == Severity::Warning && !tracing::enabled!(Level::WARN) {
+            return;
+        }
+        if self
+            .max_errors
+            .is_some_and(|max_errors| self.errors >= max_errors.get())
+        {
+            self.suppressed += 1;
+            return;
+        }
+        if diagnostic.severity == Severity::Error {
+            self.errors += 1;
+        }
+        report(prefix, diagnostic);
+    }
+
+    /// Writes a summary of the suppressed diagnostics, if any.
+    fn finish(self) {
+        if self.suppressed == 0 || !tracing::enabled!(Level::WARN) {
+            return;
+        }
+        let summary = Diagnostic::warning(format!(
+            "aborted after {} errors, {} suppressed",
+            self.errors, self.suppressed
+        ));
+        report("", &summary);
+    }
+}
+
+/// Writes a diagnostic to the standard error, in the format set by
+/// `--error-format`. The prefix only precedes the diagnostics rendered for a
+/// reader, the JSON ones having the path in their span.
+fn report(prefix: &str, diagnostic: &Diagnostic) {
+    match diagnostic::stderr_format() {
+        ErrorFormat::Human => eprintln!(
+            "{}{}",
+            prefix,
+            diagnostic.render(diagnostic::stderr_color())
+        ),
+        ErrorFormat::Json => eprintln!("{}", diagnostic.to_json()),
+    }
+}
+
+/// Reports the error of a subcommand and exits with its code, or returns the
+/// value.
+fn exit_on_error<T>(result: Result<T, AssemblerError>) -> T {
+    exit_on_error_in(result, None, "")
+}
+
+/// Reports the error of a subcommand on its input, pointing into the source
+/// of the input, and exits with its code, or returns the value.
+fn exit_on_input_error<T>(result: Result<T, AssemblerError>, input: &Path) -> T {
+    result.unwrap_or_else(|error| {
+        let source = std::fs::read_to_string(input).unwrap_or_default();
+        exit_on_error_in(Err(error), Some(input), &source)
+    })
+}
+
+/// Reports the error of a subcommand, pointing into the source of the file it
+/// was raised on, and exits with its code, or returns the value.
+fn exit_on_error_in<T>(result: Result<T, AssemblerError>, file: Option<&Path>, source: &str) -> T {
+    result.unwrap_or_else(|error| {
+        for diagnostic in error.to_diagnostics(file, source) {
+            report("", &diagnostic);
+        }
+        process::exit(Exit::of(&error) as i32)
+    })
+}
+
+/// Writes the contents of an output file.
+fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), AssemblerError> {
+    std::fs::write(path, contents)
+        .map_err(|error| AssemblerError::io(format!("failed to write {}", path.display()), error))
+}
+
+/// Writes a program translated to assembly, or assembled to machine code.
+/// The output is next to the input by default, inside it for a directory.
+/// Returns an error if the program doesn't assemble or the output can't be
+/// written.
+fn write_program(
+    input: &Path,
+    output: Option<PathBuf>,
+    assembly: &str,
+    asm: bool,
+    level: OptLevel,
+) -> Result<(), AssemblerError> {
+    let extension = if asm { "asm" } else { "hack" };
+    let output = output.unwrap_or_else(|| match input.file_name() {
+        Some(name) if input.is_dir() => input.join(name).with_extension(extension),
+        _ => input.with_extension(extension),
+    });
+    let contents = if asm {
+        assembly.to_string()
+    } else {
+        let words = Assembler::from_source(assembly)
+            .optimize(level)
+            .fill_symbol_table()
+            .try_assemble()?;
+        words
+            .iter()
+            .map(|word| format!("{:016b}\n", word))
+            .collect()
+    };
+    write_file(&output, contents)
+}
+
+/// Compiles Jack classes up to the stage. Returns an error if a class is
+/// invalid or an output can't be written.
+#[cfg(feature = "jack")]
+fn compile_jack(
+    input: &Path,
+    output: Option<PathBuf>,
+    stage: JackStage,
+    level: OptLevel,
+) -> Result<(), AssemblerError> {
+    match stage {
+        JackStage::Asm | JackStage::Hack => {
+            let assembly = vm::translate(&jack::compile_program(input)?);
+            write_program(input, output, &assembly, stage == JackStage::Asm, level)
+        }
+        JackStage::Tokens | JackStage::Tree | JackStage::Vm => {
+            for path in jack::sources(input)? {
+                let source = std::fs::read_to_string(&path).map_err(|error| {
+                    AssemblerError::io(format!("failed to read {}", path.display()), error)
+                })?;
+                let file = path.display().to_string();
+                let tokens = jack::tokenizer::tokenize(&file, &source)?;
+                let (output, contents) = match stage {
+                    JackStage::Tokens => {
+                        let stem = path.file_stem().expect("missing file name");
+                        let name = format!("{}T.xml", stem.to_string_lossy());
+                        (
+                            path.with_file_name(name),
+                            jack::tokenizer::tokens_xml(&tokens),
+                        )
+                    }
+                    JackStage::Tree => {
+                        let (_, xml) = jack::parser::parse_with_xml(&file, &tokens)?;
+                        (path.with_extension("xml"), xml)
+                    }
+                    _ => {
+                        let class = jack::parser::parse(&file, &tokens)?;
+                        let vm: String = jack::codegen::compile(&class)?
+                            .iter()
+                            .map(|command| format!("{}\n", command))
+                            .collect();
+                        (path.with_extension("vm"), vm)
+                    }
+                };
+                write_file(&output, contents)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Links the `.hobj` objects and `.hlib` libraries into a program, writing
+/// its memory map if asked. Returns an error if an input can't be read, the
+/// program fails to link, or an output can't be written.
+fn link(
+    inputs: &[PathBuf],
+    output: &Path,
+    layout: Option<&Path>,
+    map: Option<&Path>,
+) -> Result<(), AssemblerError> {
+    let mut objects = Vec::new();
+    let mut libraries = Vec::new();
+    for path in inputs {
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "hlib")
+        {
+            libraries.push(object::read_library(path)?);
+        } else {
+            objects.push(object::read_object(path)?);
+        }
+    }
+    let layout = match layout {
+        Some(path) => Layout::load(path)?,
+        None => Layout::default(),
+    };
+    let executable = linker::link_with_layout(objects, &libraries, &layout)?;
+    let hack: String = executable
+        .words
+        .iter()
+        .map(|word| format!("{:016b}\n", word))
+        .collect();
+    write_file(output, hack)?;
+    if let Some(map) = map {
+        let memory_map = MemoryMap::from_executable(&executable);
+        let contents = match map.extension().and_then(|e| e.to_str()) {
+            Some("md") => memory_map.to_markdown(),
+            Some("json") => memory_map.to_json(),
+            _ => executable.map(),
+        };
+        write_file(map, contents)?;
+    }
+    Ok(())
+}
+
+/// Loads the program to run or debug, restoring the machine state instead if
+/// asked. Returns an error if a file can't be read or the program doesn't
+/// assemble.
+fn load(input: &Path, state: Option<&Path>) -> Result<(Emulator, SymbolTable), AssemblerError> {
+    let (rom, symbol_table) = emulator::load_program(input)?;
+    let emulator = match state {
+        Some(path) => emulator::load_state(path)?,
+        None => Emulator::new(rom),
+    };
+    Ok((emulator, symbol_table))
+}
+
+/// Creates an output file.
+fn create_file(path: &Path) -> Result<BufWriter<File>, AssemblerError> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|error| AssemblerError::io(format!("failed to create {}", path.display()), error))
+}
+
+fn run(args: RunArgs) {
+    let (emulator, stop) = exit_on_input_error(run_program(&args), &args.input);
+
+    let stop = match stop {
+        Stop::Halted => "halted",
+        Stop::EndOfRom => "reached the end of the ROM",
+        Stop::CycleLimit => "reached the cycle limit",
+        Stop::Interrupted => "interrupted",
+        Stop::OutOfRam => "stopped at an access to M past the end of the RAM",
+    };
+    println!("{} after {} cycles", stop, emulator.cycles());
+    println!(
+        "A: {}  D: {}  PC: {}",
+        emulator.a(),
+        emulator.d(),
+        emulator.pc()
+    );
+    for (address, value) in emulator.ram()[..16].iter().enumerate() {
+        println!("R{}: {}", address, *value as i16);
+    }
+}
+
+/// Runs the program, writing the outputs asked for. Returns an error if the
+/// program can't be loaded or an output can't be written.
+fn run_program(args: &RunArgs) -> Result<(Emulator, Stop), AssemblerError> {
+    let (mut emulator, symbol_table) = load(&args.input, args.load_state.as_deref())?;
+    for (address, value) in &args.assignments {
+        emulator.ram_mut()[*address] = *value;
+    }
+    let stop = if args.screen || args.keyboard || args.gif.is_some() || args.watch {
+        run_frames(&mut emulator, args)?
+    } else {
+        emulator.run(args.cycles)
+    };
+    if let Some(path) = &args.png {
+        capture::write_png(emulator.ram(), create_file(path)?);
+    }
+    if let Some(path) = &args.profile {
+        let program = profile::listing(&args.input, emulator.rom());
+        let report = format!(
+            "{}\n{}",
+            profile::flat_report(&program, emulator.counts()),
+            profile::annotated_listing(&program, emulator.counts())
+        );
+        write_file(path, report)?;
+    }
+    if let Some(path) = &args.coverage {
+        let report = coverage::report(&args.input, emulator.rom(), emulator.counts());
+        write_file(path, report)?;
+    }
+    if let Some(path) = &args.heatmap {
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "html")
+        {
+            write_file(path, heatmap::html(emulator.counts(), emulator.accesses()))?;
+        } else {
+            heatmap::write_png(emulator.counts(), emulator.accesses(), create_file(path)?);
+        }
+    }
+    if let Some(path) = &args.save_state {
+        write_file(path, emulator.save_state())?;
+    }
+    if let Some(path) = &args.dump {
+        let range = args.dump_range.clone();
+        write_file(
+            path,
+            dump::dump(emulator.ram(), range, args.dump_format, &symbol_table),
+        )?;
+    }
+    Ok((emulator, stop))
+}
+
+/// Runs the emulator frame by frame, redrawing the screen and recording it
+/// after each frame when requested. In watch mode, the program is reloaded
+/// before the frame whenever it changed, and the run only ends when
+/// interrupted. Returns an error if the GIF file can't be created.
+fn run_frames(emulator: &mut Emulator, args: &RunArgs) -> Result<Stop, AssemblerError> {
+    let frame = Duration::from_secs_f64(1.0 / args.fps.max(1) as f64);
+    let mut recorder = match &args.gif {
+        Some(path) => Some(GifRecorder::new(create_file(path)?, args.fps)),
+        None => None,
+    };
+    let mut recorded = 0;
+    // The cycles executed since the program was loaded or reloaded.
+    let mut ran = 0;
+    let mut keyboard = Keyboard::default();
+    let _raw_mode = args.keyboard.then(RawMode::enable);
+    let input = std::slice::from_ref(&args.input);
+    let mut cache = IncrementalCache::new();
+    if args.watch {
+        cache.changed(input);
+    }
+
+    if args.screen {
+        // Clear the terminal once, then only move the cursor back home on each frame.
+        print!("\x1b[2J");
+    }
+    loop {
+        let started = Instant::now();
+        if args.keyboard {
+            if !keyboard.poll() {
+                return Ok(Stop::Interrupted);
+            }
+            emulator.ram_mut()[KBD] = keyboard.code();
+        }
+        if args.watch && !cache.changed(input).is_empty() {
+            // A program that doesn't assemble leaves the previous one running.
+            match emulator::load_rom(&args.input) {
+                Ok(rom) => {
+                    emulator.reload(rom, args.keep_ram);
+                    ran = 0;
+                }
+                Err(error) => {
+                    let source = std::fs::read_to_string(&args.input).unwrap_or_default();
+                    for diagnostic in error.to_diagnostics(Some(&args.input), &source) {
+                        report("", &diagnostic);
+                    }
+                }
+            }
+        }
+        let before = emulator.cycles();
+        let stop = emulator.run(args.frame_cycles.min(args.cycles - ran));
+        ran += emulator.cycles() - before;
+
+        if let Some(recorder) = recorder.as_mut().filter(|_| recorded < args.gif_frames) {
+            recorder.add_frame(emulator.ram());
+            recorded += 1;
+        }
+        if args.screen {
+            let mut output = screen::render(emulator.ram(), args.charset);
+            if args.keyboard {
+                // The raw mode doesn't move back to the first column on new lines.
+                output = output.replace('\n', "\r\n");
+            }
+            print!("\x1b[H{}", output);
+            let _ = io::stdout().flush();
+        }
 
-    let assembler = Assembler::new(args.input);
-    let assembler = assembler.fill_symbol_table();
-    assembler.compile();
+        if !args.watch && (stop != Stop::CycleLimit || ran >= args.cycles) {
+            return Ok(stop);
+        }
+        if args.screen || args.keyboard || args.watch {
+            thread::sleep(frame.saturating_sub(started.elapsed()));
+        }
+    }
 }
@@ -0,0 +1,212 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::{emulator::ROM_SIZE, ir::Ir, linker::Executable, symbol_table::SymbolTable};
+
+/// The first RAM address of the variables, right after R0–R15.
+const FIRST_VARIABLE: u32 = 16;
+/// The first RAM address of the screen, right after the variables.
+const SCREEN: u32 = 16384;
+
+/// A range of ROM or RAM addresses.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    pub start: u32,
+    pub size: u32,
+}
+
+/// A variable and its RAM address.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    pub address: u32,
+}
+
+/// The final memory map of a program: where its code and variables are,
+/// which RAM regions are reserved, and how much space is left.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct MemoryMap {
+    /// The code regions, in ROM order.
+    pub code: Vec<Region>,
+    /// The variables, by address.
+    pub variables: Vec<Variable>,
+    /// The RAM regions that aren't available to the variables.
+    pub reserved: Vec<Region>,
+    /// The number of unused ROM words.
+    pub rom_free: u32,
+    /// The number of RAM words left for the variables.
+    pub ram_free: u32,
+}
+
+impl MemoryMap {
+    /// Returns the memory map of an assembled program, its code split in
+    /// functions: a region starts at each label, except the labels with a
+    /// `$`, which are local to the function of the VM translator.
+    pub fn from_ir(ir: &Ir, symbol_table: &SymbolTable) -> Self {
+        let mut code: Vec<Region> = Vec::new();
+        for node in &ir.nodes {
+            let function = node.labels.iter().find(|label| !label.contains('$'));
+            match code.last_mut() {
+                Some(region) if function.is_none() => region.size += 1,
+                _ => code.push(Region {
+                    name: function.map_or_else(|| String::from("<entry>"), String::clone),
+                    start: node.address,
+                    size: 1,
+                }),
+            }
+        }
+        let variables = symbol_table
+            .iter()
+            .filter(|(symbol, address)| {
+                (FIRST_VARIABLE..SCREEN).contains(address) && !symbol_table.is_label(symbol)
+            })
+            .map(|(name, address)| Variable {
+                name: name.to_string(),
+                address,
+            })
+            .collect();
+        Self::new(code, ir.nodes.len() as u32, variables)
+    }
+
+    /// Returns the memory map of a linked program, its code split in
+    /// sections, one per object.
+    pub fn from_executable(executable: &Executable) -> Self {
+        let code = executable
+            .sections
+            .iter()
+            .map(|section| Region {
+                name: section.name.clone(),
+                start: section.start as u32,
+                size: section.size as u32,
+            })
+            .collect();
+        let variables = executable
+            .variables
+            .iter()
+            .map(|(name, address)| Variable {
+                name: name.clone(),
+                address: *address,
+            })
+            .collect();
+        Self::new(code, executable.words.len() as u32, variables)
+    }
+
+    fn new(code: Vec<Region>, rom_used: u32, mut variables: Vec<Variable>) -> Self {
+        variables.sort_by(|a, b| a.address.cmp(&b.address).then(a.name.cmp(&b.name)));
+        let reserved = [
+            ("R0-R15", 0, 16),
+            ("SCREEN", SCREEN, 8192),
+            ("KBD", 24576, 1),
+        ]
+        .into_iter()
+        .map(|(name, start, size)| Region {
+            name: name.to_string(),
+            start,
+            size,
+        })
+        .collect();
+        let ram_used = variables.iter().map(|variable| variable.address + 1).max();
+        Self {
+            code,
+            variables,
+            reserved,
+            rom_free: (ROM_SIZE as u32).saturating_sub(rom_used),
+            ram_free: SCREEN - ram_used.unwrap_or(FIRST_VARIABLE),
+        }
+    }
+
+    /// Renders the map as Markdown tables, for documentation.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from("# Memory map\n\n## ROM\n\n");
+        regions_table(&mut markdown, &self.code);
+        writeln!(
+            markdown,
+            "\n{} of {} words free.\n\n## RAM\n\n| Variable | Address |\n|---|---:|",
+            self.rom_free, ROM_SIZE
+        )
+        .expect("write to string");
+        for variable in &self.variables {
+            writeln!(markdown, "| {} | {} |", variable.name, variable.address)
+                .expect("write to string");
+        }
+        markdown.push_str("\n### Reserved\n\n");
+        regions_table(&mut markdown, &self.reserved);
+        writeln!(
+            markdown,
+            "\n{} of {} variable words free.",
+            self.ram_free,
+            SCREEN - FIRST_VARIABLE
+        )
+        .expect("write to string");
+        markdown
+    }
+
+    /// Serializes the map to JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("failed to serialize memory map")
+    }
+}
+
+/// Writes the regions as a Markdown table.
+fn regions_table(markdown: &mut String, regions: &[Region]) {
+    markdown.push_str("| Region | Start | End | Size |\n|---|---:|---:|---:|\n");
+    for region in regions {
+        writeln!(
+            markdown,
+            "| {} | {} | {} | {} |",
+            region.name,
+            region.start,
+            region.start + region.size,
+            region.size
+        )
+        .expect("write to string");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+
+    #[test]
+    fn test_map_of_a_program() {
+        // Given
+        let program = Program::from_source(
+            "@x\nM=0\n(Main.main)\n@y\nM=1\n(Main.main$LOOP)\n@Main.main$LOOP\n0;JMP\n",
+        );
+        let symbol_table = program.resolve();
+        let ir = Ir::lower(&program, &symbol_table);
+
+        // When
+        let map = MemoryMap::from_ir(&ir, &symbol_table);
+
+        // Then
+        assert_eq!(
+            vec![
+                Region {
+                    name: String::from("<entry>"),
+                    start: 0,
+                    size: 2
+                },
+                Region {
+                    name: String::from("Main.main"),
+                    start: 2,
+                    size: 4
+                },
+            ],
+            map.code
+        );
+        assert_eq!(
+            vec!["x@16", "y@17"],
+            map.variables
+                .iter()
+                .map(|variable| format!("{}@{}", variable.name, variable.address))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(32762, map.rom_free);
+        assert_eq!(16366, map.ram_free);
+        assert!(map.to_markdown().contains("| Main.main | 2 | 6 | 4 |\n"));
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    program::{Instruction, Program},
+    symbol_table::SymbolTable,
+};
+
+/// A run of instructions starting at its labels, up to the next labels.
+type Chunk = Vec<Instruction>;
+
+/// Obfuscates the program while preserving its behavior:
+/// - labels and variables are renamed to opaque identifiers,
+/// - the code is split into blocks entered only at their labels, which are
+///   shuffled, keeping the entry block first and the block running off the
+///   end of the ROM last. A block falling through to the next one gets a
+///   jump to it, when the next one overwrites A first.
+///
+/// Variables are still allocated in the same order, as their first uses are
+/// loaded at the start of the program, before A is reset to 0.
+///
+/// # Panic
+///
+/// Panics if a jump goes to a fixed ROM address, which moving the code breaks.
+pub fn obfuscate(program: &Program, seed: u64) -> Program {
+    let mut rng = fastrand::Rng::with_seed(seed);
+    let symbol_table = program.resolve();
+    let instructions = program.instructions();
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        if !matches!(instruction, Instruction::C { jump, .. } if !jump.is_empty()) {
+            continue;
+        }
+        let load = instructions[..index].iter().rev().find(|instruction| {
+            matches!(instruction, Instruction::A(_) | Instruction::L(_))
+                || matches!(instruction, Instruction::C { dest, .. } if dest.contains('A'))
+        });
+        if let Some(Instruction::A(symbol)) = load {
+            assert!(
+                symbol_table.is_label(symbol),
+                "the jump to the fixed ROM address {} can't be moved",
+                symbol
+            );
+        }
+    }
+
+    let mut chunks: Vec<Chunk> = vec![Vec::new()];
+    for instruction in instructions {
+        let chunk = chunks.last_mut().expect("at least one chunk");
+        let started = chunk
+            .iter()
+            .any(|instruction| !matches!(instruction, Instruction::L(_)));
+        if matches!(instruction, Instruction::L(_)) && started {
+            chunks.push(Vec::new());
+        }
+        chunks
+            .last_mut()
+            .expect("at least one chunk")
+            .push(instruction.clone());
+    }
+
+    // The blocks: runs of chunks falling through to each other.
+    let mut blocks: Vec<Vec<Chunk>> = Vec::new();
+    for chunk in chunks {
+        let Some(previous) = blocks.last_mut() else {
+            blocks.push(vec![chunk]);
+            continue;
+        };
+        let last = previous.last_mut().expect("blocks aren't empty");
+        let first = chunk
+            .iter()
+            .find(|instruction| !matches!(instruction, Instruction::L(_)));
+        match (last.last(), first) {
+            (Some(Instruction::C { jump, .. }), Some(_)) if jump == "JMP" => {
+                blocks.push(vec![chunk]);
+            }
+            (_, Some(Instruction::A(_))) => {
+                let Some(Instruction::L(label)) = chunk.first() else {
+                    unreachable!("chunks after the first start with a label");
+                };
+                last.push(Instruction::A(label.clone()));
+                last.push(Instruction::C {
+                    dest: String::new(),
+                    comp: String::from("0"),
+                    jump: String::from("JMP"),
+                });
+                blocks.push(vec![chunk]);
+            }
+            _ => previous.push(chunk),
+        }
+    }
+    let ends = !matches!(
+        blocks.last().and_then(|block| block.last()).and_then(|chunk| chunk.last()),
+        Some(Instruction::C { jump, .. }) if jump == "JMP"
+    );
+    let end = (blocks.len() > 1 && ends).then(|| blocks.pop()).flatten();
+    if blocks.len() > 1 {
+        rng.shuffle(&mut blocks[1..]);
+    }
+    blocks.extend(end);
+
+    let predefined = SymbolTable::new();
+    let mut variables: Vec<(&str, u32)> = symbol_table
+        .iter()
+        .filter(|(symbol, _)| {
+            !symbol_table.is_label(symbol) && predefined.address(symbol).is_none()
+        })
+        .collect();
+    variables.sort_by_key(|(_, address)| *address);
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut used: HashSet<String> = HashSet::new();
+    let symbols = symbol_table
+        .iter()
+        .filter(|(symbol, _)| symbol_table.is_label(symbol))
+        .map(|(symbol, _)| symbol)
+        .chain(variables.iter().map(|(symbol, _)| *symbol));
+    for symbol in symbols {
+        let name = loop {
+            let name = format!("_{:06x}", rng.u32(..0x100_0000));
+            if used.insert(name.clone()) {
+                break name;
+            }
+        };
+        names.insert(symbol.to_string(), name);
+    }
+    let rename = |symbol: &String| names.get(symbol).unwrap_or(symbol).clone();
+
+    let mut obfuscated = Program::new();
+    if !variables.is_empty() {
+        for (variable, _) in &variables {
+            obfuscated.push(Instruction::A(rename(&variable.to_string())));
+        }
+        obfuscated.push(Instruction::A(String::from("0")));
+    }
+    for instruction in blocks.into_iter().flatten().flatten() {
+        obfuscated.push(match instruction {
+            Instruction::A(symbol) => Instruction::A(rename(&symbol)),
+            Instruction::L(label) => Instruction::L(rename(&label)),
+            instruction => instruction,
+        });
+    }
+    obfuscated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{emulator::Emulator, golden::CORPUS};
+
+    fn run(program: &Program, inputs: &[(usize, u16)]) -> Vec<u16> {
+        let mut emulator = Emulator::new(program.assemble());
+        for (address, value) in inputs {
+            emulator.ram_mut()[*address] = *value;
+        }
+        emulator.run(1_000_000);
+        emulator.ram().to_vec()
+    }
+
+    #[test]
+    fn test_obfuscate_preserves_behavior() {
+        for case in CORPUS
+            .iter()
+            .filter(|case| ["Max", "Rect"].contains(&case.name))
+        {
+            // Given
+            let program = Program::from_source(case.source);
+            let inputs = [(0, 7), (1, 12)];
+
+            // When
+            let obfuscated = obfuscate(&program, 42);
+
+            // Then
+            let source = obfuscated.to_string();
+            for symbol in [
+                "LOOP",
+                "END",
+                "OUTPUT_FIRST",
+                "OUTPUT_D",
+                "INFINITE_LOOP",
+                "addr",
+            ] {
+                assert!(!source.contains(symbol), "{}: {}", case.name, symbol);
+            }
+            assert_eq!(
+                run(&program, &inputs),
+                run(&obfuscated, &inputs),
+                "{}",
+                case.name
+            );
+        }
+    }
+}
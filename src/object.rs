@@ -0,0 +1,286 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    code::c_instruction,
+    error::{self, AssemblerError},
+    program::{Instruction, Program},
+    snapshot::SnapshotError,
+    symbol_table::SymbolTable,
+};
+
+/// The current version of the object and library formats. Bumped on any
+/// incompatible change.
+pub const OBJECT_VERSION: u32 = 1;
+
+/// How a relocated symbol is used, which decides what the linker does when no
+/// object defines it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolUse {
+    /// Loaded right before a jump: it must be a label of some object.
+    Jump,
+    /// Any other use: it becomes a variable if no object defines it.
+    Data,
+}
+
+/// A ROM word whose value is the address of a symbol, patched at link time.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Relocation {
+    /// The offset of the word in the code of the object.
+    pub offset: u16,
+    pub symbol: String,
+    pub usage: SymbolUse,
+}
+
+/// A relocatable object (`.hobj`): a single file assembled on its own, its
+/// labels relative to the start of its code.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Object {
+    /// The version of the object format.
+    pub version: u32,
+    /// The name of the object, which is also the name of its code section.
+    pub name: String,
+    /// The binary words, relocated words being 0.
+    pub words: Vec<u16>,
+    /// The labels defined by the object and their offsets.
+    pub labels: BTreeMap<String, u16>,
+    pub relocations: Vec<Relocation>,
+}
+
+/// A library (`.hlib`): an archive of objects, each linked only if it defines
+/// a symbol the program needs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Library {
+    /// The version of the library format.
+    pub version: u32,
+    pub objects: Vec<Object>,
+}
+
+impl Object {
+    /// Assembles a source into a relocatable object. Numbers and predefined
+    /// symbols are resolved, other symbols are relocated.
+    ///
+    /// Returns an error, located at its line:
+    ///
+    /// - if the source contains an invalid instruction,
+    /// - if a constant doesn't fit in an A-instruction,
+    /// - if a label is defined twice.
+    pub fn assemble(name: &str, source: &str) -> Result<Self, AssemblerError> {
+        let program = Program::parse(source)?;
+        AssemblerError::combine(program.duplicate_labels())?;
+        let predefined = SymbolTable::new();
+        let mut object = Self {
+            version: OBJECT_VERSION,
+            name: name.to_string(),
+            words: Vec::new(),
+            labels: BTreeMap::new(),
+            relocations: Vec::new(),
+        };
+
+        let instructions = program.instructions();
+        for (index, instruction) in instructions.iter().enumerate() {
+            let offset = object.words.len() as u16;
+            match instruction {
+                Instruction::L(label) => {
+                    object.labels.insert(label.clone(), offset);
+                }
+                Instruction::A(value) => {
+                    let word = match value.parse::<u16>() {
+                        Ok(value) => Some(value),
+                        Err(_) => predefined.address(value).map(|address| *address as u16),
+                    };
+                    match word {
+                        Some(word) if word >= 0x8000 => {
+                            return Err(AssemblerError::semantic(format!(
+                                "constant {} is too large",
+                                value
+                            ))
+                            .at_line(program.source_line(index)));
+                        }
+                        Some(word) => object.words.push(word),
+                        None => {
+                            let jumps = instructions[index + 1..]
+                                .iter()
+                                .find(|instruction| !matches!(instruction, Instruction::L(_)))
+                                .is_some_and(|next| {
+                                    matches!(next, Instruction::C { jump, .. } if !jump.is_empty())
+                                });
+                            object.relocations.push(Relocation {
+                                offset,
+                                symbol: value.clone(),
+                                usage: if jumps {
+                                    SymbolUse::Jump
+                                } else {
+                                    SymbolUse::Data
+                                },
+                            });
+                            object.words.push(0);
+                        }
+                    }
+                }
+                Instruction::C { dest, comp, jump } => {
+                    let word = c_instruction(dest, comp, jump)
+                        .map_err(|error| error.at_line(program.source_line(index)))?;
+                    object.words.push(word);
+                }
+            }
+        }
+        Ok(object)
+    }
+
+    /// Resolves the relocations of the symbol to the constant, so the symbol
+    /// is no longer left to the linker.
+    ///
+    /// Returns a semantic error if the value doesn't fit in an A-instruction,
+    /// or if the object defines a label of that name.
+    pub fn define(&mut self, symbol: &str, value: u16) -> Result<(), AssemblerError> {
+        if value >= 0x8000 {
+            return Err(AssemblerError::semantic(format!(
+                "constant {} is too large",
+                value
+            )));
+        }
+        if self.labels.contains_key(symbol) {
+            return Err(AssemblerError::semantic(format!(
+                "{} is both defined and a label of {}",
+                symbol, self.name
+            )));
+        }
+        let words = &mut self.words;
+        self.relocations.retain(|relocation| {
+            if relocation.symbol != symbol {
+                return true;
+            }
+            words[relocation.offset as usize] = value;
+            false
+        });
+        Ok(())
+    }
+
+    /// Serializes the object to JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("failed to serialize object")
+    }
+
+    /// Loads an object from JSON.
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        from_json(json)
+    }
+}
+
+impl Library {
+    /// Returns a library archiving the objects.
+    pub fn new(objects: Vec<Object>) -> Self {
+        Self {
+            version: OBJECT_VERSION,
+            objects,
+        }
+    }
+
+    /// Serializes the library to JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("failed to serialize library")
+    }
+
+    /// Loads a library from JSON.
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        from_json(json)
+    }
+}
+
+/// Loads a versioned object or library from JSON.
+fn from_json<T: for<'de> Deserialize<'de>>(json: &str) -> Result<T, SnapshotError> {
+    let version = serde_json::from_str::<serde_json::Value>(json)
+        .map_err(|err| SnapshotError::Malformed(err.to_string()))?
+        .get("version")
+        .and_then(|version| version.as_u64())
+        .ok_or_else(|| SnapshotError::Malformed(String::from("missing version")))?;
+    if version != OBJECT_VERSION as u64 {
+        return Err(SnapshotError::UnsupportedVersion(version as u32));
+    }
+    serde_json::from_str(json).map_err(|err| SnapshotError::Malformed(err.to_string()))
+}
+
+/// Assembles an assembly file into an object named after the file. Returns an
+/// error if the file can't be read or doesn't assemble.
+pub fn assemble_file(path: &Path) -> Result<Object, AssemblerError> {
+    let source = error::read_to_string(path)?;
+    let name = path
+        .file_stem()
+        .expect("missing file name")
+        .to_string_lossy()
+        .to_string();
+    Object::assemble(&name, &source)
+}
+
+/// Reads a `.hobj` object. Returns an error if the file can't be read or is
+/// not a valid object.
+pub fn read_object(path: &Path) -> Result<Object, AssemblerError> {
+    Object::from_json(&error::read_to_string(path)?)
+        .map_err(|error| AssemblerError::syntax(format!("{}: {}", path.display(), error)))
+}
+
+/// Reads a `.hlib` library. Returns an error if the file can't be read or is
+/// not a valid library.
+pub fn read_library(path: &Path) -> Result<Library, AssemblerError> {
+    Library::from_json(&error::read_to_string(path)?)
+        .map_err(|error| AssemblerError::syntax(format!("{}: {}", path.display(), error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble_object() {
+        // Given
+        let source = "(LOOP)\n@SCREEN\nD=A\n@i\nM=D\n@Other.start\n0;JMP\n@LOOP\n(END)\n0;JMP\n";
+
+        // When
+        let object = Object::assemble("Main", source).unwrap();
+
+        // Then
+        assert_eq!(16384, object.words[0]);
+        assert_eq!(
+            BTreeMap::from([(String::from("LOOP"), 0), (String::from("END"), 7)]),
+            object.labels
+        );
+        let relocations: Vec<(u16, &str, SymbolUse)> = object
+            .relocations
+            .iter()
+            .map(|relocation| {
+                (
+                    relocation.offset,
+                    relocation.symbol.as_str(),
+                    relocation.usage,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (2, "i", SymbolUse::Data),
+                (4, "Other.start", SymbolUse::Jump),
+                (6, "LOOP", SymbolUse::Jump),
+            ],
+            relocations
+        );
+        assert_eq!(Ok(object.clone()), Object::from_json(&object.to_json()));
+    }
+
+    #[test]
+    fn test_assemble_errors_are_located() {
+        for (source, expected) in [
+            ("@1\nD=Q\n", "line 2:3: unexpected comp Q"),
+            ("(A)\n@1\n(A)\n", "line 3: label A is defined twice"),
+            ("@1\n@32768\n", "line 2:2: constant 32768 is out of range"),
+        ] {
+            // When
+            let error = Object::assemble("Main", source).unwrap_err();
+
+            // Then
+            assert_eq!(expected, error.to_string(), "{:?}", source);
+        }
+    }
+}
@@ -0,0 +1,599 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    cfg::{self, Cfg},
+    error::AssemblerError,
+    ir::{Ir, IrInstruction, IrNode},
+    pass::{Context, Pass, Stage},
+    symbol_table::SymbolTable,
+};
+
+/// How hard the optimizer works on the IR.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum OptLevel {
+    /// No optimization: the output matches the source instruction for instruction.
+    #[default]
+    #[value(name = "0")]
+    O0,
+    /// Local rewrites of a few instructions at a time.
+    #[value(name = "1")]
+    O1,
+    /// Every optimization.
+    #[value(name = "2")]
+    O2,
+}
+
+/// A peephole rule, rewriting a short window of consecutive instructions.
+///
+/// Rules only see windows where no label points past the first instruction,
+/// so the instructions of a window always run in sequence.
+pub trait Peephole: Send + Sync {
+    /// The unique name of the rule.
+    fn name(&self) -> &'static str;
+    /// The number of instructions the rule looks at.
+    fn window(&self) -> usize;
+    /// Returns the instructions replacing the window, or `None` if the rule
+    /// doesn't apply.
+    fn rewrite(&self, window: &[IrNode], symbol_table: &SymbolTable) -> Option<Vec<IrInstruction>>;
+}
+
+/// Returns the peephole rules enabled at the optimization level.
+pub fn peephole_rules(level: OptLevel) -> Vec<Box<dyn Peephole>> {
+    if level == OptLevel::O0 {
+        return Vec::new();
+    }
+    vec![
+        Box::new(SelfAssignment),
+        Box::new(RedundantLoad(0)),
+        Box::new(RedundantLoad(1)),
+        Box::new(RedundantLoad(2)),
+    ]
+}
+
+/// Applies peephole rules to the IR until none applies, then moves the labels
+/// to the new addresses of their instructions.
+///
+/// Programs jumping to numeric addresses are left as is, as their instructions
+/// can't move.
+pub struct Optimize {
+    rules: Vec<Box<dyn Peephole>>,
+}
+
+impl Optimize {
+    /// Returns the pass applying the rules, in order.
+    pub fn new(rules: Vec<Box<dyn Peephole>>) -> Self {
+        Self { rules }
+    }
+}
+
+impl Pass for Optimize {
+    fn name(&self) -> &'static str {
+        "peephole"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Optimize
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        if cfg::has_numeric_jumps(&context.ir) {
+            return Ok(());
+        }
+        let mut changed = false;
+        while self.rules.iter().any(|rule| {
+            let applied = apply(rule.as_ref(), &mut context.ir, &context.symbol_table);
+            changed |= applied;
+            applied
+        }) {}
+        if changed {
+            relayout(&mut context.ir, &mut context.symbol_table);
+        }
+        Ok(())
+    }
+}
+
+/// Applies a rule to every window it matches, in a single sweep. Returns
+/// whether the IR changed.
+fn apply(rule: &dyn Peephole, ir: &mut Ir, symbol_table: &SymbolTable) -> bool {
+    let size = rule.window();
+    let mut changed = false;
+    let mut index = 0;
+    while index + size <= ir.nodes.len() {
+        let window = &ir.nodes[index..index + size];
+        let is_straight = window[1..].iter().all(|node| node.labels.is_empty());
+        let Some(replacement) = is_straight
+            .then(|| rule.rewrite(window, symbol_table))
+            .flatten()
+        else {
+            index += 1;
+            continue;
+        };
+
+        let first = &window[0];
+        let (labels, line) = (first.labels.clone(), first.line);
+        let count = replacement.len();
+        let nodes = replacement.into_iter().map(|instruction| IrNode {
+            address: 0,
+            labels: Vec::new(),
+            instruction,
+            line,
+        });
+        ir.nodes.splice(index..index + size, nodes);
+
+        // The labels of the window point to its first remaining instruction.
+        prepend_labels(ir, index, labels);
+        changed = true;
+        index += count.max(1);
+    }
+    changed
+}
+
+/// Redirects the jumps to a label whose instruction is itself an unconditional
+/// jump, `@NEXT` then `0;JMP`, to the end of the chain of jumps.
+///
+/// A conditional jump is only redirected if the code it falls through to
+/// doesn't read the address left in A.
+pub struct JumpThreading;
+
+impl Pass for JumpThreading {
+    fn name(&self) -> &'static str {
+        "jump-threading"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Optimize
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        let nodes = &context.ir.nodes;
+        let indices: HashMap<&str, usize> = nodes
+            .iter()
+            .enumerate()
+            .flat_map(|(index, node)| node.labels.iter().map(move |label| (label.as_str(), index)))
+            .collect();
+        // The load of a label that is only used by the following jump.
+        let jump_target = |index: usize| match (&nodes[index].instruction, nodes.get(index + 1)) {
+            (
+                IrInstruction::A {
+                    symbol: Some(symbol),
+                    ..
+                },
+                Some(IrNode {
+                    instruction: IrInstruction::C { dest, comp, jump },
+                    ..
+                }),
+            ) if dest.is_empty()
+                && !jump.is_empty()
+                && !comp.contains(['A', 'M'])
+                && (jump == "JMP" || !reads_a(&nodes[index + 2..])) =>
+            {
+                indices.get(symbol.as_str()).copied()
+            }
+            _ => None,
+        };
+
+        let mut threaded = Vec::new();
+        for index in 0..nodes.len() {
+            let Some(mut target) = jump_target(index) else {
+                continue;
+            };
+            let mut visited = vec![index];
+            while let Some(next) = jump_target(target).filter(|_| {
+                matches!(&nodes[target + 1].instruction, IrInstruction::C { jump, .. } if jump == "JMP")
+            }) {
+                if visited.contains(&target) {
+                    break;
+                }
+                visited.push(target);
+                target = next;
+            }
+            // The chain goes through at least one jump: load its last label.
+            if let Some(&last) = visited.get(1..).and_then(<[usize]>::last) {
+                threaded.push((index, nodes[last].instruction.clone()));
+            }
+        }
+        for (index, instruction) in threaded {
+            context.ir.nodes[index].instruction = instruction;
+        }
+        Ok(())
+    }
+}
+
+/// Returns whether the straight-line code at the start of the nodes reads A,
+/// as an address or a value, before writing it.
+fn reads_a(nodes: &[IrNode]) -> bool {
+    for node in nodes {
+        match &node.instruction {
+            IrInstruction::A { .. } => return false,
+            IrInstruction::C { dest, comp, jump } => {
+                if comp.contains(['A', 'M']) || dest.contains('M') || !jump.is_empty() {
+                    return true;
+                }
+                if dest.contains('A') {
+                    return false;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Prepends labels to those of the instruction at the index, or to the
+/// trailing labels past the last instruction.
+fn prepend_labels(ir: &mut Ir, index: usize, mut labels: Vec<String>) {
+    let existing = match ir.nodes.get_mut(index) {
+        Some(node) => &mut node.labels,
+        None => &mut ir.trailing_labels,
+    };
+    labels.append(existing);
+    *existing = labels;
+}
+
+/// A block of instructions removed because no execution reaches it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeadBlock {
+    /// The ROM address of the block before it was removed.
+    pub address: u32,
+    /// The source line of the first instruction, if known.
+    pub line: Option<usize>,
+    /// The removed instructions, as assembly.
+    pub instructions: Vec<String>,
+}
+
+impl fmt::Display for DeadBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "removed {} unreachable instructions at ROM[{}]",
+            self.instructions.len(),
+            self.address
+        )?;
+        if let Some(line) = self.line {
+            write!(f, " (line {})", line)?;
+        }
+        write!(f, ": {}", self.instructions.join(" "))
+    }
+}
+
+/// The blocks removed by the dead-code elimination.
+///
+/// Clones share the same blocks, so the report can be read after the
+/// assembler consumed the context.
+#[derive(Clone, Debug, Default)]
+pub struct DeadCodeReport {
+    blocks: Arc<Mutex<Vec<DeadBlock>>>,
+}
+
+impl DeadCodeReport {
+    /// Returns a new empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the removed blocks, in ROM order.
+    pub fn blocks(&self) -> Vec<DeadBlock> {
+        self.blocks.lock().expect("poisoned report").clone()
+    }
+}
+
+/// Removes the blocks of instructions the control-flow graph proves
+/// unreachable from the entry, recording them in the report of the context.
+///
+/// Like the peephole rules, it leaves programs jumping to numeric addresses
+/// as is.
+pub struct DeadCodeElimination;
+
+impl Pass for DeadCodeElimination {
+    fn name(&self) -> &'static str {
+        "dead-code"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Optimize
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        if cfg::has_numeric_jumps(&context.ir) {
+            return Ok(());
+        }
+        let cfg = Cfg::new(&context.ir);
+        let mut dead: Vec<Range<usize>> = Vec::new();
+        for (block, reachable) in cfg.blocks.iter().zip(cfg.reachable()) {
+            if reachable {
+                continue;
+            }
+            match dead.last_mut() {
+                Some(range) if range.end == block.nodes.start => range.end = block.nodes.end,
+                _ => dead.push(block.nodes.clone()),
+            }
+        }
+        if dead.is_empty() {
+            return Ok(());
+        }
+
+        let mut blocks = context.dead_code.blocks.lock().expect("poisoned report");
+        for range in dead.into_iter().rev() {
+            let removed: Vec<IrNode> = context.ir.nodes.drain(range.clone()).collect();
+            blocks.push(DeadBlock {
+                address: removed[0].address,
+                line: removed[0].line,
+                instructions: removed
+                    .iter()
+                    .map(|node| node.instruction.to_string())
+                    .collect(),
+            });
+            let labels = removed.into_iter().flat_map(|node| node.labels).collect();
+            prepend_labels(&mut context.ir, range.start, labels);
+        }
+        blocks.sort_by_key(|block| block.address);
+        drop(blocks);
+        relayout(&mut context.ir, &mut context.symbol_table);
+        Ok(())
+    }
+}
+
+/// Renumbers the instructions and updates the labels, in the symbol table and
+/// in the A-instructions loading them.
+fn relayout(ir: &mut Ir, symbol_table: &mut SymbolTable) {
+    let mut addresses = HashMap::new();
+    for (address, node) in ir.nodes.iter_mut().enumerate() {
+        node.address = address as u32;
+        for label in &node.labels {
+            addresses.insert(label.clone(), address as u32);
+        }
+    }
+    for label in &ir.trailing_labels {
+        addresses.insert(label.clone(), ir.nodes.len() as u32);
+    }
+
+    for node in &mut ir.nodes {
+        if let IrInstruction::A {
+            value,
+            symbol: Some(symbol),
+        } = &mut node.instruction
+        {
+            if let Some(address) = addresses.get(symbol.as_str()) {
+                *value = *address;
+            }
+        }
+    }
+    for (label, address) in addresses {
+        symbol_table.add_label(label, address);
+    }
+}
+
+/// Removes `D=D`, `A=A` and `M=M`, which leave the machine unchanged.
+struct SelfAssignment;
+
+impl Peephole for SelfAssignment {
+    fn name(&self) -> &'static str {
+        "self-assignment"
+    }
+
+    fn window(&self) -> usize {
+        1
+    }
+
+    fn rewrite(&self, window: &[IrNode], _: &SymbolTable) -> Option<Vec<IrInstruction>> {
+        match &window[0].instruction {
+            IrInstruction::C { dest, comp, jump }
+                if jump.is_empty() && dest == comp && matches!(dest.as_str(), "A" | "D" | "M") =>
+            {
+                Some(Vec::new())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Removes an `@X` reloading the value A already holds, the instructions
+/// since the previous `@X` (their number being the gap) not writing A.
+struct RedundantLoad(usize);
+
+impl RedundantLoad {
+    /// Returns whether two A-instructions load the same value. Labels may move
+    /// when the IR is laid out again, so they only match themselves.
+    fn same_load(
+        first: &IrInstruction,
+        second: &IrInstruction,
+        symbol_table: &SymbolTable,
+    ) -> bool {
+        let (
+            IrInstruction::A {
+                value: first_value,
+                symbol: first_symbol,
+            },
+            IrInstruction::A {
+                value: second_value,
+                symbol: second_symbol,
+            },
+        ) = (first, second)
+        else {
+            return false;
+        };
+        let is_label = |symbol: &Option<String>| {
+            symbol
+                .as_ref()
+                .is_some_and(|symbol| symbol_table.is_label(symbol))
+        };
+        if is_label(first_symbol) || is_label(second_symbol) {
+            first_symbol == second_symbol
+        } else {
+            first_value == second_value
+        }
+    }
+}
+
+impl Peephole for RedundantLoad {
+    fn name(&self) -> &'static str {
+        "redundant-load"
+    }
+
+    fn window(&self) -> usize {
+        self.0 + 2
+    }
+
+    fn rewrite(&self, window: &[IrNode], symbol_table: &SymbolTable) -> Option<Vec<IrInstruction>> {
+        let (last, rest) = window.split_last()?;
+        let keeps_a = rest[1..].iter().all(|node| {
+            matches!(&node.instruction, IrInstruction::C { dest, .. } if !dest.contains('A'))
+        });
+        (keeps_a && Self::same_load(&rest[0].instruction, &last.instruction, symbol_table))
+            .then(|| rest.iter().map(|node| node.instruction.clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+
+    #[test]
+    fn test_optimize_moves_labels() {
+        // Given
+        let source = "@1\nD=D\n(LOOP)\nA=A\n@LOOP\n0;JMP\n";
+
+        // When
+        let words = Assembler::from_source(source)
+            .optimize(OptLevel::O1)
+            .fill_symbol_table()
+            .assemble();
+
+        // Then
+        assert_eq!(
+            vec![
+                0b0000_0000_0000_0001,
+                0b0000_0000_0000_0001,
+                0b1110_1010_1000_0111
+            ],
+            words
+        );
+    }
+
+    #[test]
+    fn test_optimize_keeps_numeric_jump_targets() {
+        // Given
+        let source = "@5\n0;JMP\n@1\nD=A\nD=D\n(END)\n@END\n0;JMP\n";
+        let assemble = |level| {
+            Assembler::from_source(source)
+                .optimize(level)
+                .fill_symbol_table()
+                .assemble()
+        };
+
+        for level in [OptLevel::O1, OptLevel::O2] {
+            // When
+            let words = assemble(level);
+
+            // Then
+            assert_eq!(assemble(OptLevel::O0), words, "{:?}", level);
+            assert_eq!(0b0000_0000_0000_0101, words[0]);
+        }
+    }
+
+    #[test]
+    fn test_jump_threading() {
+        // Given
+        let source = "@A\nD;JGT\n(A)\n@B\n0;JMP\n(B)\n@C\n0;JMP\n(C)\n@C\n0;JMP\n";
+
+        // When
+        let words = Assembler::from_source(source)
+            .optimize(OptLevel::O2)
+            .fill_symbol_table()
+            .assemble();
+
+        // Then
+        let loads: Vec<u16> = words.iter().step_by(2).copied().collect();
+        assert_eq!(
+            vec![
+                0b0000_0000_0000_0100,
+                0b0000_0000_0000_0100,
+                0b0000_0000_0000_0100
+            ],
+            loads
+        );
+    }
+
+    #[test]
+    fn test_jump_threading_keeps_the_address_read_by_the_fall_through() {
+        // Given
+        let source = "@L\nD;JGT\nM=D\n(L)\n@END\n0;JMP\n(END)\n@END\n0;JMP\n";
+
+        // When
+        let words = Assembler::from_source(source)
+            .optimize(OptLevel::O2)
+            .fill_symbol_table()
+            .assemble();
+
+        // Then
+        assert_eq!(0b0000_0000_0000_0011, words[0]);
+    }
+
+    #[test]
+    fn test_dead_code_elimination() {
+        // Given
+        let source = "@END\n0;JMP\n(UNUSED)\nD=1\n@END\nD;JGT\nM=D\n(END)\n@END\n0;JMP\nD=0\n";
+        let report = DeadCodeReport::new();
+
+        // When
+        let words = Assembler::from_source(source)
+            .with_dead_code_report(report.clone())
+            .optimize(OptLevel::O2)
+            .fill_symbol_table()
+            .assemble();
+
+        // Then
+        assert_eq!(
+            vec![
+                0b0000_0000_0000_0010,
+                0b1110_1010_1000_0111,
+                0b0000_0000_0000_0010,
+                0b1110_1010_1000_0111,
+            ],
+            words
+        );
+        assert_eq!(
+            vec![
+                "removed 4 unreachable instructions at ROM[2] (line 4): D=1 @6 D;JGT M=D",
+                "removed 1 unreachable instructions at ROM[8] (line 11): D=0",
+            ],
+            report
+                .blocks()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_redundant_load() {
+        // Given
+        let source = "@SP\nM=M-1\n@SP\nA=M\n@0\n@SP\n(LOOP)\n@LOOP\n@LOOP\n0;JMP\n";
+
+        // When
+        let words = Assembler::from_source(source)
+            .optimize(OptLevel::O1)
+            .fill_symbol_table()
+            .assemble();
+
+        // Then
+        assert_eq!(
+            vec![
+                0b0000_0000_0000_0000,
+                0b1111_1100_1000_1000,
+                0b1111_1100_0010_0000,
+                0b0000_0000_0000_0000,
+                0b0000_0000_0000_0100,
+                0b1110_1010_1000_0111,
+            ],
+            words
+        );
+    }
+}
@@ -1,13 +1,29 @@
-use std::{iter::Peekable, path::PathBuf, vec::IntoIter};
-
-#[derive(Clone)]
-pub struct Parser {
-    /// An iterator over the program lines.
-    program: Peekable<IntoIter<String>>,
-    /// The current instruction.
-    current_instruction: Option<String>,
+use std::{borrow::Cow, iter};
+
+use memchr::{memchr_iter, memmem};
+
+use crate::{
+    code::{commuted_comp, dest_bits, suggested_comp, suggested_jump, COMP, JUMP},
+    error::AssemblerError,
+};
+
+/// A parser over a program source, returning slices of it whenever possible.
+pub struct Parser<'a> {
+    /// The instructions of the program, with their source lines and columns.
+    instructions: Vec<(usize, usize, &'a str)>,
+    /// The index of the next instruction.
+    next: usize,
+    /// The current instruction, without its comment and surrounding spaces.
+    current_instruction: Option<&'a str>,
     /// The current line number.
     instruction_index: u32,
+    /// The source line of the current instruction.
+    line: usize,
+    /// The source column of the current instruction.
+    column: usize,
+    /// Whether comps with their operands swapped, such as `A+D`, are accepted
+    /// as their canonical spelling.
+    accept_commuted: bool,
 }
 
 /// The type of instruction.
@@ -18,161 +34,392 @@ pub enum InstructionType {
     L,
 }
 
-impl Parser {
-    /// Create a new parser from a file path.
-    pub fn new(path: PathBuf) -> Self {
-        let program = std::fs::read_to_string(path).expect("failed to read file");
+/// Returns the instructions of the source with their 1-based lines and
+/// columns, without their comments and surrounding spaces, in a single pass
+/// over the source. The lines left empty are skipped.
+fn scan(source: &str) -> Vec<(usize, usize, &str)> {
+    let comment = memmem::Finder::new("//");
+    let mut instructions = Vec::new();
+    let mut start = 0;
+    let ends = memchr_iter(b'\n', source.as_bytes()).chain(iter::once(source.len()));
+    for (index, end) in ends.enumerate() {
+        let line = &source[start..end];
+        start = end + 1;
+        let code = comment
+            .find(line.as_bytes())
+            .map_or(line, |comment| &line[..comment])
+            .trim();
+        if !code.is_empty() {
+            let indent = line.len() - line.trim_start().len();
+            instructions.push((index + 1, line[..indent].chars().count() + 1, code));
+        }
+    }
+    instructions
+}
+
+/// Returns the source with a UTF-8 byte order mark removed, `\r\n` and `\r`
+/// line endings turned into `\n`, and any other whitespace, such as tabs
+/// or non-breaking spaces, turned into plain spaces. Whitespace is replaced
+/// character for character, so that columns are kept. The source is only
+/// copied if it has to change.
+pub fn normalize(source: &str) -> Cow<'_, str> {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let is_odd = |c: char| (c.is_whitespace() && c != '\n' && c != ' ') || c == '\u{feff}';
+    if !source.contains(is_odd) {
+        return Cow::Borrowed(source);
+    }
+    let source = source.replace("\r\n", "\n");
+    Cow::Owned(
+        source
+            .chars()
+            .map(|c| match c {
+                '\r' => '\n',
+                c if is_odd(c) => ' ',
+                c => c,
+            })
+            .collect(),
+    )
+}
+
+/// Blanks out the `/* */` block comments of a source given in one or more
+/// pieces, such as lines, so that comments can span several pieces. The
+/// comments are replaced by spaces, their line breaks kept, so that lines
+/// and columns are unchanged. `/*` within a `//` comment doesn't start a
+/// block comment.
+#[derive(Debug)]
+pub struct BlockComments {
+    /// The line and column of the next character.
+    line: usize,
+    column: usize,
+    /// The line and column of the block comment left open, if any.
+    open: Option<(usize, usize)>,
+}
+
+impl Default for BlockComments {
+    fn default() -> Self {
+        Self {
+            line: 1,
+            column: 1,
+            open: None,
+        }
+    }
+}
+
+impl BlockComments {
+    /// Returns the next piece of the source with its block comments blanked
+    /// out. The piece is only copied if it has to change.
+    pub fn blank<'t>(&mut self, text: &'t str) -> Cow<'t, str> {
+        if self.open.is_none() && !text.contains("/*") {
+            match text.rfind('\n') {
+                Some(index) => {
+                    self.line += text.matches('\n').count();
+                    self.column = text[index + 1..].chars().count() + 1;
+                }
+                None => self.column += text.chars().count(),
+            }
+            return Cow::Borrowed(text);
+        }
+
+        let mut blanked = String::with_capacity(text.len());
+        let mut in_line_comment = false;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            let next = chars.peek().copied();
+            let (line, column) = (self.line, self.column);
+            let mut push = |blanked: &mut String, c: char| {
+                if c == '\n' {
+                    self.line += 1;
+                    self.column = 1;
+                } else {
+                    self.column += 1;
+                }
+                blanked.push(c);
+            };
+            match (c, next) {
+                ('\n', _) => {
+                    in_line_comment = false;
+                    push(&mut blanked, '\n');
+                }
+                ('*', Some('/')) if self.open.is_some() => {
+                    chars.next();
+                    self.open = None;
+                    push(&mut blanked, ' ');
+                    push(&mut blanked, ' ');
+                }
+                (_, _) if self.open.is_some() => push(&mut blanked, ' '),
+                ('/', Some('/')) if !in_line_comment => {
+                    in_line_comment = true;
+                    push(&mut blanked, c);
+                }
+                ('/', Some('*')) if !in_line_comment => {
+                    chars.next();
+                    self.open = Some((line, column));
+                    push(&mut blanked, ' ');
+                    push(&mut blanked, ' ');
+                }
+                _ => push(&mut blanked, c),
+            }
+        }
+        Cow::Owned(blanked)
+    }
 
-        let mut lines = Vec::with_capacity(program.lines().count());
-        for line in program.lines() {
-            lines.push(line.to_string())
+    /// Returns an error if a block comment is left open at the end of the
+    /// source.
+    pub fn finish(&self) -> Result<(), AssemblerError> {
+        match self.open {
+            Some((line, column)) => Err(AssemblerError::syntax("unterminated block comment")
+                .at(line, column)
+                .with_note("block comments end with */")),
+            None => Ok(()),
         }
+    }
+}
 
-        let iterator = lines.into_iter().peekable();
+/// Returns the text without its spaces. The text is only copied if it has
+/// to change.
+fn compact(text: &str) -> Cow<'_, str> {
+    let text = text.trim();
+    if !text.contains(char::is_whitespace) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.replace(char::is_whitespace, ""))
+}
 
+impl<'a> Parser<'a> {
+    /// Create a new parser from the program source.
+    pub fn from_source(program: &'a str) -> Self {
         Self {
-            program: iterator,
+            instructions: scan(program),
+            next: 0,
             current_instruction: None,
             instruction_index: 0,
+            line: 0,
+            column: 0,
+            accept_commuted: false,
         }
     }
 
-    /// Returns wether the program has remaining lines.
-    pub fn has_more_lines(&mut self) -> bool {
-        self.program.peek().is_some()
+    /// Accepts the comps with their operands swapped, such as `A+D` or
+    /// `M&D`, returning their canonical spelling from [`Parser::comp`].
+    #[must_use]
+    pub fn accept_commuted(mut self, accept: bool) -> Self {
+        self.accept_commuted = accept;
+        self
+    }
+
+    /// Numbers the lines of the source from the given line rather than 1, for
+    /// a source that is part of a larger one.
+    pub(crate) fn starting_at(mut self, line: usize) -> Self {
+        for (number, _, _) in &mut self.instructions {
+            *number += line - 1;
+        }
+        self
+    }
+
+    /// Returns wether the program has remaining instructions.
+    /// Trailing comments and empty lines are not counted.
+    pub fn has_more_lines(&self) -> bool {
+        self.next < self.instructions.len()
     }
 
     /// Advance the program to the next executable instruction.
     /// Skips comments and empty lines.
     pub fn advance(&mut self) {
-        while self
-            .program
-            .peek()
-            .map(|line| line.replace(' ', ""))
-            .map(|line| line.is_empty() || line.starts_with("//"))
-            .unwrap_or_default()
-        {
-            self.program.next();
+        let next = self.instructions.get(self.next).copied();
+        self.current_instruction = next.map(|(_, _, instruction)| instruction);
+        if let Some((line, column, _)) = next {
+            self.line = line;
+            self.column = column;
         }
-
-        self.current_instruction = self.program.next().map(|c| c.replace(' ', ""));
+        self.next += 1;
         // We don't need to increment the line on L instructions
-        if !matches!(self.instruction_type(), InstructionType::L) {
+        if !matches!(self.instruction_type(), Ok(InstructionType::L)) {
             self.instruction_index += 1;
         }
     }
 
-    /// Returns the current instruction type.
+    /// Returns the current instruction type, or a syntax error if the current
+    /// instruction is invalid (neither A, L, or C).
     ///
     /// # Panic
     ///
-    /// - Panics if there is no current instruction.
-    /// - Panics if the current instruction is invalid (neither A, L, or C)
-    pub fn instruction_type(&self) -> InstructionType {
-        if let Some(instruction) = &self.current_instruction {
-            if instruction.starts_with('@') {
-                InstructionType::A
-            } else if instruction.starts_with('(') {
-                InstructionType::L
-            } else if instruction.contains('=') || instruction.contains(';') {
-                InstructionType::C
-            } else {
-                panic!("invalid instruction");
-            }
+    /// Panics if there is no current instruction.
+    pub fn instruction_type(&self) -> Result<InstructionType, AssemblerError> {
+        let instruction = self.current_instruction();
+        if instruction.starts_with('@') {
+            Ok(InstructionType::A)
+        } else if instruction.starts_with('(') {
+            Ok(InstructionType::L)
+        } else if instruction.contains('=') || instruction.contains(';') {
+            Ok(InstructionType::C)
         } else {
-            panic!("no current instruction");
+            Err(self.error(format!("invalid instruction {}", instruction)))
         }
     }
 
-    /// Returns the current instruction symbol.
-    ///
-    /// # Panic
-    ///
-    /// Panics if the current instruction is not an A or L instruction.
-    pub fn symbol(&self) -> String {
-        let instruction_type = self.instruction_type();
+    /// Returns the current instruction symbol, or a syntax error if the
+    /// current instruction is not an A or L instruction, or its symbol is
+    /// missing or malformed.
+    pub fn symbol(&self) -> Result<Cow<'a, str>, AssemblerError> {
+        let instruction_type = self.instruction_type()?;
         let instruction = self.current_instruction();
         let symbol = match instruction_type {
             InstructionType::A => instruction.trim_start_matches('@'),
-            InstructionType::L => instruction.trim_start_matches('(').trim_end_matches(')'),
-            InstructionType::C => panic!("symbol cannot be called on C instruction type"),
+            InstructionType::L => match instruction.strip_suffix(')') {
+                Some(label) => label.trim_start_matches('('),
+                None => {
+                    return Err(
+                        self.error(format!("malformed label {}, expected (LABEL)", instruction))
+                    )
+                }
+            },
+            InstructionType::C => {
+                return Err(self.error(format!("expected a symbol in {}", instruction)))
+            }
         };
-        symbol.to_string()
+        let symbol = compact(symbol);
+        if symbol.is_empty() {
+            return Err(self.error(format!("missing symbol in {}", instruction)));
+        }
+        self.check_symbol(&instruction_type, &symbol)?;
+        Ok(symbol)
+    }
+
+    /// Returns an error pointing at the symbol of the current instruction if
+    /// it's malformed: symbols are made of letters, digits, `_`, `.`, `$`
+    /// and `:`, and don't start with a digit. A-instructions may instead load
+    /// a constant, which must fit in 15 bits.
+    fn check_symbol(
+        &self,
+        instruction_type: &InstructionType,
+        symbol: &str,
+    ) -> Result<(), AssemblerError> {
+        let digits = symbol.strip_prefix('-').unwrap_or(symbol);
+        let is_number = !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit());
+        if *instruction_type == InstructionType::A && is_number {
+            return match symbol.parse::<i64>() {
+                Ok(0..=32767) => Ok(()),
+                _ => Err(self
+                    .error_at(1, format!("constant {} is out of range", symbol))
+                    .with_note("A-instructions load constants from 0 to 32767")),
+            };
+        }
+        let valid = !symbol.starts_with(|c: char| c.is_ascii_digit())
+            && symbol
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_.$:".contains(c));
+        if valid {
+            return Ok(());
+        }
+        Err(self
+            .error_at(1, format!("malformed symbol {}", symbol))
+            .with_note(
+                "symbols are made of letters, digits, _, ., $ and :, and don't start with a digit",
+            ))
     }
 
     /// Return the dest for a C instruction.
     /// C instructions are in the form of `dest=comp;jump`
     /// where `dest` and `jump` are optional.
     ///
-    /// # Panic
-    ///
-    /// Panics if the current instruction is not a C instruction.
-    pub fn dest(&self) -> String {
-        self.assert_current_instruction(InstructionType::C);
+    /// Returns a syntax error if the current instruction is not a C
+    /// instruction, or if its dest isn't made of A, D and M, each at most
+    /// once.
+    pub fn dest(&self) -> Result<Cow<'a, str>, AssemblerError> {
+        self.expect_instruction(InstructionType::C)?;
 
-        let instruction = self.current_instruction();
-        if !instruction.contains('=') {
-            return String::default();
+        let Some((dest, _)) = self.current_instruction().split_once('=') else {
+            return Ok(Cow::Borrowed(""));
+        };
+        let dest = compact(dest);
+        match dest_bits(&dest) {
+            Ok(_) => Ok(dest),
+            Err(error) => Err(error.at(self.line, self.column)),
         }
-        instruction
-            .split('=')
-            .next()
-            .expect("missing dest item")
-            .to_string()
     }
 
     /// Return the comp for a C instruction.
     /// C instructions are in the form of `dest=comp;jump`
     /// where `dest` and `jump` are optional.
     ///
-    /// # Panic
-    ///
-    /// - Panics if the current instruction is not a C instruction.
-    /// - Panics if the comp item is missing.
-    pub fn comp(&self) -> String {
-        self.assert_current_instruction(InstructionType::C);
+    /// Returns a syntax error if the current instruction is not a C
+    /// instruction, or if its comp is invalid. The comps with their operands
+    /// swapped are only accepted if asked.
+    pub fn comp(&self) -> Result<Cow<'a, str>, AssemblerError> {
+        self.expect_instruction(InstructionType::C)?;
 
         let instruction = self.current_instruction();
-        let comp = if instruction.contains('=') {
-            instruction.split('=').nth(1)
-        } else if instruction.contains(';') {
-            instruction.split(';').next()
-        } else {
-            panic!("failed to find comp item");
-        };
-
-        comp.expect("missing comp item").to_string()
+        let start = instruction.find('=').map_or(0, |index| index + 1);
+        let comp = &instruction[start..];
+        let comp = comp.split_once(';').map_or(comp, |(comp, _)| comp);
+        let compacted = compact(comp);
+        if COMP.iter().any(|(mnemonic, _)| *mnemonic == compacted) {
+            return Ok(compacted);
+        }
+        let error = self.error_at(start, format!("unexpected comp {}", compacted));
+        match commuted_comp(&compacted) {
+            Some(commuted) if self.accept_commuted => Ok(Cow::Borrowed(commuted)),
+            Some(commuted) => Err(error
+                .with_note(format!(
+                    "the specification only spells it {}, with its operands in this order",
+                    commuted
+                ))
+                .with_suggestion(commuted)),
+            None => Err(match suggested_comp(&compacted) {
+                Some(suggestion) => error.with_suggestion(suggestion),
+                None => error,
+            }),
+        }
     }
 
     /// Return the jump for a C instruction.
     /// C instructions are in the form of `dest=comp;jump`
     /// where `dest` and `jump` are optional.
     ///
-    /// # Panic
-    ///
-    /// Panics if the current instruction is not a C instruction.
-    pub fn jump(&self) -> String {
-        self.assert_current_instruction(InstructionType::C);
+    /// Returns a syntax error if the current instruction is not a C
+    /// instruction.
+    pub fn jump(&self) -> Result<Cow<'a, str>, AssemblerError> {
+        self.expect_instruction(InstructionType::C)?;
 
         let instruction = self.current_instruction();
-        if !instruction.contains(';') {
-            return String::default();
-        }
-        instruction
-            .split(';')
-            .last()
-            .expect("missing jump item")
-            .to_string()
-    }
-
-    fn assert_current_instruction(&self, expected_instruction_type: InstructionType) {
-        if self.instruction_type() != expected_instruction_type {
-            panic!(
-                "expected {:?} got {:?}",
-                expected_instruction_type,
-                self.instruction_type()
-            )
+        let Some(start) = instruction.find(';').map(|index| index + 1) else {
+            return Ok(Cow::Borrowed(""));
+        };
+        let jump = compact(&instruction[start..]);
+        if !JUMP.contains(&&*jump) {
+            let error = self.error_at(start, format!("unexpected jump {}", jump));
+            return Err(match suggested_jump(&jump) {
+                Some(suggestion) => error.with_suggestion(suggestion),
+                None => error,
+            });
+        }
+        Ok(jump)
+    }
+
+    fn expect_instruction(&self, expected: InstructionType) -> Result<(), AssemblerError> {
+        let instruction_type = self.instruction_type()?;
+        if instruction_type != expected {
+            return Err(self.error(format!(
+                "expected {:?} instruction, got {:?}",
+                expected, instruction_type
+            )));
         }
+        Ok(())
+    }
+
+    /// Returns a syntax error at the start of the current instruction.
+    fn error(&self, message: String) -> AssemblerError {
+        self.error_at(0, message)
+    }
+
+    /// Returns a syntax error at the first character which isn't a space
+    /// from the byte offset in the current instruction.
+    fn error_at(&self, offset: usize, message: String) -> AssemblerError {
+        let instruction = self.current_instruction();
+        let rest = &instruction[offset..];
+        let offset = offset + rest.len() - rest.trim_start().len();
+        let column = self.column + instruction[..offset].chars().count();
+        AssemblerError::syntax(message).at(self.line, column)
     }
 
     /// Returns the index of the current instruction.
@@ -180,15 +427,214 @@ impl Parser {
         self.instruction_index
     }
 
+    /// Returns the source line of the current instruction, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the source column of the current instruction, in characters
+    /// starting at 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
     /// Returns the current instruction.
     ///
     /// # Panic
     ///
     /// Panics if there is no current instruction.
-    fn current_instruction(&self) -> &str {
-        self.current_instruction
-            .as_ref()
-            .expect("expected instruction")
-            .as_str()
+    fn current_instruction(&self) -> &'a str {
+        self.current_instruction.expect("expected instruction")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_borrow_the_source() {
+        // Given
+        let mut parser =
+            Parser::from_source("// Comment\n  AM=M-1;JGT // Decrement\n@ loop\nD = D + A\n");
+
+        // When
+        parser.advance();
+        let fields = (
+            parser.dest().unwrap(),
+            parser.comp().unwrap(),
+            parser.jump().unwrap(),
+        );
+        parser.advance();
+        let symbol = parser.symbol().unwrap();
+        parser.advance();
+        let compacted = (
+            parser.dest().unwrap(),
+            parser.comp().unwrap(),
+            parser.jump().unwrap(),
+        );
+
+        // Then
+        assert!(matches!(
+            fields,
+            (
+                Cow::Borrowed("AM"),
+                Cow::Borrowed("M-1"),
+                Cow::Borrowed("JGT")
+            )
+        ));
+        assert!(matches!(symbol, Cow::Borrowed("loop")));
+        assert_eq!(
+            ("D", "D+A", ""),
+            (&*compacted.0, &*compacted.1, &*compacted.2)
+        );
+        assert_eq!(4, parser.line());
+        assert!(!parser.has_more_lines());
+    }
+
+    #[test]
+    fn test_invalid_instruction_is_a_syntax_error() {
+        // Given
+        let mut parser = Parser::from_source(
+            "@1
+
+D+1
+",
+        );
+        parser.advance();
+        parser.advance();
+
+        // When
+        let error = parser.instruction_type().unwrap_err();
+
+        // Then
+        assert!(matches!(
+            error,
+            AssemblerError::Syntax { line: Some(3), .. }
+        ));
+        assert_eq!("line 3:1: invalid instruction D+1", error.to_string());
+        assert!(parser.dest().is_err());
+    }
+
+    #[test]
+    fn test_commuted_comps() {
+        // Given
+        let mut strict = Parser::from_source("AM=A+D;JGT\n");
+        let mut relaxed = Parser::from_source("M=M&D\n").accept_commuted(true);
+        strict.advance();
+        relaxed.advance();
+
+        // When
+        let rejected = strict.comp().unwrap_err();
+        let accepted = relaxed.comp().unwrap();
+
+        // Then
+        assert_eq!("line 1:4: unexpected comp A+D", rejected.to_string());
+        assert!(matches!(
+            rejected,
+            AssemblerError::Syntax { note: Some(note), suggestion: Some(suggestion), .. }
+                if note.contains("only spells it D+A") && suggestion == "D+A"
+        ));
+        assert_eq!("D&M", accepted);
+    }
+
+    #[test]
+    fn test_constants_are_range_checked() {
+        // Given
+        let mut parser = Parser::from_source("@32767\n  @ 70000\n@-1\n@12ab\n@-\n");
+        let mut results = Vec::new();
+
+        // When
+        while parser.has_more_lines() {
+            parser.advance();
+            results.push(parser.symbol().map_err(|error| error.to_string()));
+        }
+
+        // Then
+        assert_eq!(
+            vec![
+                Ok(Cow::Borrowed("32767")),
+                Err(String::from("line 2:5: constant 70000 is out of range")),
+                Err(String::from("line 3:2: constant -1 is out of range")),
+                Err(String::from("line 4:2: malformed symbol 12ab")),
+                Err(String::from("line 5:2: malformed symbol -")),
+            ],
+            results
+        );
+    }
+
+    #[test]
+    fn test_malformed_symbols() {
+        // Given
+        let mut parser = Parser::from_source("@2foo\n(LOOP-1)\n@Main.f$ret:1\n(_END)\n");
+        let mut results = Vec::new();
+
+        // When
+        while parser.has_more_lines() {
+            parser.advance();
+            results.push(parser.symbol().map_err(|error| error.to_string()));
+        }
+
+        // Then
+        assert_eq!(
+            vec![
+                Err(String::from("line 1:2: malformed symbol 2foo")),
+                Err(String::from("line 2:2: malformed symbol LOOP-1")),
+                Ok(Cow::Borrowed("Main.f$ret:1")),
+                Ok(Cow::Borrowed("_END")),
+            ],
+            results
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        // Given
+        let source = "\u{feff}@2\r\nD\t=\u{a0}A\r(END)\n";
+
+        // When
+        let normalized = normalize(source);
+
+        // Then
+        assert_eq!("@2\nD = A\n(END)\n", normalized);
+        assert!(matches!(normalize("@2\nD=A\n"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_block_comments_are_blanked() {
+        // Given
+        let mut comments = BlockComments::default();
+        let mut open = BlockComments::default();
+
+        // When
+        let first = comments.blank("@1 /* one\n");
+        let second = comments.blank("two */ D=A // not /* a block\n");
+        let third = comments.blank("M=D/**/;JGT\n");
+        open.blank("@1\n  /* open\n@2\n");
+
+        // Then
+        assert_eq!("@1       \n", first);
+        assert_eq!("       D=A // not /* a block\n", second);
+        assert_eq!("M=D    ;JGT\n", third);
+        assert!(comments.finish().is_ok());
+        assert_eq!(
+            "line 2:3: unterminated block comment",
+            open.finish().unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_scan_skips_comments_and_blank_lines() {
+        // Given
+        let source = "// Header\r\n\r\n  @2 // Two\r\n\t\n   // Indented\nD=A\n(END)//\n";
+
+        // When
+        let instructions = scan(source);
+
+        // Then
+        assert_eq!(
+            vec![(3, 3, "@2"), (6, 1, "D=A"), (7, 1, "(END)")],
+            instructions
+        );
     }
 }
@@ -0,0 +1,544 @@
+use std::{borrow::Cow, io::Write, ops::RangeInclusive, time::Instant};
+
+use clap::ValueEnum;
+
+use crate::{
+    backpatch::out_of_ram,
+    callgraph::CallGraph,
+    cancel::CancellationToken,
+    cfg::Cfg,
+    code::{a_instruction, c_instruction},
+    error::AssemblerError,
+    ir::{Ir, IrInstruction},
+    limits::Limits,
+    lint::LintReport,
+    memmap::MemoryMap,
+    optimize::DeadCodeReport,
+    parser::{normalize, BlockComments, Parser},
+    program::Program,
+    snapshot::Snapshot,
+    stats::StatsReport,
+    symbol_table::SymbolTable,
+    usage::UsageReport,
+};
+
+/// The number of instructions encoded between two cancellation checks.
+pub(crate) const CANCELLATION_CHUNK: usize = 1024;
+
+/// The stages of the assembly pipeline, in execution order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Preprocess,
+    Parse,
+    Resolve,
+    Analyze,
+    Optimize,
+    Encode,
+    Emit,
+}
+
+/// The state shared by the passes of the pipeline.
+#[derive(Default)]
+pub struct Context {
+    /// The program source.
+    pub source: String,
+    /// The parsed program.
+    pub program: Program,
+    /// The resolved labels and variables of the program.
+    pub symbol_table: SymbolTable,
+    /// The program with its symbols resolved.
+    pub ir: Ir,
+    /// The binary words, one per instruction.
+    pub words: Vec<u16>,
+    /// The emitted output, when there is no writer.
+    pub output: String,
+    /// The destination of the emitted output, written as it's produced.
+    pub writer: Option<Box<dyn Write + Send + Sync>>,
+    /// The token checked between passes, and by long passes between chunks.
+    pub cancellation: CancellationToken,
+    /// The resource limits enforced by the passes.
+    pub limits: Limits,
+    /// The report of the blocks removed by the dead-code elimination.
+    pub dead_code: DeadCodeReport,
+    /// The report of the lints found by the analysis passes.
+    pub lints: LintReport,
+    /// The report of the composition of the encoded program.
+    pub stats: StatsReport,
+    /// The errors the passes recovered from, so that a single run reports
+    /// as many of them as possible.
+    pub errors: Vec<AssemblerError>,
+}
+
+impl Context {
+    /// Returns a new context for the program source.
+    pub fn new(source: String) -> Self {
+        Self {
+            source,
+            symbol_table: SymbolTable::new(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the errors the passes recovered from, in source order, and
+    /// clears them.
+    pub fn take_errors(&mut self) -> Result<(), AssemblerError> {
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.sort_by_key(|error| error.line().unwrap_or(usize::MAX));
+        AssemblerError::combine(errors)
+    }
+}
+
+/// A single step of the assembly pipeline.
+///
+/// Passes must be `Send + Sync` so that assemblers can be moved to, and
+/// pass managers shared between, worker threads.
+pub trait Pass: Send + Sync {
+    /// The unique name of the pass.
+    fn name(&self) -> &'static str;
+    /// The stage the pass belongs to.
+    fn stage(&self) -> Stage;
+    /// Runs the pass on the context, returning an error if the program can't
+    /// be assembled.
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError>;
+}
+
+struct Entry {
+    pass: Box<dyn Pass>,
+    enabled: bool,
+}
+
+/// Runs the passes of the pipeline ordered by stage, then by insertion order.
+pub struct PassManager {
+    passes: Vec<Entry>,
+}
+
+impl Default for PassManager {
+    /// Returns a pass manager with the standard passes.
+    fn default() -> Self {
+        let mut manager = Self::empty();
+        manager.add(Preprocess);
+        manager.add(Parse::default());
+        manager.add(Resolve);
+        manager.add(Lower);
+        manager.add(Encode);
+        manager.add(Emit(EmitFormat::Hack));
+        manager
+    }
+}
+
+impl PassManager {
+    /// Returns a pass manager without any pass.
+    pub fn empty() -> Self {
+        Self { passes: Vec::new() }
+    }
+
+    /// Adds an enabled pass after all the passes of the same or earlier stages.
+    pub fn add(&mut self, pass: impl Pass + 'static) {
+        let index = self
+            .passes
+            .iter()
+            .position(|entry| entry.pass.stage() > pass.stage())
+            .unwrap_or(self.passes.len());
+        self.passes.insert(
+            index,
+            Entry {
+                pass: Box::new(pass),
+                enabled: true,
+            },
+        );
+    }
+
+    /// Replaces the pass having the same name, keeping its enabled state.
+    /// Returns whether the pass was found.
+    pub fn replace(&mut self, pass: impl Pass + 'static) -> bool {
+        self.passes
+            .iter_mut()
+            .find(|entry| entry.pass.name() == pass.name())
+            .map(|entry| entry.pass = Box::new(pass))
+            .is_some()
+    }
+
+    /// Enables or disables the pass with the given name.
+    /// Returns whether the pass was found.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        self.passes
+            .iter_mut()
+            .find(|entry| entry.pass.name() == name)
+            .map(|entry| entry.enabled = enabled)
+            .is_some()
+    }
+
+    /// Returns the name, stage and enabled state of each pass, in execution order.
+    pub fn passes(&self) -> impl Iterator<Item = (&'static str, Stage, bool)> + '_ {
+        self.passes
+            .iter()
+            .map(|entry| (entry.pass.name(), entry.pass.stage(), entry.enabled))
+    }
+
+    /// Runs all the enabled passes on the context, then reports the errors
+    /// they recovered from.
+    pub fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        self.run_stages(Stage::Preprocess..=Stage::Emit, context)?;
+        context.take_errors()
+    }
+
+    /// Runs the enabled passes belonging to the given stages on the context.
+    /// Stops at the first error a pass can't recover from, or before the next
+    /// pass if the context's token was cancelled. The errors recovered from
+    /// are left in the context.
+    pub fn run_stages(
+        &self,
+        stages: RangeInclusive<Stage>,
+        context: &mut Context,
+    ) -> Result<(), AssemblerError> {
+        for entry in self
+            .passes
+            .iter()
+            .filter(|entry| entry.enabled && stages.contains(&entry.pass.stage()))
+        {
+            context.cancellation.check()?;
+            let start = Instant::now();
+            entry.pass.run(context)?;
+            tracing::debug!(
+                pass = entry.pass.name(),
+                stage = ?entry.pass.stage(),
+                elapsed = ?start.elapsed(),
+                "ran pass"
+            );
+        }
+        Ok(context.cancellation.check()?)
+    }
+}
+
+/// Normalizes the line endings and whitespace of the source, failing if the
+/// source exceeds the size limit.
+pub struct Preprocess;
+
+impl Pass for Preprocess {
+    fn name(&self) -> &'static str {
+        "preprocess"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Preprocess
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        if context.source.len() > context.limits.max_source_bytes {
+            return Err(AssemblerError::semantic(format!(
+                "source exceeds the limit of {} bytes",
+                context.limits.max_source_bytes
+            )));
+        }
+        if let Cow::Owned(source) = normalize(&context.source) {
+            context.source = source;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the source into a program, recording the errors of the invalid
+/// instructions and leaving them out. Fails if the program exceeds the
+/// instruction limit or doesn't fit in the ROM.
+#[derive(Default)]
+pub struct Parse {
+    /// Whether comps with their operands swapped, such as `A+D`, are accepted
+    /// as their canonical spelling.
+    pub accept_commuted: bool,
+}
+
+impl Pass for Parse {
+    fn name(&self) -> &'static str {
+        "parse"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Parse
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        let mut comments = BlockComments::default();
+        let source = comments.blank(&context.source);
+        let parser = Parser::from_source(&source).accept_commuted(self.accept_commuted);
+        let (program, errors) = Program::parse_recovering_with(parser);
+        context.program = program;
+        context.errors.extend(errors);
+        context.errors.extend(comments.finish().err());
+
+        let instructions = context.program.rom_address(context.program.len()) as usize;
+        if instructions > context.limits.max_instructions {
+            return Err(AssemblerError::semantic(format!(
+                "program exceeds the limit of {} instructions",
+                context.limits.max_instructions
+            )));
+        }
+        // The labels past the ROM don't fit in A-instructions, the errors of
+        // their uses would only be noise.
+        if let Some(error) = context.program.rom_overflow() {
+            context.errors.push(error);
+            return context.take_errors();
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the labels and variables of the program, recording the errors of
+/// the labels defined twice. Fails if the program exceeds the symbol limit.
+pub struct Resolve;
+
+impl Pass for Resolve {
+    fn name(&self) -> &'static str {
+        "resolve"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Resolve
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        context.symbol_table = context.program.resolve();
+        context.errors.extend(context.program.duplicate_labels());
+        let max_address = context.limits.max_variable_address;
+        if let Some((variable, address)) = context.symbol_table.variable_past(max_address) {
+            let line = context.program.first_use(variable);
+            context
+                .errors
+                .push(out_of_ram(variable, address, &context.limits).at_line(line));
+        }
+
+        if context.symbol_table.len() > context.limits.max_symbols {
+            return Err(AssemblerError::semantic(format!(
+                "program exceeds the limit of {} symbols",
+                context.limits.max_symbols
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Lowers the program to its IR.
+pub struct Lower;
+
+impl Pass for Lower {
+    fn name(&self) -> &'static str {
+        "lower"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Resolve
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        context.ir = Ir::lower(&context.program, &context.symbol_table);
+        Ok(())
+    }
+}
+
+/// Encodes the IR into binary words, recording the errors of the
+/// instructions that can't be encoded.
+pub struct Encode;
+
+impl Pass for Encode {
+    fn name(&self) -> &'static str {
+        "encode"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Encode
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        let mut words = Vec::with_capacity(context.ir.nodes.len());
+        for chunk in context.ir.nodes.chunks(CANCELLATION_CHUNK) {
+            if context.cancellation.is_cancelled() {
+                return Ok(());
+            }
+            for node in chunk {
+                let word = match &node.instruction {
+                    IrInstruction::A { value, .. } => a_instruction(*value),
+                    IrInstruction::C { dest, comp, jump } => c_instruction(dest, comp, jump),
+                };
+                match word {
+                    Ok(word) => words.push(word),
+                    Err(error) => context.errors.push(error.at_line(node.line)),
+                }
+            }
+        }
+        context.words = words;
+        Ok(())
+    }
+}
+
+/// The output formats of the emit pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum EmitFormat {
+    /// The binary words of a `.hack` file.
+    Hack,
+    /// The pretty-printed IR.
+    Ir,
+    /// The control-flow graph, in the Graphviz DOT language.
+    Cfg,
+    /// The call graph of VM-translated code, with per-function instruction counts.
+    Calls,
+    /// The read and write counts of the registers and variables.
+    Usage,
+    /// The versioned JSON snapshot of the assembled program.
+    Snapshot,
+    /// The memory map of the program, as Markdown.
+    MemoryMap,
+    /// The memory map of the program, as JSON.
+    MemoryMapJson,
+}
+
+impl EmitFormat {
+    /// Returns the extension of the output file.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            EmitFormat::Hack => "hack",
+            EmitFormat::Ir => "ir",
+            EmitFormat::Cfg => "dot",
+            EmitFormat::Calls => "calls",
+            EmitFormat::Usage => "usage",
+            EmitFormat::Snapshot => "snapshot.json",
+            EmitFormat::MemoryMap => "map.md",
+            EmitFormat::MemoryMapJson => "map.json",
+        }
+    }
+
+    /// Writes the output of the context in this format. The words of a
+    /// `.hack` file are written one by one.
+    pub fn write(&self, context: &Context, writer: &mut dyn Write) -> std::io::Result<()> {
+        let output = match self {
+            EmitFormat::Hack => {
+                for word in &context.words {
+                    writer.write_all(&binary_line(*word))?;
+                }
+                return Ok(());
+            }
+            EmitFormat::Ir => context.ir.to_string(),
+            EmitFormat::Cfg => Cfg::new(&context.ir).to_dot(&context.ir),
+            EmitFormat::Calls => CallGraph::new(&context.ir).to_string(),
+            EmitFormat::Usage => UsageReport::new(&context.ir, &context.symbol_table).to_string(),
+            EmitFormat::Snapshot => Snapshot::from_context(context).to_json(),
+            EmitFormat::MemoryMap => {
+                MemoryMap::from_ir(&context.ir, &context.symbol_table).to_markdown()
+            }
+            EmitFormat::MemoryMapJson => {
+                MemoryMap::from_ir(&context.ir, &context.symbol_table).to_json()
+            }
+        };
+        writer.write_all(output.as_bytes())
+    }
+}
+
+/// Returns the line of a word in a `.hack` file: its 16 bits, most
+/// significant first, followed by a newline.
+pub(crate) fn binary_line(word: u16) -> [u8; 17] {
+    let mut line = [b'\n'; 17];
+    for (index, digit) in line[..16].iter_mut().enumerate() {
+        *digit = b'0' + (word >> (15 - index) & 1) as u8;
+    }
+    line
+}
+
+/// Emits the output in the given format, to the writer of the context if any,
+/// failing if the output can't be written.
+pub struct Emit(pub EmitFormat);
+
+impl Pass for Emit {
+    fn name(&self) -> &'static str {
+        "emit"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Emit
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        match context.writer.take() {
+            Some(mut writer) => {
+                let written = self
+                    .0
+                    .write(context, &mut writer)
+                    .and_then(|()| writer.flush());
+                context.writer = Some(writer);
+                written
+                    .map_err(|error| AssemblerError::io("failed to write compiled output", error))
+            }
+            None => {
+                let mut output = Vec::new();
+                self.0.write(context, &mut output).expect("write to memory");
+                context.output = String::from_utf8(output).expect("outputs are UTF-8");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Count;
+
+    impl Pass for Count {
+        fn name(&self) -> &'static str {
+            "count"
+        }
+
+        fn stage(&self) -> Stage {
+            Stage::Analyze
+        }
+
+        fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+            context.output = context.program.len().to_string();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_pass_ordering_and_disabling() {
+        // Given
+        let mut manager = PassManager::default();
+        manager.add(Count);
+        manager.set_enabled("emit", false);
+        let mut context = Context::new(String::from("@1\r\nD=A\r\n"));
+
+        // When
+        manager.run(&mut context).unwrap();
+
+        // Then
+        assert_eq!(
+            vec![
+                "preprocess",
+                "parse",
+                "resolve",
+                "lower",
+                "count",
+                "encode",
+                "emit"
+            ],
+            manager
+                .passes()
+                .map(|(name, _, _)| name)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("2", context.output);
+        assert_eq!(2, context.words.len());
+    }
+
+    #[test]
+    fn test_cancelled_run() {
+        // Given
+        let manager = PassManager::default();
+        let mut context = Context::new(String::from("@1\nD=A\n"));
+        context.cancellation.cancel();
+
+        // When
+        let result = manager.run(&mut context);
+
+        // Then
+        assert!(matches!(result, Err(AssemblerError::Cancelled)));
+        assert!(context.program.is_empty());
+    }
+}
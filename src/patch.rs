@@ -0,0 +1,168 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{
+    assembler::Assembler,
+    error::AssemblerError,
+    pass::{Context, Pass, Stage},
+    program::Instruction,
+    snapshot::Snapshot,
+    symbol_table::SymbolTable,
+};
+
+/// The `0` C-instruction, which changes nothing: fills the replaced
+/// instructions left over by a shorter patch.
+pub const NOP: u16 = 0b1110_1010_1000_0000;
+
+/// Where a patch is applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatchTarget {
+    /// A label of the debug info.
+    Label(String),
+    /// A ROM address.
+    Address(u32),
+}
+
+impl PatchTarget {
+    /// Parses a ROM address such as `12`, or a label otherwise.
+    pub fn parse(target: &str) -> Self {
+        match target.parse::<u32>() {
+            Ok(address) => PatchTarget::Address(address),
+            Err(_) => PatchTarget::Label(target.to_string()),
+        }
+    }
+}
+
+/// Resolves the symbols of the patch against the debug info, the labels of
+/// the patch being placed from the patched address.
+struct ResolvePatch {
+    symbols: BTreeMap<String, u32>,
+    base: u32,
+}
+
+impl Pass for ResolvePatch {
+    fn name(&self) -> &'static str {
+        "resolve"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Resolve
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        let mut symbol_table = SymbolTable::with_capacity(self.symbols.len());
+        for (symbol, address) in &self.symbols {
+            symbol_table.add_label(symbol.clone(), *address);
+        }
+        let mut address = self.base;
+        for instruction in context.program.instructions() {
+            match instruction {
+                Instruction::L(label) => symbol_table.add_label(label.clone(), address),
+                _ => address += 1,
+            }
+        }
+        // The free RAM is unknown, so new variables can't be allocated.
+        for (index, instruction) in context.program.instructions().iter().enumerate() {
+            if let Instruction::A(symbol) = instruction {
+                if symbol_table.address(symbol).is_none() && symbol.parse::<u32>().is_err() {
+                    return Err(AssemblerError::semantic(format!(
+                        "unknown symbol {} in the patch",
+                        symbol
+                    ))
+                    .at_line(context.program.source_line(index)));
+                }
+            }
+        }
+        context.symbol_table = symbol_table;
+        Ok(())
+    }
+}
+
+/// Replaces the instructions of the ROM at the target with the assembled
+/// code, resolving its symbols with the debug info. `count` instructions are
+/// replaced, the length of the code by default, the ones left over becoming
+/// [`NOP`]s. Returns the range of replaced addresses.
+///
+/// Returns a semantic error:
+///
+/// - if the debug info doesn't describe the ROM,
+/// - if the label is unknown, or the code uses an unknown symbol,
+/// - if the code is longer than `count`, or goes past the end of the ROM.
+///
+/// The errors of the code are located at their line in the code.
+pub fn patch(
+    rom: &mut [u16],
+    debug: &Snapshot,
+    target: &PatchTarget,
+    code: &str,
+    count: Option<usize>,
+) -> Result<Range<usize>, AssemblerError> {
+    if debug.words != rom {
+        return Err(AssemblerError::semantic(
+            "the debug info doesn't match the ROM",
+        ));
+    }
+    let base = match target {
+        PatchTarget::Address(address) => *address,
+        PatchTarget::Label(label) => *debug
+            .symbols
+            .get(label)
+            .ok_or_else(|| AssemblerError::semantic(format!("unknown label {}", label)))?,
+    };
+
+    let mut assembler = Assembler::from_source(code);
+    assembler.passes_mut().replace(ResolvePatch {
+        symbols: debug.symbols.clone(),
+        base,
+    });
+    let words = assembler.fill_symbol_table().try_assemble()?;
+
+    let count = count.unwrap_or(words.len());
+    if words.len() > count {
+        return Err(AssemblerError::semantic(format!(
+            "the patch of {} instructions doesn't fit in the {} replaced",
+            words.len(),
+            count
+        )));
+    }
+    let start = base as usize;
+    if start + count > rom.len() {
+        return Err(AssemblerError::semantic(format!(
+            "the patch at ROM[{}] goes past the end of the ROM of {} instructions",
+            start,
+            rom.len()
+        )));
+    }
+    let replaced = start..start + count;
+    rom[replaced.clone()].fill(NOP);
+    rom[start..start + words.len()].copy_from_slice(&words);
+    Ok(replaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pass::EmitFormat, testing};
+
+    #[test]
+    fn test_patch_at_label() {
+        // Given
+        let source = "@R0\nD=M\n(STORE)\n@R1\nM=D\n(END)\n@END\n0;JMP\n";
+        let debug = Snapshot::from_json(&testing::emit(source, EmitFormat::Snapshot)).unwrap();
+        let mut rom = debug.words.clone();
+
+        // When
+        let replaced = patch(
+            &mut rom,
+            &debug,
+            &PatchTarget::parse("STORE"),
+            "(SKIP)\n@SKIP\nD=D+A\n",
+            Some(3),
+        )
+        .unwrap();
+
+        // Then
+        assert_eq!(2..5, replaced);
+        assert_eq!(vec![2, 0b1110_0000_1001_0000, NOP], rom[2..5].to_vec());
+        assert_eq!(debug.words[5..], rom[5..]);
+    }
+}
@@ -0,0 +1,30 @@
+//! The stable public API of the crate.
+//!
+//! Items exported here follow semantic versioning: they are only removed or
+//! changed incompatibly in a new major version. Everything else may change
+//! in any release.
+//!
+//! ```
+//! use hack_assembler::prelude::*;
+//!
+//! let program = Program::parse("@2\nD=A\n")?;
+//! let words = Assembler::from_source(&program.to_string())
+//!     .fill_symbol_table()
+//!     .try_assemble()?;
+//! assert_eq!(2, words.len());
+//! # Ok::<(), AssemblerError>(())
+//! ```
+
+pub use crate::{
+    assembler::{Assembler, Initialized, Uninitialized},
+    cancel::{CancellationToken, Cancelled},
+    diagnostic::{Diagnostic, DiagnosticsSink, JsonCollector, Severity, TerminalSink},
+    error::AssemblerError,
+    hack,
+    ir::{Ir, IrInstruction, IrNode},
+    limits::Limits,
+    pass::{Context, EmitFormat, Pass, PassManager, Stage},
+    program::{Instruction, Program},
+    snapshot::{Snapshot, SnapshotError, SNAPSHOT_VERSION},
+    symbol_table::SymbolTable,
+};
@@ -0,0 +1,143 @@
+use std::{fmt::Write, path::Path};
+
+use crate::{
+    disassembler::{disassemble, reconstruct_symbols},
+    program::{Instruction, Program},
+};
+
+/// Returns the listing of a program loaded in ROM: its source if it was
+/// assembled from a file, or its disassembly if it was loaded from a `.hack` file.
+pub fn listing(path: &Path, rom: &[u16]) -> Program {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "hack")
+    {
+        let mut program = disassemble(rom);
+        reconstruct_symbols(&mut program);
+        return program;
+    }
+    let source = std::fs::read_to_string(path).expect("failed to read file");
+    Program::from_source(&source)
+}
+
+/// A run of instructions starting at a label, up to the next label.
+struct Region {
+    name: String,
+    start: u32,
+    end: u32,
+    count: u64,
+}
+
+/// Splits the program in label regions and sums their execution counts.
+fn regions(program: &Program, counts: &[u64]) -> Vec<Region> {
+    let mut regions = vec![Region {
+        name: String::from("<entry>"),
+        start: 0,
+        end: 0,
+        count: 0,
+    }];
+
+    for instruction in program.instructions() {
+        let region = regions.last_mut().expect("at least one region");
+        match instruction {
+            // A label right after another one starts the same region.
+            Instruction::L(label) if region.start == region.end => region.name = label.clone(),
+            Instruction::L(label) => {
+                let start = region.end;
+                regions.push(Region {
+                    name: label.clone(),
+                    start,
+                    end: start,
+                    count: 0,
+                });
+            }
+            _ => {
+                region.count += counts.get(region.end as usize).copied().unwrap_or(0);
+                region.end += 1;
+            }
+        }
+    }
+    regions.retain(|region| region.start != region.end);
+    regions
+}
+
+fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
+
+/// Returns the flat profile: the execution count of each label region, the
+/// most executed first.
+pub fn flat_report(program: &Program, counts: &[u64]) -> String {
+    let total = counts.iter().sum();
+    let mut regions = regions(program, counts);
+    regions.sort_by(|a, b| b.count.cmp(&a.count).then(a.start.cmp(&b.start)));
+
+    let mut report = format!("{:>12} {:>8}  region\n", "count", "%");
+    for region in regions {
+        writeln!(
+            report,
+            "{:>12} {:>7.2}%  {} ({}..{})",
+            region.count,
+            percent(region.count, total),
+            region.name,
+            region.start,
+            region.end
+        )
+        .expect("write to string");
+    }
+    writeln!(report, "{:>12} {:>7.2}%  total", total, 100.0).expect("write to string");
+    report
+}
+
+/// Returns the annotated listing: each instruction of the program preceded by
+/// its execution count.
+pub fn annotated_listing(program: &Program, counts: &[u64]) -> String {
+    let total = counts.iter().sum();
+    let mut listing = String::new();
+    let mut address = 0;
+    for instruction in program.instructions() {
+        if let Instruction::L(_) = instruction {
+            writeln!(listing, "{:>12} {:>8}  {}", "", "", instruction).expect("write to string");
+            continue;
+        }
+        let count = counts.get(address).copied().unwrap_or(0);
+        writeln!(
+            listing,
+            "{:>12} {:>7.2}%  {:>5}    {}",
+            count,
+            percent(count, total),
+            address,
+            instruction
+        )
+        .expect("write to string");
+        address += 1;
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flat_report() {
+        // Given
+        let program = Program::from_source("@3\nD=A\n(LOOP)\nD=D-1\n@LOOP\nD;JGT\n");
+        let counts = [1, 1, 3, 3, 3];
+
+        // When
+        let report = flat_report(&program, &counts);
+
+        // Then
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(
+            vec!["9", "81.82%", "LOOP", "(2..5)"],
+            lines[1].split_whitespace().collect::<Vec<_>>()
+        );
+        assert!(lines[2].ends_with("  <entry> (0..2)"));
+    }
+}
@@ -0,0 +1,545 @@
+use std::fmt;
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    assembler::Assembler,
+    backpatch::rom_overflow,
+    emulator::ROM_SIZE,
+    error::AssemblerError,
+    parser::{BlockComments, InstructionType, Parser},
+    symbol_table::SymbolTable,
+};
+
+/// A single Hack assembly instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// An A-instruction `@value`, where the value is a constant or a symbol.
+    A(String),
+    /// A C-instruction `dest=comp;jump`, where `dest` and `jump` can be empty.
+    C {
+        dest: String,
+        comp: String,
+        jump: String,
+    },
+    /// A label declaration `(symbol)`.
+    L(String),
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::A(value) => write!(f, "@{}", value),
+            Instruction::C { dest, comp, jump } => {
+                if !dest.is_empty() {
+                    write!(f, "{}=", dest)?;
+                }
+                write!(f, "{}", comp)?;
+                if !jump.is_empty() {
+                    write!(f, ";{}", jump)?;
+                }
+                Ok(())
+            }
+            Instruction::L(symbol) => write!(f, "({})", symbol),
+        }
+    }
+}
+
+/// A Hack assembly program, as a list of instructions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    /// The source line of each instruction, if it was parsed from a source.
+    source_lines: Vec<Option<usize>>,
+}
+
+impl Program {
+    /// Returns a new empty program.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the program from its source, a convenience for sources known to
+    /// be valid, such as in tests. Use [`Program::parse`] to handle invalid
+    /// sources.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the source contains an invalid instruction.
+    pub fn from_source(source: &str) -> Self {
+        Self::parse(source).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Parses the program from its source, or returns the syntax errors of
+    /// all its invalid instructions.
+    pub fn parse(source: &str) -> Result<Self, AssemblerError> {
+        let (program, errors) = Self::parse_recovering(source);
+        AssemblerError::combine(errors)?;
+        Ok(program)
+    }
+
+    /// Parses the program from its source, skipping the invalid instructions.
+    /// Returns the program of the valid instructions along with the syntax
+    /// errors of the invalid ones, in source order.
+    pub fn parse_recovering(source: &str) -> (Self, Vec<AssemblerError>) {
+        let mut comments = BlockComments::default();
+        let source = comments.blank(source);
+        let (program, mut errors) = Self::parse_recovering_with(Parser::from_source(&source));
+        errors.extend(comments.finish().err());
+        (program, errors)
+    }
+
+    /// Parses the program with the parser, as configured, skipping the
+    /// invalid instructions like [`Program::parse_recovering`].
+    pub fn parse_recovering_with(mut parser: Parser) -> (Self, Vec<AssemblerError>) {
+        let mut program = Self::new();
+        let mut errors = Vec::new();
+
+        while parser.has_more_lines() {
+            parser.advance();
+            match Self::parse_instruction(&parser) {
+                Ok(instruction) => program.push_with_line(instruction, Some(parser.line())),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        (program, errors)
+    }
+
+    /// Returns the current instruction of the parser.
+    fn parse_instruction(parser: &Parser) -> Result<Instruction, AssemblerError> {
+        Ok(match parser.instruction_type()? {
+            InstructionType::A => Instruction::A(parser.symbol()?.into_owned()),
+            InstructionType::C => Instruction::C {
+                dest: parser.dest()?.into_owned(),
+                comp: parser.comp()?.into_owned(),
+                jump: parser.jump()?.into_owned(),
+            },
+            InstructionType::L => Instruction::L(parser.symbol()?.into_owned()),
+        })
+    }
+
+    /// Returns the instructions of the program.
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Returns the source line of the instruction at the given index, if any.
+    pub fn source_line(&self, index: usize) -> Option<usize> {
+        self.source_lines.get(index).copied().flatten()
+    }
+
+    /// Returns the source line of the first A-instruction loading the symbol,
+    /// if any.
+    pub fn first_use(&self, symbol: &str) -> Option<usize> {
+        let index = self.instructions.iter().position(
+            |instruction| matches!(instruction, Instruction::A(loaded) if loaded == symbol),
+        )?;
+        self.source_line(index)
+    }
+
+    /// Returns the number of instructions, labels included.
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Returns whether the program has no instructions.
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// Appends an instruction at the end of the program.
+    pub fn push(&mut self, instruction: Instruction) {
+        self.push_with_line(instruction, None);
+    }
+
+    fn push_with_line(&mut self, instruction: Instruction, line: Option<usize>) {
+        self.instructions.push(instruction);
+        self.source_lines.push(line);
+    }
+
+    /// Inserts an instruction at the given index.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, instruction: Instruction) {
+        self.instructions.insert(index, instruction);
+        self.source_lines.insert(index, None);
+    }
+
+    /// Removes and returns the instruction at the given index.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Instruction {
+        self.source_lines.remove(index);
+        self.instructions.remove(index)
+    }
+
+    /// Replaces the instruction at the given index, returning the previous one.
+    /// The replacement keeps the source line of the previous instruction.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn replace(&mut self, index: usize, instruction: Instruction) -> Instruction {
+        std::mem::replace(&mut self.instructions[index], instruction)
+    }
+
+    /// Returns the index of the declaration of the label.
+    pub fn label_index(&self, label: &str) -> Option<usize> {
+        self.instructions
+            .iter()
+            .position(|i| matches!(i, Instruction::L(symbol) if symbol == label))
+    }
+
+    /// Removes the declaration of the label and returns whether it was found.
+    /// References to the label are left untouched.
+    pub fn remove_label(&mut self, label: &str) -> bool {
+        self.label_index(label)
+            .map(|index| self.remove(index))
+            .is_some()
+    }
+
+    /// Renames a symbol in its label declaration and all its references.
+    pub fn rename_symbol(&mut self, from: &str, to: &str) {
+        for index in 0..self.instructions.len() {
+            let renamed = match &self.instructions[index] {
+                Instruction::A(symbol) if symbol == from => Instruction::A(to.to_string()),
+                Instruction::L(symbol) if symbol == from => Instruction::L(to.to_string()),
+                _ => continue,
+            };
+            self.replace(index, renamed);
+        }
+    }
+
+    /// Returns the ROM address of the instruction at the given index. Labels
+    /// resolve to the address of the instruction following them.
+    pub fn rom_address(&self, index: usize) -> u32 {
+        self.instructions[..index]
+            .iter()
+            .filter(|i| !matches!(i, Instruction::L(_)))
+            .count() as u32
+    }
+
+    /// Returns an error if the program has more instructions than the ROM
+    /// holds, located at the first instruction past it.
+    pub fn rom_overflow(&self) -> Option<AssemblerError> {
+        let mut indices = self
+            .instructions
+            .iter()
+            .enumerate()
+            .filter(|(_, instruction)| !matches!(instruction, Instruction::L(_)))
+            .map(|(index, _)| index);
+        let index = indices.nth(ROM_SIZE)?;
+        let count = ROM_SIZE + 1 + indices.count();
+        Some(rom_overflow(count).at_line(self.source_line(index)))
+    }
+
+    /// Returns an error for each redefinition of a label, located at the
+    /// redefinition with the line of the first definition in a note.
+    pub fn duplicate_labels(&self) -> Vec<AssemblerError> {
+        let mut first: FxHashMap<&str, usize> = FxHashMap::default();
+        let mut errors = Vec::new();
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let Instruction::L(label) = instruction else {
+                continue;
+            };
+            let Some(&defined) = first.get(label.as_str()) else {
+                first.insert(label, index);
+                continue;
+            };
+            let mut error = AssemblerError::semantic(format!("label {} is defined twice", label))
+                .at_line(self.source_line(index));
+            if let Some(line) = self.source_line(defined) {
+                error = error.with_note(format!("first defined on line {}", line));
+            }
+            errors.push(error);
+        }
+        errors
+    }
+
+    /// Resolves the labels and variables of the program against its current
+    /// instructions. Must be called again after the program is modified.
+    pub fn resolve(&self) -> SymbolTable {
+        let labels = self
+            .instructions
+            .iter()
+            .filter(|i| matches!(i, Instruction::L(_)))
+            .count();
+        let mut symbol_table = SymbolTable::with_capacity(labels);
+
+        let mut address = 0;
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::L(symbol) => symbol_table.add_label(symbol.clone(), address),
+                _ => address += 1,
+            }
+        }
+
+        for instruction in &self.instructions {
+            if let Instruction::A(symbol) = instruction {
+                if symbol_table.address(symbol).is_none() && str::parse::<u32>(symbol).is_err() {
+                    symbol_table.add_variable(symbol.clone());
+                }
+            }
+        }
+
+        symbol_table
+    }
+
+    /// Assembles the program and returns the binary words, one per instruction,
+    /// a convenience for programs known to be valid. Use
+    /// [`Program::try_assemble`] to handle invalid programs.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the program can't be assembled.
+    pub fn assemble(&self) -> Vec<u16> {
+        self.try_assemble()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Assembles the program and returns the binary words, one per instruction,
+    /// or an error if the program can't be assembled, such as when a constant
+    /// is out of range.
+    pub fn try_assemble(&self) -> Result<Vec<u16>, AssemblerError> {
+        Assembler::from_source(&self.to_string())
+            .fill_symbol_table()
+            .try_assemble()
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for instruction in &self.instructions {
+            writeln!(f, "{}", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds a [`Program`] from Hack assembly written inline, or returns the
+/// syntax errors of its invalid instructions, like [`Program::parse`].
+///
+/// Instructions are separated by `;`, except before a jump mnemonic, and
+/// labels don't need a separator. Rust values can be interpolated in
+/// A-instructions with `@{expr}`.
+///
+/// ```
+/// use hack_assembler::{error::AssemblerError, hack};
+///
+/// let n = 10;
+/// let program = hack! {
+///     @{n}; D=A; @i; M=D;
+///     (LOOP)
+///     @i; M=M-1; D=M;
+///     @LOOP; D;JGT
+/// }?;
+/// assert_eq!(9, program.try_assemble()?.len());
+/// # Ok::<(), AssemblerError>(())
+/// ```
+#[macro_export]
+macro_rules! hack {
+    // Labels end on their own.
+    (@munch [$($lines:expr,)*] [] ($($label:tt)*) $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)* concat!("(", stringify!($($label)*), ")").to_string(),] [] $($rest)*)
+    };
+    // Interpolated A-instruction values.
+    (@munch [$($lines:expr,)*] [] @ {$value:expr} $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)* format!("@{}", $value),] [] $($rest)*)
+    };
+    // A separator followed by a jump mnemonic belongs to the current instruction.
+    (@munch [$($lines:expr,)*] [$($current:tt)+] ; JGT $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)*] [$($current)+ ; JGT] $($rest)*)
+    };
+    (@munch [$($lines:expr,)*] [$($current:tt)+] ; JEQ $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)*] [$($current)+ ; JEQ] $($rest)*)
+    };
+    (@munch [$($lines:expr,)*] [$($current:tt)+] ; JGE $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)*] [$($current)+ ; JGE] $($rest)*)
+    };
+    (@munch [$($lines:expr,)*] [$($current:tt)+] ; JLT $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)*] [$($current)+ ; JLT] $($rest)*)
+    };
+    (@munch [$($lines:expr,)*] [$($current:tt)+] ; JNE $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)*] [$($current)+ ; JNE] $($rest)*)
+    };
+    (@munch [$($lines:expr,)*] [$($current:tt)+] ; JLE $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)*] [$($current)+ ; JLE] $($rest)*)
+    };
+    (@munch [$($lines:expr,)*] [$($current:tt)+] ; JMP $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)*] [$($current)+ ; JMP] $($rest)*)
+    };
+    // Any other separator ends the current instruction.
+    (@munch [$($lines:expr,)*] [$($current:tt)*] ; $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)* stringify!($($current)*).to_string(),] [] $($rest)*)
+    };
+    (@munch [$($lines:expr,)*] [$($current:tt)*] $token:tt $($rest:tt)*) => {
+        $crate::hack!(@munch [$($lines,)*] [$($current)* $token] $($rest)*)
+    };
+    (@munch [$($lines:expr,)*] [$($current:tt)*]) => {
+        $crate::program::Program::parse(
+            &[$($lines,)* stringify!($($current)*).to_string()].join("\n"),
+        )
+    };
+
+    ($($tokens:tt)*) => {
+        $crate::hack!(@munch [] [] $($tokens)*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_after_insert() {
+        // Given
+        let mut program = Program::from_source("@END\n0;JMP\n(END)\n@x\nM=0\n");
+
+        // When
+        program.insert(0, Instruction::A(String::from("y")));
+        let symbol_table = program.resolve();
+
+        // Then
+        assert_eq!(Some(&3), symbol_table.address("END"));
+        assert_eq!(Some(&16), symbol_table.address("y"));
+        assert_eq!(Some(&17), symbol_table.address("x"));
+    }
+
+    #[test]
+    fn test_trailing_comments_are_ignored() {
+        // Given
+        let commented =
+            "@5 // five\nD=M // load value; x=1\n(LOOP) // loop\n@LOOP// tight\n0;JMP//go\n";
+        let plain = "@5\nD=M\n(LOOP)\n@LOOP\n0;JMP\n";
+
+        // When
+        let program = Program::parse(commented).unwrap();
+
+        // Then
+        assert_eq!(plain, program.to_string());
+        assert_eq!(Program::from_source(plain).assemble(), program.assemble());
+    }
+
+    #[test]
+    fn test_block_comments_are_ignored() {
+        // Given
+        let source = "/* Header\n   spanning lines */\n@5 /* five */\nD=A\n/*\n@6\n*/(END)\n";
+
+        // When
+        let program = Program::parse(source).unwrap();
+
+        // Then
+        assert_eq!("@5\nD=A\n(END)\n", program.to_string());
+        assert_eq!(Some(7), program.source_line(2));
+    }
+
+    #[test]
+    fn test_parse_recovers_from_invalid_instructions() {
+        // Given
+        let source = "@1\nD=X\n(LOOP\nD;JXX\n@LOOP\n0;JMP\n";
+
+        // When
+        let (program, errors) = Program::parse_recovering(source);
+
+        // Then
+        assert_eq!(3, program.len());
+        assert_eq!(
+            vec![
+                "line 2:3: unexpected comp X",
+                "line 3:1: malformed label (LOOP, expected (LABEL)",
+                "line 4:3: unexpected jump JXX",
+            ],
+            errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_assembler_reports_all_errors() {
+        // Given
+        let source = "@40000\nD=X\n@1\n@32768\n0;JXX\n";
+
+        // When
+        let error = Assembler::from_source(source)
+            .fill_symbol_table()
+            .try_assemble()
+            .unwrap_err();
+
+        // Then
+        assert_eq!(
+            vec![Some(1), Some(2), Some(4), Some(5)],
+            error
+                .errors()
+                .iter()
+                .map(|error| error.line())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_labels_are_errors() {
+        // Given
+        let source = "(LOOP)\n@LOOP\n0;JMP\n\n(LOOP)\nD=0\n(LOOP)\n";
+
+        // When
+        let error = Assembler::from_source(source)
+            .fill_symbol_table()
+            .try_assemble()
+            .unwrap_err();
+
+        // Then
+        assert_eq!(
+            "line 5: label LOOP is defined twice\nline 7: label LOOP is defined twice",
+            error.to_string()
+        );
+        assert!(matches!(
+            &error.errors()[1],
+            AssemblerError::Semantic { note: Some(note), .. } if note == "first defined on line 1"
+        ));
+    }
+
+    #[test]
+    fn test_resolve_after_rename() {
+        // Given
+        let mut program = Program::from_source("(LOOP)\n@i\nM=0\n@LOOP\n0;JMP\n");
+
+        // When
+        program.rename_symbol("LOOP", "i");
+        program.rename_symbol("i", "j");
+        program.replace(1, Instruction::A(String::from("k")));
+        let symbol_table = program.resolve();
+
+        // Then
+        assert_eq!(Some(&0), symbol_table.address("j"));
+        assert_eq!(Some(&16), symbol_table.address("k"));
+        assert_eq!(None, symbol_table.address("LOOP"));
+        assert_eq!(None, symbol_table.address("i"));
+    }
+
+    #[test]
+    fn test_hack_macro() {
+        // Given
+        let address = 16384;
+
+        // When
+        let program = hack! {
+            @{address}; D=A; @R0; M=D;
+            (END)
+            @END; 0;JMP
+        }
+        .unwrap();
+
+        // Then
+        assert_eq!(
+            "@16384\nD=A\n@R0\nM=D\n(END)\n@END\n0;JMP\n",
+            program.to_string()
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    error::{self, AssemblerError},
+    linker::{self, Executable, Layout},
+    object::{self, Library, Object},
+};
+
+/// The name of the project manifest.
+pub const MANIFEST_FILE: &str = "hackproj.toml";
+
+/// The formats of the program built from a project.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// The binary words of a `.hack` file.
+    #[default]
+    Hack,
+    /// The raw big-endian words of a ROM image.
+    Raw,
+}
+
+/// A multi-file project, read from a `hackproj.toml` manifest. Paths are
+/// relative to the directory of the manifest.
+///
+/// ```toml
+/// name = "Pong"
+/// sources = ["Main.asm", "Math.asm"]
+/// include = ["lib"]
+/// libraries = ["os.hlib"]
+///
+/// [defines]
+/// PADDLE_WIDTH = 8
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// The name of the program, and of its output by default.
+    pub name: String,
+    /// The assembly files, laid out in the ROM in this order.
+    pub sources: Vec<PathBuf>,
+    /// The directories searched, in order, for the sources and libraries not
+    /// found next to the manifest.
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+    /// The `.hlib` libraries, linked after the sources as needed.
+    #[serde(default)]
+    pub libraries: Vec<PathBuf>,
+    /// The constants the symbols of the sources resolve to, before labels
+    /// and variables.
+    #[serde(default)]
+    pub defines: BTreeMap<String, u16>,
+    /// The JSON linker layout, if any.
+    #[serde(default)]
+    pub layout: Option<PathBuf>,
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// The path of the program, `<name>.hack` by default.
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+}
+
+/// A built project.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Build {
+    pub executable: Executable,
+    /// Where the program is written.
+    pub output: PathBuf,
+}
+
+impl Manifest {
+    /// Parses a manifest.
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    /// Loads the manifest from a file. Returns an error if the file can't be
+    /// read or is not a valid manifest.
+    pub fn load(path: &Path) -> Result<Self, AssemblerError> {
+        Self::from_toml(&error::read_to_string(path)?).map_err(|error| {
+            AssemblerError::syntax(format!(
+                "{}: invalid project manifest: {}",
+                path.display(),
+                error
+            ))
+        })
+    }
+
+    /// Assembles the sources, applies the defines and links the program,
+    /// with the paths relative to the root directory.
+    ///
+    /// Returns an error if a file can't be found or read, if a source fails
+    /// to assemble, if a define doesn't fit in an A-instruction or is also a
+    /// label, or if the program fails to link.
+    pub fn build(&self, root: &Path) -> Result<Build, AssemblerError> {
+        let mut objects = Vec::new();
+        for source in &self.sources {
+            let path = self.find(root, source)?;
+            let name = path
+                .file_stem()
+                .map_or_else(|| self.name.clone(), |stem| stem.to_string_lossy().into());
+            let source = error::read_to_string(&path)?;
+            objects.push(Object::assemble(&name, &source)?);
+        }
+        for (symbol, value) in &self.defines {
+            for object in &mut objects {
+                object.define(symbol, *value)?;
+            }
+        }
+
+        let libraries = self
+            .libraries
+            .iter()
+            .map(|library| object::read_library(&self.find(root, library)?))
+            .collect::<Result<Vec<Library>, _>>()?;
+        let layout = match &self.layout {
+            Some(layout) => Layout::load(&root.join(layout))?,
+            None => Layout::default(),
+        };
+
+        let executable = linker::link_with_layout(objects, &libraries, &layout)?;
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(&self.name).with_extension("hack"));
+        Ok(Build {
+            executable,
+            output: root.join(output),
+        })
+    }
+
+    /// Returns the path of the file next to the manifest, or else in the
+    /// first include directory having it. Returns an error if the file is
+    /// nowhere.
+    fn find(&self, root: &Path, file: &Path) -> Result<PathBuf, AssemblerError> {
+        std::iter::once(root.to_path_buf())
+            .chain(self.include.iter().map(|include| root.join(include)))
+            .map(|directory| directory.join(file))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                AssemblerError::io(
+                    format!("{} not found in the include paths", file.display()),
+                    io::ErrorKind::NotFound.into(),
+                )
+            })
+    }
+}
+
+impl Build {
+    /// Returns the program in the format.
+    pub fn render(&self, format: OutputFormat) -> Vec<u8> {
+        let words = &self.executable.words;
+        match format {
+            OutputFormat::Hack => words
+                .iter()
+                .flat_map(|word| format!("{:016b}\n", word).into_bytes())
+                .collect(),
+            OutputFormat::Raw => words.iter().flat_map(|word| word.to_be_bytes()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_build_project() {
+        // Given
+        let root = TempDir::new("hack-project");
+        std::fs::create_dir_all(root.join("lib")).unwrap();
+        std::fs::write(root.join("Main.asm"), "@WIDTH\nD=A\n@DOUBLE\n0;JMP\n").unwrap();
+        std::fs::write(
+            root.join("lib/Double.asm"),
+            "(DOUBLE)\n@x\nM=D\nD=D+M\n(END)\n@END\n0;JMP\n",
+        )
+        .unwrap();
+        let manifest = Manifest::from_toml(
+            "name = \"Main\"\nsources = [\"Main.asm\", \"Double.asm\"]\ninclude = [\"lib\"]\n\n[defines]\nWIDTH = 21\n",
+        )
+        .unwrap();
+
+        // When
+        let build = manifest.build(&root);
+
+        // Then
+        let build = build.unwrap();
+        assert_eq!(root.join("Main.hack"), build.output);
+        assert_eq!(21, build.executable.words[0]);
+        assert_eq!(4, build.executable.words[2]);
+        assert_eq!(Some(&16), build.executable.symbols.get("x"));
+        assert_eq!(None, build.executable.symbols.get("WIDTH"));
+    }
+}
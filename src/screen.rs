@@ -0,0 +1,93 @@
+use clap::ValueEnum;
+
+/// The RAM address of the memory-mapped screen.
+pub const SCREEN: usize = 16384;
+/// The width of the screen, in pixels.
+pub const WIDTH: usize = 512;
+/// The height of the screen, in pixels.
+pub const HEIGHT: usize = 256;
+
+/// The characters used to draw the screen in a terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Charset {
+    /// Braille characters, 2×4 pixels per character (256×64 characters).
+    Braille,
+    /// Half-block characters, 1×2 pixels per character (512×128 characters).
+    HalfBlock,
+}
+
+/// Returns whether the pixel at the given coordinates is black.
+/// The pixels of a row are stored 16 per word, the leftmost in the least significant bit.
+pub fn pixel(ram: &[u16], x: usize, y: usize) -> bool {
+    let word = ram[SCREEN + y * WIDTH / 16 + x / 16];
+    word & (1 << (x % 16)) != 0
+}
+
+/// Renders the screen memory as text, one line per character row.
+pub fn render(ram: &[u16], charset: Charset) -> String {
+    match charset {
+        Charset::Braille => render_braille(ram),
+        Charset::HalfBlock => render_half_block(ram),
+    }
+}
+
+fn render_braille(ram: &[u16]) -> String {
+    // The dot bits of a braille character, indexed by [row][column].
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+    let mut output = String::with_capacity((WIDTH / 2 + 1) * HEIGHT / 4 * 3);
+    for y in (0..HEIGHT).step_by(4) {
+        for x in (0..WIDTH).step_by(2) {
+            let mut dots = 0;
+            for (row, bits) in DOTS.iter().enumerate() {
+                for (column, bit) in bits.iter().enumerate() {
+                    if pixel(ram, x + column, y + row) {
+                        dots |= bit;
+                    }
+                }
+            }
+            output.push(char::from_u32(0x2800 + dots).expect("valid braille character"));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn render_half_block(ram: &[u16]) -> String {
+    let mut output = String::with_capacity((WIDTH + 1) * HEIGHT / 2 * 3);
+    for y in (0..HEIGHT).step_by(2) {
+        for x in 0..WIDTH {
+            output.push(match (pixel(ram, x, y), pixel(ram, x, y + 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::RAM_SIZE;
+
+    #[test]
+    fn test_render_braille() {
+        // Given
+        let mut ram = vec![0; RAM_SIZE];
+        // The two leftmost pixels of the first two rows.
+        ram[SCREEN] = 0b11;
+        ram[SCREEN + WIDTH / 16] = 0b11;
+
+        // When
+        let output = render(&ram, Charset::Braille);
+
+        // Then
+        let first_line = output.lines().next().unwrap();
+        assert_eq!(WIDTH / 2, first_line.chars().count());
+        assert!(first_line.starts_with("⠛⠀"));
+    }
+}
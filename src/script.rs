@@ -0,0 +1,225 @@
+use std::path::Path;
+
+use rhai::{Engine, EvalAltResult, Position};
+
+use crate::{
+    assembler::Assembler,
+    emulator::{load_program, Emulator, Stop, RAM_SIZE},
+    error::AssemblerError,
+    keyboard::KBD,
+    symbol_table::SymbolTable,
+};
+
+/// An emulator driven by a script, with the symbols of its program.
+#[derive(Clone)]
+struct Machine {
+    emulator: Emulator,
+    symbol_table: SymbolTable,
+}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+fn error<T>(message: String) -> ScriptResult<T> {
+    Err(Box::new(EvalAltResult::ErrorRuntime(
+        message.into(),
+        Position::NONE,
+    )))
+}
+
+impl Machine {
+    fn address(&self, address: i64) -> ScriptResult<usize> {
+        match usize::try_from(address) {
+            Ok(address) if address < RAM_SIZE => Ok(address),
+            _ => error(format!("RAM address {} is out of bounds", address)),
+        }
+    }
+
+    fn symbol(&self, symbol: &str) -> ScriptResult<usize> {
+        match self.symbol_table.address(symbol) {
+            Some(address) => self.address(*address as i64),
+            None => error(format!("unknown symbol {}", symbol)),
+        }
+    }
+
+    fn peek(&mut self, address: usize) -> i64 {
+        self.emulator.ram()[address] as i16 as i64
+    }
+
+    fn poke(&mut self, address: usize, value: i64) -> ScriptResult<()> {
+        if !(i16::MIN as i64..=u16::MAX as i64).contains(&value) {
+            return error(format!("value {} doesn't fit in a word", value));
+        }
+        self.emulator.ram_mut()[address] = value as u16;
+        Ok(())
+    }
+
+    fn run(&mut self, cycles: i64) -> String {
+        let stop = self.emulator.run(cycles.max(0) as u64);
+        match stop {
+            Stop::Halted => "halted",
+            Stop::EndOfRom => "end",
+            Stop::CycleLimit => "limit",
+            Stop::Interrupted => "interrupted",
+            Stop::OutOfRam => "out_of_ram",
+        }
+        .to_string()
+    }
+}
+
+/// Returns the scripting engine with the emulator API registered.
+///
+/// - `load(path)` loads a program from a `.hack` or assembly file,
+///   `assemble(source)` from assembly source.
+/// - `m.peek(address)` and `m.poke(address, value)` read and write the RAM,
+///   addresses being numbers or symbols.
+/// - `m.run(cycles)` runs until the program halts (`"halted"`), reaches the end
+///   of the ROM (`"end"`) or executes the cycles (`"limit"`). `m.step()`
+///   executes a single instruction.
+/// - `m.press(key)` presses a key, given as a character or a Hack code,
+///   `m.release()` releases it.
+/// - `m.a`, `m.d`, `m.pc` and `m.cycles` return the registers.
+/// - `assert(condition, message)` and `assert_eq(actual, expected)` fail the script.
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.register_type_with_name::<Machine>("Machine");
+
+    engine.register_fn("load", |path: &str| -> ScriptResult<Machine> {
+        let (rom, symbol_table) =
+            load_program(Path::new(path)).or_else(|err| error(err.to_string()))?;
+        Ok(Machine {
+            emulator: Emulator::new(rom),
+            symbol_table,
+        })
+    });
+    engine.register_fn("assemble", |source: &str| {
+        let assembler = Assembler::from_source(source).fill_symbol_table();
+        let symbol_table = assembler.symbol_table().clone();
+        let rom = assembler.assemble();
+        Machine {
+            emulator: Emulator::new(rom),
+            symbol_table,
+        }
+    });
+
+    engine.register_fn(
+        "peek",
+        |machine: &mut Machine, address: i64| -> ScriptResult<i64> {
+            let address = machine.address(address)?;
+            Ok(machine.peek(address))
+        },
+    );
+    engine.register_fn(
+        "peek",
+        |machine: &mut Machine, symbol: &str| -> ScriptResult<i64> {
+            let address = machine.symbol(symbol)?;
+            Ok(machine.peek(address))
+        },
+    );
+    engine.register_fn(
+        "poke",
+        |machine: &mut Machine, address: i64, value: i64| -> ScriptResult<()> {
+            let address = machine.address(address)?;
+            machine.poke(address, value)
+        },
+    );
+    engine.register_fn(
+        "poke",
+        |machine: &mut Machine, symbol: &str, value: i64| -> ScriptResult<()> {
+            let address = machine.symbol(symbol)?;
+            machine.poke(address, value)
+        },
+    );
+
+    engine.register_fn("run", Machine::run);
+    engine.register_fn("step", |machine: &mut Machine| -> ScriptResult<()> {
+        if machine.emulator.is_at_end() {
+            return error(String::from("program counter is past the end of the ROM"));
+        }
+        machine.emulator.step();
+        Ok(())
+    });
+    engine.register_fn("press", |machine: &mut Machine, key: char| {
+        machine.poke(KBD, key as i64)
+    });
+    engine.register_fn("press", |machine: &mut Machine, code: i64| {
+        machine.poke(KBD, code)
+    });
+    engine.register_fn("release", |machine: &mut Machine| machine.poke(KBD, 0));
+
+    engine.register_get("a", |machine: &mut Machine| {
+        machine.emulator.a() as i16 as i64
+    });
+    engine.register_get("d", |machine: &mut Machine| {
+        machine.emulator.d() as i16 as i64
+    });
+    engine.register_get("pc", |machine: &mut Machine| machine.emulator.pc() as i64);
+    engine.register_get("cycles", |machine: &mut Machine| {
+        machine.emulator.cycles() as i64
+    });
+
+    engine.register_fn(
+        "assert",
+        |condition: bool, message: &str| -> ScriptResult<()> {
+            if condition {
+                Ok(())
+            } else {
+                error(format!("assertion failed: {}", message))
+            }
+        },
+    );
+    engine.register_fn(
+        "assert_eq",
+        |actual: i64, expected: i64| -> ScriptResult<()> {
+            if actual == expected {
+                Ok(())
+            } else {
+                error(format!(
+                    "assertion failed: expected {}, got {}",
+                    expected, actual
+                ))
+            }
+        },
+    );
+    engine
+}
+
+/// Runs a script driving the emulator, or returns a syntax error if the
+/// script is invalid, and a semantic error if it fails, with the position of
+/// the error.
+pub fn run(script: &str) -> Result<(), AssemblerError> {
+    engine().run(script).map_err(|err| {
+        let message = format!("script failed: {}", err);
+        match *err {
+            EvalAltResult::ErrorParsing(..) => AssemblerError::syntax(message),
+            _ => AssemblerError::semantic(message),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_script() {
+        // Given
+        let script = r#"
+            let m = load("test_data/max/Max.asm");
+            m.poke("R0", 3);
+            m.poke(1, -5);
+            assert(m.run(1000) == "halted", "Max halts");
+            assert_eq(m.peek(2), 3);
+        "#;
+
+        // When
+        let result = run(script);
+
+        // Then
+        assert!(result.is_ok(), "{:?}", result);
+        let error = run("assert_eq(1, 2);").unwrap_err();
+        assert!(matches!(error, AssemblerError::Semantic { .. }));
+        assert!(error.to_string().contains("expected 2, got 1"));
+        let error = run("let m = ;").unwrap_err();
+        assert!(matches!(error, AssemblerError::Syntax { .. }), "{}", error);
+    }
+}
@@ -0,0 +1,183 @@
+use std::io::{self, Read};
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{assembler::Assembler, diagnostic::Diagnostic, error::AssemblerError, limits::Limits};
+
+/// The result of assembling a program, as returned by the HTTP API.
+#[derive(Serialize, Debug, Default)]
+pub struct AssembleResponse {
+    /// The assembled binary words, one per instruction.
+    pub words: Vec<String>,
+    /// The diagnostics emitted while assembling the program.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Assembles the program source within the limits, through the same passes
+/// as the command line, converting any failure into diagnostics, located in
+/// the source when possible.
+pub fn assemble_source(source: &str, limits: Limits) -> AssembleResponse {
+    let result = Assembler::from_source(source)
+        .with_limits(limits)
+        .fill_symbol_table()
+        .try_assemble();
+    let (words, diagnostics) = match result {
+        Ok(words) => (words, Vec::new()),
+        Err(error) => (Vec::new(), error.to_diagnostics(None, source)),
+    };
+
+    AssembleResponse {
+        words: words.iter().map(|word| format!("{:016b}", word)).collect(),
+        diagnostics,
+    }
+}
+
+/// Serves the assembler over HTTP on the given address.
+///
+/// `POST /assemble` with the program source as body returns an [`AssembleResponse`] as JSON.
+/// Returns an error if the server can't listen on the address.
+pub fn serve(addr: &str, limits: Limits) -> Result<(), AssemblerError> {
+    let server = Server::http(addr).map_err(|error| {
+        AssemblerError::io(
+            format!("failed to listen on {}", addr),
+            io::Error::other(error),
+        )
+    })?;
+    println!("listening on http://{}", addr);
+
+    for request in server.incoming_requests() {
+        handle_request(request, limits);
+    }
+    Ok(())
+}
+
+fn handle_request(mut request: Request, limits: Limits) {
+    let cors = Header::from_bytes("Access-Control-Allow-Origin", "*").expect("valid header");
+
+    let response = match (request.method(), request.url()) {
+        (Method::Post, "/assemble") => {
+            let mut source = String::new();
+            // Read one byte past the limit so that oversized sources are reported.
+            let limit = limits.max_source_bytes.saturating_add(1) as u64;
+            if request
+                .as_reader()
+                .take(limit)
+                .read_to_string(&mut source)
+                .is_err()
+            {
+                Response::from_string("request body must be valid UTF-8").with_status_code(400)
+            } else {
+                let body = serde_json::to_string(&assemble_source(&source, limits))
+                    .expect("failed to serialize response");
+                let content_type =
+                    Header::from_bytes("Content-Type", "application/json").expect("valid header");
+                Response::from_string(body).with_header(content_type)
+            }
+        }
+        (Method::Options, _) => Response::from_string("")
+            .with_header(
+                Header::from_bytes("Access-Control-Allow-Methods", "POST, OPTIONS")
+                    .expect("valid header"),
+            )
+            .with_header(
+                Header::from_bytes("Access-Control-Allow-Headers", "Content-Type")
+                    .expect("valid header"),
+            ),
+        _ => Response::from_string("not found").with_status_code(404),
+    };
+
+    // The client might have disconnected, there is nothing left to do then.
+    let _ = request.respond(response.with_header(cors));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble_source() {
+        // Given
+        let source = "@2\nD=A\n(END)\n@END\n0;JMP\n";
+
+        // When
+        let response = assemble_source(source, Limits::default());
+
+        // Then
+        assert!(response.diagnostics.is_empty());
+        assert_eq!(
+            vec![
+                "0000000000000010",
+                "1110110000010000",
+                "0000000000000010",
+                "1110101010000111"
+            ],
+            response.words
+        );
+    }
+
+    #[test]
+    fn test_assemble_source_like_the_command_line() {
+        // Given
+        // A label shadowing a predefined symbol is known before its definition.
+        let source = "@R0\n(R0)\n@R0\n";
+
+        // When
+        let response = assemble_source(source, Limits::default());
+
+        // Then
+        assert_eq!(vec!["0000000000000001"; 2], response.words);
+    }
+
+    #[test]
+    fn test_serve_on_a_taken_address() {
+        // Given
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        // When
+        let error = serve(&addr, Limits::default()).unwrap_err();
+
+        // Then
+        assert!(matches!(error, AssemblerError::Io { .. }), "{}", error);
+    }
+
+    #[test]
+    fn test_assemble_source_invalid() {
+        // Given
+        let source = "D=Q\n";
+
+        // When
+        let response = assemble_source(source, Limits::default());
+
+        // Then
+        assert!(response.words.is_empty());
+        assert_eq!("unexpected comp Q", response.diagnostics[0].message);
+        assert_eq!(
+            Some((1, 3)),
+            response.diagnostics[0]
+                .location
+                .as_ref()
+                .map(|location| (location.line, location.column))
+        );
+    }
+
+    #[test]
+    fn test_assemble_source_exceeding_limits() {
+        // Given
+        let source = "@1\n@2\n@3\n";
+        let limits = Limits {
+            max_instructions: 2,
+            ..Limits::default()
+        };
+
+        // When
+        let response = assemble_source(source, limits);
+
+        // Then
+        assert_eq!(
+            "program exceeds the limit of 2 instructions",
+            response.diagnostics[0].message
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::pass::Context;
+
+/// The current version of the snapshot format. Bumped on any incompatible change.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The magic bytes at the start of a binary snapshot.
+const MAGIC: &[u8; 8] = b"HACKSNAP";
+
+/// An error raised when loading a snapshot.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The snapshot was written by an unsupported version of the format.
+    UnsupportedVersion(u32),
+    /// The snapshot is malformed.
+    Malformed(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::Malformed(reason) => write!(f, "malformed snapshot: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// A fully assembled program, which can be saved and loaded back without re-assembling.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The version of the snapshot format.
+    pub version: u32,
+    /// The binary words, one per ROM address.
+    pub words: Vec<u16>,
+    /// The symbols of the program and their addresses.
+    pub symbols: BTreeMap<String, u32>,
+    /// The source line of each ROM address, if known.
+    pub source_map: Vec<Option<usize>>,
+}
+
+impl Snapshot {
+    /// Returns the snapshot of a context which went through the encode stage.
+    pub fn from_context(context: &Context) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            words: context.words.clone(),
+            symbols: context
+                .symbol_table
+                .iter()
+                .map(|(symbol, address)| (symbol.to_string(), address))
+                .collect(),
+            source_map: context.ir.nodes.iter().map(|node| node.line).collect(),
+        }
+    }
+
+    /// Serializes the snapshot to JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("failed to serialize snapshot")
+    }
+
+    /// Loads a snapshot from JSON.
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        let version = serde_json::from_str::<serde_json::Value>(json)
+            .map_err(|err| SnapshotError::Malformed(err.to_string()))?
+            .get("version")
+            .and_then(|version| version.as_u64())
+            .ok_or_else(|| SnapshotError::Malformed(String::from("missing version")))?;
+        check_version(version as u32)?;
+
+        serde_json::from_str(json).map_err(|err| SnapshotError::Malformed(err.to_string()))
+    }
+
+    /// Serializes the snapshot to its binary format. All integers are little-endian:
+    /// - the magic bytes `HACKSNAP` and the `u32` version,
+    /// - the `u32` word count followed by the `u16` words,
+    /// - the `u32` symbol count followed by, for each symbol, its `u16` length,
+    ///   its UTF-8 bytes and its `u32` address,
+    /// - the `u32` source map length followed by the `u32` lines, where 0 is unknown.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+
+        bytes.extend_from_slice(&(self.words.len() as u32).to_le_bytes());
+        for word in &self.words {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.symbols.len() as u32).to_le_bytes());
+        for (symbol, address) in &self.symbols {
+            bytes.extend_from_slice(&(symbol.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(symbol.as_bytes());
+            bytes.extend_from_slice(&address.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.source_map.len() as u32).to_le_bytes());
+        for line in &self.source_map {
+            bytes.extend_from_slice(&(line.unwrap_or_default() as u32).to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Loads a snapshot from its binary format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::Malformed(String::from(
+                "invalid magic bytes",
+            )));
+        }
+        let version = reader.u32()?;
+        check_version(version)?;
+
+        let words = (0..reader.u32()?)
+            .map(|_| reader.u16())
+            .collect::<Result<_, _>>()?;
+
+        let mut symbols = BTreeMap::new();
+        for _ in 0..reader.u32()? {
+            let len = reader.u16()? as usize;
+            let symbol = String::from_utf8(reader.take(len)?.to_vec())
+                .map_err(|_| SnapshotError::Malformed(String::from("invalid symbol")))?;
+            symbols.insert(symbol, reader.u32()?);
+        }
+
+        let source_map = (0..reader.u32()?)
+            .map(|_| {
+                reader
+                    .u32()
+                    .map(|line| (line != 0).then_some(line as usize))
+            })
+            .collect::<Result<_, _>>()?;
+
+        if !reader.bytes.is_empty() {
+            return Err(SnapshotError::Malformed(String::from("trailing bytes")));
+        }
+
+        Ok(Self {
+            version,
+            words,
+            symbols,
+            source_map,
+        })
+    }
+}
+
+fn check_version(version: u32) -> Result<(), SnapshotError> {
+    if version == 0 || version > SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+/// Reads little-endian values from a byte slice.
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::Malformed(String::from("unexpected end")));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, SnapshotError> {
+        Ok(u16::from_le_bytes(
+            self.take(2)?.try_into().expect("2 bytes"),
+        ))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(
+            self.take(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(
+            self.take(8)?.try_into().expect("8 bytes"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pass::PassManager;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        // Given
+        let mut context = Context::new(String::from("// comment\n@x\nM=1\n(END)\n@END\n0;JMP\n"));
+        PassManager::default().run(&mut context).unwrap();
+        let snapshot = Snapshot::from_context(&context);
+
+        // When
+        let from_bytes = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+        let from_json = Snapshot::from_json(&snapshot.to_json()).unwrap();
+
+        // Then
+        assert_eq!(vec![16, 0xEFC8, 2, 0xEA87], snapshot.words);
+        assert_eq!(
+            vec![Some(2), Some(3), Some(5), Some(6)],
+            snapshot.source_map
+        );
+        assert_eq!(snapshot, from_bytes);
+        assert_eq!(snapshot, from_json);
+    }
+
+    #[test]
+    fn test_snapshot_unsupported_version() {
+        // Given
+        let json = r#"{"version": 2, "words": [], "symbols": {}, "source_map": []}"#;
+
+        // When
+        let result = Snapshot::from_json(json);
+
+        // Then
+        assert_eq!(Err(SnapshotError::UnsupportedVersion(2)), result);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{
+    code::{COMP, DEST, JUMP},
+    error::AssemblerError,
+    pass::{Context, Pass, Stage},
+};
+
+/// The largest constant of an A-instruction.
+const MAX_CONSTANT: u32 = 0x7fff;
+
+/// The dest mnemonics of the second edition of the book, accepted along with
+/// those of the first.
+const SECOND_EDITION_DEST: [&str; 2] = ["DM", "ADM"];
+
+/// Checks that the source only uses the grammar of chapter 6 of the
+/// nand2tetris book: `//` comments, uppercase mnemonics, decimal constants,
+/// and symbols made of letters, digits, `_`, `.`, `$` and `:`, not starting
+/// with a digit. Spaces are ignored, as the book says.
+///
+/// Returns a syntax error at the first line relying on an extension.
+pub fn check(source: &str) -> Result<(), AssemblerError> {
+    for (index, line) in source.lines().enumerate() {
+        let code = line.split_once("//").map_or(line, |(code, _)| code);
+        let code: String = code.chars().filter(|c| *c != ' ' && *c != '\t').collect();
+        if code.is_empty() {
+            continue;
+        }
+        if let Err(reason) = check_instruction(&code) {
+            return Err(AssemblerError::syntax(format!(
+                "{} is not in the Hack grammar: {}",
+                code, reason
+            ))
+            .at_line(Some(index + 1)));
+        }
+    }
+    Ok(())
+}
+
+/// Returns why the instruction, without its spaces, is not in the grammar.
+fn check_instruction(instruction: &str) -> Result<(), &'static str> {
+    if instruction.contains("/*") {
+        return Err("comments start with //");
+    }
+    if let Some(value) = instruction.strip_prefix('@') {
+        if value.starts_with(|c: char| c.is_ascii_digit()) {
+            return match value.parse::<u32>() {
+                Ok(constant) if constant <= MAX_CONSTANT => Ok(()),
+                _ => Err("constants are decimal numbers up to 32767"),
+            };
+        }
+        return check_symbol(value);
+    }
+    if let Some(label) = instruction.strip_prefix('(') {
+        let label = label
+            .strip_suffix(')')
+            .ok_or("labels are declared as (LABEL)")?;
+        return check_symbol(label);
+    }
+
+    let (dest, rest) = instruction.split_once('=').unwrap_or(("", instruction));
+    let (comp, jump) = rest.split_once(';').unwrap_or((rest, ""));
+    if !instruction.contains(['=', ';']) {
+        return Err("C-instructions are dest=comp;jump with dest or jump");
+    }
+    if instruction.chars().any(|c| c.is_ascii_lowercase()) {
+        return Err("mnemonics are uppercase");
+    }
+    if !DEST.contains(&dest) && !SECOND_EDITION_DEST.contains(&dest) {
+        return Err("unknown dest");
+    }
+    if !COMP.iter().any(|(mnemonic, _)| *mnemonic == comp) {
+        return Err("unknown comp");
+    }
+    if !JUMP.contains(&jump) {
+        return Err("unknown jump");
+    }
+    Ok(())
+}
+
+/// Returns why the symbol is not in the grammar.
+fn check_symbol(symbol: &str) -> Result<(), &'static str> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || "_.$:".contains(c);
+    match symbol.chars().next() {
+        None => Err("symbols can't be empty"),
+        Some(first) if first.is_ascii_digit() => Err("symbols can't start with a digit"),
+        _ if !symbol.chars().all(valid) => Err("symbols are letters, digits, _, ., $ and :"),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects the sources relying on extensions of the Hack grammar, so that
+/// programs can be checked against the book.
+pub struct SpecStrict;
+
+impl Pass for SpecStrict {
+    fn name(&self) -> &'static str {
+        "spec-strict"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Preprocess
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        check(&context.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::CORPUS;
+
+    #[test]
+    fn test_corpus_is_in_the_grammar() {
+        for case in CORPUS {
+            assert!(check(case.source).is_ok(), "{} was rejected", case.name);
+        }
+    }
+
+    #[test]
+    fn test_extensions_are_rejected() {
+        for source in [
+            "d=a\n",
+            "@0x10\n",
+            "@40000\n",
+            "@1abc\n",
+            "LOOP:\n",
+            "(LOOP\n",
+            "D=M # Load\n",
+            "D=M /* Load */\n",
+            "MX=D\n",
+            "D=M+D\n",
+            "0;JMPX\n",
+            "D\n",
+        ] {
+            // When
+            let result = check(source);
+
+            // Then
+            assert!(result.is_err(), "{:?} was accepted", source);
+        }
+        assert!(check("  @ R0 // Spaces\n\tD = M ; JGT\n(main.loop$1:x)\nDM=D\n").is_ok());
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+use crate::vm::{Operation, Segment, VmCommand, STACK};
+
+/// The first RAM address of the heap, which the stack must stay below.
+const HEAP: u32 = 2048;
+/// The first RAM address of the static variables.
+const STATICS: u32 = 16;
+/// The words a call pushes besides the arguments: the return address and the
+/// saved LCL, ARG, THIS and THAT.
+const CALL_FRAME: u32 = 5;
+
+/// The stack usage of a VM function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionDepth {
+    pub name: String,
+    pub locals: u16,
+    /// The deepest working stack of the body, locals excluded, without calls.
+    pub operands: u32,
+    /// The deepest stack from the arguments of the function, its frame and the
+    /// frames of its callees included, or `None` if it may recurse.
+    pub depth: Option<u32>,
+    /// The call path reaching the deepest stack, starting at the function.
+    pub path: Vec<String>,
+}
+
+/// A static estimate of the stack depth of a VM program following the
+/// standard calling convention.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StackReport {
+    /// The functions, in declaration order.
+    pub functions: Vec<FunctionDepth>,
+    /// The number of static variables, allocated from RAM[16] up to the stack.
+    pub statics: usize,
+    /// The possible collisions and inconsistencies found.
+    pub warnings: Vec<String>,
+}
+
+/// The deepest stack of a function and the call path reaching it, `None` if
+/// the function may recurse.
+type Deepest = Option<(u32, Vec<String>)>;
+
+/// A function body, with its calls and the stack depth before each of them.
+struct Body {
+    locals: u16,
+    operands: u32,
+    calls: Vec<(String, u32)>,
+}
+
+impl StackReport {
+    /// Estimates the stack depth of the VM files. The program starts at
+    /// `Sys.init` if it's defined, after the bootstrap set SP to 256.
+    pub fn new(files: &[(String, Vec<VmCommand>)]) -> Self {
+        let mut report = StackReport::default();
+        let mut bodies: Vec<(String, Body)> = Vec::new();
+        let mut statics = BTreeSet::new();
+        for (file, commands) in files {
+            for command in commands {
+                if let VmCommand::Push(Segment::Static, index)
+                | VmCommand::Pop(Segment::Static, index) = command
+                {
+                    statics.insert((file.as_str(), *index));
+                }
+            }
+            let starts: Vec<usize> = commands
+                .iter()
+                .enumerate()
+                .filter(|(_, command)| matches!(command, VmCommand::Function(..)))
+                .map(|(index, _)| index)
+                .collect();
+            for (position, start) in starts.iter().enumerate() {
+                let end = starts.get(position + 1).copied().unwrap_or(commands.len());
+                let VmCommand::Function(name, locals) = &commands[*start] else {
+                    unreachable!("functions start at their declaration");
+                };
+                let body = body(
+                    name,
+                    *locals,
+                    &commands[start + 1..end],
+                    &mut report.warnings,
+                );
+                bodies.push((name.clone(), body));
+            }
+        }
+        report.statics = statics.len();
+
+        let indices: HashMap<&str, usize> = bodies
+            .iter()
+            .enumerate()
+            .map(|(index, (name, _))| (name.as_str(), index))
+            .collect();
+        let mut depths: Vec<Option<Deepest>> = vec![None; bodies.len()];
+        for index in 0..bodies.len() {
+            depth(index, &bodies, &indices, &mut depths, &mut Vec::new());
+        }
+        for ((name, body), depth) in bodies.iter().zip(depths) {
+            let (depth, path) = depth.flatten().unzip();
+            report.functions.push(FunctionDepth {
+                name: name.clone(),
+                locals: body.locals,
+                operands: body.operands,
+                depth,
+                path: path.unwrap_or_default(),
+            });
+        }
+
+        let limit = HEAP - STACK as u32;
+        if let Some(init) = report.function("Sys.init") {
+            match init.depth {
+                Some(depth) if CALL_FRAME + depth > limit => report.warnings.push(format!(
+                    "the stack may grow to RAM[{}], into the heap at RAM[{}], along {}",
+                    STACK as u32 + CALL_FRAME + depth - 1,
+                    HEAP,
+                    init.path.join(" -> ")
+                )),
+                Some(_) => {}
+                None => report.warnings.push(String::from(
+                    "the stack depth is unbounded: Sys.init may reach a recursive call",
+                )),
+            }
+        }
+        if report.statics as u32 > STACK as u32 - STATICS {
+            report.warnings.push(format!(
+                "{} static variables overflow into the stack at RAM[{}]",
+                report.statics, STACK
+            ));
+        }
+        report
+    }
+
+    /// Returns the function with the name.
+    pub fn function(&self, name: &str) -> Option<&FunctionDepth> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+}
+
+/// Computes the working stack depths of a function body, following its jumps.
+fn body(name: &str, locals: u16, commands: &[VmCommand], warnings: &mut Vec<String>) -> Body {
+    let labels: HashMap<&str, usize> = commands
+        .iter()
+        .enumerate()
+        .filter_map(|(index, command)| match command {
+            VmCommand::Label(label) => Some((label.as_str(), index)),
+            _ => None,
+        })
+        .collect();
+    let mut depths: Vec<Option<i64>> = vec![None; commands.len()];
+    let mut pending = vec![(0, 0i64)];
+    let mut body = Body {
+        locals,
+        operands: 0,
+        calls: Vec::new(),
+    };
+    while let Some((index, depth)) = pending.pop() {
+        let Some(command) = commands.get(index) else {
+            continue;
+        };
+        match depths[index] {
+            Some(previous) if previous != depth => {
+                warnings.push(format!(
+                    "{}: the stack depth at `{}` is either {} or {}",
+                    name, command, previous, depth
+                ));
+                continue;
+            }
+            Some(_) => continue,
+            None => depths[index] = Some(depth),
+        }
+        body.operands = body.operands.max(depth.max(0) as u32);
+
+        let next = match command {
+            VmCommand::Arithmetic(Operation::Neg | Operation::Not) => depth,
+            VmCommand::Arithmetic(_) | VmCommand::Pop(..) => depth - 1,
+            VmCommand::Push(..) => depth + 1,
+            VmCommand::Label(_) | VmCommand::Function(..) => depth,
+            VmCommand::Goto(label) | VmCommand::IfGoto(label) => {
+                let after = depth - i64::from(matches!(command, VmCommand::IfGoto(_)));
+                match labels.get(label.as_str()) {
+                    Some(target) => pending.push((*target, after)),
+                    None => warnings.push(format!("{}: undefined label {}", name, label)),
+                }
+                if matches!(command, VmCommand::Goto(_)) {
+                    continue;
+                }
+                after
+            }
+            VmCommand::Call(callee, arguments) => {
+                body.calls.push((callee.clone(), depth.max(0) as u32));
+                depth - i64::from(*arguments) + 1
+            }
+            VmCommand::Return => continue,
+        };
+        body.operands = body.operands.max(next.max(0) as u32);
+        pending.push((index + 1, next));
+    }
+    body
+}
+
+/// Computes the deepest stack of a function, from its first local to the
+/// deepest point of its callees, memoized in `depths`. `None` marks a
+/// function that may recurse.
+fn depth(
+    index: usize,
+    bodies: &[(String, Body)],
+    indices: &HashMap<&str, usize>,
+    depths: &mut [Option<Deepest>],
+    visiting: &mut Vec<usize>,
+) -> Deepest {
+    if let Some(depth) = &depths[index] {
+        return depth.clone();
+    }
+    if visiting.contains(&index) {
+        return None;
+    }
+    visiting.push(index);
+    let (name, body) = &bodies[index];
+    let frame = u32::from(body.locals);
+    let mut deepest = Some((frame + body.operands, vec![name.clone()]));
+    for (callee, before) in &body.calls {
+        // Calls to functions outside the program, such as the OS, only count
+        // their frame.
+        let inner = match indices.get(callee.as_str()) {
+            Some(callee) => depth(*callee, bodies, indices, depths, visiting),
+            None => Some((0, vec![callee.clone()])),
+        };
+        deepest = match (deepest, inner) {
+            (Some((current, path)), Some((callee_depth, callee_path))) => {
+                let through = frame + before + CALL_FRAME + callee_depth;
+                if through > current {
+                    Some((through, [vec![name.clone()], callee_path].concat()))
+                } else {
+                    Some((current, path))
+                }
+            }
+            _ => None,
+        };
+    }
+    visiting.pop();
+    depths[index] = Some(deepest.clone());
+    deepest
+}
+
+/// Lists the depth of each function, then the warnings.
+impl fmt::Display for StackReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for function in &self.functions {
+            match function.depth {
+                Some(depth) => writeln!(
+                    f,
+                    "{}: {} words ({} locals, {} operands), along {}",
+                    function.name,
+                    depth,
+                    function.locals,
+                    function.operands,
+                    function.path.join(" -> ")
+                )?,
+                None => writeln!(f, "{}: unbounded (recursive)", function.name)?,
+            }
+        }
+        writeln!(f, "statics: {}", self.statics)?;
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::parse;
+
+    #[test]
+    fn test_stack_depth() {
+        // Given
+        let source = "function Sys.init 1\npush constant 1\npush constant 2\ncall Main.f 2\npop local 0\nlabel L\ngoto L\nfunction Main.f 2\npush argument 0\npush static 0\nadd\nreturn\nfunction Main.r 0\ncall Main.r 0\nreturn\n";
+        let files = vec![(String::from("Main"), parse("Main.vm", source).unwrap())];
+
+        // When
+        let report = StackReport::new(&files);
+
+        // Then
+        let init = report.function("Sys.init").unwrap();
+        assert_eq!(Some(1 + 2 + 5 + 4), init.depth);
+        assert_eq!(vec!["Sys.init", "Main.f"], init.path);
+        assert_eq!(2, init.operands);
+        assert_eq!(None, report.function("Main.r").unwrap().depth);
+        assert_eq!(1, report.statics);
+        assert!(report.warnings.is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    emulator::ROM_SIZE,
+    error::AssemblerError,
+    ir::{Ir, IrInstruction},
+    pass::{Context, Pass, Stage},
+    symbol_table::SymbolTable,
+};
+
+/// The composition of an assembled program, to check its size at a glance.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub a_instructions: usize,
+    pub c_instructions: usize,
+    /// The labels, those shadowing predefined symbols included.
+    pub labels: usize,
+    /// The variables allocated in the RAM, predefined symbols excluded.
+    pub variables: usize,
+    /// The number of ROM words, after the optimizations.
+    pub rom_size: usize,
+}
+
+impl Stats {
+    /// Counts the instructions of the IR and the symbols it was resolved with.
+    pub fn new(ir: &Ir, symbol_table: &SymbolTable) -> Self {
+        let a_instructions = ir
+            .nodes
+            .iter()
+            .filter(|node| matches!(node.instruction, IrInstruction::A { .. }))
+            .count();
+        let (labels, variables) = symbol_table
+            .iter()
+            .filter(|(symbol, _)| {
+                symbol_table.is_label(symbol) || !SymbolTable::is_predefined(symbol)
+            })
+            .partition::<Vec<_>, _>(|(symbol, _)| symbol_table.is_label(symbol));
+        Self {
+            a_instructions,
+            c_instructions: ir.nodes.len() - a_instructions,
+            labels: labels.len(),
+            variables: variables.len(),
+            rom_size: ir.nodes.len(),
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count: usize, noun: &str| match count {
+            1 => format!("{} {}", count, noun),
+            _ => format!("{} {}s", count, noun),
+        };
+        write!(
+            f,
+            "{}, {}, {}, {}, {} of {} ROM words",
+            plural(self.a_instructions, "A-instruction"),
+            plural(self.c_instructions, "C-instruction"),
+            plural(self.labels, "label"),
+            plural(self.variables, "variable"),
+            self.rom_size,
+            ROM_SIZE
+        )
+    }
+}
+
+/// The composition of the program counted while assembling it.
+///
+/// Clones share the same stats, so the report can be read after the
+/// assembler consumed the context.
+#[derive(Clone, Debug, Default)]
+pub struct StatsReport {
+    stats: Arc<Mutex<Option<Stats>>>,
+}
+
+impl StatsReport {
+    /// Returns a new empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the stats, if the program was encoded.
+    pub fn stats(&self) -> Option<Stats> {
+        *self.stats.lock().expect("poisoned report")
+    }
+}
+
+/// Counts the composition of the encoded program, recording it in the report
+/// of the context.
+pub struct Statistics;
+
+impl Pass for Statistics {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Encode
+    }
+
+    fn run(&self, context: &mut Context) -> Result<(), AssemblerError> {
+        let stats = Stats::new(&context.ir, &context.symbol_table);
+        *context.stats.stats.lock().expect("poisoned report") = Some(stats);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+
+    #[test]
+    fn test_stats_of_a_program() {
+        // Given
+        let source = "@i\nM=1\n(LOOP)\n@i\nM=M+1\n@SCREEN\nD=A\n@LOOP\n0;JMP\n(END)\n";
+        let report = StatsReport::new();
+
+        // When
+        Assembler::from_source(source)
+            .with_stats_report(report.clone())
+            .fill_symbol_table()
+            .assemble();
+
+        // Then
+        let stats = report.stats().unwrap();
+        assert_eq!(
+            Stats {
+                a_instructions: 4,
+                c_instructions: 4,
+                labels: 2,
+                variables: 1,
+                rom_size: 8,
+            },
+            stats
+        );
+        assert_eq!(
+            "4 A-instructions, 4 C-instructions, 2 labels, 1 variable, 8 of 32768 ROM words",
+            stats.to_string()
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    backpatch::{check_instructions, check_symbols, Backpatcher},
+    error::AssemblerError,
+    limits::Limits,
+    parser::{normalize, BlockComments, Parser},
+    pass::binary_line,
+    symbol_table::SymbolTable,
+};
+
+/// The length of a word in a `.hack` file, newline included.
+const LINE_LENGTH: u64 = 17;
+
+/// Assembles the source read line by line into `.hack` text, written from
+/// the start of the output as the words are encoded. The references to
+/// symbols not known yet are written as placeholders and overwritten in
+/// place at the end of the source, so only the symbols and the forward
+/// references are held in memory, whatever the size of the program.
+///
+/// Returns the first error, located in the source, if the input can't be
+/// read, the output can't be written, the source contains an invalid
+/// instruction, a label is defined twice, or the program exceeds the limits.
+/// The output is then left incomplete.
+pub fn assemble_stream(
+    mut input: impl BufRead,
+    output: impl Write + Seek,
+    limits: Limits,
+) -> Result<SymbolTable, AssemblerError> {
+    let write_error = |error| AssemblerError::io("failed to write compiled output", error);
+    let mut output = BufWriter::new(output);
+    let mut backpatcher = Backpatcher::new();
+    let mut comments = BlockComments::default();
+    let mut line = String::new();
+    let mut bytes = 0;
+    let mut number = 0;
+    loop {
+        line.clear();
+        let read = input
+            .read_line(&mut line)
+            .map_err(|error| AssemblerError::io("failed to read input", error))?;
+        if read == 0 {
+            break;
+        }
+        bytes += read;
+        number += 1;
+        if bytes > limits.max_source_bytes {
+            return Err(AssemblerError::semantic(format!(
+                "source exceeds the limit of {} bytes",
+                limits.max_source_bytes
+            ))
+            .at_line(Some(number)));
+        }
+
+        let code = normalize(&line);
+        let code = comments.blank(&code);
+        let mut parser = Parser::from_source(&code).starting_at(number);
+        if !parser.has_more_lines() {
+            continue;
+        }
+        parser.advance();
+        if let Some(word) = backpatcher.encode(&parser)? {
+            check_instructions(backpatcher.len(), &limits)
+                .map_err(|error| error.at(parser.line(), parser.column()))?;
+            output.write_all(&binary_line(word)).map_err(write_error)?;
+        }
+    }
+
+    comments.finish()?;
+    let (symbol_table, mut patches) = backpatcher.finish(&limits)?;
+    check_symbols(&symbol_table, &limits)?;
+    patches.sort_unstable();
+    for (index, word) in patches {
+        output
+            .seek(SeekFrom::Start(index as u64 * LINE_LENGTH))
+            .and_then(|_| output.write_all(&binary_line(word)))
+            .map_err(write_error)?;
+    }
+    output.flush().map_err(write_error)?;
+    Ok(symbol_table)
+}
+
+/// Assembles the file into the `.hack` output, streaming both. Returns an
+/// error if the input can't be read, the output can't be written, or the
+/// assembly fails.
+pub fn compile(input: &Path, output: &Path, limits: Limits) -> Result<SymbolTable, AssemblerError> {
+    let source = File::open(input).map_err(|error| {
+        AssemblerError::io(format!("failed to read {}", input.display()), error)
+    })?;
+    compile_from(BufReader::new(source), output, limits)
+}
+
+/// Assembles the source read line by line into the `.hack` output. The words
+/// are streamed to a temporary file next to the output, which replaces the
+/// output once the assembly succeeded, so that a failed assembly leaves the
+/// previous output, if any, untouched.
+pub fn compile_from(
+    input: impl BufRead,
+    output: &Path,
+    limits: Limits,
+) -> Result<SymbolTable, AssemblerError> {
+    let mut partial = output.as_os_str().to_owned();
+    partial.push(".tmp");
+    let partial = PathBuf::from(partial);
+    let file = File::create(&partial).map_err(|error| {
+        AssemblerError::io(format!("failed to create {}", partial.display()), error)
+    })?;
+    let symbol_table = assemble_stream(input, file, limits)
+        .and_then(|symbol_table| {
+            fs::rename(&partial, output).map_err(|error| {
+                AssemblerError::io(format!("failed to create {}", output.display()), error)
+            })?;
+            Ok(symbol_table)
+        })
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&partial);
+        })?;
+    tracing::info!(output = %output.display(), "wrote output");
+    Ok(symbol_table)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{golden::CORPUS, testing::TempDir};
+
+    #[test]
+    fn test_stream_matches_the_corpus() {
+        for case in CORPUS {
+            // Given
+            let mut output = Cursor::new(Vec::new());
+
+            // When
+            assemble_stream(case.source.as_bytes(), &mut output, Limits::default()).unwrap();
+
+            // Then
+            assert_eq!(
+                case.expected,
+                String::from_utf8(output.into_inner()).unwrap(),
+                "{}",
+                case.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_stream_errors_are_located() {
+        // Given
+        let source = "@1\nD=A\n  D=X\n";
+
+        // When
+        let error = assemble_stream(
+            source.as_bytes(),
+            Cursor::new(Vec::new()),
+            Limits::default(),
+        )
+        .unwrap_err();
+
+        // Then
+        assert_eq!("line 3:5: unexpected comp X", error.to_string());
+    }
+
+    #[test]
+    fn test_failed_compilation_keeps_the_previous_output() {
+        // Given
+        let dir = TempDir::new("hack-stream");
+        let output = dir.join("Prog.hack");
+        std::fs::write(&output, "previous").unwrap();
+
+        // When
+        let result = compile_from("@1\nD=X\n".as_bytes(), &output, Limits::default());
+
+        // Then
+        assert!(result.is_err());
+        assert_eq!("previous", std::fs::read_to_string(&output).unwrap());
+        assert_eq!(1, std::fs::read_dir(&*dir).unwrap().count());
+    }
+}
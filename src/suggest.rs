@@ -0,0 +1,81 @@
+/// Returns the number of characters to insert, delete or substitute to turn
+/// one word into the other, their Levenshtein distance.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Returns the candidate closest to the word, at most `max_distance` edits
+/// away, if any. Words no longer than the distance are too short to tell
+/// what they stand for. Ties go to the candidate sharing the longest prefix
+/// with the word, then to the first one.
+pub fn closest<'a>(
+    word: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    max_distance: usize,
+) -> Option<&'a str> {
+    let prefix = |candidate: &str| {
+        word.chars()
+            .zip(candidate.chars())
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != word)
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance && *distance < word.chars().count())
+        .min_by(|(a, x), (b, y)| a.cmp(b).then(prefix(y).cmp(&prefix(x))))
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        // Given
+        let cases = [
+            ("JGT", "JGTT", 1),
+            ("LOOP", "LOPP", 1),
+            ("D+M", "M+D", 2),
+            ("", "JMP", 3),
+            ("END", "END", 0),
+        ];
+
+        for (a, b, expected) in cases {
+            // When
+            let distance = edit_distance(a, b);
+
+            // Then
+            assert_eq!(expected, distance, "{} {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_closest_prefers_the_longest_common_prefix() {
+        // Given
+        let candidates = ["D+1", "D+M", "JGT"];
+
+        // When
+        let comp = closest("D+M1", candidates, 1);
+        let jump = closest("JXX", candidates, 1);
+        let short = closest("X", ["0", "1", "D"], 1);
+
+        // Then
+        assert_eq!(Some("D+M"), comp);
+        assert_eq!(None, jump);
+        assert_eq!(None, short);
+    }
+}
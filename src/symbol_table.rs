@@ -1,48 +1,81 @@
-use std::collections::HashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
-#[derive(Default)]
+#[derive(Clone, Debug, Default)]
 pub struct SymbolTable {
-    table: HashMap<String, u32>,
+    table: FxHashMap<String, u32>,
+    /// The symbols added as labels, whose addresses are ROM addresses.
+    labels: FxHashSet<String>,
     current_address: u32,
 }
 
+/// The predefined symbols and their RAM addresses.
+const PREDEFINED: [(&str, u32); 23] = [
+    ("R0", 0),
+    ("R1", 1),
+    ("R2", 2),
+    ("R3", 3),
+    ("R4", 4),
+    ("R5", 5),
+    ("R6", 6),
+    ("R7", 7),
+    ("R8", 8),
+    ("R9", 9),
+    ("R10", 10),
+    ("R11", 11),
+    ("R12", 12),
+    ("R13", 13),
+    ("R14", 14),
+    ("R15", 15),
+    ("SP", 0),
+    ("LCL", 1),
+    ("ARG", 2),
+    ("THIS", 3),
+    ("THAT", 4),
+    ("SCREEN", 16384),
+    ("KBD", 24576),
+];
+
 impl SymbolTable {
     /// Create a new SymbolTable with the default values.
     pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Returns whether the symbol is predefined, such as `R0` or `SCREEN`.
+    pub fn is_predefined(symbol: &str) -> bool {
+        Self::predefined(symbol).is_some()
+    }
+
+    /// Returns the RAM address of the symbol if it's predefined.
+    pub fn predefined(symbol: &str) -> Option<u32> {
+        PREDEFINED
+            .iter()
+            .find(|(predefined, _)| *predefined == symbol)
+            .map(|(_, address)| *address)
+    }
+
+    /// Create a new SymbolTable with the default values, with room for the
+    /// given number of labels.
+    pub fn with_capacity(labels: usize) -> Self {
+        let mut table =
+            FxHashMap::with_capacity_and_hasher(PREDEFINED.len() + labels, Default::default());
+        table.extend(
+            PREDEFINED
+                .iter()
+                .map(|(symbol, address)| (symbol.to_string(), *address)),
+        );
         Self {
             current_address: 16,
-            table: [
-                (String::from("R0"), 0),
-                (String::from("R1"), 1),
-                (String::from("R2"), 2),
-                (String::from("R3"), 3),
-                (String::from("R4"), 4),
-                (String::from("R5"), 5),
-                (String::from("R6"), 6),
-                (String::from("R7"), 7),
-                (String::from("R8"), 8),
-                (String::from("R9"), 9),
-                (String::from("R10"), 10),
-                (String::from("R11"), 11),
-                (String::from("R12"), 12),
-                (String::from("R13"), 13),
-                (String::from("R14"), 14),
-                (String::from("R15"), 15),
-                (String::from("SP"), 0),
-                (String::from("LCL"), 1),
-                (String::from("ARG"), 2),
-                (String::from("THIS"), 3),
-                (String::from("THAT"), 4),
-                (String::from("SCREEN"), 16384),
-                (String::from("KBD"), 24576),
-            ]
-            .into_iter()
-            .collect(),
+            labels: FxHashSet::with_capacity_and_hasher(labels, Default::default()),
+            table,
         }
     }
 
-    /// Add a label to the symbol table.
+    /// Add a label to the symbol table, replacing the address of a label of
+    /// the same name: redefinitions are reported by
+    /// [`Program::duplicate_labels`](crate::program::Program::duplicate_labels).
     pub fn add_label(&mut self, symbol: String, address: u32) {
+        self.labels.insert(symbol.clone());
         self.table.insert(symbol, address);
     }
 
@@ -53,9 +86,46 @@ impl SymbolTable {
         self.current_address - 1
     }
 
+    /// Returns the first variable allocated past the address, with its
+    /// address, if any.
+    pub fn variable_past(&self, max_address: u32) -> Option<(&str, u32)> {
+        let address = max_address.saturating_add(1).max(16);
+        if self.current_address <= address {
+            return None;
+        }
+        self.table
+            .iter()
+            .find(|(symbol, variable)| {
+                **variable == address && !self.is_label(symbol) && !Self::is_predefined(symbol)
+            })
+            .map(|(symbol, _)| (symbol.as_str(), address))
+    }
+
     /// Get the address of a symbol in the symbol table.
     /// If the symbol is not in the table, return None.
     pub fn address(&self, symbol: &str) -> Option<&u32> {
         self.table.get(symbol)
     }
+
+    /// Returns whether the symbol is a label, rather than a RAM address.
+    pub fn is_label(&self, symbol: &str) -> bool {
+        self.labels.contains(symbol)
+    }
+
+    /// Returns the number of symbols, predefined symbols included.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns whether the table has no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Returns an iterator over the symbols and their addresses, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.table
+            .iter()
+            .map(|(symbol, address)| (symbol.as_str(), *address))
+    }
 }
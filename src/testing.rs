@@ -0,0 +1,111 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use crate::{assembler::Assembler, diagnostic::Diagnostic, pass::EmitFormat};
+
+/// A program exercising every part of the outputs: variables, labels, a call
+/// through a return address, an unreachable function and a variable never read.
+pub const SAMPLE: &str = "// Computes R1 = 2 * R0 through a function.
+@RET
+D=A
+@R15
+M=D
+@Main.double
+0;JMP
+(RET)
+@R1
+M=D
+(END)
+@END
+0;JMP
+(Main.double)
+@R0
+D=M
+D=D+M
+@R15
+A=M
+0;JMP
+(Main.unused)
+@i
+M=1
+";
+
+/// Programs failing to assemble, one per kind of error.
+pub const INVALID: [&str; 4] = ["D=X\n", "M=D+2\n", "0;JXX\n", "@-\n"];
+
+/// Returns the output formats, so that every format is covered when one is added.
+pub fn formats() -> &'static [EmitFormat] {
+    EmitFormat::value_variants()
+}
+
+/// Returns the command-line name of the format, such as `hack` or `ir`.
+pub fn format_name(format: EmitFormat) -> String {
+    format
+        .to_possible_value()
+        .expect("formats are never skipped")
+        .get_name()
+        .to_string()
+}
+
+/// Assembles the source and returns the output in the format.
+///
+/// # Panic
+///
+/// Panics if the source fails to assemble.
+pub fn emit(source: &str, format: EmitFormat) -> String {
+    Assembler::from_source(source)
+        .emit(format)
+        .fill_symbol_table()
+        .render()
+}
+
+/// Assembles the source and returns the diagnostics reported, located in
+/// the source when possible.
+pub fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    match Assembler::from_source(source)
+        .fill_symbol_table()
+        .try_assemble()
+    {
+        Ok(_) => Vec::new(),
+        Err(error) => error.to_diagnostics(None, source),
+    }
+}
+
+/// A directory of the system's temporary directory, removed with its
+/// contents when dropped.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates an empty directory named after the prefix and the process, so
+    /// that concurrent test binaries don't share it.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the directory can't be created.
+    pub fn new(prefix: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{}-{}", prefix, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("failed to create temporary directory");
+        Self { path }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    emulator::{load_program, Emulator, RAM_SIZE},
+    error::{self, AssemblerError},
+};
+
+/// A value read or written by a test script.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Variable {
+    A,
+    D,
+    Pc,
+    Time,
+    Ram(usize),
+}
+
+/// A column of the output list, such as `RAM[0]%D2.6.2`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Column {
+    name: String,
+    variable: Variable,
+    /// The radix letter: `D`ecimal, `X` hexadecimal, `B`inary or `S`tring.
+    format: char,
+    left: usize,
+    width: usize,
+    right: usize,
+}
+
+/// A command of a test script.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Command {
+    Load(String),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<Column>),
+    Set(Variable, u16),
+    Repeat(u32, Vec<Command>),
+    Tick,
+    Tock,
+    Output,
+    Echo(String),
+}
+
+/// The outcome of a test script which ran to completion.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestReport {
+    /// The lines output by the script, header included.
+    pub output: String,
+    /// The file the script asked to write the output to, if any.
+    pub output_file: Option<PathBuf>,
+    /// Whether the output was compared to an expected output file.
+    pub compared: bool,
+}
+
+/// Runs a nand2tetris CPU emulator test script (`.tst`), comparing its output
+/// to the `.cmp` file it names, if any. Files are relative to the script.
+///
+/// # Errors
+///
+/// Returns a syntax error if the script is invalid or uses an unsupported
+/// command, and a semantic error if its output differs from the expected
+/// output, with the first differing line.
+pub fn run_test(path: &Path) -> Result<TestReport, AssemblerError> {
+    let source = error::read_to_string(path)?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let commands = parse(&source)?;
+
+    let mut runner = Runner {
+        directory,
+        emulator: Emulator::new(Vec::new()),
+        columns: Vec::new(),
+        output: String::new(),
+        output_file: None,
+        expected: None,
+        half_cycle: false,
+    };
+    runner.execute(&commands)?;
+
+    Ok(TestReport {
+        compared: runner.expected.is_some(),
+        output: runner.output,
+        output_file: runner.output_file,
+    })
+}
+
+struct Runner<'a> {
+    directory: &'a Path,
+    emulator: Emulator,
+    columns: Vec<Column>,
+    output: String,
+    output_file: Option<PathBuf>,
+    /// The lines of the expected output.
+    expected: Option<Vec<String>>,
+    /// Whether a `tick` is waiting for its `tock`.
+    half_cycle: bool,
+}
+
+impl Runner<'_> {
+    fn execute(&mut self, commands: &[Command]) -> Result<(), AssemblerError> {
+        for command in commands {
+            match command {
+                Command::Load(file) => {
+                    let (rom, _) = load_program(&self.directory.join(file))?;
+                    self.emulator = Emulator::new(rom);
+                }
+                Command::OutputFile(file) => self.output_file = Some(self.directory.join(file)),
+                Command::CompareTo(file) => {
+                    let expected = error::read_to_string(&self.directory.join(file))?;
+                    self.expected = Some(expected.lines().map(str::to_string).collect());
+                }
+                Command::OutputList(columns) => {
+                    self.columns = columns.clone();
+                    let header = self.columns.iter().fold(String::from("|"), |line, column| {
+                        let total = column.left + column.width + column.right;
+                        let name: String = column.name.chars().take(total).collect();
+                        let left = (total - name.len()) / 2;
+                        format!("{}{:left$}{:right$}|", line, "", name, right = total - left)
+                    });
+                    self.write_line(header)?;
+                }
+                Command::Set(variable, value) => self.set(variable, *value)?,
+                Command::Repeat(count, commands) => {
+                    for _ in 0..*count {
+                        self.execute(commands)?;
+                    }
+                }
+                Command::Tick => self.half_cycle = true,
+                Command::Tock => {
+                    self.half_cycle = false;
+                    if !self.emulator.is_at_end() {
+                        self.emulator.step();
+                    }
+                }
+                Command::Output => {
+                    let line = self.columns.iter().fold(String::from("|"), |line, column| {
+                        format!("{}{}|", line, self.format(column))
+                    });
+                    self.write_line(line)?;
+                }
+                Command::Echo(text) => println!("{}", text),
+            }
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, variable: &Variable, value: u16) -> Result<(), AssemblerError> {
+        match variable {
+            Variable::A => self.emulator.set_a(value),
+            Variable::D => self.emulator.set_d(value),
+            Variable::Pc => self.emulator.set_pc(value),
+            Variable::Ram(address) => self.emulator.ram_mut()[*address] = value,
+            Variable::Time => return Err(AssemblerError::syntax("time can't be set")),
+        }
+        Ok(())
+    }
+
+    fn format(&self, column: &Column) -> String {
+        let value = match column.variable {
+            Variable::A => self.emulator.a(),
+            Variable::D => self.emulator.d(),
+            Variable::Pc => self.emulator.pc(),
+            Variable::Ram(address) => self.emulator.ram()[address],
+            Variable::Time => {
+                // The time counts cycles, with a `+` while a tick awaits its tock.
+                let time = format!(
+                    "{}{}",
+                    self.emulator.cycles(),
+                    if self.half_cycle { "+" } else { "" }
+                );
+                return pad(column, &time, false);
+            }
+        };
+        let text = match column.format {
+            'X' => format!("{:04X}", value),
+            'B' => format!("{:016b}", value),
+            _ => (value as i16).to_string(),
+        };
+        // Hexadecimal and binary values keep their least significant digits.
+        let text = match column.format {
+            'X' | 'B' if text.len() > column.width => text[text.len() - column.width..].to_string(),
+            _ => text,
+        };
+        pad(column, &text, column.format == 'S')
+    }
+
+    fn write_line(&mut self, line: String) -> Result<(), AssemblerError> {
+        let number = self.output.lines().count();
+        if let Some(expected) = &self.expected {
+            if let Some(expected) = expected.get(number) {
+                let matches = expected.len() == line.len()
+                    && expected
+                        .chars()
+                        .zip(line.chars())
+                        .all(|(expected, actual)| expected == '*' || expected == actual);
+                if !matches {
+                    return Err(AssemblerError::semantic(format!(
+                        "comparison failure at line {}\nexpected: {}\nactual:   {}",
+                        number + 1,
+                        expected,
+                        line
+                    )));
+                }
+            }
+        }
+        writeln!(self.output, "{}", line).expect("write to string");
+        Ok(())
+    }
+}
+
+/// Pads a value within its column, left-aligned for strings and right-aligned otherwise.
+fn pad(column: &Column, text: &str, left_aligned: bool) -> String {
+    let text = if left_aligned {
+        format!("{:<width$}", text, width = column.width)
+    } else {
+        format!("{:>width$}", text, width = column.width)
+    };
+    format!(
+        "{:left$}{}{:right$}",
+        "",
+        text,
+        "",
+        left = column.left,
+        right = column.right
+    )
+}
+
+/// Splits a script in words and the `,` `;` `{` `}` separators, dropping comments.
+fn tokenize(source: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut word = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '"' => {
+                let text: String = chars.by_ref().take_while(|c| *c != '"').collect();
+                tokens.push(format!("\"{}", text));
+            }
+            ',' | ';' | '{' | '}' => {
+                if !word.is_empty() {
+                    tokens.push(std::mem::take(&mut word));
+                }
+                tokens.push(c.to_string());
+            }
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    tokens.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+fn parse(source: &str) -> Result<Vec<Command>, AssemblerError> {
+    let tokens = tokenize(source);
+    let mut tokens = tokens.iter().map(String::as_str).peekable();
+    let commands = parse_block(&mut tokens)?;
+    if let Some(token) = tokens.next() {
+        return Err(AssemblerError::syntax(format!(
+            "unexpected {} in test script",
+            token
+        )));
+    }
+    Ok(commands)
+}
+
+fn parse_block<'a>(
+    tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>,
+) -> Result<Vec<Command>, AssemblerError> {
+    let mut commands = Vec::new();
+    while let Some(token) = tokens.peek().copied() {
+        if token == "}" {
+            break;
+        }
+        tokens.next();
+        let mut argument = || {
+            tokens
+                .next()
+                .ok_or_else(|| AssemblerError::syntax(format!("missing argument of {}", token)))
+        };
+        let command = match token {
+            "," | ";" => continue,
+            "load" => Command::Load(argument()?.to_string()),
+            "output-file" => Command::OutputFile(argument()?.to_string()),
+            "compare-to" => Command::CompareTo(argument()?.to_string()),
+            "output-list" => {
+                let mut columns = Vec::new();
+                while let Some(column) = tokens.next_if(|token| !matches!(*token, "," | ";")) {
+                    columns.push(parse_column(column)?);
+                }
+                Command::OutputList(columns)
+            }
+            "set" => {
+                let variable = parse_variable(argument()?)?;
+                Command::Set(variable, parse_value(argument()?)?)
+            }
+            "repeat" => {
+                let count = argument()?;
+                let count = count.parse().map_err(|_| {
+                    AssemblerError::syntax(format!("invalid repeat count {}", count))
+                })?;
+                if tokens.next() != Some("{") {
+                    return Err(AssemblerError::syntax("expected { after repeat"));
+                }
+                let commands = parse_block(tokens)?;
+                if tokens.next() != Some("}") {
+                    return Err(AssemblerError::syntax("missing } after repeat"));
+                }
+                Command::Repeat(count, commands)
+            }
+            "ticktock" => Command::Repeat(1, vec![Command::Tick, Command::Tock]),
+            "tick" => Command::Tick,
+            "tock" => Command::Tock,
+            "output" => Command::Output,
+            "echo" => Command::Echo(argument()?.trim_start_matches('"').to_string()),
+            "clear-echo" => continue,
+            command => {
+                return Err(AssemblerError::syntax(format!(
+                    "unsupported test script command {}",
+                    command
+                )))
+            }
+        };
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+pub(crate) fn parse_variable(name: &str) -> Result<Variable, AssemblerError> {
+    let variable = match name {
+        "A" | "ARegister" => Variable::A,
+        "D" | "DRegister" => Variable::D,
+        "PC" => Variable::Pc,
+        "time" => Variable::Time,
+        _ => {
+            let address = name
+                .strip_prefix("RAM[")
+                .and_then(|name| name.strip_suffix(']'))
+                .and_then(|address| address.parse::<usize>().ok())
+                .filter(|address| *address < RAM_SIZE)
+                .ok_or_else(|| AssemblerError::syntax(format!("unsupported variable {}", name)))?;
+            Variable::Ram(address)
+        }
+    };
+    Ok(variable)
+}
+
+/// Parses a value such as `-1`, `%X7FFF` or `%B101`.
+pub(crate) fn parse_value(value: &str) -> Result<u16, AssemblerError> {
+    let parsed = match value.get(..2) {
+        Some("%X") => u16::from_str_radix(&value[2..], 16).ok(),
+        Some("%B") => u16::from_str_radix(&value[2..], 2).ok(),
+        Some("%D") => value[2..].parse::<i16>().ok().map(|value| value as u16),
+        _ => value
+            .parse::<i16>()
+            .ok()
+            .map(|value| value as u16)
+            .or_else(|| value.parse::<u16>().ok()),
+    };
+    parsed.ok_or_else(|| AssemblerError::syntax(format!("invalid value {}", value)))
+}
+
+/// Parses a column such as `RAM[0]%D2.6.2`, binary `%B1.16.1` by default.
+fn parse_column(column: &str) -> Result<Column, AssemblerError> {
+    let invalid = || AssemblerError::syntax(format!("invalid column format {}", column));
+    let (name, format) = column.split_once('%').unwrap_or((column, "B1.16.1"));
+    let mut chars = format.chars();
+    let radix = chars.next().ok_or_else(invalid)?;
+    let sizes = chars
+        .as_str()
+        .split('.')
+        .map(|size| size.parse().map_err(|_| invalid()))
+        .collect::<Result<Vec<usize>, _>>()?;
+    let [left, width, right] = sizes[..] else {
+        return Err(invalid());
+    };
+    Ok(Column {
+        name: name.to_string(),
+        variable: parse_variable(name)?,
+        format: radix,
+        left,
+        width,
+        right,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_max_test_script() {
+        // When
+        let report = run_test(Path::new("test_data/max/Max.tst")).unwrap();
+
+        // Then
+        assert!(report.compared);
+        let expected = std::fs::read_to_string("test_data/max/Max.cmp").unwrap();
+        assert_eq!(expected, report.output);
+    }
+}
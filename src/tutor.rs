@@ -0,0 +1,263 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    code::{binary_to_comp, binary_to_dest, binary_to_jump},
+    emulator::{self, Emulator, Stop},
+    error::{self, AssemblerError},
+    profile::listing,
+    program::{Instruction, Program},
+};
+
+/// The width of the column of the source and encoding, left of the changes.
+const COLUMN_WIDTH: usize = 44;
+
+/// A guided trace of a program for newcomers, explaining each instruction
+/// along with its effect on the registers and the RAM.
+pub struct Tutor {
+    emulator: Emulator,
+    /// The source line of each ROM address, with its line number.
+    lines: Vec<(usize, String)>,
+}
+
+impl Tutor {
+    /// Returns a tutor for the program, with its source lines. Programs
+    /// without source are explained on their disassembly.
+    pub fn new(program: &Program, source: &str, rom: Vec<u16>) -> Self {
+        let source: Vec<&str> = source.lines().collect();
+        let lines = program
+            .instructions()
+            .iter()
+            .enumerate()
+            .filter(|(_, instruction)| !matches!(instruction, Instruction::L(_)))
+            .map(|(index, _)| {
+                let line = program.source_line(index).unwrap_or(index + 1);
+                let text = source.get(line - 1).map_or("", |text| text.trim());
+                (line, text.to_string())
+            })
+            .collect();
+        Self {
+            emulator: Emulator::new(rom),
+            lines,
+        }
+    }
+
+    /// Loads the program of a tutor from a `.hack` file, or assembles it
+    /// from any other file. Returns an error if the file can't be read or
+    /// doesn't assemble.
+    pub fn load(path: &Path) -> Result<Self, AssemblerError> {
+        let rom = emulator::load_rom(path)?;
+        let program = listing(path, &rom);
+        let source = if path
+            .extension()
+            .is_some_and(|extension| extension == "hack")
+        {
+            program.to_string()
+        } else {
+            error::read_to_string(path)?
+        };
+        Ok(Self::new(&program, &source, rom))
+    }
+
+    /// Returns the emulator running the program.
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    /// Returns why the program can't go on, if it halted, ran past the end of
+    /// the ROM or is about to access M past the end of the RAM.
+    pub fn stop(&self) -> Option<Stop> {
+        if self.emulator.is_at_end() {
+            Some(Stop::EndOfRom)
+        } else if self.emulator.is_halted() {
+            Some(Stop::Halted)
+        } else if self.emulator.is_out_of_ram() {
+            Some(Stop::OutOfRam)
+        } else {
+            None
+        }
+    }
+
+    /// Executes the next instruction and returns its explanation: its
+    /// source and encoding on the left, the changes it made on the right.
+    /// Returns `None` if the program can't go on.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the instruction accesses M while A is outside of the RAM.
+    pub fn step(&mut self) -> Option<String> {
+        if self.stop().is_some() {
+            return None;
+        }
+        let pc = self.emulator.pc();
+        let (a, d) = (self.emulator.a(), self.emulator.d());
+        let word = self.emulator.rom()[pc as usize];
+        let access = self.emulator.step();
+
+        let mut left = Vec::new();
+        match self.lines.get(pc as usize) {
+            Some((line, text)) => left.push(format!("ROM[{}], line {}: {}", pc, line, text)),
+            None => left.push(format!("ROM[{}]", pc)),
+        }
+        left.extend(explain(word));
+
+        let mut right = vec![String::from("changes:")];
+        let mut change = |name: &str, before: u16, after: u16| {
+            if before != after {
+                right.push(format!("{:<8}{} -> {}", name, before as i16, after as i16));
+            }
+        };
+        change("A", a, self.emulator.a());
+        change("D", d, self.emulator.d());
+        if let Some(address) = access.write {
+            let name = format!("RAM[{}]", address);
+            change(
+                &name,
+                access.previous,
+                self.emulator.ram()[address as usize],
+            );
+        }
+        change("PC", pc, self.emulator.pc());
+        if let Some(address) = access.read {
+            let value = match access.write {
+                Some(_) => access.previous,
+                None => self.emulator.ram()[address as usize],
+            };
+            right.push(format!("read M = RAM[{}] = {}", address, value as i16));
+        }
+        if right.len() == 1 {
+            right.push(String::from("none"));
+        }
+
+        let mut lesson = String::new();
+        for row in 0..left.len().max(right.len()) {
+            let left = left.get(row).map_or("", String::as_str);
+            let right = right.get(row).map_or("", String::as_str);
+            let row = format!("{:<width$}{}", left, right, width = COLUMN_WIDTH);
+            writeln!(lesson, "{}", row.trim_end()).expect("write to string");
+        }
+        Some(lesson)
+    }
+}
+
+/// Returns the breakdown of the bits of an instruction and their meaning.
+fn explain(word: u16) -> Vec<String> {
+    if word & 0x8000 == 0 {
+        return vec![
+            format!("  0 {:015b}", word),
+            format!("  A-instruction: A = {}", word),
+        ];
+    }
+
+    let comp = (word >> 6) & 0b111_1111;
+    let dest = binary_to_dest(word >> 3);
+    let jump = binary_to_jump(word);
+    let or_none = |mnemonic: &'static str| {
+        if mnemonic.is_empty() {
+            "none"
+        } else {
+            mnemonic
+        }
+    };
+    vec![
+        format!(
+            "  111 {} {:06b} {:03b} {:03b}",
+            comp >> 6,
+            comp & 0b11_1111,
+            (word >> 3) & 0b111,
+            word & 0b111
+        ),
+        String::from("  C-instruction:"),
+        format!(
+            "    a={}: the ALU reads {}",
+            comp >> 6,
+            if comp >> 6 == 1 { "M" } else { "A" }
+        ),
+        format!("    comp {}", binary_to_comp(comp).unwrap_or("invalid")),
+        format!("    dest {}", or_none(dest)),
+        format!("    jump {}", or_none(jump)),
+    ]
+}
+
+/// Walks through the program, explaining an instruction whenever Enter is
+/// pressed, until the program stops or `q` is entered.
+///
+/// # Errors
+///
+/// Returns an error if the input can't be read or the output written.
+pub fn run(tutor: &mut Tutor, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    writeln!(
+        output,
+        "Press Enter to execute the next instruction, q to quit.\n"
+    )?;
+    let mut line = String::new();
+    while let Some(lesson) = tutor.step() {
+        write!(output, "{}", lesson)?;
+        output.flush()?;
+
+        line.clear();
+        if input.read_line(&mut line)? == 0 || line.trim() == "q" {
+            return Ok(());
+        }
+    }
+    let end = match tutor.stop() {
+        Some(Stop::EndOfRom) => "The program ran past the end of the ROM.",
+        Some(Stop::OutOfRam) => "The program would access M past the end of the RAM.",
+        _ => "The program reached its halting loop.",
+    };
+    writeln!(
+        output,
+        "{} It executed {} instructions.",
+        end,
+        tutor.emulator().cycles()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_explains_the_instruction() {
+        // Given
+        let source = "// Store 5\n@5\nD=A\n@16\nM=D;JGT\n";
+        let program = Program::from_source(source);
+        let mut tutor = Tutor::new(&program, source, program.assemble());
+
+        // When
+        let load = tutor.step().unwrap();
+        tutor.step();
+        tutor.step();
+        let store = tutor.step().unwrap();
+
+        // Then
+        assert!(load.starts_with("ROM[0], line 2: @5"));
+        assert!(load.contains("  0 000000000000101"));
+        assert!(load.contains("A       0 -> 5"));
+        assert!(store.contains("  111 0 001100 001 001"));
+        assert!(store.contains("dest M"));
+        assert!(store.contains("RAM[16] 0 -> 5"));
+        assert!(store.contains("PC      3 -> 16"));
+    }
+
+    #[test]
+    fn test_run_stops_at_the_halting_loop() {
+        // Given
+        let source = "@1\nD=A\n(END)\n@END\n0;JMP\n";
+        let program = Program::from_source(source);
+        let mut tutor = Tutor::new(&program, source, program.assemble());
+        let mut output = Vec::new();
+
+        // When
+        run(&mut tutor, "\n\n\n".as_bytes(), &mut output).unwrap();
+
+        // Then
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.ends_with("The program reached its halting loop. It executed 2 instructions.\n")
+        );
+    }
+}
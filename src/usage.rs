@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::{
+    ir::{Ir, IrInstruction},
+    symbol_table::SymbolTable,
+};
+
+/// How a register or variable is accessed by the program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Usage {
+    /// `Rn` for the registers, whatever alias is used, or the variable name.
+    pub name: String,
+    pub address: u32,
+    /// The number of instructions reading the cell through M.
+    pub reads: usize,
+    /// The number of instructions writing the cell through M.
+    pub writes: usize,
+    /// The ROM addresses of the first and last instructions accessing the cell.
+    pub first: u32,
+    pub last: u32,
+}
+
+/// The usage of R0–R15 and of the variables, statically counted from the
+/// M accesses following each A-instruction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UsageReport {
+    /// The accessed cells, registers first, by address.
+    pub cells: Vec<Usage>,
+}
+
+impl UsageReport {
+    /// Analyzes the IR. An access is counted when M is used while A still holds
+    /// a loaded register or variable address. Accesses through computed
+    /// addresses, such as `A=M`, aren't counted.
+    pub fn new(ir: &Ir, symbol_table: &SymbolTable) -> Self {
+        let mut report = UsageReport::default();
+        let mut current: Option<usize> = None;
+        for node in &ir.nodes {
+            if !node.labels.is_empty() {
+                // A may hold anything when jumped to.
+                current = None;
+            }
+            match &node.instruction {
+                IrInstruction::A { value, symbol } => {
+                    let name = match symbol {
+                        _ if *value < 16 => format!("R{}", value),
+                        Some(symbol) if !symbol_table.is_label(symbol) => symbol.clone(),
+                        _ => {
+                            current = None;
+                            continue;
+                        }
+                    };
+                    current = Some(report.cell(name, *value));
+                }
+                IrInstruction::C { dest, comp, jump } => {
+                    if let Some(index) = current {
+                        let cell = &mut report.cells[index];
+                        let reads = comp.contains('M');
+                        let writes = dest.contains('M');
+                        cell.reads += usize::from(reads);
+                        cell.writes += usize::from(writes);
+                        if reads || writes {
+                            cell.first = cell.first.min(node.address);
+                            cell.last = cell.last.max(node.address);
+                        }
+                    }
+                    if dest.contains('A') || !jump.is_empty() {
+                        current = None;
+                    }
+                }
+            }
+        }
+        report
+            .cells
+            .retain(|cell| cell.reads > 0 || cell.writes > 0);
+        report.cells.sort_by_key(|cell| cell.address);
+        report
+    }
+
+    fn cell(&mut self, name: String, address: u32) -> usize {
+        if let Some(index) = self.cells.iter().position(|cell| cell.name == name) {
+            return index;
+        }
+        self.cells.push(Usage {
+            name,
+            address,
+            reads: 0,
+            writes: 0,
+            first: u32::MAX,
+            last: 0,
+        });
+        self.cells.len() - 1
+    }
+
+    /// Returns the cells written but never read.
+    pub fn never_read(&self) -> impl Iterator<Item = &Usage> {
+        self.cells
+            .iter()
+            .filter(|cell| cell.reads == 0 && cell.writes > 0)
+    }
+}
+
+/// Renders the report as a table, followed by a warning per cell never read.
+impl fmt::Display for UsageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16} {:>7} {:>7} {:>7} {:>7} {:>7}",
+            "cell", "address", "reads", "writes", "first", "last"
+        )?;
+        for cell in &self.cells {
+            writeln!(
+                f,
+                "{:<16} {:>7} {:>7} {:>7} {:>7} {:>7}",
+                cell.name, cell.address, cell.reads, cell.writes, cell.first, cell.last
+            )?;
+        }
+        for cell in self.never_read() {
+            writeln!(f, "warning: {} is written but never read", cell.name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+
+    #[test]
+    fn test_usage_report() {
+        // Given
+        let source = "@SP\nAM=M-1\nD=M\n@R13\nM=D\n@i\nM=1\n(LOOP)\nD=M\n@i\nM=D+M\n@LOOP\n0;JMP\n";
+        let program = Program::from_source(source);
+        let symbol_table = program.resolve();
+        let ir = Ir::lower(&program, &symbol_table);
+
+        // When
+        let report = UsageReport::new(&ir, &symbol_table);
+
+        // Then
+        let cells: Vec<(&str, usize, usize, u32, u32)> = report
+            .cells
+            .iter()
+            .map(|cell| {
+                (
+                    cell.name.as_str(),
+                    cell.reads,
+                    cell.writes,
+                    cell.first,
+                    cell.last,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![("R0", 1, 1, 1, 1), ("R13", 0, 1, 4, 4), ("i", 1, 2, 6, 9)],
+            cells
+        );
+        assert_eq!(
+            vec!["R13"],
+            report
+                .never_read()
+                .map(|cell| cell.name.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+}
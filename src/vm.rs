@@ -0,0 +1,517 @@
+use std::{
+    fmt::{self, Write},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::error::{self, AssemblerError};
+
+/// A memory segment of the VM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Segment {
+    Argument,
+    Local,
+    Static,
+    Constant,
+    This,
+    That,
+    Pointer,
+    Temp,
+}
+
+/// An arithmetic or logical command, operating on the top of the stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Add,
+    Sub,
+    Neg,
+    Eq,
+    Gt,
+    Lt,
+    And,
+    Or,
+    Not,
+}
+
+/// A command of the VM language.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VmCommand {
+    Arithmetic(Operation),
+    Push(Segment, u16),
+    Pop(Segment, u16),
+    Label(String),
+    Goto(String),
+    IfGoto(String),
+    /// A function declaration, with its number of local variables.
+    Function(String, u16),
+    /// A function call, with its number of arguments.
+    Call(String, u16),
+    Return,
+}
+
+impl fmt::Display for VmCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let segment = |segment: &Segment| format!("{:?}", segment).to_lowercase();
+        match self {
+            VmCommand::Arithmetic(operation) => {
+                write!(f, "{}", format!("{:?}", operation).to_lowercase())
+            }
+            VmCommand::Push(s, index) => write!(f, "push {} {}", segment(s), index),
+            VmCommand::Pop(s, index) => write!(f, "pop {} {}", segment(s), index),
+            VmCommand::Label(label) => write!(f, "label {}", label),
+            VmCommand::Goto(label) => write!(f, "goto {}", label),
+            VmCommand::IfGoto(label) => write!(f, "if-goto {}", label),
+            VmCommand::Function(name, locals) => write!(f, "function {} {}", name, locals),
+            VmCommand::Call(name, arguments) => write!(f, "call {} {}", name, arguments),
+            VmCommand::Return => write!(f, "return"),
+        }
+    }
+}
+
+/// The first RAM address of the stack.
+pub(crate) const STACK: u16 = 256;
+/// The first RAM address of the `temp` segment.
+const TEMP: u16 = 5;
+/// The first RAM address of the `pointer` segment.
+const POINTER: u16 = 3;
+
+/// Parses the source of a `.vm` file, named in the errors.
+///
+/// Returns a syntax error, with its file and line number, if a command is
+/// invalid.
+pub fn parse(file: &str, source: &str) -> Result<Vec<VmCommand>, AssemblerError> {
+    let mut commands = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split("//").next().unwrap_or_default();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let error =
+            |message: &str| AssemblerError::syntax(format!("{}:{}: {}", file, index + 1, message));
+        let number = |word: Option<&&str>| -> Result<u16, AssemblerError> {
+            word.and_then(|word| word.parse().ok())
+                .ok_or_else(|| error("expected a number"))
+        };
+        let name = |word: Option<&&str>| -> Result<String, AssemblerError> {
+            word.map(|word| word.to_string())
+                .ok_or_else(|| error("expected a name"))
+        };
+        let segment = |word: Option<&&str>| match word.copied() {
+            Some("argument") => Ok(Segment::Argument),
+            Some("local") => Ok(Segment::Local),
+            Some("static") => Ok(Segment::Static),
+            Some("constant") => Ok(Segment::Constant),
+            Some("this") => Ok(Segment::This),
+            Some("that") => Ok(Segment::That),
+            Some("pointer") => Ok(Segment::Pointer),
+            Some("temp") => Ok(Segment::Temp),
+            Some(segment) => Err(error(&format!("unknown segment {}", segment))),
+            None => Err(error("expected a segment")),
+        };
+
+        let arity = match words[0] {
+            "push" | "pop" | "function" | "call" => 3,
+            "label" | "goto" | "if-goto" => 2,
+            _ => 1,
+        };
+        if words.len() > arity {
+            return Err(error(&format!("unexpected {}", words[arity])));
+        }
+        let command = match words[0] {
+            "add" => VmCommand::Arithmetic(Operation::Add),
+            "sub" => VmCommand::Arithmetic(Operation::Sub),
+            "neg" => VmCommand::Arithmetic(Operation::Neg),
+            "eq" => VmCommand::Arithmetic(Operation::Eq),
+            "gt" => VmCommand::Arithmetic(Operation::Gt),
+            "lt" => VmCommand::Arithmetic(Operation::Lt),
+            "and" => VmCommand::Arithmetic(Operation::And),
+            "or" => VmCommand::Arithmetic(Operation::Or),
+            "not" => VmCommand::Arithmetic(Operation::Not),
+            "push" => VmCommand::Push(segment(words.get(1))?, number(words.get(2))?),
+            "pop" => {
+                let segment = segment(words.get(1))?;
+                if segment == Segment::Constant {
+                    return Err(error("can't pop to the constant segment"));
+                }
+                VmCommand::Pop(segment, number(words.get(2))?)
+            }
+            "label" => VmCommand::Label(name(words.get(1))?),
+            "goto" => VmCommand::Goto(name(words.get(1))?),
+            "if-goto" => VmCommand::IfGoto(name(words.get(1))?),
+            "function" => VmCommand::Function(name(words.get(1))?, number(words.get(2))?),
+            "call" => VmCommand::Call(name(words.get(1))?, number(words.get(2))?),
+            "return" => VmCommand::Return,
+            command => return Err(error(&format!("unknown command {}", command))),
+        };
+        match command {
+            VmCommand::Push(Segment::Temp, index) | VmCommand::Pop(Segment::Temp, index)
+                if index >= 8 =>
+            {
+                return Err(error("temp index out of range"));
+            }
+            VmCommand::Push(Segment::Pointer, index) | VmCommand::Pop(Segment::Pointer, index)
+                if index >= 2 =>
+            {
+                return Err(error("pointer index out of range"));
+            }
+            VmCommand::Push(Segment::Constant, value) if value > 0x7FFF => {
+                return Err(error("constant out of range"));
+            }
+            _ => {}
+        }
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+/// Translates VM files, given as their name and commands, into Hack assembly.
+///
+/// The name of a file prefixes its static variables. The bootstrap code,
+/// setting the stack pointer and calling `Sys.init`, is only emitted if a file
+/// declares `Sys.init`.
+pub fn translate(files: &[(String, Vec<VmCommand>)]) -> String {
+    let mut translator = Translator::default();
+    let has_sys_init = files.iter().any(|(_, commands)| {
+        commands
+            .iter()
+            .any(|command| matches!(command, VmCommand::Function(name, _) if name == "Sys.init"))
+    });
+    if has_sys_init {
+        translator.file = String::from("bootstrap");
+        translator.bootstrap();
+    }
+    for (file, commands) in files {
+        translator.file = file.clone();
+        translator.function = String::new();
+        for command in commands {
+            translator.translate(command);
+        }
+    }
+    translator.output
+}
+
+/// Translates a `.vm` file, or a directory of `.vm` files in name order, into
+/// Hack assembly. Returns an error if a file can't be read or is invalid.
+pub fn translate_path(path: &Path) -> Result<String, AssemblerError> {
+    Ok(translate(&read_path(path)?))
+}
+
+/// Reads and parses a `.vm` file, or the `.vm` files of a directory. Returns
+/// an error if there is no `.vm` file, or if a file can't be read or is
+/// invalid.
+pub fn read_path(path: &Path) -> Result<Vec<(String, Vec<VmCommand>)>, AssemblerError> {
+    let paths = files_with_extension(path, "vm")?;
+    if paths.is_empty() {
+        return Err(no_file(path, "vm"));
+    }
+    paths.iter().map(|path| read_file(path)).collect()
+}
+
+/// Reads and parses a `.vm` file, returning it with its name. Returns an
+/// error if the file can't be read or is invalid.
+pub fn read_file(path: &Path) -> Result<(String, Vec<VmCommand>), AssemblerError> {
+    let source = error::read_to_string(path)?;
+    let name = path
+        .file_stem()
+        .expect("missing file name")
+        .to_string_lossy()
+        .to_string();
+    Ok((name, parse(&path.display().to_string(), &source)?))
+}
+
+/// Returns the path if it's a file, or the files of the directory with the
+/// extension, in name order. Returns an error if the directory can't be read.
+pub(crate) fn files_with_extension(
+    path: &Path,
+    extension: &str,
+) -> Result<Vec<PathBuf>, AssemblerError> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let error = |error| AssemblerError::io(format!("failed to read {}", path.display()), error);
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(path).map_err(error)? {
+        let path = entry.map_err(error)?.path();
+        if path.extension().is_some_and(|ext| ext == extension) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Returns the error of a directory without any file with the extension.
+pub(crate) fn no_file(path: &Path, extension: &str) -> AssemblerError {
+    AssemblerError::io(
+        format!("no .{} file in {}", extension, path.display()),
+        io::ErrorKind::NotFound.into(),
+    )
+}
+
+#[derive(Default)]
+struct Translator {
+    output: String,
+    /// The name of the file being translated, prefixing its statics.
+    file: String,
+    /// The function being translated, prefixing its labels.
+    function: String,
+    /// The number of labels generated, keeping them unique.
+    labels: usize,
+}
+
+impl Translator {
+    fn emit(&mut self, lines: &[&str]) {
+        for line in lines {
+            writeln!(self.output, "{}", line).expect("write to string");
+        }
+    }
+
+    fn unique_label(&mut self, name: &str) -> String {
+        self.labels += 1;
+        format!("{}${}.{}", self.prefix(), name, self.labels)
+    }
+
+    /// Returns the scope of the labels: the current function, or the file
+    /// outside of functions.
+    fn prefix(&self) -> &str {
+        if self.function.is_empty() {
+            &self.file
+        } else {
+            &self.function
+        }
+    }
+
+    fn bootstrap(&mut self) {
+        self.emit(&["// bootstrap", &format!("@{}", STACK), "D=A", "@SP", "M=D"]);
+        self.translate(&VmCommand::Call(String::from("Sys.init"), 0));
+    }
+
+    /// Pushes the D register.
+    fn push_d(&mut self) {
+        self.emit(&["@SP", "A=M", "M=D", "@SP", "M=M+1"]);
+    }
+
+    /// Pops the top of the stack into the D register.
+    fn pop_d(&mut self) {
+        self.emit(&["@SP", "AM=M-1", "D=M"]);
+    }
+
+    /// Returns the address of a fixed segment cell, or the base pointer of a
+    /// dynamic segment.
+    fn address(&self, segment: Segment, index: u16) -> Result<String, &'static str> {
+        match segment {
+            Segment::Static => Ok(format!("{}.{}", self.file, index)),
+            Segment::Temp => Ok((TEMP + index).to_string()),
+            Segment::Pointer => Ok((POINTER + index).to_string()),
+            Segment::Argument => Err("ARG"),
+            Segment::Local => Err("LCL"),
+            Segment::This => Err("THIS"),
+            Segment::That => Err("THAT"),
+            Segment::Constant => unreachable!("the constant segment has no address"),
+        }
+    }
+
+    fn translate(&mut self, command: &VmCommand) {
+        writeln!(self.output, "// {}", command).expect("write to string");
+        match command {
+            VmCommand::Arithmetic(operation) => self.arithmetic(*operation),
+            VmCommand::Push(Segment::Constant, value) => {
+                self.emit(&[&format!("@{}", value), "D=A"]);
+                self.push_d();
+            }
+            VmCommand::Push(segment, index) => {
+                match self.address(*segment, *index) {
+                    Ok(address) => self.emit(&[&format!("@{}", address), "D=M"]),
+                    Err(base) => self.emit(&[
+                        &format!("@{}", index),
+                        "D=A",
+                        &format!("@{}", base),
+                        "A=D+M",
+                        "D=M",
+                    ]),
+                }
+                self.push_d();
+            }
+            VmCommand::Pop(segment, index) => match self.address(*segment, *index) {
+                Ok(address) => {
+                    self.pop_d();
+                    self.emit(&[&format!("@{}", address), "M=D"]);
+                }
+                Err(base) => {
+                    self.emit(&[
+                        &format!("@{}", index),
+                        "D=A",
+                        &format!("@{}", base),
+                        "D=D+M",
+                        "@R13",
+                        "M=D",
+                    ]);
+                    self.pop_d();
+                    self.emit(&["@R13", "A=M", "M=D"]);
+                }
+            },
+            VmCommand::Label(label) => {
+                let label = format!("({}${})", self.prefix(), label);
+                self.emit(&[&label]);
+            }
+            VmCommand::Goto(label) => {
+                let label = format!("@{}${}", self.prefix(), label);
+                self.emit(&[&label, "0;JMP"]);
+            }
+            VmCommand::IfGoto(label) => {
+                let label = format!("@{}${}", self.prefix(), label);
+                self.pop_d();
+                self.emit(&[&label, "D;JNE"]);
+            }
+            VmCommand::Function(name, locals) => {
+                self.function = name.clone();
+                self.emit(&[&format!("({})", name)]);
+                for _ in 0..*locals {
+                    self.emit(&["@SP", "A=M", "M=0", "@SP", "M=M+1"]);
+                }
+            }
+            VmCommand::Call(name, arguments) => {
+                let return_address = self.unique_label("ret");
+                self.emit(&[&format!("@{}", return_address), "D=A"]);
+                self.push_d();
+                for pointer in ["LCL", "ARG", "THIS", "THAT"] {
+                    self.emit(&[&format!("@{}", pointer), "D=M"]);
+                    self.push_d();
+                }
+                self.emit(&[
+                    "@SP",
+                    "D=M",
+                    &format!("@{}", arguments + 5),
+                    "D=D-A",
+                    "@ARG",
+                    "M=D",
+                    "@SP",
+                    "D=M",
+                    "@LCL",
+                    "M=D",
+                    &format!("@{}", name),
+                    "0;JMP",
+                    &format!("({})", return_address),
+                ]);
+            }
+            VmCommand::Return => {
+                // R13 holds the frame, R14 the return address.
+                self.emit(&[
+                    "@LCL", "D=M", "@R13", "M=D", "@5", "A=D-A", "D=M", "@R14", "M=D",
+                ]);
+                self.pop_d();
+                self.emit(&["@ARG", "A=M", "M=D", "@ARG", "D=M+1", "@SP", "M=D"]);
+                for pointer in ["THAT", "THIS", "ARG", "LCL"] {
+                    self.emit(&["@R13", "AM=M-1", "D=M", &format!("@{}", pointer), "M=D"]);
+                }
+                self.emit(&["@R14", "A=M", "0;JMP"]);
+            }
+        }
+    }
+
+    fn arithmetic(&mut self, operation: Operation) {
+        let binary = |comp| ["@SP", "AM=M-1", "D=M", "A=A-1", comp];
+        match operation {
+            Operation::Add => self.emit(&binary("M=D+M")),
+            Operation::Sub => self.emit(&binary("M=M-D")),
+            Operation::And => self.emit(&binary("M=D&M")),
+            Operation::Or => self.emit(&binary("M=D|M")),
+            Operation::Neg => self.emit(&["@SP", "A=M-1", "M=-M"]),
+            Operation::Not => self.emit(&["@SP", "A=M-1", "M=!M"]),
+            Operation::Eq | Operation::Gt | Operation::Lt => {
+                let jump = match operation {
+                    Operation::Eq => "D;JEQ",
+                    Operation::Gt => "D;JGT",
+                    _ => "D;JLT",
+                };
+                let is_true = self.unique_label("true");
+                let end = self.unique_label("end");
+                self.emit(&binary("D=M-D"));
+                self.emit(&[
+                    &format!("@{}", is_true),
+                    jump,
+                    "@SP",
+                    "A=M-1",
+                    "M=0",
+                    &format!("@{}", end),
+                    "0;JMP",
+                    &format!("({})", is_true),
+                    "@SP",
+                    "A=M-1",
+                    "M=-1",
+                    &format!("({})", end),
+                ]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assembler::Assembler, emulator::Emulator};
+
+    fn run(assembly: &str, ram: &[(usize, u16)]) -> Emulator {
+        let rom = Assembler::from_source(assembly)
+            .fill_symbol_table()
+            .assemble();
+        let mut emulator = Emulator::new(rom);
+        for (address, value) in ram {
+            emulator.ram_mut()[*address] = *value;
+        }
+        emulator.run(10_000);
+        emulator
+    }
+
+    #[test]
+    fn test_translate_arithmetic() {
+        // Given
+        let source = "push constant 7\npush constant 8\nadd // 15\npush constant 15\neq\n\
+                      push constant 3\npush constant 5\nlt\npush constant 2\nneg\n";
+
+        // When
+        let assembly = translate(&[(String::from("Test"), parse("Test.vm", source).unwrap())]);
+
+        // Then
+        let emulator = run(&assembly, &[(0, STACK)]);
+        assert_eq!(STACK + 3, emulator.ram()[0]);
+        assert_eq!(&[0xFFFF, 0xFFFF, (-2i16) as u16], &emulator.ram()[256..259]);
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_file_and_line() {
+        // Given
+        let source = "push constant 1\npop constant 2\n";
+
+        // When
+        let error = parse("Test.vm", source).unwrap_err();
+
+        // Then
+        assert!(matches!(error, AssemblerError::Syntax { .. }));
+        assert_eq!(
+            "Test.vm:2: can't pop to the constant segment",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn test_translate_call_with_bootstrap() {
+        // Given
+        let sys = "function Sys.init 0\npush constant 21\ncall Main.double 1\npop static 0\n\
+                   label END\ngoto END\n";
+        let main = "function Main.double 1\npush argument 0\npop local 0\n\
+                    push local 0\npush local 0\nadd\nreturn\n";
+
+        // When
+        let assembly = translate(&[
+            (String::from("Main"), parse("Main.vm", main).unwrap()),
+            (String::from("Sys"), parse("Sys.vm", sys).unwrap()),
+        ]);
+
+        // Then
+        let emulator = run(&assembly, &[]);
+        assert_eq!(42, emulator.ram()[16]);
+        assert!(assembly.contains("@Sys.0"));
+    }
+}
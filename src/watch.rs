@@ -0,0 +1,106 @@
+use std::{
+    hash::Hasher,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use rustc_hash::{FxHashMap, FxHasher};
+
+/// The time between two checks of the inputs in watch mode.
+pub const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The state of a file: its metadata, checked first, and the hash of its
+/// content, computed only when the metadata changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Fingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+/// The fingerprints of the inputs as last processed, so that only the
+/// changed ones are processed again. Programs don't include other files, so
+/// an input only depends on its own content.
+#[derive(Clone, Debug, Default)]
+pub struct IncrementalCache {
+    /// The fingerprint of each input, `None` if it couldn't be read.
+    fingerprints: FxHashMap<PathBuf, Option<Fingerprint>>,
+}
+
+impl IncrementalCache {
+    /// Returns a new cache, for which all the inputs have changed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the inputs added or changed since the last call, in order,
+    /// and records their fingerprints. The inputs which are no longer given
+    /// are forgotten.
+    pub fn changed(&mut self, inputs: &[PathBuf]) -> Vec<PathBuf> {
+        let mut fingerprints = FxHashMap::default();
+        let mut changed = Vec::new();
+        for input in inputs {
+            let previous = self.fingerprints.remove(input);
+            let fingerprint = fingerprint(input, previous.flatten());
+            if previous != Some(fingerprint) {
+                changed.push(input.clone());
+            }
+            fingerprints.insert(input.clone(), fingerprint);
+        }
+        self.fingerprints = fingerprints;
+        changed
+    }
+}
+
+/// Returns the fingerprint of the file, reusing the hash of the previous one
+/// when the metadata is the same, or `None` if the file can't be read.
+fn fingerprint(path: &Path, previous: Option<Fingerprint>) -> Option<Fingerprint> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok();
+    let len = metadata.len();
+    if let Some(previous) = previous.filter(|previous| {
+        previous.modified.is_some() && previous.modified == modified && previous.len == len
+    }) {
+        return Some(previous);
+    }
+
+    let mut hasher = FxHasher::default();
+    hasher.write(&std::fs::read(path).ok()?);
+    Some(Fingerprint {
+        modified,
+        len,
+        hash: hasher.finish(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_only_changed_inputs_are_returned() {
+        // Given
+        let dir = TempDir::new("hack-watch");
+        let inputs = vec![dir.join("A.asm"), dir.join("B.asm")];
+        std::fs::write(&inputs[0], "@1\n").unwrap();
+        std::fs::write(&inputs[1], "@2\n").unwrap();
+        let mut cache = IncrementalCache::new();
+
+        // When
+        let first = cache.changed(&inputs);
+        let unchanged = cache.changed(&inputs);
+        std::fs::write(&inputs[1], "@2\nD=A\n").unwrap();
+        let edited = cache.changed(&inputs);
+        std::fs::remove_file(&inputs[0]).unwrap();
+        let removed = cache.changed(&inputs);
+        let still_removed = cache.changed(&inputs);
+
+        // Then
+        assert_eq!(inputs, first);
+        assert!(unchanged.is_empty());
+        assert_eq!(vec![inputs[1].clone()], edited);
+        assert_eq!(vec![inputs[0].clone()], removed);
+        assert!(still_removed.is_empty());
+    }
+}
@@ -0,0 +1,41 @@
+// Tests Max.asm on the CPU emulator.
+
+load Max.asm,
+output-file Max.out,
+compare-to Max.cmp,
+output-list RAM[0]%D2.6.2 RAM[1]%D2.6.2 RAM[2]%D2.6.2;
+
+set RAM[0] 0,   // Sets test arguments
+set RAM[1] 0,
+set RAM[2] -1;  // Tests that the program sets R2 to the max
+repeat 14 {
+  ticktock;
+}
+output;
+
+set PC 0,
+set RAM[0] 1,
+set RAM[1] 0,
+set RAM[2] -1;
+repeat 14 {
+  ticktock;
+}
+output;
+
+set PC 0,
+set RAM[0] 0,
+set RAM[1] 2,
+set RAM[2] -1;
+repeat 14 {
+  ticktock;
+}
+output;
+
+set PC 0,
+set RAM[0] 1234,
+set RAM[1] 987,
+set RAM[2] -1;
+repeat 14 {
+  ticktock;
+}
+output;
@@ -0,0 +1,589 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use hack_assembler::{
+    golden::{self, CORPUS},
+    testing::TempDir,
+};
+
+#[test]
+fn test_corpus_assembles_byte_identical() {
+    // Given
+    let corpus = CORPUS;
+
+    // When
+    let mismatches = golden::self_test().unwrap();
+
+    // Then
+    assert_eq!(7, corpus.len());
+    assert!(mismatches.is_empty(), "{:#?}", mismatches);
+}
+
+#[test]
+fn test_cli_writes_byte_identical_hack_files() {
+    // Given
+    let dir = TempDir::new("hack-golden");
+
+    for case in CORPUS {
+        let input: PathBuf = dir.join(format!("{}.asm", case.name));
+        std::fs::write(&input, case.source).unwrap();
+
+        // When
+        let status = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .arg("-i")
+            .arg(&input)
+            .status()
+            .unwrap();
+
+        // Then
+        assert!(status.success(), "{}", case.name);
+        let output = std::fs::read_to_string(input.with_extension("hack")).unwrap();
+        assert!(output == case.expected, "{} differs", case.name);
+    }
+}
+
+#[test]
+fn test_cli_writes_into_the_output_directory() {
+    // Given
+    let dir = TempDir::new("hack-output");
+    let inputs: Vec<PathBuf> = CORPUS
+        .iter()
+        .map(|case| {
+            let input = dir.join(format!("{}.asm", case.name));
+            std::fs::write(&input, case.source).unwrap();
+            input
+        })
+        .collect();
+    let build = dir.join("build");
+
+    // When
+    let status = Command::new(env!("CARGO_BIN_EXE_assembler"))
+        .arg("-i")
+        .args(&inputs)
+        .arg("-o")
+        .arg(&build)
+        .status()
+        .unwrap();
+
+    // Then
+    assert!(status.success());
+    for case in CORPUS {
+        let output = std::fs::read_to_string(build.join(format!("{}.hack", case.name))).unwrap();
+        assert!(output == case.expected, "{} differs", case.name);
+        assert!(!dir.join(format!("{}.hack", case.name)).exists());
+    }
+}
+
+#[test]
+fn test_cli_pipes_the_standard_input_to_the_standard_output() {
+    for case in CORPUS {
+        // Given
+        let mut child = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .args(["-i", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(case.source.as_bytes())
+            .unwrap();
+
+        // When
+        let output = child.wait_with_output().unwrap();
+
+        // Then
+        assert!(output.status.success(), "{}", case.name);
+        assert!(
+            output.stdout == case.expected.as_bytes(),
+            "{} differs",
+            case.name
+        );
+    }
+}
+
+#[test]
+fn test_cli_checks_without_writing() {
+    // Given
+    let dir = TempDir::new("hack-check");
+    let valid = dir.join("Valid.asm");
+    let invalid = dir.join("Invalid.asm");
+    std::fs::write(&valid, "@1\nD=A\n").unwrap();
+    std::fs::write(&invalid, "D=Q\n").unwrap();
+
+    for (input, code) in [(&valid, 0), (&invalid, 1)] {
+        // When
+        let status = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .arg("--check")
+            .arg("-i")
+            .arg(input)
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+
+        // Then
+        assert_eq!(Some(code), status.code());
+        assert!(!input.with_extension("hack").exists());
+    }
+}
+
+#[test]
+fn test_cli_streams_with_the_exit_code_of_the_failure() {
+    // Given
+    let dir = TempDir::new("hack-stream-exit");
+    let valid = dir.join("Valid.asm");
+    let invalid = dir.join("Invalid.asm");
+    let missing = dir.join("Missing.asm");
+    std::fs::write(&valid, "@1\nD=A\n").unwrap();
+    std::fs::write(&invalid, "@1\nD=Q\n").unwrap();
+
+    for (input, code) in [(&valid, 0), (&invalid, 1), (&missing, 3)] {
+        // When
+        let output = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .arg("--stream")
+            .arg("-i")
+            .arg(input)
+            .output()
+            .unwrap();
+
+        // Then
+        assert_eq!(Some(code), output.status.code(), "{}", input.display());
+        assert_eq!(code == 0, input.with_extension("hack").exists());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(code == 1, stderr.contains("2 | D=Q"), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_cli_translates_with_the_exit_code_of_the_failure() {
+    // Given
+    let dir = TempDir::new("hack-translate-exit");
+    let valid = dir.join("Valid.vm");
+    let invalid = dir.join("Invalid.vm");
+    let missing = dir.join("Missing.vm");
+    std::fs::write(&valid, "push constant 1\n").unwrap();
+    std::fs::write(&invalid, "push constant 1\npop constant 2\n").unwrap();
+    let undefined = dir.join("Main.jack");
+    std::fs::write(
+        &undefined,
+        "class Main {\n    function int main() {\n        return x;\n    }\n}\n",
+    )
+    .unwrap();
+    let cases = [
+        ("vm", &valid, 0),
+        ("vm", &invalid, 1),
+        ("vm", &missing, 3),
+        ("jack", &undefined, 2),
+    ];
+
+    for (command, input, code) in cases
+        .into_iter()
+        .filter(|(command, ..)| cfg!(feature = "jack") || *command != "jack")
+    {
+        // When
+        let output = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .arg(command)
+            .arg("-i")
+            .arg(input)
+            .output()
+            .unwrap();
+
+        // Then
+        assert_eq!(Some(code), output.status.code(), "{}", input.display());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+        assert_eq!(code == 0, stderr.is_empty(), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_cli_links_with_the_exit_code_of_the_failure() {
+    // Given
+    let dir = TempDir::new("hack-link-exit");
+    std::fs::write(dir.join("Main.asm"), "@Missing\n0;JMP\n").unwrap();
+    std::fs::write(dir.join("Invalid.asm"), "@1\nD=Q\n").unwrap();
+    let assembler = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .current_dir(&*dir)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    assert!(assembler(&["object", "-i", "Main.asm"]).status.success());
+
+    for (args, code) in [
+        (["object", "-i", "Invalid.asm"].as_slice(), 1),
+        (&["link", "Main.hobj", "-o", "Main.hack"], 2),
+        (&["link", "Missing.hobj", "-o", "Main.hack"], 3),
+    ] {
+        // When
+        let output = assembler(args);
+
+        // Then
+        assert_eq!(Some(code), output.status.code(), "{:?}", args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_cli_runs_with_the_exit_code_of_the_failure() {
+    // Given
+    let dir = TempDir::new("hack-run-exit");
+    std::fs::write(dir.join("Valid.asm"), "@1\nD=A\n").unwrap();
+    std::fs::write(dir.join("Invalid.asm"), "@1\nD=Q\n").unwrap();
+    std::fs::write(dir.join("Invalid.hack"), "0000000000000001\n101\n").unwrap();
+    let assembler = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .current_dir(&*dir)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    for (args, code) in [
+        (["run", "-i", "Valid.asm"].as_slice(), 0),
+        (&["run", "-i", "Invalid.asm"], 1),
+        (&["run", "-i", "Missing.asm"], 3),
+        (&["debug", "-i", "Invalid.asm"], 1),
+        (&["disassemble", "-i", "Invalid.hack"], 1),
+        (
+            &["diff-trace", "-i", "Valid.asm", "--trace", "Missing.txt"],
+            3,
+        ),
+    ] {
+        // When
+        let output = assembler(args);
+
+        // Then
+        assert_eq!(Some(code), output.status.code(), "{:?}", args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+        assert_eq!(code == 1, stderr.contains("2 | "), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_cli_tests_with_the_exit_code_of_the_failure() {
+    // Given
+    let dir = TempDir::new("hack-test-exit");
+    std::fs::write(dir.join("Add.asm"), "@2\nD=A\n@0\nM=D\n").unwrap();
+    std::fs::write(dir.join("Add.cmp"), "| RAM[0] |\n|      2 |\n").unwrap();
+    let script = "load Add.asm, compare-to Add.cmp, output-list RAM[0]%D1.6.1;\n";
+    std::fs::write(
+        dir.join("Valid.tst"),
+        format!("{}repeat 4 {{ ticktock; }} output;", script),
+    )
+    .unwrap();
+    std::fs::write(dir.join("Invalid.tst"), format!("{}jump;", script)).unwrap();
+    std::fs::write(dir.join("Failing.tst"), format!("{}output;", script)).unwrap();
+
+    for (script, code) in [
+        ("Valid.tst", 0),
+        ("Invalid.tst", 1),
+        ("Failing.tst", 2),
+        ("Missing.tst", 3),
+    ] {
+        // When
+        let output = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .current_dir(&*dir)
+            .args(["test", "-i", script])
+            .output()
+            .unwrap();
+
+        // Then
+        assert_eq!(Some(code), output.status.code(), "{}", script);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_cli_reads_sources_with_the_exit_code_of_the_failure() {
+    // Given
+    let dir = TempDir::new("hack-source-exit");
+    std::fs::write(dir.join("Valid.asm"), "@1\nD=A\n").unwrap();
+    std::fs::write(dir.join("Invalid.asm"), "@1\nD=Q\n").unwrap();
+    std::fs::write(dir.join("Invalid.json"), "[").unwrap();
+    std::fs::write(dir.join("Invalid.rhai"), "let m = ;").unwrap();
+    std::fs::write(dir.join("Failing.rhai"), "assert_eq(1, 2);").unwrap();
+
+    for (args, code) in [
+        (["diff", "Valid.asm", "Valid.asm"].as_slice(), 0),
+        (&["diff", "Valid.asm", "Invalid.asm"], 1),
+        (&["diff", "Valid.asm", "Missing.asm"], 3),
+        (&["obfuscate", "-i", "Invalid.asm"], 1),
+        (&["obfuscate", "-i", "Missing.asm"], 3),
+        (&["fmt", "--config", "Invalid.json", "Valid.asm"], 1),
+        (&["fmt", "Missing.asm"], 3),
+        (&["grade", "-s", "Invalid.json", "Valid.asm"], 1),
+        (&["grade", "-s", "Missing.json", "Valid.asm"], 3),
+        (&["script", "-i", "Invalid.rhai"], 1),
+        (&["script", "-i", "Failing.rhai"], 2),
+        (&["script", "-i", "Missing.rhai"], 3),
+    ] {
+        // When
+        let output = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .current_dir(&*dir)
+            .args(args)
+            .output()
+            .unwrap();
+
+        // Then
+        assert_eq!(Some(code), output.status.code(), "{:?}", args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+        assert_eq!(
+            code == 3,
+            stderr.contains("error[io]: failed to read"),
+            "{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn test_cli_exits_with_the_io_code_on_a_missing_file() {
+    // Given
+    let dir = TempDir::new("hack-missing-exit");
+    std::fs::write(dir.join("Valid.asm"), "@1\nD=A\n").unwrap();
+
+    let cases = [
+        ["-i", "Missing.asm"].as_slice(),
+        &["run", "-i", "Missing.asm"],
+        &["debug", "-i", "Missing.asm"],
+        &["tutor", "-i", "Missing.asm"],
+        &["disassemble", "-i", "Missing.hack"],
+        &["diff", "Valid.asm", "Missing.asm"],
+        &["obfuscate", "-i", "Missing.asm"],
+        &["fmt", "Missing.asm"],
+        &["vm", "-i", "Missing.vm"],
+        &["jack", "-i", "Missing.jack"],
+        &["object", "-i", "Missing.asm"],
+        &["archive", "Missing.o", "-o", "Lib.a"],
+        &["link", "Missing.o", "-o", "Out.hack"],
+        &["build", "-m", "Missing.toml"],
+        &[
+            "patch",
+            "-i",
+            "Missing.hack",
+            "-d",
+            "Debug.json",
+            "--at",
+            "0",
+            "--code",
+            "Valid.asm",
+        ],
+        &["test", "-i", "Missing.tst"],
+        &["diff-trace", "-i", "Missing.asm", "--trace", "Trace.txt"],
+        &["grade", "-s", "Missing.json", "Valid.asm"],
+        &["script", "-i", "Missing.rhai"],
+    ];
+    for args in cases
+        .into_iter()
+        .filter(|args| cfg!(feature = "jack") || args[0] != "jack")
+    {
+        // When
+        let output = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .current_dir(&*dir)
+            .args(args)
+            .output()
+            .unwrap();
+
+        // Then
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(Some(3), output.status.code(), "{:?}: {}", args, stderr);
+        assert!(stderr.contains("error[io]: "), "{:?}: {}", args, stderr);
+    }
+}
+
+#[test]
+fn test_cli_exits_with_the_syntax_code_on_a_malformed_input_file() {
+    // Given
+    let dir = TempDir::new("hack-malformed-exit");
+    std::fs::write(dir.join("Valid.asm"), "@1\nD=A\n").unwrap();
+    std::fs::write(dir.join("Valid.hack"), "0000000000000001\n").unwrap();
+    for file in ["Bad.json", "Bad.o", "Bad.toml", "Bad.txt"] {
+        std::fs::write(dir.join(file), "{").unwrap();
+    }
+
+    for args in [
+        ["run", "-i", "Valid.asm", "--load-state", "Bad.json"].as_slice(),
+        &["debug", "-i", "Valid.asm", "--load-state", "Bad.json"],
+        &[
+            "patch",
+            "-i",
+            "Valid.hack",
+            "-d",
+            "Bad.json",
+            "--at",
+            "0",
+            "--code",
+            "Valid.asm",
+        ],
+        &["link", "Bad.o", "-o", "Out.hack"],
+        &["build", "-m", "Bad.toml"],
+        &["fmt", "--config", "Bad.json", "Valid.asm"],
+        &["grade", "-s", "Bad.json", "Valid.asm"],
+        &["diff-trace", "-i", "Valid.asm", "--trace", "Bad.txt"],
+    ] {
+        // When
+        let output = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .current_dir(&*dir)
+            .args(args)
+            .output()
+            .unwrap();
+
+        // Then
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(Some(1), output.status.code(), "{:?}: {}", args, stderr);
+        assert!(stderr.contains("error[syntax]: "), "{:?}: {}", args, stderr);
+    }
+}
+
+#[test]
+fn test_cli_logs_the_passes_or_only_the_errors() {
+    // Given
+    let dir = TempDir::new("hack-verbosity");
+    let input = dir.join("Shadow.asm");
+    std::fs::write(&input, "(R1)\n@R1\n0;JMP\n").unwrap();
+
+    for (flag, expected) in [("-vv", true), ("-q", false)] {
+        // When
+        let output = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .arg(flag)
+            .arg("-i")
+            .arg(&input)
+            .output()
+            .unwrap();
+
+        // Then
+        assert!(output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(expected, stderr.contains("pass=\"encode\""), "{}", flag);
+        assert_eq!(expected, stderr.contains("shadowed-symbol"), "{}", flag);
+    }
+}
+
+#[test]
+fn test_cli_translates_to_the_machine_code_of_the_assembler() {
+    // Given
+    let dir = TempDir::new("hack-translate");
+    let vm = dir.join("Main.vm");
+    std::fs::write(&vm, "push constant 7\npush constant 8\nadd\n").unwrap();
+    let jack = dir.join("Main.jack");
+    std::fs::write(
+        &jack,
+        "class Main {\n    function int main() {\n        return 7 + 8;\n    }\n}\n",
+    )
+    .unwrap();
+    let cases = [
+        ("vm", vm, ["--asm"].as_slice()),
+        ("jack", jack, ["--emit", "asm"].as_slice()),
+    ];
+
+    for (command, input, asm) in cases
+        .into_iter()
+        .filter(|(command, ..)| cfg!(feature = "jack") || *command != "jack")
+    {
+        let run = |args: &[&str], output: &PathBuf| {
+            Command::new(env!("CARGO_BIN_EXE_assembler"))
+                .arg(command)
+                .arg("-i")
+                .arg(&input)
+                .args(args)
+                .arg("-o")
+                .arg(output)
+                .status()
+                .unwrap()
+        };
+        let assembly = dir.join(format!("{}.asm", command));
+        let expected = dir.join(format!("{}-expected.hack", command));
+        let actual = dir.join(format!("{}.hack", command));
+        assert!(run(asm, &assembly).success(), "{}", command);
+        let status = Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .arg("-i")
+            .arg(&assembly)
+            .arg("-o")
+            .arg(&expected)
+            .status()
+            .unwrap();
+        assert!(status.success(), "{}", command);
+
+        // When
+        let status = run(&[], &actual);
+
+        // Then
+        assert!(status.success(), "{}", command);
+        let expected = std::fs::read_to_string(expected).unwrap();
+        let actual = std::fs::read_to_string(actual).unwrap();
+        assert!(!expected.is_empty());
+        assert!(actual == expected, "{} differs", command);
+    }
+}
+
+#[test]
+fn test_cli_runs_more_cycles_from_a_loaded_state() {
+    // Given
+    let dir = TempDir::new("hack-resume");
+    let input = dir.join("Count.asm");
+    let state = dir.join("Count.state");
+    std::fs::write(&input, "(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n").unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_assembler"))
+        .args(["run", "--cycles", "100", "-i"])
+        .arg(&input)
+        .arg("--save-state")
+        .arg(&state)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    for frames in [None, Some(dir.join("Count.gif"))] {
+        // When
+        let mut command = Command::new(env!("CARGO_BIN_EXE_assembler"));
+        command
+            .args(["run", "--cycles", "10", "-i"])
+            .arg(&input)
+            .arg("--load-state")
+            .arg(&state);
+        if let Some(gif) = &frames {
+            command.arg("--gif").arg(gif);
+        }
+        let output = command.output().unwrap();
+
+        // Then
+        assert!(output.status.success(), "{:?}", frames);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            stdout.starts_with("reached the cycle limit after 110 cycles"),
+            "{}",
+            stdout
+        );
+    }
+}
+
+#[test]
+fn test_self_test_subcommand() {
+    // When
+    let output = Command::new(env!("CARGO_BIN_EXE_assembler"))
+        .arg("self-test")
+        .output()
+        .unwrap();
+
+    // Then
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        CORPUS.len(),
+        stdout
+            .lines()
+            .filter(|line| line.starts_with("ok "))
+            .count()
+    );
+}
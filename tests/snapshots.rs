@@ -0,0 +1,68 @@
+use hack_assembler::{
+    diagnostic::{DiagnosticsSink, JsonCollector, TerminalSink},
+    disassembler,
+    emulator::Emulator,
+    pass::EmitFormat,
+    profile,
+    program::Program,
+    testing::{self, INVALID, SAMPLE},
+};
+
+#[test]
+fn test_emitters() {
+    for format in testing::formats() {
+        let output = testing::emit(SAMPLE, *format);
+        insta::assert_snapshot!(format!("emit_{}", testing::format_name(*format)), output);
+    }
+}
+
+#[test]
+fn test_diagnostic_rendering() {
+    // Given
+    let diagnostics: Vec<_> = INVALID
+        .iter()
+        .flat_map(|source| testing::diagnostics(source))
+        .collect();
+    let mut rendered = Vec::new();
+    let mut json = JsonCollector::default();
+
+    // When
+    let mut terminal = TerminalSink::new(&mut rendered);
+    for diagnostic in &diagnostics {
+        terminal.emit(diagnostic.clone());
+        json.emit(diagnostic.clone());
+    }
+
+    // Then
+    assert_eq!(INVALID.len(), diagnostics.len());
+    insta::assert_snapshot!("diagnostics_terminal", String::from_utf8(rendered).unwrap());
+    insta::assert_snapshot!("diagnostics_json", json.to_json());
+}
+
+#[test]
+fn test_listings() {
+    // Given
+    let program = Program::from_source(SAMPLE);
+    let words: Vec<u16> = testing::emit(SAMPLE, EmitFormat::Hack)
+        .lines()
+        .map(|word| u16::from_str_radix(word, 2).unwrap())
+        .collect();
+    let mut emulator = Emulator::new(words.clone());
+    emulator.ram_mut()[0] = 21;
+
+    // When
+    emulator.run(100);
+    let mut disassembly = disassembler::disassemble(&words);
+    disassembler::reconstruct_symbols(&mut disassembly);
+
+    // Then
+    insta::assert_snapshot!(
+        "listing_annotated",
+        profile::annotated_listing(&program, emulator.counts())
+    );
+    insta::assert_snapshot!(
+        "listing_flat",
+        profile::flat_report(&program, emulator.counts())
+    );
+    insta::assert_snapshot!("listing_disassembly", disassembly.to_string());
+}